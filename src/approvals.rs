@@ -0,0 +1,172 @@
+//! Optional K-of-N sign-off on `claim`. Until the owner configures a set of
+//! approvers, claiming works as before.
+
+use near_sdk::{assert_one_yocto, env, near, require, AccountId};
+
+use crate::auction_state;
+use crate::error::AuctionError;
+use crate::owner::only_owner;
+use crate::storage::{StorageCell, StorageKey, WriteBackSession};
+use crate::{Contract, ContractExt};
+
+#[near(serializers = [json, borsh])]
+#[derive(Clone)]
+pub struct ClaimApprovers {
+    pub approvers: Vec<AccountId>,
+    pub threshold: u32,
+}
+
+/// Panics if approvers are configured and fewer than `threshold` of them
+/// have approved the claim.
+pub fn assert_claim_approved() {
+    let config = StorageCell::<ClaimApprovers>::new(StorageKey::ClaimApprovers);
+    let Some(config) = config.try_get() else {
+        return;
+    };
+    let approvals = StorageCell::<Vec<AccountId>>::new(StorageKey::ClaimApprovals);
+    let approved = approvals.try_get().map_or(0, Vec::len);
+    if approved < config.threshold as usize {
+        AuctionError::NotEnoughApprovals.panic_with(format!("{approved} of {}", config.threshold));
+    }
+}
+
+#[near]
+impl Contract {
+    /// Requires `threshold` of `approvers` to call `approve_claim` before the
+    /// auction can be claimed. An empty list turns the requirement off.
+    /// Approvals collected so far are discarded.
+    #[payable]
+    pub fn set_claim_approvers(&mut self, approvers: Vec<AccountId>, threshold: u32) {
+        let _session = WriteBackSession::begin();
+
+        assert_one_yocto();
+        only_owner();
+        auction_state::config().assert_unclaimed();
+
+        StorageCell::<Vec<AccountId>>::new(StorageKey::ClaimApprovals).remove();
+        let mut config = StorageCell::<ClaimApprovers>::new(StorageKey::ClaimApprovers);
+        if approvers.is_empty() {
+            config.remove();
+            return;
+        }
+        require!(
+            threshold > 0 && threshold as usize <= approvers.len(),
+            AuctionError::InvalidThreshold.as_str()
+        );
+        config.set(ClaimApprovers {
+            approvers,
+            threshold,
+        });
+    }
+
+    #[payable]
+    pub fn approve_claim(&mut self) {
+        let _session = WriteBackSession::begin();
+
+        assert_one_yocto();
+        let approver = env::predecessor_account_id();
+        let config = StorageCell::<ClaimApprovers>::new(StorageKey::ClaimApprovers);
+        require!(
+            config
+                .try_get()
+                .is_some_and(|config| config.approvers.contains(&approver)),
+            AuctionError::NotApprover.as_str()
+        );
+
+        let mut approvals = StorageCell::<Vec<AccountId>>::new(StorageKey::ClaimApprovals);
+        let approvals = approvals.get_mut_or_insert_with(Vec::new);
+        require!(
+            !approvals.contains(&approver),
+            AuctionError::AlreadyApproved.as_str()
+        );
+        approvals.push(approver);
+    }
+
+    pub fn get_claim_approvers(&self) -> Option<ClaimApprovers> {
+        StorageCell::<ClaimApprovers>::new(StorageKey::ClaimApprovers)
+            .try_get()
+            .cloned()
+    }
+
+    pub fn get_claim_approvals(&self) -> Vec<AccountId> {
+        StorageCell::<Vec<AccountId>>::new(StorageKey::ClaimApprovals)
+            .try_get()
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::{testing_env, NearToken};
+
+    use super::*;
+    use crate::test_utils::new_contract;
+    use crate::ClaimStatus;
+
+    fn call_as(account_id: &str) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account_id.parse().unwrap())
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .block_timestamp(2000)
+            .build());
+    }
+
+    fn init_with_approvers() -> Contract {
+        // `init` runs before the end time, everything after it once it passed
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id("owner.near".parse().unwrap())
+            .build());
+        let mut contract = new_contract();
+        call_as("owner.near");
+        contract.set_claim_approvers(
+            vec![
+                "a.near".parse().unwrap(),
+                "b.near".parse().unwrap(),
+                "c.near".parse().unwrap(),
+            ],
+            2,
+        );
+        contract
+    }
+
+    #[test]
+    fn claim_goes_through_once_the_threshold_is_met() {
+        let mut contract = init_with_approvers();
+        call_as("a.near");
+        contract.approve_claim();
+        call_as("c.near");
+        contract.approve_claim();
+        assert_eq!(contract.get_claim_approvals().len(), 2);
+
+        let _ = contract.claim();
+        assert_eq!(contract.get_claim_status(), ClaimStatus::Pending);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_ENOUGH_APPROVALS")]
+    fn claim_waits_for_the_threshold() {
+        let mut contract = init_with_approvers();
+        call_as("a.near");
+        contract.approve_claim();
+        let _ = contract.claim();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ALREADY_APPROVED")]
+    fn approvals_are_counted_once() {
+        let mut contract = init_with_approvers();
+        call_as("a.near");
+        contract.approve_claim();
+        contract.approve_claim();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_APPROVER")]
+    fn outsiders_cannot_approve() {
+        let mut contract = init_with_approvers();
+        call_as("mallory.near");
+        contract.approve_claim();
+    }
+}
@@ -0,0 +1,178 @@
+//! The asset an NFT auction settles in: native NEAR, or a NEP-141 token
+//! chosen when the auction starts. It is recorded under the auction's own
+//! keys, and every payment the auction makes goes through `transfer`, which
+//! picks the matching path. Token transfers that fail, e.g. because the
+//! receiver has no storage on the token contract, are recorded as owed and
+//! can be retried with `retry_asset_transfer`.
+
+use near_sdk::json_types::U128;
+use near_sdk::serde_json::{self, json};
+use near_sdk::{env, near, AccountId, Gas, NearToken, Promise};
+
+use crate::error::AuctionError;
+use crate::storage::{self, key, StorageCell, WriteBackSession};
+use crate::storage_management::charge_storage;
+use crate::{math, refunds, Contract, ContractExt};
+
+/// Gas for `ft_transfer` on the token contract.
+const FT_TRANSFER_GAS: Gas = Gas::from_tgas(10);
+/// Gas reserved for `resolve_asset_transfer`.
+const RESOLVE_TRANSFER_GAS: Gas = Gas::from_tgas(5);
+
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum Asset {
+    #[default]
+    Near,
+    /// A NEP-141 token; bids arrive through `ft_on_transfer`.
+    Ft { contract_id: AccountId },
+}
+
+fn asset_key(auction_id: u32) -> Vec<u8> {
+    key!("auction", "nft", auction_id, "asset")
+}
+
+fn owed_key(auction_id: u32, account_id: &AccountId) -> Vec<u8> {
+    key!("auction", "nft", auction_id, "owed", account_id)
+}
+
+/// How many accounts auction `auction_id` owes, so `has_owed` doesn't have
+/// to look for their records. Absent while it owes no one.
+fn owed_count_key(auction_id: u32) -> Vec<u8> {
+    key!("auction", "nft", auction_id, "owed_count")
+}
+
+/// What auction `auction_id` settles in. Auctions started before assets
+/// could be chosen have none recorded and settle in NEAR.
+pub fn asset(auction_id: u32) -> Asset {
+    StorageCell::<Asset>::new(asset_key(auction_id))
+        .try_get()
+        .cloned()
+        .unwrap_or_default()
+}
+
+pub fn record(auction_id: u32, asset: Asset) {
+    StorageCell::new(asset_key(auction_id)).set(asset);
+}
+
+pub fn forget(auction_id: u32) {
+    StorageCell::<Asset>::new(asset_key(auction_id)).remove();
+}
+
+/// Whether failed token transfers in auction `auction_id` still owe anyone,
+/// which needs the recorded asset to retry them.
+pub fn has_owed(auction_id: u32) -> bool {
+    storage::has_key(&owed_count_key(auction_id))
+}
+
+fn set_owed_count(auction_id: u32, count: u32) {
+    let mut cell = StorageCell::<u32>::new(owed_count_key(auction_id));
+    if count == 0 {
+        cell.remove();
+    } else {
+        cell.set(count);
+    }
+}
+
+fn owed_count(auction_id: u32) -> u32 {
+    StorageCell::<u32>::new(owed_count_key(auction_id))
+        .try_get()
+        .copied()
+        .unwrap_or(0)
+}
+
+/// Counts the owed records of auction `auction_id` from before the count
+/// was kept. Walks `indexed_keys`, so it is only meant for migrations.
+pub(crate) fn backfill_owed_count(auction_id: u32, indexed_keys: &[Vec<u8>]) {
+    let owed = key!("auction", "nft", auction_id, "owed");
+    let count = indexed_keys
+        .iter()
+        .filter(|key| key.starts_with(&owed))
+        .count();
+    set_owed_count(auction_id, count as u32);
+}
+
+/// Pays `amount` of auction `auction_id`'s asset to `account_id`. NEAR goes
+/// through the refund ledger, tokens through `ft_transfer`; either way a
+/// failed payment is recorded as owed instead of lost.
+pub fn transfer(auction_id: u32, account_id: AccountId, amount: NearToken) -> Promise {
+    let Asset::Ft { contract_id } = asset(auction_id) else {
+        return refunds::refund(account_id, amount);
+    };
+    let args = json!({
+        "receiver_id": account_id,
+        "amount": U128(amount.as_yoctonear()),
+    });
+    Promise::new(contract_id)
+        .function_call(
+            "ft_transfer".to_string(),
+            serde_json::to_vec(&args).unwrap(),
+            NearToken::from_yoctonear(1),
+            FT_TRANSFER_GAS,
+        )
+        .then(
+            Contract::ext(env::current_account_id())
+                .with_static_gas(RESOLVE_TRANSFER_GAS)
+                .resolve_asset_transfer(auction_id, account_id, amount),
+        )
+}
+
+fn owed(auction_id: u32, account_id: &AccountId) -> NearToken {
+    StorageCell::<NearToken>::new(owed_key(auction_id, account_id))
+        .try_get()
+        .copied()
+        .unwrap_or(NearToken::from_yoctonear(0))
+}
+
+#[near]
+impl Contract {
+    #[private]
+    pub fn resolve_asset_transfer(
+        &mut self,
+        auction_id: u32,
+        account_id: AccountId,
+        amount: NearToken,
+    ) {
+        if env::promise_result_checked(0, 0).is_ok() {
+            return;
+        }
+        let _session = WriteBackSession::begin();
+        env::log_str(&format!(
+            "Transfer of {} to {account_id} in auction {auction_id} failed",
+            amount.as_yoctonear()
+        ));
+        let previous = owed(auction_id, &account_id);
+        let total = math::add(previous, amount).unwrap_or_else(|err| err.panic());
+        charge_storage(&account_id.clone(), || {
+            if previous.is_zero() {
+                set_owed_count(auction_id, owed_count(auction_id) + 1);
+            }
+            StorageCell::new(owed_key(auction_id, &account_id)).set(total)
+        });
+    }
+
+    /// Pays `account_id` what failed transfers in auction `auction_id` left
+    /// owed to it. Anyone can call this since the tokens only ever go to
+    /// `account_id`.
+    pub fn retry_asset_transfer(&mut self, auction_id: u32, account_id: AccountId) -> Promise {
+        let _session = WriteBackSession::begin();
+
+        let mut owed = StorageCell::<NearToken>::new(owed_key(auction_id, &account_id));
+        let Some(amount) = owed.try_get().copied() else {
+            AuctionError::NothingOwed.panic();
+        };
+        owed.remove();
+        set_owed_count(auction_id, owed_count(auction_id).saturating_sub(1));
+        transfer(auction_id, account_id, amount)
+    }
+
+    pub fn get_nft_auction_asset(&self, auction_id: u32) -> Asset {
+        asset(auction_id)
+    }
+
+    /// What failed token transfers in auction `auction_id` still owe
+    /// `account_id`, in the token's smallest unit.
+    pub fn get_asset_owed(&self, auction_id: u32, account_id: AccountId) -> U128 {
+        U128(owed(auction_id, &account_id).as_yoctonear())
+    }
+}
@@ -0,0 +1,208 @@
+//! The auction's own keys behind one struct, so methods can read and mutate
+//! them like the default contract's fields. Nothing is read until a field is
+//! used, and nothing is written until `flush` or drop, which hands every
+//! changed field to storage in one pass.
+//!
+//! ```ignore
+//! let mut state = AuctionState::new();
+//! require!(env::block_timestamp() < state.config.get().auction_end_time);
+//! state.config.get_mut().auction_end_time = new_end_time;
+//! ```
+
+use std::cell::OnceCell;
+
+use near_sdk::json_types::U64;
+use near_sdk::{near, AccountId, NearToken};
+
+use crate::error::AuctionError;
+use crate::highest_bid::{self, BidMetadata, HotBid};
+use crate::storage::{self, StorageCell, StorageKey};
+use crate::Bid;
+
+/// Where the auction is in being claimed. `Open` and `Claimed` encode like
+/// the `claimed: bool` this replaced, so configs stored before `Pending`
+/// existed still decode.
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClaimStatus {
+    Open,
+    Claimed,
+    /// `claim` has sent the payout and `resolve_claim` hasn't seen it land.
+    Pending,
+}
+
+impl ClaimStatus {
+    pub fn assert_open(self) {
+        match self {
+            ClaimStatus::Open => {}
+            ClaimStatus::Claimed => AuctionError::AlreadyClaimed.panic(),
+            ClaimStatus::Pending => AuctionError::ClaimPending.panic(),
+        }
+    }
+}
+
+/// The end time, the auctioneer and how far the claim has got. `claim`
+/// needs all three, so they share one key.
+#[near(serializers = [borsh])]
+#[derive(Clone)]
+pub struct AuctionConfig {
+    pub auction_end_time: u64,
+    pub auctioneer: AccountId,
+    pub claim: ClaimStatus,
+}
+
+impl AuctionConfig {
+    pub fn is_claimed(&self) -> bool {
+        self.claim == ClaimStatus::Claimed
+    }
+
+    /// Panics unless the auction can still be claimed: neither claimed nor
+    /// waiting on a payout.
+    pub fn assert_unclaimed(&self) {
+        self.claim.assert_open();
+    }
+}
+
+/// The stored config, or `None` before `init`.
+pub fn try_config() -> Option<AuctionConfig> {
+    storage::try_load(&StorageKey::AuctionConfig.to_bytes())
+}
+
+pub fn config() -> AuctionConfig {
+    storage::load(&StorageKey::AuctionConfig.to_bytes())
+}
+
+pub struct AuctionState {
+    pub config: StorageCell<AuctionConfig>,
+    pub bid_count: StorageCell<U64>,
+    pub total_volume: StorageCell<NearToken>,
+    highest_bid: StorageCell<HotBid>,
+    /// Loaded only when the whole bid is asked for, and pending a write when
+    /// `metadata_dirty` is set.
+    metadata: OnceCell<BidMetadata>,
+    metadata_dirty: bool,
+}
+
+impl AuctionState {
+    pub fn new() -> Self {
+        Self {
+            config: StorageCell::new(StorageKey::AuctionConfig),
+            bid_count: StorageCell::new(StorageKey::BidCount),
+            total_volume: StorageCell::new(StorageKey::TotalVolume),
+            highest_bid: StorageCell::new(StorageKey::HighestBid),
+            metadata: OnceCell::new(),
+            metadata_dirty: false,
+        }
+    }
+
+    /// The bidder and amount of the highest bid, without its metadata.
+    pub fn highest_bid(&self) -> &HotBid {
+        self.highest_bid.get()
+    }
+
+    /// The highest bid with its metadata, which is read on first use.
+    pub fn full_highest_bid(&self) -> Bid {
+        let metadata = self.metadata.get_or_init(|| {
+            let key = StorageKey::HighestBidMetadata.to_bytes();
+            let bytes = storage::read(&key).unwrap_or_else(|| storage::panic_missing(&key));
+            highest_bid::decode_metadata(&bytes).unwrap_or_else(|| storage::panic_undecodable(&key))
+        });
+        Bid::join(self.highest_bid().clone(), metadata.clone())
+    }
+
+    pub fn set_highest_bid(&mut self, bid: Bid) {
+        let (hot, metadata) = bid.split();
+        self.highest_bid.set(hot);
+        self.metadata = OnceCell::from(metadata);
+        self.metadata_dirty = true;
+    }
+
+    /// Writes every field changed since the last flush.
+    pub fn flush(&mut self) {
+        let mut changes: Vec<(Vec<u8>, Vec<u8>)> = [
+            self.config.take_change(),
+            self.bid_count.take_change(),
+            self.total_volume.take_change(),
+            self.highest_bid.take_change(),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if std::mem::take(&mut self.metadata_dirty) {
+            if let Some(metadata) = self.metadata.get() {
+                changes.push((
+                    StorageKey::HighestBidMetadata.to_bytes(),
+                    highest_bid::encode_metadata(metadata.clone()),
+                ));
+            }
+        }
+        for (key, value) in changes {
+            storage::write(&key, &value);
+        }
+    }
+}
+
+impl Default for AuctionState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Runs before the fields' own drops, which then find nothing left to write
+impl Drop for AuctionState {
+    fn drop(&mut self) {
+        self.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::new_contract;
+
+    fn stored<T: near_sdk::borsh::BorshDeserialize>(key: StorageKey) -> T {
+        storage::read_key(&key.to_bytes())
+    }
+
+    #[test]
+    fn changes_are_written_on_flush() {
+        new_contract();
+        let mut state = AuctionState::new();
+        state.config.get_mut().claim = ClaimStatus::Claimed;
+        state.bid_count.set(U64(5));
+        assert!(!stored::<AuctionConfig>(StorageKey::AuctionConfig).is_claimed());
+        assert_eq!(stored::<U64>(StorageKey::BidCount), U64(0));
+
+        state.flush();
+        assert!(stored::<AuctionConfig>(StorageKey::AuctionConfig).is_claimed());
+        assert_eq!(stored::<U64>(StorageKey::BidCount), U64(5));
+    }
+
+    #[test]
+    fn claim_status_decodes_the_old_claimed_flag() {
+        for (claimed, status) in [(false, ClaimStatus::Open), (true, ClaimStatus::Claimed)] {
+            let bytes = near_sdk::borsh::to_vec(&claimed).unwrap();
+            assert_eq!(
+                near_sdk::borsh::from_slice::<ClaimStatus>(&bytes).unwrap(),
+                status
+            );
+        }
+    }
+
+    #[test]
+    fn drop_flushes_the_highest_bid_and_its_metadata() {
+        new_contract();
+        let mut bid = AuctionState::new().full_highest_bid();
+        bid.bidder = "bob.near".parse().unwrap();
+        bid.bid_block_height = U64(42);
+        {
+            let mut state = AuctionState::new();
+            state.set_highest_bid(bid);
+            assert_eq!(state.highest_bid().bidder.as_str(), "bob.near");
+        }
+
+        let state = AuctionState::new();
+        assert_eq!(state.highest_bid().bidder.as_str(), "bob.near");
+        assert_eq!(state.full_highest_bid().bid_block_height, U64(42));
+    }
+}
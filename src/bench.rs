@@ -0,0 +1,76 @@
+//! Named benchmark scenarios, enabled with the `bench` feature, so tests can
+//! run any of them with one call and get a structured result back.
+
+use near_sdk::json_types::U64;
+use near_sdk::serde_json::Value;
+use near_sdk::{env, near};
+
+use crate::error::AuctionError;
+use crate::profile;
+use crate::{Contract, ContractExt};
+
+/// Every scenario `run_benchmark` accepts.
+pub const SCENARIOS: [&str; 8] = [
+    "fill_vector",
+    "fill_sdk_vector",
+    "fill_sdk_iterable_map",
+    "fill_lookup_map",
+    "fill_tree_map",
+    "fill_lookup_set",
+    "fill_iterable_set",
+    "fill_record_blob",
+];
+
+#[near(serializers = [json])]
+pub struct BenchResult {
+    pub name: String,
+    /// Per-phase gas breakdown of the scenario, `null` if it isn't profiled.
+    pub gas_profile: Value,
+    pub storage_before: U64,
+    pub storage_after: U64,
+}
+
+#[near]
+impl Contract {
+    /// Runs the scenario called `name` against the live state.
+    pub fn run_benchmark(&mut self, name: String) -> BenchResult {
+        let storage_before = env::storage_usage();
+        match name.as_str() {
+            "fill_vector" => self.fill_vector(1000, 0, None),
+            "fill_sdk_vector" => self.fill_sdk_vector(1000, 0, None),
+            "fill_sdk_iterable_map" => self.fill_sdk_iterable_map(1000, 0, None),
+            "fill_lookup_map" => self.fill_lookup_map(1000, 0, None),
+            "fill_tree_map" => self.fill_tree_map(200, 0),
+            "fill_lookup_set" => self.fill_lookup_set(100, 0),
+            "fill_iterable_set" => self.fill_iterable_set(100, 0),
+            "fill_record_blob" => self.fill_record_blob(1000, 0),
+            _ => AuctionError::UnknownBenchmark.panic_with(name),
+        }
+        BenchResult {
+            name,
+            gas_profile: profile::take_last().unwrap_or(Value::Null),
+            storage_before: U64(storage_before),
+            storage_after: U64(env::storage_usage()),
+        }
+    }
+
+    pub fn list_benchmarks(&self) -> Vec<String> {
+        SCENARIOS.iter().map(|name| name.to_string()).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::new_contract;
+
+    #[test]
+    fn every_scenario_runs() {
+        let mut contract = new_contract();
+        for name in SCENARIOS {
+            let result = contract.run_benchmark(name.to_string());
+            assert_eq!(result.gas_profile["method"], name);
+            assert!(result.storage_after.0 > result.storage_before.0);
+        }
+    }
+}
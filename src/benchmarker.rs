@@ -0,0 +1,95 @@
+//! The account allowed to run the `fill_*` methods. They grow the state
+//! without attaching a deposit, so the storage is staked from the contract's
+//! own balance; only the owner, or a benchmarker they name, may do that.
+
+use near_sdk::{assert_one_yocto, env, near, require, AccountId};
+
+use crate::error::AuctionError;
+use crate::owner::only_owner;
+use crate::storage::{StorageCell, StorageKey, WriteBackSession};
+use crate::{Contract, ContractExt};
+
+fn benchmarker() -> AccountId {
+    StorageCell::<AccountId>::new(StorageKey::Benchmarker)
+        .try_get()
+        .cloned()
+        .unwrap_or_else(|| {
+            StorageCell::<AccountId>::new(StorageKey::Owner)
+                .get()
+                .clone()
+        })
+}
+
+/// Panics unless the caller is the benchmarker, which is the owner until
+/// one is named.
+pub fn only_benchmarker() {
+    require!(
+        env::predecessor_account_id() == benchmarker(),
+        AuctionError::NotBenchmarker.as_str()
+    );
+}
+
+#[near]
+impl Contract {
+    /// Lets `benchmarker` fill the state, or hands it back to the owner with
+    /// `None`.
+    #[payable]
+    pub fn set_benchmarker(&mut self, benchmarker: Option<AccountId>) {
+        let _session = WriteBackSession::begin();
+
+        assert_one_yocto();
+        only_owner();
+        let mut cell = StorageCell::<AccountId>::new(StorageKey::Benchmarker);
+        match benchmarker {
+            Some(benchmarker) => cell.set(benchmarker),
+            None => cell.remove(),
+        }
+    }
+
+    pub fn get_benchmarker(&self) -> AccountId {
+        benchmarker()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::{testing_env, NearToken};
+
+    use super::*;
+    use crate::test_utils::new_contract;
+
+    fn call_as(account_id: &AccountId) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account_id.clone())
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .build());
+    }
+
+    #[test]
+    fn owner_benchmarks_until_someone_is_named() {
+        let mut contract = new_contract();
+        let owner = contract.get_owner();
+        let benchmarker: AccountId = "bench.near".parse().unwrap();
+        assert_eq!(contract.get_benchmarker(), owner);
+
+        call_as(&owner);
+        contract.set_benchmarker(Some(benchmarker.clone()));
+        assert_eq!(contract.get_benchmarker(), benchmarker);
+        call_as(&benchmarker);
+        only_benchmarker();
+
+        call_as(&owner);
+        contract.set_benchmarker(None);
+        assert_eq!(contract.get_benchmarker(), owner);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER")]
+    fn only_the_owner_names_the_benchmarker() {
+        let mut contract = new_contract();
+
+        call_as(&"bench.near".parse().unwrap());
+        contract.set_benchmarker(Some("bench.near".parse().unwrap()));
+    }
+}
@@ -0,0 +1,336 @@
+//! Optional payout on an EVM chain. The auctioneer registers an address
+//! there, and instead of transferring NEAR, `claim` builds an EIP-1559
+//! transaction that sends the winning amount of bridged NEAR (an ERC-20 with
+//! the same 24 decimals) from the contract's derived address to it. The
+//! chain signatures (MPC) contract signs the transaction's hash, and anyone
+//! can attach the signature to the stored transaction and broadcast it. The
+//! winning bid stays here to make up for what the derived address pays, and
+//! counts as held until the transaction is signed.
+
+use near_sdk::json_types::{Base64VecU8, U128, U64};
+use near_sdk::serde_json::{self, json, Value};
+use near_sdk::{assert_one_yocto, env, near, require, AccountId, Gas, NearToken, Promise};
+
+use crate::auction_state;
+use crate::error::AuctionError;
+use crate::evm::{self, Eip1559Transaction};
+use crate::storage::{StorageCell, StorageKey, WriteBackSession};
+use crate::{Contract, ContractExt};
+
+/// Derivation path of the key that controls the payout funds.
+const PAYOUT_PATH: &str = "auction-payout";
+/// Gas for `sign`, which waits for the MPC network to respond.
+const SIGN_GAS: Gas = Gas::from_tgas(50);
+/// Gas reserved for `resolve_signature`.
+const RESOLVE_SIGNATURE_GAS: Gas = Gas::from_tgas(10);
+/// Longest signature response `resolve_signature` will read.
+const MAX_SIGNATURE_LEN: usize = 1024;
+
+#[near(serializers = [json, borsh])]
+#[derive(Clone)]
+pub struct ForeignPayout {
+    /// EIP-155 id of the chain, e.g. 1 for Ethereum.
+    pub chain_id: U64,
+    /// The auctioneer's address there: `0x` and 40 hex digits.
+    pub address: String,
+    /// The bridged NEAR ERC-20 the derived address pays out in.
+    pub token: String,
+    /// The derived address's next nonce.
+    pub nonce: U64,
+    pub max_priority_fee_per_gas: U128,
+    pub max_fee_per_gas: U128,
+    pub gas_limit: U64,
+    /// The chain signatures contract to ask: `v1.signer` on mainnet.
+    pub mpc_contract: AccountId,
+}
+
+impl ForeignPayout {
+    /// The transaction paying `amount` out, or `None` if an address doesn't
+    /// parse or the fees are inconsistent.
+    fn transaction(&self, amount: NearToken) -> Option<Eip1559Transaction> {
+        let receiver = evm::parse_address(&self.address)?;
+        let token = evm::parse_address(&self.token)?;
+        (self.max_priority_fee_per_gas.0 <= self.max_fee_per_gas.0).then(|| Eip1559Transaction {
+            chain_id: self.chain_id.0,
+            nonce: self.nonce.0,
+            max_priority_fee_per_gas: self.max_priority_fee_per_gas.0,
+            max_fee_per_gas: self.max_fee_per_gas.0,
+            gas_limit: self.gas_limit.0,
+            to: token,
+            value: 0,
+            data: Eip1559Transaction::erc20_transfer_data(receiver, amount.as_yoctonear()),
+        })
+    }
+}
+
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Debug, PartialEq)]
+pub enum SignStatus {
+    Pending,
+    /// The MPC response, as returned by `sign`.
+    Signed {
+        signature: String,
+    },
+    Failed,
+}
+
+#[near(serializers = [json, borsh])]
+#[derive(Clone)]
+pub struct SignRequest {
+    pub payout: ForeignPayout,
+    pub amount: NearToken,
+    /// The unsigned EIP-1559 transaction, `0x02` followed by its RLP fields.
+    pub transaction: Base64VecU8,
+    /// Its keccak256 signing hash, which is what gets signed.
+    pub payload: Base64VecU8,
+    pub path: String,
+    pub status: SignStatus,
+}
+
+pub fn foreign_payout() -> Option<ForeignPayout> {
+    StorageCell::<ForeignPayout>::new(StorageKey::ForeignPayout)
+        .try_get()
+        .cloned()
+}
+
+/// Whether the winning bid still backs a foreign payout that hasn't been
+/// signed, so the contract can't count it as its own yet.
+pub fn payout_unsigned() -> bool {
+    StorageCell::<SignRequest>::new(StorageKey::SignRequest)
+        .try_get()
+        .is_some_and(|request| !matches!(request.status, SignStatus::Signed { .. }))
+}
+
+/// Records a signing request for the transaction paying `amount` out to
+/// `payout` and sends it to the MPC contract.
+pub fn request_payout(payout: ForeignPayout, amount: NearToken) -> Promise {
+    let Some(transaction) = payout.transaction(amount) else {
+        AuctionError::InvalidForeignPayout.panic();
+    };
+    let request = SignRequest {
+        payout,
+        amount,
+        transaction: transaction.unsigned_bytes().into(),
+        payload: transaction.signing_hash().to_vec().into(),
+        path: PAYOUT_PATH.to_string(),
+        status: SignStatus::Pending,
+    };
+    let sign = sign(&request);
+    StorageCell::new(StorageKey::SignRequest).set(request);
+    sign
+}
+
+fn sign(request: &SignRequest) -> Promise {
+    let args = json!({
+        "request": {
+            "payload": request.payload.0,
+            "path": request.path,
+            "key_version": 0,
+        }
+    });
+    Promise::new(request.payout.mpc_contract.clone())
+        .function_call(
+            "sign".to_string(),
+            serde_json::to_vec(&args).unwrap(),
+            NearToken::from_yoctonear(1),
+            SIGN_GAS,
+        )
+        .then(
+            Contract::ext(env::current_account_id())
+                .with_static_gas(RESOLVE_SIGNATURE_GAS)
+                .resolve_signature(),
+        )
+}
+
+#[near]
+impl Contract {
+    #[private]
+    pub fn resolve_signature(&mut self) {
+        let _session = WriteBackSession::begin();
+
+        let signature = env::promise_result_checked(0, MAX_SIGNATURE_LEN)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<Value>(&bytes).ok());
+        let mut request = StorageCell::<SignRequest>::new(StorageKey::SignRequest);
+        request.get_mut().status = match signature {
+            Some(signature) => SignStatus::Signed {
+                signature: signature.to_string(),
+            },
+            None => {
+                env::log_str("The chain signatures contract did not sign the payout");
+                SignStatus::Failed
+            }
+        };
+    }
+
+    /// Has the winning bid paid out to `address` on the EVM chain
+    /// `chain_id` instead of in NEAR, or in NEAR again with `None`. Only the
+    /// auctioneer can choose, before the claim.
+    #[payable]
+    pub fn set_foreign_payout(&mut self, payout: Option<ForeignPayout>) {
+        let _session = WriteBackSession::begin();
+
+        assert_one_yocto();
+        let config = auction_state::config();
+        require!(
+            env::predecessor_account_id() == config.auctioneer,
+            AuctionError::NotAuctioneer.as_str()
+        );
+        config.assert_unclaimed();
+        require!(
+            payout
+                .as_ref()
+                .is_none_or(|payout| payout.transaction(NearToken::from_yoctonear(0)).is_some()),
+            AuctionError::InvalidForeignPayout.as_str()
+        );
+
+        let mut cell = StorageCell::<ForeignPayout>::new(StorageKey::ForeignPayout);
+        match payout {
+            Some(payout) => cell.set(payout),
+            None => cell.remove(),
+        }
+    }
+
+    /// Asks for the payout signature again after the MPC contract failed.
+    #[payable]
+    pub fn retry_foreign_payout(&mut self) -> Promise {
+        let _session = WriteBackSession::begin();
+
+        assert_one_yocto();
+        let mut request = StorageCell::<SignRequest>::new(StorageKey::SignRequest);
+        require!(
+            request
+                .try_get()
+                .is_some_and(|request| request.status == SignStatus::Failed),
+            AuctionError::NoFailedPayout.as_str()
+        );
+        let request = request.get_mut();
+        request.status = SignStatus::Pending;
+        sign(request)
+    }
+
+    pub fn get_foreign_payout(&self) -> Option<ForeignPayout> {
+        foreign_payout()
+    }
+
+    pub fn get_sign_request(&self) -> Option<SignRequest> {
+        StorageCell::<SignRequest>::new(StorageKey::SignRequest)
+            .try_get()
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::{test_vm_config, testing_env, PromiseResult, RuntimeFeesConfig};
+
+    use super::*;
+    use crate::test_utils::new_contract;
+
+    fn call_as(account_id: &str, block_timestamp: u64) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account_id.parse().unwrap())
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .block_timestamp(block_timestamp)
+            .build());
+    }
+
+    fn resolve_with(result: PromiseResult) {
+        testing_env!(
+            VMContextBuilder::new()
+                .predecessor_account_id(env::current_account_id())
+                .build(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![result],
+        );
+    }
+
+    fn payout() -> ForeignPayout {
+        ForeignPayout {
+            chain_id: U64(1),
+            address: "0x00000000000000000000000000000000deadbeef".to_string(),
+            token: "0x85f17cf997934a597031b2e18a9ab6ebd4b9f6a4".to_string(),
+            nonce: U64(7),
+            max_priority_fee_per_gas: U128(1_000_000_000),
+            max_fee_per_gas: U128(30_000_000_000),
+            gas_limit: U64(100_000),
+            mpc_contract: "v1.signer".parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn claim_requests_a_signature_for_the_payout() {
+        let mut contract = new_contract();
+        call_as("auctioneer.near", 0);
+        contract.set_foreign_payout(Some(payout()));
+
+        call_as("auctioneer.near", 2000);
+        let _ = contract.claim();
+        let request = contract.get_sign_request().unwrap();
+        assert_eq!(request.status, SignStatus::Pending);
+        assert_eq!(request.amount, NearToken::from_yoctonear(1));
+        assert_eq!(request.transaction.0[0], 0x02);
+        assert_eq!(
+            request.payload.0,
+            env::keccak256_array(&request.transaction.0).to_vec()
+        );
+        assert!(payout_unsigned());
+
+        resolve_with(PromiseResult::Failed);
+        contract.resolve_signature();
+        assert_eq!(
+            contract.get_sign_request().unwrap().status,
+            SignStatus::Failed
+        );
+
+        call_as("bob.near", 3000);
+        let _ = contract.retry_foreign_payout();
+        resolve_with(PromiseResult::Successful(br#"{"recovery_id":0}"#.to_vec()));
+        contract.resolve_signature();
+        assert_eq!(
+            contract.get_sign_request().unwrap().status,
+            SignStatus::Signed {
+                signature: r#"{"recovery_id":0}"#.to_string()
+            }
+        );
+        assert!(!payout_unsigned());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NO_FAILED_PAYOUT")]
+    fn pending_payouts_cannot_be_retried() {
+        let mut contract = new_contract();
+        call_as("bob.near", 0);
+        let _ = contract.retry_foreign_payout();
+    }
+
+    #[test]
+    fn the_transaction_sends_the_bid_in_the_token() {
+        let transaction = payout().transaction(NearToken::from_near(2)).unwrap();
+        assert_eq!(transaction.chain_id, 1);
+        assert_eq!(transaction.nonce, 7);
+        assert_eq!(transaction.value, 0);
+        assert_eq!(transaction.to, evm::parse_address(&payout().token).unwrap());
+        assert_eq!(
+            transaction.data,
+            Eip1559Transaction::erc20_transfer_data(
+                evm::parse_address(&payout().address).unwrap(),
+                NearToken::from_near(2).as_yoctonear()
+            )
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_FOREIGN_PAYOUT")]
+    fn payouts_need_evm_addresses() {
+        let mut contract = new_contract();
+        call_as("auctioneer.near", 0);
+        contract.set_foreign_payout(Some(ForeignPayout {
+            address: "bc1qar0srrr7xfkvy5l643lydnw9re59gtzzwf5mdq".to_string(),
+            ..payout()
+        }));
+    }
+}
@@ -0,0 +1,134 @@
+use crate::storage::{StorageCell, StorageKey};
+use crate::VectorBlob;
+
+/// Bytes per chunk. Large enough that a compressed chunk still finds repeats
+/// in the benchmark data, small enough that an append rewrites little.
+pub const CHUNK_SIZE: u32 = 512;
+
+/// A byte vector split into fixed-size chunks: the length lives under the
+/// registered key and chunk `n` under `StorageKey::chunk_key(n)`. Appends only
+/// touch the last chunk, which is kept in memory until the vector is dropped.
+pub struct ChunkedVector {
+    key: StorageKey,
+    len: StorageCell<u32>,
+    tail: Option<(u32, StorageCell<VectorBlob>)>,
+}
+
+impl ChunkedVector {
+    pub fn new(key: StorageKey) -> Self {
+        Self {
+            key,
+            len: StorageCell::new(key),
+            tail: None,
+        }
+    }
+
+    pub fn len(&self) -> u32 {
+        self.len.try_get().copied().unwrap_or(0)
+    }
+
+    pub fn push(&mut self, value: u8) {
+        let len = self.len();
+        let chunk = len / CHUNK_SIZE;
+        if self.tail.as_ref().map(|(n, _)| *n) != Some(chunk) {
+            self.tail = Some((chunk, StorageCell::new(self.key.chunk_key(chunk))));
+        }
+        let (_, tail) = self.tail.as_mut().unwrap();
+        tail.get_mut_or_insert_with(VectorBlob::default).push(value);
+        self.len.set(len + 1);
+    }
+
+    pub fn get(&self, index: u32) -> Option<u8> {
+        if index >= self.len() {
+            return None;
+        }
+        self.with_chunk(index / CHUNK_SIZE, |chunk| {
+            chunk.get((index % CHUNK_SIZE) as usize).copied()
+        })
+    }
+
+    /// Elements in `from..to`, clamped to the current length.
+    pub fn get_range(&self, from: u32, to: u32) -> Vec<u8> {
+        let to = to.min(self.len());
+        let mut values = Vec::with_capacity(to.saturating_sub(from) as usize);
+        let mut index = from;
+        while index < to {
+            let chunk = index / CHUNK_SIZE;
+            let start = (index % CHUNK_SIZE) as usize;
+            let end = (to - chunk * CHUNK_SIZE).min(CHUNK_SIZE) as usize;
+            self.with_chunk(chunk, |bytes| values.extend_from_slice(&bytes[start..end]));
+            index = (chunk + 1) * CHUNK_SIZE;
+        }
+        values
+    }
+
+    /// Removes every chunk and the length.
+    #[cfg(any(test, not(feature = "minimal")))]
+    pub fn clear(&mut self) {
+        self.tail = None;
+        for chunk in 0..self.len().div_ceil(CHUNK_SIZE) {
+            StorageCell::<VectorBlob>::new(self.key.chunk_key(chunk)).remove();
+        }
+        self.len.remove();
+    }
+
+    /// Runs `f` on chunk `n`, preferring the unflushed tail over storage.
+    fn with_chunk<R>(&self, n: u32, f: impl FnOnce(&[u8]) -> R) -> R {
+        match &self.tail {
+            Some((tail, cell)) if *tail == n => f(cell.get()),
+            _ => f(StorageCell::<VectorBlob>::new(self.key.chunk_key(n)).get()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{self, WriteBackSession};
+
+    #[test]
+    fn appends_only_rewrite_the_last_chunk() {
+        let _session = WriteBackSession::begin();
+        let mut vector = ChunkedVector::new(StorageKey::Vector);
+        for i in 0..(CHUNK_SIZE + 10) {
+            vector.push(i as u8);
+        }
+        drop(vector);
+        storage::flush();
+
+        let first_chunk = env_bytes(StorageKey::Vector.chunk_key(0));
+        let mut vector = ChunkedVector::new(StorageKey::Vector);
+        vector.push(7);
+        drop(vector);
+        storage::flush();
+
+        assert_eq!(env_bytes(StorageKey::Vector.chunk_key(0)), first_chunk);
+        let vector = ChunkedVector::new(StorageKey::Vector);
+        assert_eq!(vector.len(), CHUNK_SIZE + 11);
+        assert_eq!(vector.get(CHUNK_SIZE + 10), Some(7));
+        assert_eq!(vector.get(CHUNK_SIZE + 11), None);
+    }
+
+    #[test]
+    fn ranges_span_chunks() {
+        let mut vector = ChunkedVector::new(StorageKey::Vector);
+        for i in 0..(2 * CHUNK_SIZE + 5) {
+            vector.push((i % 251) as u8);
+        }
+        let expected: Vec<u8> = (CHUNK_SIZE - 3..2 * CHUNK_SIZE + 5)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        assert_eq!(vector.get_range(CHUNK_SIZE - 3, u32::MAX), expected);
+        assert_eq!(vector.get_range(5, 5), Vec::<u8>::new());
+
+        vector.clear();
+        assert_eq!(vector.len(), 0);
+        assert!(!near_sdk::env::storage_has_key(
+            &StorageKey::Vector.chunk_key(2)
+        ));
+    }
+
+    fn env_bytes(key: Vec<u8>) -> Option<Vec<u8>> {
+        near_sdk::env::storage_read(&key)
+    }
+}
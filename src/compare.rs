@@ -125,7 +125,7 @@ mod tests {
     fn init_contract() {
         let end_time: U64 = U64::from(1000);
         let alice: AccountId = "alice.near".parse().unwrap();
-        let contract = Contract::init(end_time.clone(), alice.clone());
+        let contract = Contract::init(end_time.clone(), alice.clone(), None);
 
         let default_bid = contract.get_highest_bid();
         assert_eq!(default_bid.bidder, env::current_account_id());
@@ -0,0 +1,174 @@
+//! A tiny LZ77 codec for large blobs, enabled with the `compression` feature.
+//!
+//! The output is `[original length: u32 le]` followed by a token stream:
+//! a control byte below `0x80` introduces `control + 1` literal bytes, any
+//! other control byte is a back-reference of `(control & 0x7f) + 4` bytes at
+//! the `u16 le` distance that follows it.
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use std::io;
+use std::ops::{Deref, DerefMut};
+
+const MIN_MATCH: usize = 4;
+const MAX_MATCH: usize = 0x7f + MIN_MATCH;
+const MAX_LITERALS: usize = 0x80;
+const MAX_DISTANCE: usize = u16::MAX as usize;
+const HASH_BITS: u32 = 12;
+
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(4 + input.len() / 2);
+    out.extend_from_slice(&(input.len() as u32).to_le_bytes());
+
+    // Last position (plus one) at which each 4-byte hash was seen
+    let mut table = vec![0u32; 1 << HASH_BITS];
+    let mut literals_from = 0;
+    let mut pos = 0;
+    while pos + MIN_MATCH <= input.len() {
+        let slot = hash(&input[pos..pos + MIN_MATCH]);
+        let candidate = table[slot] as usize;
+        table[slot] = pos as u32 + 1;
+
+        if candidate > 0 {
+            let start = candidate - 1;
+            let distance = pos - start;
+            if distance <= MAX_DISTANCE
+                && input[start..start + MIN_MATCH] == input[pos..pos + MIN_MATCH]
+            {
+                let mut len = MIN_MATCH;
+                while len < MAX_MATCH
+                    && pos + len < input.len()
+                    && input[start + len] == input[pos + len]
+                {
+                    len += 1;
+                }
+                push_literals(&mut out, &input[literals_from..pos]);
+                out.push(0x80 | (len - MIN_MATCH) as u8);
+                out.extend_from_slice(&(distance as u16).to_le_bytes());
+                pos += len;
+                literals_from = pos;
+                continue;
+            }
+        }
+        pos += 1;
+    }
+    push_literals(&mut out, &input[literals_from..]);
+    out
+}
+
+/// Returns `None` if `input` is not a well-formed stream produced by
+/// `compress`.
+pub fn decompress(input: &[u8]) -> Option<Vec<u8>> {
+    let (len, mut rest) = input.split_first_chunk::<4>()?;
+    let len = u32::from_le_bytes(*len) as usize;
+    // The length header is untrusted, so don't let it alone size the buffer
+    let mut out = Vec::with_capacity(len.min(input.len() * MAX_MATCH));
+    while let Some((&control, tail)) = rest.split_first() {
+        if (control as usize) < MAX_LITERALS {
+            let count = control as usize + 1;
+            if tail.len() < count {
+                return None;
+            }
+            out.extend_from_slice(&tail[..count]);
+            rest = &tail[count..];
+        } else {
+            let (distance, tail) = tail.split_first_chunk::<2>()?;
+            let distance = u16::from_le_bytes(*distance) as usize;
+            if distance == 0 || distance > out.len() {
+                return None;
+            }
+            // Copy byte by byte: a match may overlap the bytes it produces
+            let start = out.len() - distance;
+            for i in 0..(control & 0x7f) as usize + MIN_MATCH {
+                out.push(out[start + i]);
+            }
+            rest = tail;
+        }
+        if out.len() > len {
+            return None;
+        }
+    }
+    (out.len() == len).then_some(out)
+}
+
+fn push_literals(out: &mut Vec<u8>, literals: &[u8]) {
+    for chunk in literals.chunks(MAX_LITERALS) {
+        out.push((chunk.len() - 1) as u8);
+        out.extend_from_slice(chunk);
+    }
+}
+
+fn hash(bytes: &[u8]) -> usize {
+    let word = u32::from_le_bytes(bytes.try_into().unwrap());
+    (word.wrapping_mul(0x9E37_79B1) >> (32 - HASH_BITS)) as usize
+}
+
+/// Borsh-serializes `T` and stores the compressed bytes instead.
+#[derive(Clone, Default)]
+pub struct Compressed<T>(pub T);
+
+impl<T> Deref for Compressed<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Compressed<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: BorshSerialize> BorshSerialize for Compressed<T> {
+    fn serialize<W: io::Write>(&self, writer: &mut W) -> io::Result<()> {
+        compress(&borsh::to_vec(&self.0)?).serialize(writer)
+    }
+}
+
+impl<T: BorshDeserialize> BorshDeserialize for Compressed<T> {
+    fn deserialize_reader<R: io::Read>(reader: &mut R) -> io::Result<Self> {
+        let bytes = decompress(&Vec::<u8>::deserialize_reader(reader)?).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidData, "corrupt compressed value")
+        })?;
+        borsh::from_slice(&bytes).map(Compressed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roundtrips() {
+        let pattern: Vec<u8> = (0..5000).map(|i| i as u8).collect();
+        let text = b"abcabcabcabcd the quick brown fox, the quick brown fox".to_vec();
+        for input in [vec![], vec![7], b"abc".to_vec(), text, pattern] {
+            assert_eq!(decompress(&compress(&input)), Some(input));
+        }
+    }
+
+    #[test]
+    fn shrinks_repetitive_values() {
+        let vector: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        assert!(compress(&vector).len() < 300);
+        assert!(compress(&[0; 1000]).len() < 40);
+    }
+
+    #[test]
+    fn rejects_corrupt_streams() {
+        let compressed = compress(&[1, 2, 3, 4, 1, 2, 3, 4, 1, 2, 3, 4]);
+        assert_eq!(decompress(&compressed[..compressed.len() - 1]), None);
+        assert_eq!(decompress(&[2, 0, 0, 0, 0x80, 1, 0]), None);
+        assert_eq!(decompress(&[9, 0, 0, 0, 0, 1]), None);
+    }
+
+    #[test]
+    fn wraps_borsh_values() {
+        let value = Compressed(vec![5u8; 300]);
+        let bytes = borsh::to_vec(&value).unwrap();
+        assert!(bytes.len() < 300);
+        let decoded: Compressed<Vec<u8>> = borsh::from_slice(&bytes).unwrap();
+        assert_eq!(*decoded, *value);
+    }
+}
@@ -0,0 +1,602 @@
+//! Batch Dutch auctions: `units` identical units offered at a price that
+//! falls linearly from `start_price` to `floor_price` at the end time.
+//! Buyers take units at the current price with `buy`, and the auction ends
+//! as soon as the units sell out or the price reaches the floor. With
+//! `clearing_price` set, everyone pays the last, lowest price in the end and
+//! gets the difference back from `claim_dutch_refund`.
+//!
+//! The terms, the inventory and each buyer's purchase have keys of their
+//! own, so `buy` only rewrites the inventory and that buyer's record.
+
+use near_sdk::json_types::U64;
+use near_sdk::{assert_one_yocto, env, near, require, AccountId, NearToken, Promise};
+
+use crate::error::AuctionError;
+use crate::events::AuctionEvent;
+use crate::storage::{key, StorageCell, StorageKey, WriteBackSession};
+use crate::{math, pause, refunds, storage_management, Contract, ContractExt};
+
+/// Most bytes one `buy` adds: a first purchase record and its two records in
+/// the key index, which makes 422 bytes for the longest account id.
+pub const PURCHASE_BYTES: u64 = 450;
+
+/// Most bytes `start_dutch_auction` adds: the terms and inventory of the
+/// auction, the auction count for the first one and their key index
+/// records, which makes 963 bytes for the longest account id.
+pub const AUCTION_BYTES: u64 = 1000;
+
+#[near(serializers = [json])]
+pub struct DutchAuctionArgs {
+    pub units: u32,
+    pub start_price: NearToken,
+    pub floor_price: NearToken,
+    pub end_time: U64,
+    pub clearing_price: bool,
+}
+
+/// The terms of an auction, fixed when it starts.
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct DutchAuction {
+    pub seller: AccountId,
+    pub units: u32,
+    pub start_price: NearToken,
+    pub floor_price: NearToken,
+    pub start_time: U64,
+    pub auction_end_time: U64,
+    pub clearing_price: bool,
+    pub settled: bool,
+}
+
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct DutchInventory {
+    pub remaining: u32,
+    /// Price of the latest purchase, the lowest so far.
+    pub last_price: Option<NearToken>,
+    /// Everything buyers have paid, before any clearing-price refunds.
+    pub proceeds: NearToken,
+    /// Clearing-price refunds buyers have claimed so far.
+    pub refunded: NearToken,
+}
+
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct DutchPurchase {
+    pub units: u32,
+    pub paid: NearToken,
+}
+
+fn auction_key(auction_id: u32) -> Vec<u8> {
+    key!("auction", "dutch", auction_id)
+}
+
+pub(crate) fn inventory_key(auction_id: u32) -> Vec<u8> {
+    key!("auction", "dutch", auction_id, "inventory")
+}
+
+fn purchase_key(auction_id: u32, account_id: &AccountId) -> Vec<u8> {
+    key!("auction", "dutch", auction_id, "purchase", account_id)
+}
+
+/// What the contract still holds across all Dutch auctions.
+pub fn held() -> NearToken {
+    let count = StorageCell::<u32>::new(StorageKey::DutchAuctionCount)
+        .try_get()
+        .copied()
+        .unwrap_or(0);
+    math::sum((0..count).map(held_for)).unwrap_or_else(|err| err.panic())
+}
+
+/// What the contract still holds for auction `auction_id`: everything buyers
+/// paid, less the refunds claimed so far and, once settled, the seller's
+/// share. What is left after settling is owed back to buyers.
+fn held_for(auction_id: u32) -> NearToken {
+    let auction = StorageCell::<DutchAuction>::new(auction_key(auction_id));
+    let Some(auction) = auction.try_get() else {
+        return NearToken::from_yoctonear(0);
+    };
+    let inventory = StorageCell::<DutchInventory>::new(inventory_key(auction_id));
+    let inventory = inventory.get();
+    let seller = if auction.settled {
+        auction.seller_amount(inventory)
+    } else {
+        NearToken::from_yoctonear(0)
+    };
+    // Both are parts of `proceeds`, so neither can go out of range
+    inventory
+        .proceeds
+        .saturating_sub(seller.saturating_add(inventory.refunded))
+}
+
+/// Sets the refunds claimed from auction `auction_id` before its inventory
+/// tracked them: what buyers paid above the clearing price, less what the
+/// purchase records still on file are owed. Walks `indexed_keys`, so it is
+/// only meant for migrations.
+pub(crate) fn backfill_refunded(auction_id: u32, indexed_keys: &[Vec<u8>]) {
+    let auction = StorageCell::<DutchAuction>::new(auction_key(auction_id));
+    let Some(auction) = auction.try_get() else {
+        return;
+    };
+    let mut inventory = StorageCell::<DutchInventory>::new(inventory_key(auction_id));
+    let inventory = inventory.get_mut();
+    if !auction.has_ended(inventory) {
+        return;
+    }
+    let purchases = key!("auction", "dutch", auction_id, "purchase");
+    let outstanding = indexed_keys
+        .iter()
+        .filter(|key| key.starts_with(&purchases))
+        .map(|key| auction.refund_owed(inventory, StorageCell::new(key.clone()).get()));
+    let outstanding = math::sum(outstanding).unwrap_or_else(|err| err.panic());
+    let above_clearing = inventory
+        .proceeds
+        .saturating_sub(auction.seller_amount(inventory));
+    inventory.refunded = above_clearing.saturating_sub(outstanding);
+}
+
+fn load(auction_id: u32) -> (StorageCell<DutchAuction>, StorageCell<DutchInventory>) {
+    let auction = StorageCell::<DutchAuction>::new(auction_key(auction_id));
+    require!(
+        auction.try_get().is_some(),
+        AuctionError::NoSuchAuction.as_str()
+    );
+    (auction, StorageCell::new(inventory_key(auction_id)))
+}
+
+impl DutchAuction {
+    /// The price at `timestamp`. The drop rounds down, so the price never
+    /// falls faster than the line from start to floor.
+    pub fn price_at(&self, timestamp: u64) -> NearToken {
+        if timestamp >= self.auction_end_time.0 {
+            return self.floor_price;
+        }
+        let elapsed = u128::from(timestamp.saturating_sub(self.start_time.0));
+        let duration = u128::from(self.auction_end_time.0 - self.start_time.0);
+        let spread = self.start_price.as_yoctonear() - self.floor_price.as_yoctonear();
+        // `spread * elapsed / duration`, split so that neither product can
+        // overflow: `elapsed <= duration` and both fit in a u64
+        let drop = spread / duration * elapsed + spread % duration * elapsed / duration;
+        NearToken::from_yoctonear(self.start_price.as_yoctonear() - drop)
+    }
+
+    pub fn has_ended(&self, inventory: &DutchInventory) -> bool {
+        inventory.remaining == 0 || env::block_timestamp() >= self.auction_end_time.0
+    }
+
+    /// What a unit finally costs: the last price with a clearing price,
+    /// `None` when every buyer keeps the price they paid.
+    fn clearing_price(&self, inventory: &DutchInventory) -> Option<NearToken> {
+        if self.clearing_price {
+            inventory.last_price
+        } else {
+            None
+        }
+    }
+
+    /// What settling pays the seller: everything buyers paid, or the
+    /// clearing price for every unit sold.
+    fn seller_amount(&self, inventory: &DutchInventory) -> NearToken {
+        match self.clearing_price(inventory) {
+            Some(price) => math::times(price, self.units - inventory.remaining)
+                .unwrap_or_else(|err| err.panic()),
+            None => inventory.proceeds,
+        }
+    }
+
+    /// What `purchase` paid above the clearing price, if there is one.
+    fn refund_owed(&self, inventory: &DutchInventory, purchase: &DutchPurchase) -> NearToken {
+        match self.clearing_price(inventory) {
+            // Every unit was bought at the clearing price or above
+            Some(price) => purchase
+                .paid
+                .saturating_sub(price.saturating_mul(purchase.units.into())),
+            None => NearToken::from_yoctonear(0),
+        }
+    }
+}
+
+#[near]
+impl Contract {
+    /// Starts an auction of `args.units` that the caller sells, and returns
+    /// its id. The price starts falling right away. Unless the seller's
+    /// storage balance covers the auction's records, the deposit has to;
+    /// whatever is left of it is sent back.
+    #[payable]
+    pub fn start_dutch_auction(&mut self, args: DutchAuctionArgs) -> u32 {
+        let _session = WriteBackSession::begin();
+        pause::assert_not_paused();
+
+        require!(
+            args.units > 0
+                && args.floor_price <= args.start_price
+                && args.end_time.0 > env::block_timestamp(),
+            AuctionError::InvalidDutchAuction.as_str()
+        );
+        let seller = env::predecessor_account_id();
+        let auction = DutchAuction {
+            seller: seller.clone(),
+            units: args.units,
+            start_price: args.start_price,
+            floor_price: args.floor_price,
+            start_time: U64(env::block_timestamp()),
+            auction_end_time: args.end_time,
+            clearing_price: args.clearing_price,
+            settled: false,
+        };
+
+        let prepaid = storage_management::required_deposit(&seller, AUCTION_BYTES);
+        require!(
+            env::attached_deposit() >= prepaid,
+            AuctionError::DepositBelowStorageCost.as_str()
+        );
+        let (auction_id, unused) = storage_management::charge_deposit(&seller, prepaid, || {
+            let mut count = StorageCell::<u32>::new(StorageKey::DutchAuctionCount);
+            let auction_id = count.try_get().copied().unwrap_or(0);
+            count.set(auction_id + 1);
+
+            AuctionEvent::DutchAuctionStarted {
+                auction_id,
+                seller: seller.clone(),
+                units: auction.units,
+                start_price: auction.start_price,
+                floor_price: auction.floor_price,
+                auction_end_time: auction.auction_end_time,
+            }
+            .emit();
+            StorageCell::new(inventory_key(auction_id)).set(DutchInventory {
+                remaining: auction.units,
+                last_price: None,
+                proceeds: NearToken::from_yoctonear(0),
+                refunded: NearToken::from_yoctonear(0),
+            });
+            StorageCell::new(auction_key(auction_id)).set(auction);
+            auction_id
+        });
+
+        // Whatever wasn't needed for storage, plus anything attached on top
+        let back = unused.saturating_add(env::attached_deposit().saturating_sub(prepaid));
+        if !back.is_zero() {
+            Promise::new(seller).transfer(back).detach();
+        }
+        auction_id
+    }
+
+    /// Buys `quantity` units at the current price. The deposit must cover
+    /// them plus, unless the buyer's storage balance does, the storage of
+    /// their purchase record; whatever is left is sent back.
+    #[payable]
+    pub fn buy(&mut self, auction_id: u32, quantity: u32) -> Option<Promise> {
+        let _session = WriteBackSession::begin();
+        pause::assert_not_paused();
+
+        let (auction, mut inventory) = load(auction_id);
+        let auction = auction.get();
+        let inventory = inventory.get_mut();
+        require!(
+            !auction.has_ended(inventory),
+            AuctionError::AuctionEnded.as_str()
+        );
+        require!(
+            quantity > 0 && quantity <= inventory.remaining,
+            AuctionError::NotEnoughUnits.as_str()
+        );
+
+        let buyer = env::predecessor_account_id();
+        let price = auction.price_at(env::block_timestamp());
+        let cost = math::times(price, quantity).unwrap_or_else(|err| err.panic());
+        let prepaid = storage_management::required_deposit(&buyer, PURCHASE_BYTES);
+        let available = math::increment(prepaid, env::attached_deposit())
+            .unwrap_or_else(|_| AuctionError::DepositBelowStorageCost.panic());
+        let excess = math::increment(cost, available)
+            .unwrap_or_else(|_| AuctionError::DepositBelowPrice.panic());
+        let proceeds = math::add(inventory.proceeds, cost).unwrap_or_else(|err| err.panic());
+
+        inventory.remaining -= quantity;
+        inventory.last_price = Some(price);
+        inventory.proceeds = proceeds;
+        let ((), unused) = storage_management::charge_deposit(&buyer, prepaid, || {
+            let mut purchase = StorageCell::<DutchPurchase>::new(purchase_key(auction_id, &buyer));
+            let purchase = purchase.get_mut_or_insert_with(|| DutchPurchase {
+                units: 0,
+                paid: NearToken::from_yoctonear(0),
+            });
+            purchase.units += quantity;
+            // Never more than `proceeds`, which didn't overflow
+            purchase.paid = purchase.paid.saturating_add(cost);
+        });
+
+        // Both came out of the deposit, so together they can't overflow
+        let back = unused.saturating_add(excess);
+        (!back.is_zero()).then(|| Promise::new(buyer).transfer(back))
+    }
+
+    /// Pays the seller once the auction has ended.
+    #[payable]
+    pub fn settle_dutch_auction(&mut self, auction_id: u32) -> Promise {
+        let _session = WriteBackSession::begin();
+
+        assert_one_yocto();
+        let (mut auction, inventory) = load(auction_id);
+        let inventory = inventory.get();
+        let auction = auction.get_mut();
+        require!(
+            auction.has_ended(inventory),
+            AuctionError::AuctionNotEnded.as_str()
+        );
+        require!(!auction.settled, AuctionError::AlreadyClaimed.as_str());
+        auction.settled = true;
+
+        let amount = auction.seller_amount(inventory);
+        Promise::new(auction.seller.clone()).transfer(amount)
+    }
+
+    /// Sends the caller what they paid above the clearing price once the
+    /// auction has ended, and drops their purchase record.
+    pub fn claim_dutch_refund(&mut self, auction_id: u32) -> Promise {
+        let _session = WriteBackSession::begin();
+
+        let (auction, mut inventory) = load(auction_id);
+        let (auction, inventory) = (auction.get(), inventory.get_mut());
+        require!(
+            auction.has_ended(inventory),
+            AuctionError::AuctionNotEnded.as_str()
+        );
+        let buyer = env::predecessor_account_id();
+        let mut purchase = StorageCell::<DutchPurchase>::new(purchase_key(auction_id, &buyer));
+        let owed = purchase
+            .try_get()
+            .map_or(NearToken::from_yoctonear(0), |purchase| {
+                auction.refund_owed(inventory, purchase)
+            });
+        require!(!owed.is_zero(), AuctionError::NothingOwed.as_str());
+
+        // Never more than `proceeds`, which every refund comes out of
+        inventory.refunded = inventory.refunded.saturating_add(owed);
+        purchase.remove();
+        refunds::refund(buyer, owed)
+    }
+
+    pub fn get_dutch_auction(&self, auction_id: u32) -> Option<DutchAuction> {
+        StorageCell::<DutchAuction>::new(auction_key(auction_id))
+            .try_get()
+            .cloned()
+    }
+
+    pub fn get_dutch_inventory(&self, auction_id: u32) -> Option<DutchInventory> {
+        StorageCell::<DutchInventory>::new(inventory_key(auction_id))
+            .try_get()
+            .cloned()
+    }
+
+    /// What one unit costs right now, `None` once the auction has ended.
+    pub fn get_dutch_price(&self, auction_id: u32) -> Option<NearToken> {
+        let auction = self.get_dutch_auction(auction_id)?;
+        let inventory = self.get_dutch_inventory(auction_id)?;
+        (!auction.has_ended(&inventory)).then(|| auction.price_at(env::block_timestamp()))
+    }
+
+    pub fn get_dutch_purchase(
+        &self,
+        auction_id: u32,
+        account_id: AccountId,
+    ) -> Option<DutchPurchase> {
+        StorageCell::<DutchPurchase>::new(purchase_key(auction_id, &account_id))
+            .try_get()
+            .cloned()
+    }
+
+    /// Number of auctions started with `start_dutch_auction`; their ids are
+    /// `0..count`.
+    pub fn get_dutch_auction_count(&self) -> u32 {
+        StorageCell::<u32>::new(StorageKey::DutchAuctionCount)
+            .try_get()
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::mock::MockAction;
+    use near_sdk::test_utils::{get_created_receipts, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    use super::*;
+    use crate::storage;
+    use crate::storage_management::storage_cost;
+    use crate::test_utils::new_contract;
+
+    fn near(amount: u128) -> NearToken {
+        NearToken::from_near(amount)
+    }
+
+    fn call_as(account_id: &str, deposit: NearToken, block_timestamp: u64) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account_id.parse().unwrap())
+            .attached_deposit(deposit)
+            .block_timestamp(block_timestamp)
+            .build());
+    }
+
+    /// Amounts transferred to `account_id` by the last call.
+    fn transfers_to(account_id: &str) -> Vec<NearToken> {
+        get_created_receipts()
+            .into_iter()
+            .filter(|receipt| receipt.receiver_id.as_str() == account_id)
+            .flat_map(|receipt| receipt.actions)
+            .filter_map(|action| match action {
+                MockAction::Transfer { deposit, .. } => Some(deposit),
+                _ => None,
+            })
+            .collect()
+    }
+
+    fn args(clearing_price: bool) -> DutchAuctionArgs {
+        DutchAuctionArgs {
+            units: 10,
+            start_price: near(10),
+            floor_price: near(2),
+            end_time: U64(800),
+            clearing_price,
+        }
+    }
+
+    /// Ten units from 10 NEAR down to 2 NEAR between 0 and 800.
+    fn start(clearing_price: bool) -> (Contract, u32) {
+        let mut contract = new_contract();
+        call_as("seller.near", storage_cost(AUCTION_BYTES), 0);
+        let auction_id = contract.start_dutch_auction(args(clearing_price));
+        (contract, auction_id)
+    }
+
+    /// Buys `quantity` units at `timestamp`, attaching exactly their price
+    /// plus the storage set aside for the purchase record.
+    fn buy_as(contract: &mut Contract, buyer: &str, quantity: u32, timestamp: u64) {
+        let price = contract.get_dutch_auction(0).unwrap().price_at(timestamp);
+        let deposit = price
+            .saturating_mul(quantity.into())
+            .saturating_add(storage_cost(PURCHASE_BYTES));
+        call_as(buyer, deposit, timestamp);
+        let _ = contract.buy(0, quantity);
+    }
+
+    #[test]
+    fn starting_charges_the_seller_for_storage() {
+        let (_, auction_id) = start(false);
+        // Only what the records took is kept, the rest goes back
+        let unused = transfers_to("seller.near");
+        assert_eq!(unused.len(), 1);
+        assert!(unused[0] < storage_cost(AUCTION_BYTES));
+        assert_eq!(auction_id, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DEPOSIT_BELOW_STORAGE_COST")]
+    fn starting_without_a_deposit_or_storage_balance_is_rejected() {
+        let mut contract = new_contract();
+        call_as("seller.near", NearToken::from_yoctonear(0), 0);
+        contract.start_dutch_auction(args(false));
+    }
+
+    #[test]
+    fn price_falls_linearly_to_the_floor() {
+        let (contract, auction_id) = start(false);
+        let auction = contract.get_dutch_auction(auction_id).unwrap();
+        assert_eq!(auction.price_at(0), near(10));
+        assert_eq!(auction.price_at(400), near(6));
+        assert_eq!(auction.price_at(100), near(9));
+        assert_eq!(auction.price_at(800), near(2));
+        assert_eq!(auction.price_at(5000), near(2));
+
+        // A drop that doesn't divide evenly rounds in the seller's favour
+        let odd = DutchAuction {
+            start_price: NearToken::from_yoctonear(u128::MAX),
+            floor_price: NearToken::from_yoctonear(0),
+            auction_end_time: U64(3),
+            ..auction
+        };
+        assert_eq!(
+            odd.price_at(1),
+            NearToken::from_yoctonear(u128::MAX - u128::MAX / 3)
+        );
+    }
+
+    #[test]
+    fn buying_takes_units_at_the_current_price() {
+        let (mut contract, auction_id) = start(false);
+        buy_as(&mut contract, "bob.near", 4, 400);
+
+        let inventory = contract.get_dutch_inventory(auction_id).unwrap();
+        assert_eq!(inventory.remaining, 6);
+        assert_eq!(inventory.last_price, Some(near(6)));
+        assert_eq!(inventory.proceeds, near(24));
+        assert_eq!(
+            contract.get_dutch_purchase(auction_id, "bob.near".parse().unwrap()),
+            Some(DutchPurchase {
+                units: 4,
+                paid: near(24),
+            })
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_ENOUGH_UNITS")]
+    fn buying_more_than_is_left_is_rejected() {
+        let (mut contract, _) = start(false);
+        buy_as(&mut contract, "bob.near", 11, 400);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_DEPOSIT_BELOW_PRICE")]
+    fn deposit_must_cover_every_unit() {
+        let (mut contract, auction_id) = start(false);
+        call_as("bob.near", near(11), 0);
+        let _ = contract.buy(auction_id, 2);
+    }
+
+    #[test]
+    fn selling_out_ends_the_auction_early() {
+        let (mut contract, auction_id) = start(false);
+        buy_as(&mut contract, "bob.near", 10, 100);
+        assert_eq!(contract.get_dutch_price(auction_id), None);
+
+        call_as("seller.near", NearToken::from_yoctonear(1), 200);
+        let _ = contract.settle_dutch_auction(auction_id);
+        assert!(contract.get_dutch_auction(auction_id).unwrap().settled);
+        assert_eq!(transfers_to("seller.near"), vec![near(90)]);
+    }
+
+    #[test]
+    fn clearing_price_refunds_what_earlier_buyers_paid_above_it() {
+        let (mut contract, auction_id) = start(true);
+        buy_as(&mut contract, "bob.near", 2, 0);
+        buy_as(&mut contract, "alice.near", 3, 400);
+
+        // The clearing price is only known once the auction has ended
+        call_as("bob.near", NearToken::from_yoctonear(0), 900);
+        assert_eq!(held(), near(38));
+        let _ = contract.claim_dutch_refund(auction_id);
+        assert_eq!(
+            contract.get_dutch_inventory(auction_id).unwrap().refunded,
+            near(8)
+        );
+        assert_eq!(
+            contract.get_dutch_purchase(auction_id, "bob.near".parse().unwrap()),
+            None
+        );
+        assert_eq!(transfers_to("bob.near"), vec![near(8)]);
+
+        call_as("seller.near", NearToken::from_yoctonear(1), 900);
+        let _ = contract.settle_dutch_auction(auction_id);
+        assert_eq!(transfers_to("seller.near"), vec![near(30)]);
+        assert!(held().is_zero());
+    }
+
+    #[test]
+    fn auction_bytes_cover_the_first_auction() {
+        let mut contract = new_contract();
+        let seller = "a".repeat(64);
+        call_as(&seller, storage_cost(AUCTION_BYTES), 0);
+
+        let before = env::storage_usage();
+        contract.start_dutch_auction(args(true));
+        let added = env::storage_usage() - before;
+        assert!(added <= AUCTION_BYTES, "{added} bytes");
+    }
+
+    #[test]
+    fn purchase_bytes_cover_a_first_purchase_for_the_longest_account_id() {
+        let (_, auction_id) = start(false);
+        let buyer: AccountId = "a".repeat(64).parse().unwrap();
+
+        let before = env::storage_usage();
+        StorageCell::new(purchase_key(auction_id, &buyer)).set(DutchPurchase {
+            units: u32::MAX,
+            paid: NearToken::from_yoctonear(u128::MAX),
+        });
+        storage::flush();
+        let added = env::storage_usage() - before;
+        assert!(added <= PURCHASE_BYTES, "{added} bytes");
+    }
+}
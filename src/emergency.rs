@@ -0,0 +1,201 @@
+//! Last-resort recovery of the contract balance. The guardian announces an
+//! emergency, and once the stored delay has passed without anyone cancelling
+//! it, can sweep everything not needed for storage to the recovery account.
+
+use near_sdk::json_types::U64;
+use near_sdk::{assert_one_yocto, env, near, require, AccountId, Promise};
+
+use crate::error::AuctionError;
+use crate::events::AuctionEvent;
+use crate::owner::only_owner;
+use crate::storage::{StorageCell, StorageKey, WriteBackSession};
+use crate::{Contract, ContractExt};
+
+/// Delay between `initiate_emergency` and `emergency_withdraw` written by
+/// `init`: seven days, in nanoseconds.
+pub const EMERGENCY_DELAY: U64 = U64(7 * 24 * 60 * 60 * 1_000_000_000);
+
+#[near(serializers = [json, borsh])]
+#[derive(Clone)]
+pub struct Emergency {
+    pub recovery_account: AccountId,
+    pub initiated_at: U64,
+    pub unlocks_at: U64,
+}
+
+fn only_guardian() {
+    let guardian = StorageCell::<AccountId>::new(StorageKey::Guardian);
+    require!(
+        env::predecessor_account_id() == *guardian.get(),
+        AuctionError::NotGuardian.as_str()
+    );
+}
+
+#[near]
+impl Contract {
+    /// Starts the timelock after which the guardian may sweep the balance to
+    /// `recovery_account`.
+    #[payable]
+    pub fn initiate_emergency(&mut self, recovery_account: AccountId) {
+        let _session = WriteBackSession::begin();
+
+        assert_one_yocto();
+        only_guardian();
+        let mut emergency = StorageCell::<Emergency>::new(StorageKey::Emergency);
+        require!(
+            emergency.try_get().is_none(),
+            AuctionError::EmergencyInProgress.as_str()
+        );
+
+        let delay = *StorageCell::<U64>::new(StorageKey::EmergencyDelay).get();
+        let initiated_at = env::block_timestamp();
+        let pending = Emergency {
+            recovery_account,
+            initiated_at: U64(initiated_at),
+            unlocks_at: U64(initiated_at.saturating_add(delay.0)),
+        };
+        AuctionEvent::EmergencyInitiated {
+            recovery_account: pending.recovery_account.clone(),
+            unlocks_at: pending.unlocks_at,
+        }
+        .emit();
+        emergency.set(pending);
+    }
+
+    /// Calls off a pending emergency. The owner can do this too, so a
+    /// compromised guardian can be stopped during the delay.
+    #[payable]
+    pub fn cancel_emergency(&mut self) {
+        let _session = WriteBackSession::begin();
+
+        assert_one_yocto();
+        let guardian = StorageCell::<AccountId>::new(StorageKey::Guardian);
+        if env::predecessor_account_id() != *guardian.get() {
+            only_owner();
+        }
+        let mut emergency = StorageCell::<Emergency>::new(StorageKey::Emergency);
+        require!(
+            emergency.try_get().is_some(),
+            AuctionError::NoEmergency.as_str()
+        );
+
+        emergency.remove();
+        AuctionEvent::EmergencyCancelled {
+            by: env::predecessor_account_id(),
+        }
+        .emit();
+    }
+
+    /// Sends the whole balance, minus what storage staking needs, to the
+    /// recovery account once the timelock has elapsed.
+    #[payable]
+    pub fn emergency_withdraw(&mut self) -> Promise {
+        let _session = WriteBackSession::begin();
+
+        assert_one_yocto();
+        only_guardian();
+        let mut emergency = StorageCell::<Emergency>::new(StorageKey::Emergency);
+        let Some(pending) = emergency.try_get().cloned() else {
+            AuctionError::NoEmergency.panic();
+        };
+        require!(
+            env::block_timestamp() >= pending.unlocks_at.0,
+            AuctionError::TimelockPending.as_str()
+        );
+        emergency.remove();
+
+        let storage_cost = env::storage_byte_cost().saturating_mul(env::storage_usage().into());
+        let amount = env::account_balance().saturating_sub(storage_cost);
+        AuctionEvent::EmergencyWithdrawn {
+            recovery_account: pending.recovery_account.clone(),
+            amount,
+        }
+        .emit();
+        Promise::new(pending.recovery_account).transfer(amount)
+    }
+
+    pub fn get_guardian(&self) -> AccountId {
+        StorageCell::<AccountId>::new(StorageKey::Guardian)
+            .get()
+            .clone()
+    }
+
+    pub fn get_emergency(&self) -> Option<Emergency> {
+        StorageCell::<Emergency>::new(StorageKey::Emergency)
+            .try_get()
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::{testing_env, NearToken};
+
+    use super::*;
+    use crate::test_utils::new_contract;
+
+    fn call_at(account_id: &str, block_timestamp: u64) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account_id.parse().unwrap())
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .block_timestamp(block_timestamp)
+            .build());
+    }
+
+    fn init() -> Contract {
+        call_at("owner.near", 0);
+        Contract::init(
+            U64(1000),
+            "auctioneer.near".parse().unwrap(),
+            Some("guardian.near".parse().unwrap()),
+            None,
+        )
+    }
+
+    #[test]
+    fn guardian_withdraws_after_the_delay() {
+        let mut contract = init();
+        call_at("guardian.near", 100);
+        contract.initiate_emergency("recovery.near".parse().unwrap());
+        let emergency = contract.get_emergency().unwrap();
+        assert_eq!(emergency.unlocks_at, U64(100 + EMERGENCY_DELAY.0));
+
+        call_at("guardian.near", emergency.unlocks_at.0);
+        let _ = contract.emergency_withdraw();
+        assert!(contract.get_emergency().is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_TIMELOCK_PENDING")]
+    fn withdrawal_waits_for_the_timelock() {
+        let mut contract = init();
+        call_at("guardian.near", 100);
+        contract.initiate_emergency("recovery.near".parse().unwrap());
+        call_at("guardian.near", 100 + EMERGENCY_DELAY.0 - 1);
+        let _ = contract.emergency_withdraw();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NO_EMERGENCY")]
+    fn owner_can_cancel_an_emergency() {
+        let mut contract = init();
+        call_at("guardian.near", 100);
+        contract.initiate_emergency("recovery.near".parse().unwrap());
+        call_at("owner.near", 200);
+        contract.cancel_emergency();
+
+        call_at("guardian.near", 100 + EMERGENCY_DELAY.0);
+        let _ = contract.emergency_withdraw();
+    }
+
+    #[test]
+    fn guardian_defaults_to_the_owner() {
+        call_at("owner.near", 0);
+        let contract = new_contract();
+        assert_eq!(
+            contract.get_guardian(),
+            "owner.near".parse::<AccountId>().unwrap()
+        );
+    }
+}
@@ -0,0 +1,121 @@
+//! Every value written through `storage` is wrapped as
+//! `[version: u8][crc32(payload): u32 le][payload]`, so a partially applied
+//! manual patch shows up as an integrity failure instead of an opaque
+//! deserialization panic somewhere downstream.
+//!
+//! Values written with `ttl::write_with_ttl` carry version 2 instead, with
+//! the same layout, so only they pay for looking up their expiry on reads.
+
+const ENVELOPE_VERSION: u8 = 1;
+const EXPIRING_VERSION: u8 = 2;
+const HEADER_LEN: usize = 5;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum IntegrityError {
+    Truncated,
+    UnknownVersion(u8),
+    ChecksumMismatch,
+}
+
+impl std::fmt::Display for IntegrityError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            IntegrityError::Truncated => write!(f, "value is shorter than its envelope"),
+            IntegrityError::UnknownVersion(version) => {
+                write!(f, "unknown envelope version {version}")
+            }
+            IntegrityError::ChecksumMismatch => write!(f, "checksum mismatch"),
+        }
+    }
+}
+
+pub fn seal(payload: &[u8]) -> Vec<u8> {
+    seal_as(ENVELOPE_VERSION, payload)
+}
+
+/// Seals a value that has an expiry record next to it.
+pub fn seal_expiring(payload: &[u8]) -> Vec<u8> {
+    seal_as(EXPIRING_VERSION, payload)
+}
+
+fn seal_as(version: u8, payload: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.push(version);
+    bytes.extend_from_slice(&crc32(payload).to_le_bytes());
+    bytes.extend_from_slice(payload);
+    bytes
+}
+
+pub fn open(bytes: &[u8]) -> Result<&[u8], IntegrityError> {
+    if bytes.len() < HEADER_LEN {
+        return Err(IntegrityError::Truncated);
+    }
+    if bytes[0] != ENVELOPE_VERSION && bytes[0] != EXPIRING_VERSION {
+        return Err(IntegrityError::UnknownVersion(bytes[0]));
+    }
+    let checksum = u32::from_le_bytes(bytes[1..HEADER_LEN].try_into().unwrap());
+    let payload = &bytes[HEADER_LEN..];
+    if crc32(payload) != checksum {
+        return Err(IntegrityError::ChecksumMismatch);
+    }
+    Ok(payload)
+}
+
+/// The payload of bytes that were sealed here or already passed `open`.
+pub fn payload(sealed: &[u8]) -> &[u8] {
+    &sealed[HEADER_LEN..]
+}
+
+pub fn is_expiring(sealed: &[u8]) -> bool {
+    sealed.first() == Some(&EXPIRING_VERSION)
+}
+
+/// CRC-32 (IEEE), computed bitwise to keep the wasm free of lookup tables.
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn crc32_matches_reference() {
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn detects_tampering() {
+        let sealed = seal(b"payload");
+        assert_eq!(open(&sealed), Ok(&b"payload"[..]));
+
+        let mut flipped = sealed.clone();
+        *flipped.last_mut().unwrap() ^= 1;
+        assert_eq!(open(&flipped), Err(IntegrityError::ChecksumMismatch));
+        assert_eq!(
+            open(&sealed[..sealed.len() - 1]),
+            Err(IntegrityError::ChecksumMismatch)
+        );
+        assert_eq!(open(&sealed[..3]), Err(IntegrityError::Truncated));
+        assert_eq!(
+            open(&[9, 0, 0, 0, 0]),
+            Err(IntegrityError::UnknownVersion(9))
+        );
+    }
+
+    #[test]
+    fn expiring_values_open_like_any_other() {
+        let sealed = seal_expiring(b"payload");
+        assert_eq!(open(&sealed), Ok(&b"payload"[..]));
+        assert_eq!(payload(&sealed), b"payload");
+        assert!(is_expiring(&sealed));
+        assert!(!is_expiring(&seal(b"payload")));
+    }
+}
@@ -0,0 +1,185 @@
+//! Every reason the contract panics, each with a stable code. A failed call
+//! reports `"ERR_AUCTION_ENDED: Auction has ended"`, so frontends can match
+//! on the code before the colon and leave the sentence to humans. `minimal`
+//! builds panic with the code alone, keeping the sentences out of the wasm.
+//!
+//! Codes are part of the public interface: add new ones freely, but never
+//! rename or reuse one.
+
+use std::fmt;
+
+use near_sdk::env;
+
+macro_rules! auction_errors {
+    ($($variant:ident => $code:literal, $message:literal;)*) => {
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub enum AuctionError {
+            $($variant,)*
+        }
+
+        impl AuctionError {
+            pub const ALL: [AuctionError; [$(AuctionError::$variant),*].len()] =
+                [$(AuctionError::$variant),*];
+
+            pub const fn code(self) -> &'static str {
+                match self {
+                    $(AuctionError::$variant => $code,)*
+                }
+            }
+
+            pub const fn message(self) -> &'static str {
+                match self {
+                    $(AuctionError::$variant => $message,)*
+                }
+            }
+
+            /// What the contract panics with, as `require!` takes it.
+            #[cfg(not(feature = "minimal"))]
+            pub const fn as_str(self) -> &'static str {
+                match self {
+                    $(AuctionError::$variant => concat!($code, ": ", $message),)*
+                }
+            }
+
+            #[cfg(feature = "minimal")]
+            pub const fn as_str(self) -> &'static str {
+                self.code()
+            }
+        }
+    };
+}
+
+auction_errors! {
+    AlreadyInitialized => "ERR_ALREADY_INITIALIZED", "Contract is already initialized";
+    AuctionEnded => "ERR_AUCTION_ENDED", "Auction has ended";
+    AuctionNotEnded => "ERR_AUCTION_NOT_ENDED", "Auction has not ended yet";
+    AlreadyClaimed => "ERR_ALREADY_CLAIMED", "Auction has already been claimed";
+    ClaimPending => "ERR_CLAIM_PENDING", "The claim is waiting for its payout to resolve";
+    BidTooLow => "ERR_BID_TOO_LOW", "You must place a higher bid";
+    DepositBelowStorageCost => "ERR_DEPOSIT_BELOW_STORAGE_COST", "The attached deposit doesn't cover the storage the bid adds";
+    AmountOverflow => "ERR_AMOUNT_OVERFLOW", "The amount is too large to represent in yoctoNEAR";
+    AmountUnderflow => "ERR_AMOUNT_UNDERFLOW", "The amount would be negative";
+    NoOraclePrice => "ERR_NO_ORACLE_PRICE", "The price oracle did not return a price";
+    BelowUsdReserve => "ERR_BELOW_USD_RESERVE", "The bid is below the USD reserve price";
+    EndTimeNotLater => "ERR_END_TIME_NOT_LATER", "The new end time must be later than the current one";
+    EndTimeInPast => "ERR_END_TIME_IN_PAST", "The end time must be in the future";
+    EndTimeTooSoon => "ERR_END_TIME_TOO_SOON", "The end time must be further away than the minimum duration";
+    AuctioneerIsContract => "ERR_AUCTIONEER_IS_CONTRACT", "The auctioneer must not be the contract account";
+    NoSuchAuction => "ERR_NO_SUCH_AUCTION", "No such auction";
+    InvalidAuctionMsg => "ERR_INVALID_AUCTION_MSG", "msg must be a JSON object with an `end_time`";
+    InvalidDutchAuction => "ERR_INVALID_DUTCH_AUCTION", "A Dutch auction needs units, a floor at or below the start price and a future end time";
+    NotEnoughUnits => "ERR_NOT_ENOUGH_UNITS", "Not enough units left to buy that many";
+    DepositBelowPrice => "ERR_DEPOSIT_BELOW_PRICE", "The deposit doesn't cover the units at the current price";
+    MinIncrementAfterBids => "ERR_MIN_INCREMENT_AFTER_BIDS", "The minimum increment can only be configured before the first bid";
+    InvalidMinIncrement => "ERR_INVALID_MIN_INCREMENT", "The minimum increment must be above zero";
+    NoDust => "ERR_NO_DUST", "There is no unaccounted balance to sweep";
+    NotArchivable => "ERR_NOT_ARCHIVABLE", "Only claimed auctions with nothing owed can be archived";
+    AssetMismatch => "ERR_ASSET_MISMATCH", "The auction settles in a different asset";
+    InvalidBidMsg => "ERR_INVALID_BID_MSG", "msg must be a JSON object with an `auction_id`";
+    NotAuctioneer => "ERR_NOT_AUCTIONEER", "Only the auctioneer can call this method";
+    NotAuctioneerOrOwner => "ERR_NOT_AUCTIONEER_OR_OWNER", "Only the auctioneer or the owner can call this method";
+    NotOwner => "ERR_NOT_OWNER", "Only the owner can call this method";
+    NotPendingOwner => "ERR_NOT_PENDING_OWNER", "Only the pending owner can accept ownership";
+    NotGuardian => "ERR_NOT_GUARDIAN", "Only the guardian can call this method";
+    NotBenchmarker => "ERR_NOT_BENCHMARKER", "Only the benchmarker can fill the state";
+    NotApprover => "ERR_NOT_APPROVER", "Only a claim approver can approve the claim";
+    AlreadyApproved => "ERR_ALREADY_APPROVED", "Claim already approved by this account";
+    InvalidThreshold => "ERR_INVALID_THRESHOLD", "Threshold must be between 1 and the number of approvers";
+    NotEnoughApprovals => "ERR_NOT_ENOUGH_APPROVALS", "Claim doesn't have enough approvals";
+    Paused => "ERR_PAUSED", "Contract is paused";
+    PauseUnchanged => "ERR_PAUSE_UNCHANGED", "Contract is already in that state";
+    EmergencyInProgress => "ERR_EMERGENCY_IN_PROGRESS", "An emergency is already in progress";
+    NoEmergency => "ERR_NO_EMERGENCY", "No emergency in progress";
+    TimelockPending => "ERR_TIMELOCK_PENDING", "The emergency timelock has not elapsed yet";
+    NothingOwed => "ERR_NOTHING_OWED", "Nothing is owed to this account";
+    NotRegistered => "ERR_NOT_REGISTERED", "The account is not registered";
+    StorageDepositTooLow => "ERR_STORAGE_DEPOSIT_TOO_LOW", "The attached deposit is less than the minimum storage balance";
+    StorageBalanceTooLow => "ERR_STORAGE_BALANCE_TOO_LOW", "The amount is greater than the available storage balance";
+    StorageNotCovered => "ERR_STORAGE_NOT_COVERED", "The available storage balance doesn't cover what this call stores";
+    NoFailedPayout => "ERR_NO_FAILED_PAYOUT", "No failed payout to retry";
+    InvalidForeignPayout => "ERR_INVALID_FOREIGN_PAYOUT", "The foreign payout's addresses or fees are invalid";
+    NoUnsettledWnear => "ERR_NO_UNSETTLED_WNEAR", "No unsettled wNEAR";
+    WnearNotConfigured => "ERR_WNEAR_NOT_CONFIGURED", "Settlement in wNEAR is not configured";
+    StakingAfterBids => "ERR_STAKING_AFTER_BIDS", "Staking can only be configured before the first bid";
+    PennyAuctionAfterBids => "ERR_PENNY_AUCTION_AFTER_BIDS", "A penny auction can only be configured before the first bid";
+    InvalidPennyAuction => "ERR_INVALID_PENNY_AUCTION", "The increment and countdown must be above zero";
+    StillStaked => "ERR_STILL_STAKED", "The highest bid is still staked";
+    NotStaking => "ERR_NOT_STAKING", "The highest bid is not being staked";
+    NotUnstaking => "ERR_NOT_UNSTAKING", "The highest bid is not being unstaked";
+    TooEarlyToUnstake => "ERR_TOO_EARLY_TO_UNSTAKE", "Too early to unstake";
+    StillUnbonding => "ERR_STILL_UNBONDING", "The staking pool hasn't released the bid yet";
+    UnstakeFailed => "ERR_UNSTAKE_FAILED", "unstake_all failed";
+    WithdrawFailed => "ERR_WITHDRAW_FAILED", "withdraw_all failed";
+    StateNewerThanCode => "ERR_STATE_NEWER_THAN_CODE", "The stored state is newer than this code";
+    MissingCode => "ERR_MISSING_CODE", "Missing contract code";
+    MissingValue => "ERR_MISSING_VALUE", "Missing value in storage";
+    UndecodableValue => "ERR_UNDECODABLE_VALUE", "Cannot deserialize value";
+    IntegrityCheckFailed => "ERR_INTEGRITY_CHECK_FAILED", "Integrity check failed";
+    KeySegmentTooLong => "ERR_KEY_SEGMENT_TOO_LONG", "Key segment is longer than 255 bytes";
+    KeyTooLongForTtl => "ERR_KEY_TOO_LONG_FOR_TTL", "Only keys up to 255 bytes can expire";
+    UnknownBenchmark => "ERR_UNKNOWN_BENCHMARK", "Unknown benchmark";
+    FillRangeOverflow => "ERR_FILL_RANGE_OVERFLOW", "start + count doesn't fit in a u32";
+    BlobTooLarge => "ERR_BLOB_TOO_LARGE", "size_kb * 1024 doesn't fit in a u32";
+}
+
+impl AuctionError {
+    pub fn panic(self) -> ! {
+        env::panic_str(self.as_str())
+    }
+
+    /// Panics with `detail` appended, for errors that name what they're
+    /// about: `"ERR_MISSING_VALUE: Missing value in storage: owner"`.
+    pub fn panic_with(self, detail: impl fmt::Display) -> ! {
+        env::panic_str(&format!("{}: {detail}", self.as_str()))
+    }
+}
+
+impl fmt::Display for AuctionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn codes_are_unique_and_prefixed() {
+        let errors = AuctionError::ALL;
+        for (i, a) in errors.iter().enumerate() {
+            assert!(a.code().starts_with("ERR_"), "{a:?}");
+            for b in &errors[i + 1..] {
+                assert_ne!(a.code(), b.code());
+            }
+        }
+    }
+
+    #[cfg(not(feature = "minimal"))]
+    #[test]
+    fn panics_lead_with_the_code() {
+        assert_eq!(
+            AuctionError::BidTooLow.as_str(),
+            "ERR_BID_TOO_LOW: You must place a higher bid"
+        );
+        assert_eq!(
+            AuctionError::AuctionEnded.to_string(),
+            "ERR_AUCTION_ENDED: Auction has ended"
+        );
+    }
+
+    #[cfg(feature = "minimal")]
+    #[test]
+    #[should_panic(expected = "ERR_MISSING_VALUE: owner")]
+    fn minimal_panics_carry_only_the_code() {
+        assert_eq!(AuctionError::BidTooLow.as_str(), "ERR_BID_TOO_LOW");
+        AuctionError::MissingValue.panic_with("owner");
+    }
+
+    #[cfg(not(feature = "minimal"))]
+    #[test]
+    #[should_panic(expected = "ERR_MISSING_VALUE: Missing value in storage: owner")]
+    fn details_follow_the_message() {
+        AuctionError::MissingValue.panic_with("owner");
+    }
+}
@@ -0,0 +1,63 @@
+//! NEP-297 events logged by the contract, so indexers can follow admin
+//! actions without diffing state.
+
+use near_sdk::json_types::U64;
+use near_sdk::{near, AccountId, NearToken};
+
+#[near(event_json(standard = "auction"))]
+pub enum AuctionEvent {
+    #[event_version("1.0.0")]
+    Paused { by: AccountId },
+
+    #[event_version("1.0.0")]
+    Unpaused { by: AccountId },
+
+    #[event_version("1.0.0")]
+    EndTimeExtended {
+        old_end_time: U64,
+        new_end_time: U64,
+    },
+
+    #[event_version("1.0.0")]
+    AuctioneerChanged {
+        old_auctioneer: AccountId,
+        new_auctioneer: AccountId,
+    },
+
+    #[event_version("1.0.0")]
+    EmergencyInitiated {
+        recovery_account: AccountId,
+        unlocks_at: U64,
+    },
+
+    #[event_version("1.0.0")]
+    EmergencyCancelled { by: AccountId },
+
+    #[event_version("1.0.0")]
+    EmergencyWithdrawn {
+        recovery_account: AccountId,
+        amount: NearToken,
+    },
+
+    #[event_version("1.0.0")]
+    NftAuctionStarted {
+        auction_id: u32,
+        nft_contract: AccountId,
+        token_id: String,
+        sender_id: AccountId,
+        auction_end_time: U64,
+    },
+
+    #[event_version("1.0.0")]
+    NftAuctionArchived { auction_id: u32 },
+
+    #[event_version("1.0.0")]
+    DutchAuctionStarted {
+        auction_id: u32,
+        seller: AccountId,
+        units: u32,
+        start_price: NearToken,
+        floor_price: NearToken,
+        auction_end_time: U64,
+    },
+}
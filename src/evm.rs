@@ -0,0 +1,208 @@
+//! Just enough of Ethereum's wire format for `chain_signatures` to build the
+//! payout it has signed: RLP and unsigned EIP-1559 (type 2) transactions.
+//! The signing hash is what the MPC network signs; a relayer appends the
+//! signature's `y_parity`, `r` and `s` to the same fields to broadcast it.
+
+use near_sdk::env;
+
+/// Selector of `transfer(address,uint256)`.
+const ERC20_TRANSFER: [u8; 4] = [0xa9, 0x05, 0x9c, 0xbb];
+/// EIP-2718 type of an EIP-1559 transaction.
+const EIP1559_TYPE: u8 = 0x02;
+
+/// Parses a `0x`-prefixed, 40 hex digit address.
+pub fn parse_address(address: &str) -> Option<[u8; 20]> {
+    let digits = address.strip_prefix("0x")?.as_bytes();
+    if digits.len() != 40 || !digits.iter().all(u8::is_ascii_hexdigit) {
+        return None;
+    }
+    let mut bytes = [0; 20];
+    for (byte, pair) in bytes.iter_mut().zip(digits.chunks(2)) {
+        // Two ASCII hex digits, so both conversions succeed
+        *byte = u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()?;
+    }
+    Some(bytes)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Eip1559Transaction {
+    pub chain_id: u64,
+    pub nonce: u64,
+    pub max_priority_fee_per_gas: u128,
+    pub max_fee_per_gas: u128,
+    pub gas_limit: u64,
+    pub to: [u8; 20],
+    pub value: u128,
+    pub data: Vec<u8>,
+}
+
+impl Eip1559Transaction {
+    /// Calldata sending `amount` of the ERC-20 the transaction goes to.
+    pub fn erc20_transfer_data(receiver: [u8; 20], amount: u128) -> Vec<u8> {
+        let mut data = ERC20_TRANSFER.to_vec();
+        data.extend_from_slice(&[0; 12]);
+        data.extend_from_slice(&receiver);
+        data.extend_from_slice(&[0; 16]);
+        data.extend_from_slice(&amount.to_be_bytes());
+        data
+    }
+
+    /// `0x02 || rlp([chain_id, nonce, max_priority_fee_per_gas,
+    /// max_fee_per_gas, gas_limit, to, value, data, access_list])`, with an
+    /// empty access list.
+    pub fn unsigned_bytes(&self) -> Vec<u8> {
+        let fields = [
+            rlp_uint(self.chain_id.into()),
+            rlp_uint(self.nonce.into()),
+            rlp_uint(self.max_priority_fee_per_gas),
+            rlp_uint(self.max_fee_per_gas),
+            rlp_uint(self.gas_limit.into()),
+            rlp_bytes(&self.to),
+            rlp_uint(self.value),
+            rlp_bytes(&self.data),
+            rlp_list(&[]),
+        ];
+        let mut bytes = vec![EIP1559_TYPE];
+        bytes.extend(rlp_list(&fields));
+        bytes
+    }
+
+    pub fn signing_hash(&self) -> [u8; 32] {
+        env::keccak256_array(self.unsigned_bytes())
+    }
+}
+
+fn rlp_bytes(bytes: &[u8]) -> Vec<u8> {
+    match bytes {
+        [byte] if *byte < 0x80 => vec![*byte],
+        _ => [length_prefix(bytes.len(), 0x80), bytes.to_vec()].concat(),
+    }
+}
+
+/// Integers are their big-endian bytes without leading zeros, so zero is
+/// the empty string.
+fn rlp_uint(value: u128) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let start = bytes
+        .iter()
+        .position(|&byte| byte != 0)
+        .unwrap_or(bytes.len());
+    rlp_bytes(&bytes[start..])
+}
+
+/// A list of already encoded items.
+fn rlp_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload = items.concat();
+    [length_prefix(payload.len(), 0xc0), payload].concat()
+}
+
+/// `offset + len` for up to 55 bytes, else `offset + 55 + ` the length of
+/// the big-endian length, followed by it.
+fn length_prefix(len: usize, offset: u8) -> Vec<u8> {
+    if len <= 55 {
+        return vec![offset + len as u8];
+    }
+    let len = (len as u64).to_be_bytes();
+    let start = len.iter().position(|&byte| byte != 0).unwrap_or(len.len());
+    let mut prefix = vec![offset + 55 + (len.len() - start) as u8];
+    prefix.extend_from_slice(&len[start..]);
+    prefix
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rlp_matches_the_spec_examples() {
+        assert_eq!(rlp_bytes(b"dog"), b"\x83dog");
+        assert_eq!(
+            rlp_list(&[rlp_bytes(b"cat"), rlp_bytes(b"dog")]),
+            b"\xc8\x83cat\x83dog"
+        );
+        assert_eq!(rlp_bytes(b""), [0x80]);
+        assert_eq!(rlp_list(&[]), [0xc0]);
+        assert_eq!(rlp_uint(0), [0x80]);
+        assert_eq!(rlp_uint(15), [0x0f]);
+        assert_eq!(rlp_uint(1024), [0x82, 0x04, 0x00]);
+        let lorem = b"Lorem ipsum dolor sit amet, consectetur adipisicing elit";
+        assert_eq!(rlp_bytes(lorem), [b"\xb8\x38".as_slice(), lorem].concat());
+    }
+
+    /// The signing data and hash of the example transaction in EIP-155.
+    #[test]
+    fn rlp_matches_the_eip155_example() {
+        let fields = [
+            rlp_uint(9),
+            rlp_uint(20_000_000_000),
+            rlp_uint(21_000),
+            rlp_bytes(&[0x35; 20]),
+            rlp_uint(1_000_000_000_000_000_000),
+            rlp_bytes(&[]),
+            rlp_uint(1),
+            rlp_uint(0),
+            rlp_uint(0),
+        ];
+        let expected = [
+            [
+                0xec, 0x09, 0x85, 0x04, 0xa8, 0x17, 0xc8, 0x00, 0x82, 0x52, 0x08, 0x94,
+            ]
+            .as_slice(),
+            &[0x35; 20],
+            &[0x88, 0x0d, 0xe0, 0xb6, 0xb3, 0xa7, 0x64, 0x00, 0x00],
+            &[0x80, 0x01, 0x80, 0x80],
+        ]
+        .concat();
+        assert_eq!(rlp_list(&fields), expected);
+        assert_eq!(
+            env::keccak256_array(&expected),
+            [
+                0xda, 0xf5, 0xa7, 0x79, 0xae, 0x97, 0x2f, 0x97, 0x21, 0x97, 0x30, 0x3d, 0x7b, 0x57,
+                0x47, 0x46, 0xc7, 0xef, 0x83, 0xea, 0xda, 0xc0, 0xf2, 0x79, 0x1a, 0xd2, 0x3d, 0xb9,
+                0x2e, 0x4c, 0x8e, 0x53,
+            ]
+        );
+    }
+
+    #[test]
+    fn transactions_encode_as_type_2() {
+        let transaction = Eip1559Transaction {
+            chain_id: 1,
+            nonce: 0,
+            max_priority_fee_per_gas: 1,
+            max_fee_per_gas: 2,
+            gas_limit: 21_000,
+            to: [0x11; 20],
+            value: 0,
+            data: vec![],
+        };
+        let expected = [
+            [0x02, 0xdf, 0x01, 0x80, 0x01, 0x02, 0x82, 0x52, 0x08, 0x94].as_slice(),
+            &[0x11; 20],
+            &[0x80, 0x80, 0xc0],
+        ]
+        .concat();
+        assert_eq!(transaction.unsigned_bytes(), expected);
+        assert_eq!(transaction.signing_hash(), env::keccak256_array(&expected));
+    }
+
+    #[test]
+    fn erc20_transfers_pad_both_arguments() {
+        let data = Eip1559Transaction::erc20_transfer_data([0xab; 20], 0x0102);
+        assert_eq!(data.len(), 4 + 32 + 32);
+        assert_eq!(data[..4], ERC20_TRANSFER);
+        assert_eq!(data[4..16], [0; 12]);
+        assert_eq!(data[16..36], [0xab; 20]);
+        assert_eq!(data[66..], [0x01, 0x02]);
+    }
+
+    #[test]
+    fn addresses_need_the_prefix_and_40_digits() {
+        let address = parse_address("0x00000000000000000000000000000000deadbeef").unwrap();
+        assert_eq!(address[16..], [0xde, 0xad, 0xbe, 0xef]);
+        assert!(parse_address("00000000000000000000000000000000deadbeef").is_none());
+        assert!(parse_address("0xdeadbeef").is_none());
+        assert!(parse_address("0x00000000000000000000000000000000deadbeeg").is_none());
+        assert!(parse_address("0x+0000000000000000000000000000000deadbeef").is_none());
+    }
+}
@@ -0,0 +1,225 @@
+//! The highest bid, stored in two parts. Every `bid` call compares against
+//! the bidder and amount, so those sit under `highest_bid` on their own; the
+//! timestamps, heights and flags that only views and audits look at live
+//! under `highest_bid_metadata`. Getters join the two on demand.
+//!
+//! The metadata is stored as a `VersionedBidMetadata`. Values written before
+//! it existed are a bare `BidMetadata` and are upgraded when read.
+
+use near_sdk::json_types::U64;
+use near_sdk::{borsh, near, AccountId, NearToken};
+
+use crate::storage::{self, StorageKey};
+use crate::Bid;
+
+/// The part of the highest bid that `bid` reads.
+#[near(serializers = [json, borsh])]
+#[derive(Clone)]
+pub struct HotBid {
+    pub bidder: AccountId,
+    pub bid: NearToken,
+}
+
+/// When the highest bid was placed, and how. Plain integers, as in storage;
+/// `Bid` is what goes out as JSON.
+#[near(serializers = [borsh])]
+#[derive(Clone)]
+pub struct BidMetadata {
+    pub bid_time: u64,
+    pub bid_block_height: u64,
+    pub bid_block_timestamp: u64,
+    pub bid_epoch_height: u64,
+    pub premium: bool,
+}
+
+/// Bit set in `BidMetadataV2::flags` for a premium bid.
+const PREMIUM: u8 = 1 << 0;
+
+/// `BidMetadata` without `bid_block_timestamp`, which always equals
+/// `bid_time`, and with the flags packed into one byte.
+#[near(serializers = [borsh])]
+#[derive(Clone)]
+pub struct BidMetadataV2 {
+    pub bid_time: u64,
+    pub bid_block_height: u64,
+    pub bid_epoch_height: u64,
+    pub flags: u8,
+}
+
+/// The stored form of `BidMetadata`, tagged with its layout.
+#[near(serializers = [borsh])]
+#[derive(Clone)]
+pub enum VersionedBidMetadata {
+    V1(BidMetadata),
+    V2(BidMetadataV2),
+}
+
+impl From<VersionedBidMetadata> for BidMetadata {
+    fn from(metadata: VersionedBidMetadata) -> Self {
+        match metadata {
+            VersionedBidMetadata::V1(metadata) => metadata,
+            VersionedBidMetadata::V2(metadata) => BidMetadata {
+                bid_time: metadata.bid_time,
+                bid_block_height: metadata.bid_block_height,
+                bid_block_timestamp: metadata.bid_time,
+                bid_epoch_height: metadata.bid_epoch_height,
+                premium: metadata.flags & PREMIUM != 0,
+            },
+        }
+    }
+}
+
+/// Encodes `metadata` in the current layout.
+pub fn encode_metadata(metadata: BidMetadata) -> Vec<u8> {
+    let flags = if metadata.premium { PREMIUM } else { 0 };
+    borsh::to_vec(&VersionedBidMetadata::V2(BidMetadataV2 {
+        bid_time: metadata.bid_time,
+        bid_block_height: metadata.bid_block_height,
+        bid_epoch_height: metadata.bid_epoch_height,
+        flags,
+    }))
+    .unwrap()
+}
+
+/// Decodes metadata in any layout it was ever stored in. The untagged one
+/// is 33 bytes, a length no `VersionedBidMetadata` has, so the two can't be
+/// mistaken for each other.
+pub fn decode_metadata(bytes: &[u8]) -> Option<BidMetadata> {
+    borsh::from_slice::<VersionedBidMetadata>(bytes)
+        .map(BidMetadata::from)
+        .or_else(|_| borsh::from_slice::<BidMetadata>(bytes))
+        .ok()
+}
+
+impl Bid {
+    pub fn split(self) -> (HotBid, BidMetadata) {
+        (
+            HotBid {
+                bidder: self.bidder,
+                bid: self.bid,
+            },
+            BidMetadata {
+                bid_time: self.bid_time.0,
+                bid_block_height: self.bid_block_height.0,
+                bid_block_timestamp: self.bid_block_timestamp.0,
+                bid_epoch_height: self.bid_epoch_height.0,
+                premium: self.premium,
+            },
+        )
+    }
+
+    pub fn join(hot: HotBid, metadata: BidMetadata) -> Self {
+        Bid {
+            bidder: hot.bidder,
+            bid: hot.bid,
+            bid_time: U64(metadata.bid_time),
+            bid_block_height: U64(metadata.bid_block_height),
+            bid_block_timestamp: U64(metadata.bid_block_timestamp),
+            bid_epoch_height: U64(metadata.bid_epoch_height),
+            premium: metadata.premium,
+        }
+    }
+}
+
+/// The whole highest bid, or `None` if either part is missing.
+pub fn try_load() -> Option<Bid> {
+    let hot = storage::try_load(&StorageKey::HighestBid.to_bytes())?;
+    let metadata_key = StorageKey::HighestBidMetadata.to_bytes();
+    let bytes = storage::read(&metadata_key)?;
+    let metadata =
+        decode_metadata(&bytes).unwrap_or_else(|| storage::panic_undecodable(&metadata_key));
+    Some(Bid::join(hot, metadata))
+}
+
+pub fn load() -> Bid {
+    try_load().unwrap_or_else(|| storage::panic_missing(&StorageKey::HighestBid.to_bytes()))
+}
+
+/// Makes `bid` the highest bid, writing both parts.
+pub fn store(bid: Bid) {
+    let (hot, metadata) = bid.split();
+    storage::write_many(&[
+        (
+            &StorageKey::HighestBid.to_bytes(),
+            borsh::to_vec(&hot).unwrap(),
+        ),
+        (
+            &StorageKey::HighestBidMetadata.to_bytes(),
+            encode_metadata(metadata),
+        ),
+    ]);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bid() -> Bid {
+        Bid {
+            bidder: "bob.near".parse().unwrap(),
+            bid: NearToken::from_near(1),
+            bid_time: U64(10),
+            bid_block_height: U64(20),
+            bid_block_timestamp: U64(10),
+            bid_epoch_height: U64(30),
+            premium: true,
+        }
+    }
+
+    #[test]
+    fn parts_join_back_into_the_bid() {
+        store(bid());
+        let loaded = load();
+        assert_eq!(loaded.bidder, bid().bidder);
+        assert_eq!(loaded.bid, bid().bid);
+        assert_eq!(loaded.bid_block_height, U64(20));
+        assert_eq!(loaded.bid_epoch_height, U64(30));
+        assert!(loaded.premium);
+
+        // The hot part is just the account id and the amount
+        let hot = storage::read(&StorageKey::HighestBid.to_bytes()).unwrap();
+        assert_eq!(hot.len(), 4 + "bob.near".len() + 16);
+    }
+
+    #[test]
+    fn metadata_is_stored_in_the_slim_layout() {
+        store(bid());
+        let stored = storage::read(&StorageKey::HighestBidMetadata.to_bytes()).unwrap();
+        assert_eq!(stored.len(), 1 + 3 * 8 + 1);
+        assert_eq!(borsh::to_vec(&bid().split().1).unwrap().len(), 4 * 8 + 1);
+    }
+
+    #[test]
+    fn untagged_metadata_is_upgraded_on_read() {
+        store(bid());
+        let (_, metadata) = bid().split();
+        storage::write(
+            &StorageKey::HighestBidMetadata.to_bytes(),
+            &borsh::to_vec(&metadata).unwrap(),
+        );
+
+        let loaded = load();
+        assert_eq!(loaded.bid_time, U64(10));
+        assert_eq!(loaded.bid_block_timestamp, U64(10));
+        assert_eq!(loaded.bid_block_height, U64(20));
+        assert!(loaded.premium);
+
+        // Whatever bit pattern `bid_time` starts with, the lengths differ
+        for bid_time in [0, 1, 2, 0x0101] {
+            let metadata = BidMetadata {
+                bid_time,
+                ..metadata.clone()
+            };
+            let decoded = decode_metadata(&borsh::to_vec(&metadata).unwrap()).unwrap();
+            assert_eq!(decoded.bid_time, bid_time);
+            assert_eq!(decoded.bid_epoch_height, 30);
+        }
+    }
+
+    #[test]
+    fn a_missing_part_is_a_missing_bid() {
+        store(bid());
+        storage::remove(&StorageKey::HighestBidMetadata.to_bytes());
+        assert!(try_load().is_none());
+    }
+}
@@ -0,0 +1,139 @@
+//! Optional minimum bid increments, either a flat amount or basis points of
+//! the current highest bid. The main auction's is set by the owner before
+//! the first bid; each NFT auction picks its own in the `msg` that starts
+//! it. Without one, any bid above the current one wins.
+
+use near_sdk::json_types::U64;
+use near_sdk::{assert_one_yocto, near, require, NearToken};
+
+use crate::auction_state::AuctionState;
+use crate::error::AuctionError;
+use crate::owner::only_owner;
+use crate::storage::{StorageCell, StorageKey, WriteBackSession};
+use crate::{math, penny, Contract, ContractExt};
+
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Debug, PartialEq)]
+pub enum BidIncrement {
+    Flat(NearToken),
+    /// Rounded up, so a bid never beats the last one by less than the
+    /// percentage.
+    BasisPoints(u32),
+}
+
+impl BidIncrement {
+    pub fn is_valid(&self) -> bool {
+        match self {
+            BidIncrement::Flat(amount) => !amount.is_zero(),
+            BidIncrement::BasisPoints(basis_points) => *basis_points > 0,
+        }
+    }
+}
+
+/// The least a bid must be to beat `highest_bid`: `highest_bid` plus the
+/// increment, and always at least one yoctoNEAR more.
+pub fn min_next_bid(
+    increment: Option<&BidIncrement>,
+    highest_bid: NearToken,
+) -> Result<NearToken, AuctionError> {
+    let step = match increment {
+        None => NearToken::from_yoctonear(0),
+        Some(BidIncrement::Flat(amount)) => *amount,
+        Some(BidIncrement::BasisPoints(basis_points)) => {
+            math::percent_of_rounded_up(highest_bid, *basis_points)?
+        }
+    };
+    math::add(highest_bid, step.max(NearToken::from_yoctonear(1)))
+}
+
+/// The main auction's increment.
+pub fn min_increment() -> Option<BidIncrement> {
+    StorageCell::<BidIncrement>::new(StorageKey::MinIncrement)
+        .try_get()
+        .cloned()
+}
+
+#[near]
+impl Contract {
+    /// Sets the main auction's minimum increment, or removes it with `None`.
+    /// Only possible before the first bid. A penny auction ignores it and
+    /// uses its own increment.
+    #[payable]
+    pub fn set_min_increment(&mut self, min_increment: Option<BidIncrement>) {
+        let _session = WriteBackSession::begin();
+
+        assert_one_yocto();
+        only_owner();
+        require!(
+            StorageCell::<U64>::new(StorageKey::BidCount).get().0 == 0,
+            AuctionError::MinIncrementAfterBids.as_str()
+        );
+        let mut cell = StorageCell::<BidIncrement>::new(StorageKey::MinIncrement);
+        match min_increment {
+            Some(min_increment) => {
+                require!(
+                    min_increment.is_valid(),
+                    AuctionError::InvalidMinIncrement.as_str()
+                );
+                cell.set(min_increment);
+            }
+            None => cell.remove(),
+        }
+    }
+
+    pub fn get_min_increment(&self) -> Option<BidIncrement> {
+        min_increment()
+    }
+
+    /// The smallest bid the main auction accepts right now, not counting
+    /// storage for its receipt. In a penny auction it is the next price;
+    /// the fee comes on top.
+    pub fn get_min_next_bid(&self) -> NearToken {
+        let highest_bid = AuctionState::new().highest_bid().bid;
+        let next = match penny::penny_auction() {
+            Some(penny_auction) => math::add(highest_bid, penny_auction.increment),
+            None => min_next_bid(min_increment().as_ref(), highest_bid),
+        };
+        next.unwrap_or_else(|err| err.panic())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    use super::*;
+    use crate::test_utils::new_contract;
+
+    fn yocto(amount: u128) -> NearToken {
+        NearToken::from_yoctonear(amount)
+    }
+
+    #[test]
+    fn increments_round_up_and_always_step() {
+        let bps = BidIncrement::BasisPoints(500);
+        assert_eq!(min_next_bid(Some(&bps), yocto(1_000)), Ok(yocto(1_050)));
+        assert_eq!(min_next_bid(Some(&bps), yocto(1_001)), Ok(yocto(1_052)));
+        assert_eq!(min_next_bid(Some(&bps), yocto(10)), Ok(yocto(11)));
+        assert_eq!(min_next_bid(Some(&bps), yocto(0)), Ok(yocto(1)));
+        let flat = BidIncrement::Flat(yocto(25));
+        assert_eq!(min_next_bid(Some(&flat), yocto(1_000)), Ok(yocto(1_025)));
+        assert_eq!(min_next_bid(None, yocto(1_000)), Ok(yocto(1_001)));
+        assert_eq!(
+            min_next_bid(Some(&bps), NearToken::from_yoctonear(u128::MAX)),
+            Err(AuctionError::AmountOverflow)
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_MIN_INCREMENT")]
+    fn zero_increments_are_rejected() {
+        let mut contract = new_contract();
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(contract.get_owner())
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .build());
+        contract.set_min_increment(Some(BidIncrement::BasisPoints(0)));
+    }
+}
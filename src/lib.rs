@@ -1,9 +1,73 @@
-use std::vec;
-
-use near_sdk::borsh::{self};
-use near_sdk::env::{storage_read, storage_write};
 use near_sdk::json_types::U64;
-use near_sdk::{env, near, require, store, AccountId, NearToken, Promise};
+use near_sdk::{
+    assert_one_yocto, borsh, env, near, require, store, AccountId, Gas, NearToken, Promise,
+    PromiseError, PromiseOrValue,
+};
+
+mod approvals;
+mod assets;
+mod auction_state;
+#[cfg(any(test, not(feature = "minimal")))]
+mod benchmarker;
+// The scenarios drive the benchmark methods, which `minimal` leaves out
+#[cfg(all(feature = "bench", not(feature = "minimal")))]
+mod bench;
+mod chain_signatures;
+mod chunked;
+#[cfg(any(test, feature = "compression"))]
+mod compress;
+mod dutch_auctions;
+mod emergency;
+mod envelope;
+mod error;
+mod events;
+mod evm;
+mod highest_bid;
+mod increments;
+#[cfg(feature = "unsafe-lowlevel")]
+mod lowlevel;
+pub mod math;
+mod microbench;
+mod migrate;
+mod nft_auctions;
+mod oracle;
+mod owner;
+#[cfg(any(test, feature = "partial-decode"))]
+mod partial;
+mod pause;
+mod penny;
+mod profile;
+mod raw_state;
+mod receipts;
+mod records;
+mod refunds;
+mod social;
+mod staking;
+mod storage;
+mod storage_management;
+mod sweep;
+#[cfg(test)]
+mod test_utils;
+mod ttl;
+mod wnear;
+
+pub use auction_state::ClaimStatus;
+use auction_state::{AuctionConfig, AuctionState};
+use chunked::ChunkedVector;
+use emergency::EMERGENCY_DELAY;
+pub use error::AuctionError;
+use events::AuctionEvent;
+use highest_bid::HotBid;
+use migrate::STATE_VERSION;
+use profile::GasProfile;
+use storage::{new_collection, StorageCell, StorageKey, WriteBackSession};
+
+/// How far past the current block `init` wants the end time when no
+/// `min_duration` is passed: zero, so any future end time is accepted.
+pub const MIN_AUCTION_DURATION: U64 = U64(0);
+
+/// Gas for `resolve_claim` itself, before any publishing to SocialDB.
+const RESOLVE_CLAIM_GAS: Gas = Gas::from_tgas(5);
 
 #[near(serializers = [json, borsh])]
 #[derive(Clone)]
@@ -17,6 +81,26 @@ pub struct Bid {
     pub premium: bool,
 }
 
+/// Everything a frontend shows about the auction, returned by one view call.
+#[near(serializers = [json])]
+pub struct AuctionInfo {
+    pub highest_bid: Bid,
+    pub auction_end_time: U64,
+    pub auctioneer: AccountId,
+    pub claimed: bool,
+    /// Nanoseconds left until `auction_end_time`, zero once it has passed.
+    pub time_remaining: U64,
+    pub has_ended: bool,
+}
+
+/// Encoding of each benchmark vector chunk: compressed with the `compression`
+/// feature. The two layouts are not interchangeable, so clear the vector
+/// before redeploying with the feature toggled.
+#[cfg(feature = "compression")]
+type VectorBlob = compress::Compressed<Vec<u8>>;
+#[cfg(not(feature = "compression"))]
+type VectorBlob = Vec<u8>;
+
 #[near(contract_state)]
 #[derive(Default)]
 pub struct Contract {
@@ -25,8 +109,36 @@ pub struct Contract {
 
 #[near]
 impl Contract {
+    /// `guardian` can recover the balance through the emergency timelock and
+    /// defaults to the caller, which like the owner is the contract account.
+    /// `end_time` must be more than `min_duration` nanoseconds away, which
+    /// defaults to `MIN_AUCTION_DURATION`.
     #[init]
-    pub fn init(end_time: U64, auctioneer: AccountId) -> Self {
+    #[private] // only callable by the contract's account
+    pub fn init(
+        end_time: U64,
+        auctioneer: AccountId,
+        guardian: Option<AccountId>,
+        min_duration: Option<U64>,
+    ) -> Self {
+        let _session = WriteBackSession::begin();
+
+        // The keys live outside `STATE`, so the SDK's own check can't see them
+        require!(
+            !storage::has_key(&StorageKey::StateInitialized.to_bytes()),
+            AuctionError::AlreadyInitialized.as_str()
+        );
+        let min_duration = min_duration.unwrap_or(MIN_AUCTION_DURATION).0;
+        require!(
+            end_time.0 > env::block_timestamp().saturating_add(min_duration),
+            AuctionError::EndTimeTooSoon.as_str()
+        );
+        // The proceeds would be sent to the contract itself
+        require!(
+            auctioneer != env::current_account_id(),
+            AuctionError::AuctioneerIsContract.as_str()
+        );
+
         let highest_bid = Bid {
             bidder: env::current_account_id(),
             bid: NearToken::from_yoctonear(1),
@@ -36,148 +148,1066 @@ impl Contract {
             bid_epoch_height: U64::from(env::epoch_height()),
             premium: false,
         };
-        let vector: Vec<u8> = vec![];
-        let sdk_vector: store::Vector<u8> = store::Vector::new(b"s");
-        let sdk_iterable_map: store::IterableMap<u8, u8> = store::IterableMap::new(b"m");
-        storage_write(b"highest_bid", &borsh::to_vec(&highest_bid).unwrap());
-        storage_write(b"auction_end_time", &borsh::to_vec(&end_time).unwrap());
-        storage_write(b"auctioneer", &borsh::to_vec(&auctioneer).unwrap());
-        storage_write(b"claimed", &borsh::to_vec(&false).unwrap());
-        storage_write(b"vector", &borsh::to_vec(&vector).unwrap());
-        storage_write(b"s", &borsh::to_vec(&sdk_vector).unwrap());
-        storage_write(b"i", &borsh::to_vec(&sdk_iterable_map).unwrap());
+        let sdk_vector: store::Vector<u8> = new_collection(StorageKey::SdkVector);
+        let sdk_iterable_map: store::IterableMap<u8, u8> =
+            new_collection(StorageKey::SdkIterableMap);
+        let sdk_lookup_map: store::LookupMap<u8, u8> = new_collection(StorageKey::SdkLookupMap);
+        let sdk_tree_map: store::TreeMap<u8, u8> = new_collection(StorageKey::SdkTreeMap);
+        let sdk_lookup_set: store::LookupSet<u8> = new_collection(StorageKey::SdkLookupSet);
+        let sdk_iterable_set: store::IterableSet<u8> = new_collection(StorageKey::SdkIterableSet);
+        let sdk_nested_map: store::IterableMap<u8, store::Vector<u8>> =
+            new_collection(StorageKey::SdkNestedMap);
+        let refunds: store::IterableMap<AccountId, NearToken> = new_collection(StorageKey::Refunds);
+        let storage_balances: store::LookupMap<AccountId, storage_management::StorageBalance> =
+            new_collection(StorageKey::StorageBalances);
+        microbench::write_bid_encodings(&highest_bid);
+        let mut state = AuctionState::new();
+        state.set_highest_bid(highest_bid);
+        state.config.set(AuctionConfig {
+            auction_end_time: end_time.0,
+            auctioneer,
+            claim: ClaimStatus::Open,
+        });
+        state.bid_count.set(U64(0));
+        state.total_volume.set(NearToken::from_yoctonear(0));
+        state.flush();
+        StorageCell::new(StorageKey::Refunds).set(refunds);
+        StorageCell::new(StorageKey::StorageBalances).set(storage_balances);
+        StorageCell::new(StorageKey::Vector).set(0u32);
+        StorageCell::new(StorageKey::SdkVector).set(sdk_vector);
+        StorageCell::new(StorageKey::SdkIterableMap).set(sdk_iterable_map);
+        StorageCell::new(StorageKey::SdkLookupMap).set(sdk_lookup_map);
+        StorageCell::new(StorageKey::SdkTreeMap).set(sdk_tree_map);
+        StorageCell::new(StorageKey::SdkLookupSet).set(sdk_lookup_set);
+        StorageCell::new(StorageKey::SdkIterableSet).set(sdk_iterable_set);
+        StorageCell::new(StorageKey::SdkNestedMap).set(sdk_nested_map);
+        StorageCell::new(StorageKey::BigValue).set(Vec::<u8>::new());
+        StorageCell::new(StorageKey::RecordBlob).set(Vec::<records::Record>::new());
+        StorageCell::new(StorageKey::RecordEntries).set(0u32);
+        StorageCell::new(StorageKey::Blob).set(Vec::<u8>::new());
+        StorageCell::new(StorageKey::Owner).set(env::predecessor_account_id());
+        StorageCell::new(StorageKey::Paused).set(false);
+        StorageCell::new(StorageKey::Guardian)
+            .set(guardian.unwrap_or_else(env::predecessor_account_id));
+        StorageCell::new(StorageKey::EmergencyDelay).set(EMERGENCY_DELAY);
+        StorageCell::new(StorageKey::StateVersion).set(STATE_VERSION);
+        StorageCell::new(StorageKey::StateInitialized).set(true);
 
         Self {}
     }
 
+    /// Places the attached deposit as a bid. With a USD reserve configured
+    /// the bid is only accepted once the oracle has priced it, in
+    /// `resolve_bid`. The bidder is the predecessor, so a bid relayed as a
+    /// NEP-366 meta transaction belongs to whoever signed the delegate
+    /// action, not to the relayer paying for the gas.
     #[payable]
     pub fn bid(&mut self) -> Promise {
-        // Assert the auction is still ongoing
-        let auction_end_time: U64 =
-            borsh::from_slice(&storage_read(b"auction_end_time").unwrap()).unwrap();
-        require!(
-            env::block_timestamp() < auction_end_time.0,
-            "Auction has ended"
-        );
+        let _session = WriteBackSession::begin();
+        pause::assert_not_paused();
 
-        // Current bid
+        let bidder = env::predecessor_account_id();
         let bid = env::attached_deposit();
+        if let Some(reserve) = oracle::usd_reserve() {
+            return oracle::price_bid(reserve, bidder, bid);
+        }
+        place_bid(bidder, bid).unwrap_or_else(|err| err.panic())
+    }
+
+    /// `bid` for callers that can't handle a failed call: a bid that can't
+    /// be accepted is logged and its deposit sent straight back, and the
+    /// call resolves to `false` instead of panicking.
+    #[payable]
+    pub fn try_bid(&mut self) -> PromiseOrValue<bool> {
+        let _session = WriteBackSession::begin();
+
         let bidder = env::predecessor_account_id();
+        let bid = env::attached_deposit();
+        if pause::currently_paused() {
+            return accept_or_refund(bidder, bid, Err(AuctionError::Paused));
+        }
+        if let Some(reserve) = oracle::usd_reserve() {
+            return PromiseOrValue::Promise(oracle::price_bid(reserve, bidder, bid));
+        }
+        let result = place_bid(bidder.clone(), bid);
+        accept_or_refund(bidder, bid, result)
+    }
 
-        // Last bid
-        let Bid {
-            bidder: last_bidder,
-            bid: last_bid,
-            bid_time: _last_bid_time,
-            bid_block_height: _last_bid_block_height,
-            bid_block_timestamp: _last_bid_block_timestamp,
-            bid_epoch_height: _last_bid_epoch_height,
-            premium: _last_premium,
-        } = borsh::from_slice(&storage_read(b"highest_bid").unwrap()).unwrap();
-
-        // Check if the deposit is higher than the current bid
-        require!(bid > last_bid, "You must place a higher bid");
-
-        // Update the highest bid
-        // self.highest_bid = Bid { bidder, bid };
-        storage_write(
-            b"highest_bid",
-            &borsh::to_vec(&Bid {
-                bidder,
-                bid,
-                bid_time: U64::from(env::block_timestamp()),
-                bid_block_height: U64::from(env::block_height()),
-                bid_block_timestamp: U64::from(env::block_timestamp()),
-                bid_epoch_height: U64::from(env::epoch_height()),
-                premium: false,
-            })
-            .unwrap(),
+    #[payable]
+    pub fn claim(&mut self) -> Promise {
+        let _session = WriteBackSession::begin();
+        assert_one_yocto();
+        let mut profile = GasProfile::start("claim");
+
+        let keys = [
+            StorageKey::AuctionConfig,
+            StorageKey::HighestBid,
+            StorageKey::HighestBidMetadata,
+        ]
+        .map(StorageKey::to_bytes);
+        storage::prefetch(&keys.each_ref().map(Vec::as_slice));
+        profile.checkpoint("read");
+
+        let config: AuctionConfig = storage::load(&keys[0]);
+        let highest_bid = highest_bid::load();
+        profile.checkpoint("deserialize");
+
+        require!(
+            env::block_timestamp() > config.auction_end_time,
+            AuctionError::AuctionNotEnded.as_str()
         );
+        config.assert_unclaimed();
+        approvals::assert_claim_approved();
+        staking::assert_escrow_released();
+        profile.checkpoint("logic");
+
+        let auctioneer = config.auctioneer.clone();
+        // Claimed only once `resolve_claim` sees the payout succeed
+        let config = borsh::to_vec(&AuctionConfig {
+            claim: ClaimStatus::Pending,
+            ..config
+        })
+        .unwrap();
+        profile.checkpoint("serialize");
+
+        storage::write(&keys[0], &config);
+        storage::flush();
+        profile.checkpoint("write");
+        profile.finish();
 
-        // Transfer tokens back to the last bidder
-        Promise::new(last_bidder).transfer(last_bid)
+        // Pay out on the auctioneer's chain of choice, or here in (w)NEAR
+        let payout = match chain_signatures::foreign_payout() {
+            Some(payout) => chain_signatures::request_payout(payout, highest_bid.bid),
+            None => wnear::pay_auctioneer(auctioneer, highest_bid.bid),
+        };
+        payout.then(
+            Self::ext(env::current_account_id())
+                .with_static_gas(RESOLVE_CLAIM_GAS.saturating_add(social::publish_gas()))
+                .resolve_claim(highest_bid),
+        )
     }
 
-    pub fn claim(&mut self) -> Promise {
-        let auction_end_time: U64 =
-            borsh::from_slice(&storage_read(b"auction_end_time").unwrap()).unwrap();
+    /// Marks the auction claimed and publishes the result once the payout
+    /// has gone through. If it failed, the claim is reopened so `claim` can
+    /// be called again instead of the bid being stranded.
+    #[private]
+    pub fn resolve_claim(&mut self, winning_bid: Bid) -> Option<Promise> {
+        let _session = WriteBackSession::begin();
+
+        // Only whether the payout failed matters, not what it returned
+        let failed = matches!(env::promise_result_checked(0, 0), Err(PromiseError::Failed));
+        let mut state = AuctionState::new();
+        if failed {
+            state.config.get_mut().claim = ClaimStatus::Open;
+            env::log_str("The payout failed, the auction can be claimed again");
+            return None;
+        }
+        state.config.get_mut().claim = ClaimStatus::Claimed;
+        social::publish(&winning_bid)
+    }
+
+    /// Moves the end of a running auction to the later `new_end_time`.
+    #[payable]
+    pub fn extend_end_time(&mut self, new_end_time: U64) {
+        let _session = WriteBackSession::begin();
+
+        assert_one_yocto();
+        let mut state = AuctionState::new();
+        require!(
+            env::predecessor_account_id() == state.config.get().auctioneer,
+            AuctionError::NotAuctioneer.as_str()
+        );
+        let old_end_time = state.config.get().auction_end_time;
+        require!(
+            env::block_timestamp() < old_end_time,
+            AuctionError::AuctionEnded.as_str()
+        );
         require!(
-            env::block_timestamp() > auction_end_time.0,
-            "Auction has not ended yet"
+            new_end_time.0 > old_end_time,
+            AuctionError::EndTimeNotLater.as_str()
         );
 
-        let claimed: bool = borsh::from_slice(&storage_read(b"claimed").unwrap()).unwrap();
-        require!(!claimed, "Auction has already been claimed");
-        // self.claimed = true;
-        storage_write(b"claimed", &borsh::to_vec(&true).unwrap());
+        state.config.get_mut().auction_end_time = new_end_time.0;
+        AuctionEvent::EndTimeExtended {
+            old_end_time: U64(old_end_time),
+            new_end_time,
+        }
+        .emit();
+    }
 
-        // Transfer tokens to the auctioneer
-        let auctioneer: AccountId =
-            borsh::from_slice(&storage_read(b"auctioneer").unwrap()).unwrap();
-        let highest_bid: Bid = borsh::from_slice(&storage_read(b"highest_bid").unwrap()).unwrap();
-        Promise::new(auctioneer).transfer(highest_bid.bid)
+    /// Hands the payout over to `new_auctioneer`, e.g. when the current
+    /// account is compromised. Only allowed until the auction is claimed.
+    #[payable]
+    pub fn set_auctioneer(&mut self, new_auctioneer: AccountId) {
+        let _session = WriteBackSession::begin();
+
+        assert_one_yocto();
+        let mut state = AuctionState::new();
+        let owner = StorageCell::<AccountId>::new(StorageKey::Owner);
+        let caller = env::predecessor_account_id();
+        require!(
+            caller == state.config.get().auctioneer || caller == *owner.get(),
+            AuctionError::NotAuctioneerOrOwner.as_str()
+        );
+        state.config.get().assert_unclaimed();
+
+        let config = state.config.get_mut();
+        let old_auctioneer = std::mem::replace(&mut config.auctioneer, new_auctioneer.clone());
+        AuctionEvent::AuctioneerChanged {
+            old_auctioneer,
+            new_auctioneer,
+        }
+        .emit();
     }
+}
 
-    pub fn fill_vector(&mut self) {
-        let mut vector: Vec<u8> = borsh::from_slice(&storage_read(b"vector").unwrap()).unwrap();
-        for i in 0..1000 {
-            vector.push(i as u8);
+// Methods that only exist to fill and measure the state. `minimal` builds
+// leave them out; unit tests always have them
+#[cfg(any(test, not(feature = "minimal")))]
+#[near]
+impl Contract {
+    /// Appends the values `start..start + count`, truncated to bytes, or the
+    /// seeded test data for those indices when `seed` is given.
+    pub fn fill_vector(&mut self, count: u32, start: u32, seed: Option<u64>) {
+        let _session = WriteBackSession::begin();
+        pause::assert_not_paused();
+        benchmarker::only_benchmarker();
+        let mut profile = GasProfile::start("fill_vector");
+
+        // Chunks are read lazily by `push`, so reads count as logic here
+        let mut vector = ChunkedVector::new(StorageKey::Vector);
+        for i in fill_range(start, count) {
+            vector.push(fill_item(seed, i).1);
+        }
+        profile.checkpoint("logic");
+
+        drop(vector);
+        storage::flush();
+        profile.checkpoint("write");
+        profile.finish();
+    }
+
+    pub fn fill_sdk_vector(&mut self, count: u32, start: u32, seed: Option<u64>) {
+        let _session = WriteBackSession::begin();
+        pause::assert_not_paused();
+        benchmarker::only_benchmarker();
+        let mut profile = GasProfile::start("fill_sdk_vector");
+
+        {
+            let mut sdk_vector = StorageCell::<store::Vector<u8>>::new(StorageKey::SdkVector);
+            let sdk_vector =
+                sdk_vector.get_mut_or_insert_with(|| new_collection(StorageKey::SdkVector));
+            profile.checkpoint("read");
+
+            for i in fill_range(start, count) {
+                sdk_vector.push(fill_item(seed, i).1);
+            }
+            profile.checkpoint("logic");
         }
-        storage_write(b"vector", &borsh::to_vec(&vector).unwrap());
+
+        // Dropping the cell above flushed the elements and staged the header
+        storage::flush();
+        profile.checkpoint("write");
+        profile.finish();
+    }
+
+    pub fn fill_sdk_iterable_map(&mut self, count: u32, start: u32, seed: Option<u64>) {
+        let _session = WriteBackSession::begin();
+        pause::assert_not_paused();
+        benchmarker::only_benchmarker();
+        let mut profile = GasProfile::start("fill_sdk_iterable_map");
+
+        {
+            let mut sdk_iterable_map =
+                StorageCell::<store::IterableMap<u8, u8>>::new(StorageKey::SdkIterableMap);
+            let sdk_iterable_map = sdk_iterable_map
+                .get_mut_or_insert_with(|| new_collection(StorageKey::SdkIterableMap));
+            profile.checkpoint("read");
+
+            for i in fill_range(start, count) {
+                let (key, value) = fill_item(seed, i);
+                sdk_iterable_map.insert(key, value);
+            }
+            profile.checkpoint("logic");
+        }
+
+        // Dropping the cell above flushed the entries and staged the header
+        storage::flush();
+        profile.checkpoint("write");
+        profile.finish();
     }
 
-    pub fn fill_sdk_vector(&mut self) {
-        let mut sdk_vector: store::Vector<u8> =
-            borsh::from_slice(&storage_read(b"s").unwrap()).unwrap();
-        for i in 0..1000 {
-            sdk_vector.push(i as u8);
+    /// Unlike `fill_sdk_iterable_map`, only writes one record per entry: the
+    /// map keeps no element index and its header never changes.
+    pub fn fill_lookup_map(&mut self, count: u32, start: u32, seed: Option<u64>) {
+        let _session = WriteBackSession::begin();
+        pause::assert_not_paused();
+        benchmarker::only_benchmarker();
+        let mut profile = GasProfile::start("fill_lookup_map");
+
+        {
+            let mut sdk_lookup_map =
+                StorageCell::<store::LookupMap<u8, u8>>::new(StorageKey::SdkLookupMap);
+            let sdk_lookup_map =
+                sdk_lookup_map.get_mut_or_insert_with(|| new_collection(StorageKey::SdkLookupMap));
+            profile.checkpoint("read");
+
+            for i in fill_range(start, count) {
+                let (key, value) = fill_item(seed, i);
+                sdk_lookup_map.insert(key, value);
+            }
+            profile.checkpoint("logic");
         }
-        storage_write(b"s", &borsh::to_vec(&sdk_vector).unwrap());
+
+        // Dropping the cell above flushed the entries and staged the header
+        storage::flush();
+        profile.checkpoint("write");
+        profile.finish();
+    }
+
+    /// Looks up the keys `start..start + count` and returns the values found.
+    pub fn read_lookup_map(&self, count: u32, start: u32) -> Vec<u8> {
+        let sdk_lookup_map = StorageCell::<store::LookupMap<u8, u8>>::new(StorageKey::SdkLookupMap);
+        let Some(sdk_lookup_map) = sdk_lookup_map.try_get() else {
+            return vec![];
+        };
+        fill_range(start, count)
+            .filter_map(|i| sdk_lookup_map.get(&(i as u8)).copied())
+            .collect()
     }
 
-    pub fn fill_sdk_iterable_map(&mut self) {
-        let mut sdk_iterable_map: store::IterableMap<u8, u8> =
-            borsh::from_slice(&storage_read(b"i").unwrap()).unwrap();
-        for i in 0..1000 {
-            sdk_iterable_map.insert(i as u8, i as u8);
+    pub fn fill_tree_map(&mut self, count: u32, start: u32) {
+        let _session = WriteBackSession::begin();
+        pause::assert_not_paused();
+        benchmarker::only_benchmarker();
+        let mut profile = GasProfile::start("fill_tree_map");
+
+        {
+            let mut sdk_tree_map =
+                StorageCell::<store::TreeMap<u8, u8>>::new(StorageKey::SdkTreeMap);
+            let sdk_tree_map =
+                sdk_tree_map.get_mut_or_insert_with(|| new_collection(StorageKey::SdkTreeMap));
+            profile.checkpoint("read");
+
+            for i in fill_range(start, count) {
+                sdk_tree_map.insert(i as u8, i as u8);
+            }
+            profile.checkpoint("logic");
         }
-        storage_write(b"i", &borsh::to_vec(&sdk_iterable_map).unwrap());
+
+        // Dropping the cell above flushed the nodes and staged the header
+        storage::flush();
+        profile.checkpoint("write");
+        profile.finish();
+    }
+
+    /// Values of the entries with keys in `from..to`, in key order.
+    pub fn range_tree_map(&self, from: u32, to: u32) -> Vec<u8> {
+        let sdk_tree_map = StorageCell::<store::TreeMap<u8, u8>>::new(StorageKey::SdkTreeMap);
+        let (Some(sdk_tree_map), Ok(from)) = (sdk_tree_map.try_get(), u8::try_from(from)) else {
+            return vec![];
+        };
+        sdk_tree_map
+            .range(from..)
+            .take_while(|(key, _)| u32::from(**key) < to)
+            .map(|(_, value)| *value)
+            .collect()
     }
 
+    pub fn fill_lookup_set(&mut self, count: u32, start: u32) {
+        let _session = WriteBackSession::begin();
+        pause::assert_not_paused();
+        benchmarker::only_benchmarker();
+        let mut profile = GasProfile::start("fill_lookup_set");
+
+        {
+            let mut sdk_lookup_set =
+                StorageCell::<store::LookupSet<u8>>::new(StorageKey::SdkLookupSet);
+            let sdk_lookup_set =
+                sdk_lookup_set.get_mut_or_insert_with(|| new_collection(StorageKey::SdkLookupSet));
+            profile.checkpoint("read");
+
+            for i in fill_range(start, count) {
+                sdk_lookup_set.insert(i as u8);
+            }
+            profile.checkpoint("logic");
+        }
+
+        // Dropping the cell above flushed the members and staged the header
+        storage::flush();
+        profile.checkpoint("write");
+        profile.finish();
+    }
+
+    pub fn fill_iterable_set(&mut self, count: u32, start: u32) {
+        let _session = WriteBackSession::begin();
+        pause::assert_not_paused();
+        benchmarker::only_benchmarker();
+        let mut profile = GasProfile::start("fill_iterable_set");
+
+        {
+            let mut sdk_iterable_set =
+                StorageCell::<store::IterableSet<u8>>::new(StorageKey::SdkIterableSet);
+            let sdk_iterable_set = sdk_iterable_set
+                .get_mut_or_insert_with(|| new_collection(StorageKey::SdkIterableSet));
+            profile.checkpoint("read");
+
+            for i in fill_range(start, count) {
+                sdk_iterable_set.insert(i as u8);
+            }
+            profile.checkpoint("logic");
+        }
+
+        // Dropping the cell above flushed the members and staged the header
+        storage::flush();
+        profile.checkpoint("write");
+        profile.finish();
+    }
+
+    /// Checks `0..n` against both sets and returns how many members each
+    /// one had, as `(lookup_set, iterable_set)`.
+    pub fn contains_many(&self, n: u32) -> (u32, u32) {
+        let sdk_lookup_set = StorageCell::<store::LookupSet<u8>>::new(StorageKey::SdkLookupSet);
+        let sdk_iterable_set =
+            StorageCell::<store::IterableSet<u8>>::new(StorageKey::SdkIterableSet);
+        let lookup_hits = sdk_lookup_set.try_get().map_or(0, |set| {
+            (0..n).filter(|i| set.contains(&(*i as u8))).count()
+        });
+        let iterable_hits = sdk_iterable_set.try_get().map_or(0, |set| {
+            (0..n).filter(|i| set.contains(&(*i as u8))).count()
+        });
+        (lookup_hits as u32, iterable_hits as u32)
+    }
+
+    /// Appends `start..start + count` to the inner vectors under the keys
+    /// `0..outers`, creating the ones that don't exist yet.
+    pub fn fill_nested_map(&mut self, outers: u32, count: u32, start: u32) {
+        let _session = WriteBackSession::begin();
+        pause::assert_not_paused();
+        benchmarker::only_benchmarker();
+        let mut profile = GasProfile::start("fill_nested_map");
+
+        {
+            let mut sdk_nested_map = StorageCell::<store::IterableMap<u8, store::Vector<u8>>>::new(
+                StorageKey::SdkNestedMap,
+            );
+            let sdk_nested_map =
+                sdk_nested_map.get_mut_or_insert_with(|| new_collection(StorageKey::SdkNestedMap));
+            profile.checkpoint("read");
+
+            for outer in 0..outers {
+                let inner = sdk_nested_map.entry(outer as u8).or_insert_with(|| {
+                    store::Vector::new(StorageKey::SdkNestedMap.nested_prefix(outer))
+                });
+                for i in fill_range(start, count) {
+                    inner.push(i as u8);
+                }
+            }
+            profile.checkpoint("logic");
+        }
+
+        // Dropping the cell above flushed the inner vectors and staged the header
+        storage::flush();
+        profile.checkpoint("write");
+        profile.finish();
+    }
+
+    /// Elements of the inner vector under `outer`, empty if there is none.
+    pub fn read_nested_map(&self, outer: u32) -> Vec<u8> {
+        let sdk_nested_map =
+            StorageCell::<store::IterableMap<u8, store::Vector<u8>>>::new(StorageKey::SdkNestedMap);
+        let (Some(sdk_nested_map), Ok(outer)) = (sdk_nested_map.try_get(), u8::try_from(outer))
+        else {
+            return vec![];
+        };
+        sdk_nested_map
+            .get(&outer)
+            .map(|inner| inner.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Reads `n_accesses` pseudo-random elements of the vector, loading only
+    /// the chunks they fall in.
+    pub fn random_access_vector(&self, n_accesses: u32) -> Vec<u8> {
+        let vector = ChunkedVector::new(StorageKey::Vector);
+        random_indices(n_accesses, vector.len())
+            .filter_map(|index| vector.get(index))
+            .collect()
+    }
+
+    /// Reads `n_accesses` pseudo-random elements of the SDK vector.
+    pub fn random_access_sdk_vector(&self, n_accesses: u32) -> Vec<u8> {
+        let sdk_vector = StorageCell::<store::Vector<u8>>::new(StorageKey::SdkVector);
+        let Some(sdk_vector) = sdk_vector.try_get() else {
+            return vec![];
+        };
+        random_indices(n_accesses, sdk_vector.len())
+            .filter_map(|index| sdk_vector.get(index).copied())
+            .collect()
+    }
+
+    /// Looks up `n_accesses` pseudo-random keys of the SDK iterable map and
+    /// returns the values found.
+    pub fn random_access_sdk_iterable_map(&self, n_accesses: u32) -> Vec<u8> {
+        let sdk_iterable_map =
+            StorageCell::<store::IterableMap<u8, u8>>::new(StorageKey::SdkIterableMap);
+        let Some(sdk_iterable_map) = sdk_iterable_map.try_get() else {
+            return vec![];
+        };
+        random_indices(n_accesses, u8::MAX as u32 + 1)
+            .filter_map(|key| sdk_iterable_map.get(&(key as u8)).copied())
+            .collect()
+    }
+
+    /// Replaces the big value with `size` bytes. It has a key of its own, so
+    /// methods that don't touch it never pay to load or store it.
+    pub fn set_big_value(&mut self, size: u32) {
+        let _session = WriteBackSession::begin();
+        benchmarker::only_benchmarker();
+
+        let value: Vec<u8> = (0..size).map(|i| i as u8).collect();
+        StorageCell::new(StorageKey::BigValue).set(value);
+    }
+
+    pub fn big_value_len(&self) -> u32 {
+        StorageCell::<Vec<u8>>::new(StorageKey::BigValue)
+            .try_get()
+            .map_or(0, |value| value.len() as u32)
+    }
+
+    /// A mutating call that touches no state, to measure the fixed cost
+    /// every `&mut self` method pays.
+    pub fn touch_state(&mut self) {
+        let _session = WriteBackSession::begin();
+        benchmarker::only_benchmarker();
+    }
+
+    /// Removes every vector chunk plus its length and returns how many bytes
+    /// of storage were freed.
+    pub fn clear_vector(&mut self) -> u64 {
+        let _session = WriteBackSession::begin();
+        benchmarker::only_benchmarker();
+
+        let storage_before = env::storage_usage();
+        ChunkedVector::new(StorageKey::Vector).clear();
+        storage::flush();
+        storage_before - env::storage_usage()
+    }
+
+    /// Pops up to `n` elements off the end of the SDK vector and returns how
+    /// many bytes of storage were freed.
+    pub fn drain_sdk_vector(&mut self, n: u32) -> u64 {
+        let _session = WriteBackSession::begin();
+        benchmarker::only_benchmarker();
+
+        let storage_before = env::storage_usage();
+        {
+            let mut sdk_vector = StorageCell::<store::Vector<u8>>::new(StorageKey::SdkVector);
+            if sdk_vector.try_get().is_some() {
+                let vector = sdk_vector.get_mut();
+                for _ in 0..n {
+                    if vector.pop().is_none() {
+                        break;
+                    }
+                }
+            }
+        }
+
+        // Dropping the cell above removed the elements and staged the header
+        storage::flush();
+        storage_before - env::storage_usage()
+    }
+
+    /// Removes up to `n` entries of the SDK iterable map, in iteration order,
+    /// and returns how many bytes of storage were freed.
+    pub fn remove_from_iterable_map(&mut self, n: u32) -> u64 {
+        let _session = WriteBackSession::begin();
+        benchmarker::only_benchmarker();
+
+        let storage_before = env::storage_usage();
+        {
+            let mut sdk_iterable_map =
+                StorageCell::<store::IterableMap<u8, u8>>::new(StorageKey::SdkIterableMap);
+            if sdk_iterable_map.try_get().is_some() {
+                let map = sdk_iterable_map.get_mut();
+                let keys: Vec<u8> = map.keys().take(n as usize).copied().collect();
+                for key in keys {
+                    map.remove(&key);
+                }
+            }
+        }
+
+        // Dropping the cell above removed the entries and staged the header
+        storage::flush();
+        storage_before - env::storage_usage()
+    }
+
+    /// Removes every element of the SDK vector plus its header and returns how
+    /// many bytes of storage were freed.
+    pub fn clear_sdk_vector(&mut self) -> u64 {
+        let _session = WriteBackSession::begin();
+        benchmarker::only_benchmarker();
+
+        let storage_before = env::storage_usage();
+        let mut sdk_vector = StorageCell::<store::Vector<u8>>::new(StorageKey::SdkVector);
+        if sdk_vector.try_get().is_some() {
+            let vector = sdk_vector.get_mut();
+            vector.clear();
+            vector.flush();
+        }
+        sdk_vector.remove();
+        storage::flush();
+        storage_before - env::storage_usage()
+    }
+
+    /// Removes every entry of the SDK iterable map plus its header and returns
+    /// how many bytes of storage were freed.
+    pub fn clear_sdk_iterable_map(&mut self) -> u64 {
+        let _session = WriteBackSession::begin();
+        benchmarker::only_benchmarker();
+
+        let storage_before = env::storage_usage();
+        let mut sdk_iterable_map =
+            StorageCell::<store::IterableMap<u8, u8>>::new(StorageKey::SdkIterableMap);
+        if sdk_iterable_map.try_get().is_some() {
+            let map = sdk_iterable_map.get_mut();
+            map.clear();
+            map.flush();
+        }
+        sdk_iterable_map.remove();
+        storage::flush();
+        storage_before - env::storage_usage()
+    }
+
+    /// Empties every collection the `fill_*` methods write to, along with the
+    /// big value, the blobs and the records, and returns how many bytes of
+    /// storage were freed.
+    pub fn clear_benchmark_state(&mut self) -> u64 {
+        let _session = WriteBackSession::begin();
+        benchmarker::only_benchmarker();
+
+        let storage_before = env::storage_usage();
+        ChunkedVector::new(StorageKey::Vector).clear();
+        clear_collection(StorageKey::SdkVector, |vector: &mut store::Vector<u8>| {
+            vector.clear();
+            vector.flush();
+        });
+        clear_collection(
+            StorageKey::SdkIterableMap,
+            |map: &mut store::IterableMap<u8, u8>| {
+                map.clear();
+                map.flush();
+            },
+        );
+        // Lookup collections can't be iterated, but fills only use byte keys
+        clear_collection(
+            StorageKey::SdkLookupMap,
+            |map: &mut store::LookupMap<u8, u8>| {
+                for key in 0..=u8::MAX {
+                    map.remove(&key);
+                }
+                map.flush();
+            },
+        );
+        clear_collection(
+            StorageKey::SdkTreeMap,
+            |map: &mut store::TreeMap<u8, u8>| {
+                map.clear();
+                map.flush();
+            },
+        );
+        clear_collection(
+            StorageKey::SdkLookupSet,
+            |set: &mut store::LookupSet<u8>| {
+                for member in 0..=u8::MAX {
+                    set.remove(&member);
+                }
+            },
+        );
+        clear_collection(
+            StorageKey::SdkIterableSet,
+            |set: &mut store::IterableSet<u8>| {
+                set.clear();
+                set.flush();
+            },
+        );
+        clear_collection(
+            StorageKey::SdkNestedMap,
+            |map: &mut store::IterableMap<u8, store::Vector<u8>>| {
+                for inner in map.values_mut() {
+                    inner.clear();
+                    inner.flush();
+                }
+                map.clear();
+                map.flush();
+            },
+        );
+        // Values and records go back to what `init` left
+        StorageCell::new(StorageKey::BigValue).set(Vec::<u8>::new());
+        StorageCell::new(StorageKey::Blob).set(Vec::<u8>::new());
+        StorageCell::new(StorageKey::RecordBlob).set(Vec::<records::Record>::new());
+        let mut record_count = StorageCell::<u32>::new(StorageKey::RecordEntries);
+        for n in 0..record_count.try_get().copied().unwrap_or(0) {
+            StorageCell::<records::Record>::new(StorageKey::RecordEntries.entry_key(n)).remove();
+        }
+        record_count.set(0);
+        storage::flush();
+        storage_before - env::storage_usage()
+    }
+}
+
+#[near]
+impl Contract {
     pub fn get_vector(&self) -> Vec<u8> {
-        borsh::from_slice(&storage_read(b"vector").unwrap()).unwrap()
+        ChunkedVector::new(StorageKey::Vector).get_range(0, u32::MAX)
+    }
+
+    pub fn get_vector_item(&self, index: u32) -> Option<u8> {
+        ChunkedVector::new(StorageKey::Vector).get(index)
+    }
+
+    /// Up to `limit` elements of the vector starting at `from`, reading only
+    /// the chunks they span.
+    pub fn get_vector_range(&self, from: u32, limit: u32) -> Vec<u8> {
+        ChunkedVector::new(StorageKey::Vector).get_range(from, from.saturating_add(limit))
     }
 
     pub fn get_sdk_vector(&self) -> Vec<u8> {
-        borsh::from_slice(&storage_read(b"a").unwrap()).unwrap()
+        let sdk_vector = StorageCell::<store::Vector<u8>>::new(StorageKey::SdkVector);
+        sdk_vector
+            .try_get()
+            .map(|vector| vector.iter().cloned().collect::<Vec<u8>>())
+            .unwrap_or_default()
+    }
+
+    /// Up to `limit` elements of the SDK vector starting at `from`.
+    pub fn get_sdk_vector_range(&self, from: u32, limit: u32) -> Vec<u8> {
+        let sdk_vector = StorageCell::<store::Vector<u8>>::new(StorageKey::SdkVector);
+        let Some(sdk_vector) = sdk_vector.try_get() else {
+            return vec![];
+        };
+        (from..from.saturating_add(limit).min(sdk_vector.len()))
+            .filter_map(|index| sdk_vector.get(index).copied())
+            .collect()
+    }
+
+    /// Up to `limit` entries of the SDK iterable map in insertion order,
+    /// skipping the first `from`. The stored header carries the prefix the
+    /// entries were written under, so iterating it reads the real keys.
+    pub fn get_sdk_iterable_map_entries(&self, from: u32, limit: u32) -> Vec<(u8, u8)> {
+        let sdk_iterable_map =
+            StorageCell::<store::IterableMap<u8, u8>>::new(StorageKey::SdkIterableMap);
+        let Some(sdk_iterable_map) = sdk_iterable_map.try_get() else {
+            return vec![];
+        };
+        sdk_iterable_map
+            .iter()
+            .skip(from as usize)
+            .take(limit as usize)
+            .map(|(key, value)| (*key, *value))
+            .collect()
     }
 
     pub fn get_highest_bid(&self) -> Bid {
-        borsh::from_slice(&storage_read(b"highest_bid").unwrap()).unwrap()
+        highest_bid::load()
     }
 
     pub fn get_auction_end_time(&self) -> U64 {
-        borsh::from_slice(&storage_read(b"auction_end_time").unwrap()).unwrap()
+        U64(auction_state::config().auction_end_time)
     }
 
     pub fn get_auctioneer(&self) -> AccountId {
-        borsh::from_slice(&storage_read(b"auctioneer").unwrap()).unwrap()
+        auction_state::config().auctioneer
     }
 
     pub fn get_claimed(&self) -> bool {
-        borsh::from_slice(&storage_read(b"claimed").unwrap()).unwrap()
+        auction_state::config().is_claimed()
+    }
+
+    /// Whether the auction is open to `claim`, claimed, or waiting on a
+    /// payout. A claim stuck as pending is reopened by `resolve_claim` when
+    /// its payout fails.
+    pub fn get_claim_status(&self) -> ClaimStatus {
+        auction_state::config().claim
+    }
+
+    /// Number of accepted bids since `init`.
+    pub fn get_bid_count(&self) -> U64 {
+        *StorageCell::<U64>::new(StorageKey::BidCount).get()
+    }
+
+    /// Sum of every accepted bid, including the ones since refunded.
+    pub fn get_total_volume(&self) -> NearToken {
+        *StorageCell::<NearToken>::new(StorageKey::TotalVolume).get()
+    }
+
+    /// Nanoseconds left until the auction ends, zero once it has.
+    pub fn get_time_remaining(&self) -> U64 {
+        time_remaining(self.get_auction_end_time().0)
+    }
+
+    /// Whether the end time has been reached and bids are no longer accepted.
+    pub fn has_ended(&self) -> bool {
+        self.get_time_remaining().0 == 0
+    }
+
+    pub fn get_auction_info(&self) -> AuctionInfo {
+        let state = AuctionState::new();
+        let config = state.config.get();
+        let time_remaining = time_remaining(config.auction_end_time);
+        AuctionInfo {
+            highest_bid: state.full_highest_bid(),
+            auction_end_time: U64(config.auction_end_time),
+            auctioneer: config.auctioneer.clone(),
+            claimed: config.is_claimed(),
+            time_remaining,
+            has_ended: time_remaining.0 == 0,
+        }
+    }
+
+    // The `try_get_*` views return null instead of panicking when the key is
+    // missing, e.g. before `init` or halfway through a migration.
+
+    pub fn try_get_highest_bid(&self) -> Option<Bid> {
+        highest_bid::try_load()
+    }
+
+    pub fn try_get_auction_end_time(&self) -> Option<U64> {
+        auction_state::try_config().map(|config| U64(config.auction_end_time))
+    }
+
+    pub fn try_get_auctioneer(&self) -> Option<AccountId> {
+        auction_state::try_config().map(|config| config.auctioneer)
+    }
+
+    pub fn try_get_claimed(&self) -> Option<bool> {
+        auction_state::try_config().map(|config| config.is_claimed())
     }
 }
 
+/// Makes the `deposit` from `bidder`, less the storage its receipt takes,
+/// the highest bid and refunds the previous one, or returns why it can't be
+/// accepted. In a penny auction the bid is the next price instead, and the
+/// fee is kept. Writes nothing on error, so callers outside a panic can hand
+/// the deposit back themselves.
+fn place_bid(bidder: AccountId, deposit: NearToken) -> Result<Promise, AuctionError> {
+    let mut profile = GasProfile::start("bid");
+
+    // Load the config, the last bid and the running totals in one pass.
+    // Only the bidder and amount of the last bid are needed, so its metadata
+    // stays in storage
+    let config_key = StorageKey::AuctionConfig.to_bytes();
+    let highest_bid_key = StorageKey::HighestBid.to_bytes();
+    let metadata_key = StorageKey::HighestBidMetadata.to_bytes();
+    let bid_count_key = StorageKey::BidCount.to_bytes();
+    let total_volume_key = StorageKey::TotalVolume.to_bytes();
+    storage::prefetch(&[
+        &config_key,
+        &highest_bid_key,
+        &bid_count_key,
+        &total_volume_key,
+    ]);
+    profile.checkpoint("read");
+
+    let config: AuctionConfig = storage::load(&config_key);
+    let bid_count: U64 = storage::load(&bid_count_key);
+    let total_volume: NearToken = storage::load(&total_volume_key);
+    let HotBid {
+        bidder: last_bidder,
+        bid: last_bid,
+    } = load_hot_bid(&highest_bid_key);
+    profile.checkpoint("deserialize");
+
+    // Assert the auction is still ongoing
+    if env::block_timestamp() >= config.auction_end_time {
+        return Err(AuctionError::AuctionEnded);
+    }
+
+    // The deposit pays for the bid's receipt too, unless the bidder's storage
+    // balance does; whatever the receipt doesn't use is sent back below
+    let prepaid = storage_management::required_deposit(&bidder, receipts::MINT_BYTES);
+    let bid =
+        math::increment(prepaid, deposit).map_err(|_| AuctionError::DepositBelowStorageCost)?;
+
+    // A penny bid sets the next price and pays the fee out of the rest;
+    // anything else must clear the minimum increment over the current bid
+    let penny_auction = penny::penny_auction();
+    let penny_bid = match &penny_auction {
+        Some(penny_auction) => Some(penny_auction.split_bid(last_bid, bid)?),
+        None if bid < increments::min_next_bid(increments::min_increment().as_ref(), last_bid)? => {
+            return Err(AuctionError::BidTooLow)
+        }
+        None => None,
+    };
+    let (bid, excess) = match &penny_bid {
+        Some(penny_bid) => (penny_bid.price, penny_bid.excess),
+        None => (bid, NearToken::from_yoctonear(0)),
+    };
+
+    // Update the highest bid
+    let highest_bid = Bid {
+        bidder: bidder.clone(),
+        bid,
+        bid_time: U64::from(env::block_timestamp()),
+        bid_block_height: U64::from(env::block_height()),
+        bid_block_timestamp: U64::from(env::block_timestamp()),
+        bid_epoch_height: U64::from(env::epoch_height()),
+        premium: false,
+    };
+    let bid_count = U64(bid_count.0 + 1);
+    let total_volume = math::add(total_volume, bid)?;
+    // Only what this bid adds on top of the refunded one is new to the pool
+    let stake = math::increment(last_bid, bid)?;
+    profile.checkpoint("logic");
+
+    let unused = receipts::mint(&highest_bid, None, prepaid);
+    profile.checkpoint("receipt");
+
+    // Every penny bid restarts the countdown
+    let config = penny_auction.map(|penny_auction| {
+        borsh::to_vec(&AuctionConfig {
+            auction_end_time: penny_auction.end_time_after_bid(config.auction_end_time),
+            ..config
+        })
+        .unwrap()
+    });
+
+    let (hot, metadata) = highest_bid.split();
+    let hot = borsh::to_vec(&hot).unwrap();
+    let metadata = highest_bid::encode_metadata(metadata);
+    let bid_count = borsh::to_vec(&bid_count).unwrap();
+    let total_volume = borsh::to_vec(&total_volume).unwrap();
+    profile.checkpoint("serialize");
+
+    storage::write(&highest_bid_key, &hot);
+    storage::write(&metadata_key, &metadata);
+    storage::write(&bid_count_key, &bid_count);
+    storage::write(&total_volume_key, &total_volume);
+    if let Some(config) = config {
+        storage::write(&config_key, &config);
+    }
+    if let Some(penny_bid) = &penny_bid {
+        penny::collect_fees(penny_bid);
+    }
+    storage::flush();
+    profile.checkpoint("write");
+    profile.finish();
+
+    staking::stake_increment(stake);
+
+    // Transfer tokens back to the last bidder
+    let refund = refunds::refund(last_bidder, last_bid);
+    // Both came out of the deposit, so together they can't overflow
+    let unused = unused.saturating_add(excess);
+    Ok(storage_management::refund_unused(refund, bidder, unused))
+}
+
+/// Resolves a bid that must not panic: the refund of the outbid bidder if it
+/// was accepted, otherwise `false` after sending `bid` back to `bidder`.
+/// `place_bid` writes nothing on error, so the deposit is all there is to
+/// undo.
+fn accept_or_refund(
+    bidder: AccountId,
+    bid: NearToken,
+    result: Result<Promise, AuctionError>,
+) -> PromiseOrValue<bool> {
+    match result {
+        Ok(refund) => PromiseOrValue::Promise(refund),
+        Err(err) => {
+            env::log_str(&format!("Bid of {bid} from {bidder} rejected: {err}"));
+            if !bid.is_zero() {
+                Promise::new(bidder).transfer(bid).detach();
+            }
+            PromiseOrValue::Value(false)
+        }
+    }
+}
+
+/// The last bid's bidder and amount, deserialized as a whole.
+#[cfg(not(feature = "partial-decode"))]
+fn load_hot_bid(key: &[u8]) -> HotBid {
+    storage::load(key)
+}
+
+/// The last bid's bidder and amount, picked out of the stored bytes.
+#[cfg(feature = "partial-decode")]
+fn load_hot_bid(key: &[u8]) -> HotBid {
+    let bytes = storage::read(key).unwrap_or_else(|| storage::panic_missing(key));
+    partial::bid_prefix(&bytes)
+        .and_then(|prefix| prefix.to_hot_bid())
+        .unwrap_or_else(|| storage::panic_undecodable(key))
+}
+
+/// Nanoseconds from the current block until `end_time`, zero once bids are
+/// no longer accepted.
+fn time_remaining(end_time: u64) -> U64 {
+    U64(end_time.saturating_sub(env::block_timestamp()))
+}
+
+#[cfg(any(test, not(feature = "minimal")))]
+/// Key and value of element `i` of a fill: `i` itself, or the shared seeded
+/// test data when a seed is given.
+fn fill_item(seed: Option<u64>, i: u32) -> (u8, u8) {
+    match seed {
+        Some(seed) => (testdata::key(seed, i), testdata::value(seed, i)),
+        None => (i as u8, i as u8),
+    }
+}
+
+#[cfg(any(test, not(feature = "minimal")))]
+/// The indices `start..start + count` of a fill, which must fit in a `u32`.
+fn fill_range(start: u32, count: u32) -> std::ops::Range<u32> {
+    let end = start
+        .checked_add(count)
+        .unwrap_or_else(|| AuctionError::FillRangeOverflow.panic());
+    start..end
+}
+
+#[cfg(any(test, not(feature = "minimal")))]
+/// Runs `clear` on the collection stored under `key`, if there is one.
+fn clear_collection<T: near_sdk::borsh::BorshSerialize + near_sdk::borsh::BorshDeserialize>(
+    key: StorageKey,
+    clear: impl FnOnce(&mut T),
+) {
+    let mut cell = StorageCell::<T>::new(key);
+    if cell.try_get().is_some() {
+        clear(cell.get_mut());
+    }
+}
+
+#[cfg(any(test, not(feature = "minimal")))]
+/// `n` pseudo-random indices below `len`, seeded from `env::random_seed` so
+/// every call in the same block touches the same indices.
+fn random_indices(n: u32, len: u32) -> impl Iterator<Item = u32> {
+    let seed = env::random_seed_array();
+    // xorshift64 must not start from zero
+    let mut state = u64::from_le_bytes(seed[..8].try_into().unwrap()) | 1;
+    let n = if len == 0 { 0 } else { n };
+    (0..n).map(move |_| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % u64::from(len)) as u32
+    })
+}
+
 #[cfg(test)]
 mod tests {
+    use near_sdk::mock::MockAction;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::{
+        test_vm_config, testing_env, MockedBlockchain, PromiseResult, RuntimeFeesConfig,
+    };
+    use proptest::prelude::*;
+
     use super::*;
+    use crate::test_utils::new_contract;
 
     #[test]
     fn init_contract() {
         let end_time: U64 = U64::from(1000);
-        let alice: AccountId = "alice.near".parse().unwrap();
-        let contract = Contract::init(end_time.clone(), alice.clone());
+        let auctioneer: AccountId = "auctioneer.near".parse().unwrap();
+        let contract = Contract::init(end_time, auctioneer.clone(), None, None);
 
         let default_bid = contract.get_highest_bid();
         assert_eq!(default_bid.bidder, env::current_account_id());
@@ -186,10 +1216,895 @@ mod tests {
         let auction_end_time = contract.get_auction_end_time();
         assert_eq!(auction_end_time, end_time);
 
-        let auctioneer = contract.get_auctioneer();
-        assert_eq!(auctioneer, alice);
+        assert_eq!(contract.get_auctioneer(), auctioneer);
 
         let claimed = contract.get_claimed();
-        assert_eq!(claimed, false);
+        assert!(!claimed);
+    }
+
+    fn set_context(predecessor: &str, deposit: NearToken, block_timestamp: u64) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(predecessor.parse().unwrap())
+            .attached_deposit(deposit)
+            .block_timestamp(block_timestamp)
+            .build());
+    }
+
+    #[test]
+    fn bid_records_the_bidder_and_deposit() {
+        let mut contract = new_contract();
+        set_context("bob.near", NearToken::from_near(1), 100);
+        let _ = contract.bid();
+
+        let highest_bid = contract.get_highest_bid();
+        assert_eq!(highest_bid.bidder, "bob.near".parse::<AccountId>().unwrap());
+        assert_eq!(highest_bid.bid, net_of_receipt(NearToken::from_near(1)));
+        assert_eq!(highest_bid.bid_time, U64(100));
+    }
+
+    /// What a bid from a bidder without a storage balance is worth: the
+    /// deposit less what is set aside for its receipt.
+    fn net_of_receipt(deposit: NearToken) -> NearToken {
+        deposit.saturating_sub(storage_management::storage_cost(receipts::MINT_BYTES))
+    }
+
+    #[test]
+    fn unused_receipt_storage_is_sent_back() {
+        let mut contract = new_contract();
+        set_context("bob.near", NearToken::from_near(1), 100);
+        let _ = contract.bid();
+
+        // The receipt takes some of what was set aside, the rest goes back
+        let reserved = storage_management::storage_cost(receipts::MINT_BYTES);
+        let (receiver, unused) = created_transfers().pop().unwrap();
+        assert_eq!(receiver.as_str(), "bob.near");
+        assert!(!unused.is_zero() && unused < reserved);
+    }
+
+    #[test]
+    fn penny_bids_raise_the_price_keep_the_fee_and_restart_the_countdown() {
+        let mut contract = new_contract();
+        set_context(
+            contract.get_owner().as_str(),
+            NearToken::from_yoctonear(1),
+            0,
+        );
+        let increment = NearToken::from_near(1);
+        let fee = NearToken::from_millinear(100);
+        contract.set_penny_auction(Some(penny::PennyAuction {
+            fee,
+            increment,
+            countdown: U64(500),
+        }));
+
+        let info = contract.get_penny_auction_info().unwrap();
+        let reserved = storage_management::storage_cost(receipts::MINT_BYTES);
+        let deposit = info.next_bid_deposit.saturating_add(reserved);
+        set_context("bob.near", deposit, 100);
+        let _ = contract.bid();
+        assert_eq!(contract.get_highest_bid().bid, info.next_price);
+        assert_eq!(contract.get_auction_end_time(), U64(1000));
+
+        // Paying more than the next price only sends the rest back
+        set_context("alice.near", deposit.saturating_mul(2), 700);
+        let _ = contract.bid();
+        let info = contract.get_penny_auction_info().unwrap();
+        assert_eq!(info.current_price, contract.get_highest_bid().bid);
+        assert_eq!(
+            info.current_price,
+            NearToken::from_yoctonear(1).saturating_add(increment.saturating_mul(2))
+        );
+        assert_eq!(info.collected_fees, fee.saturating_mul(2));
+        assert_eq!(info.auction_end_time, U64(1200));
+        assert_eq!(info.time_remaining, U64(500));
+        // What the receipt didn't use goes back along with the excess
+        let excess = deposit
+            .saturating_mul(2)
+            .saturating_sub(reserved)
+            .saturating_sub(info.current_price.saturating_add(fee));
+        let (receiver, unused) = created_transfers().pop().unwrap();
+        assert_eq!(receiver.as_str(), "alice.near");
+        assert!(unused > excess && unused < excess.saturating_add(reserved));
+    }
+
+    #[test]
+    fn penny_bids_below_the_next_price_are_rejected() {
+        let mut contract = new_contract();
+        set_context(
+            contract.get_owner().as_str(),
+            NearToken::from_yoctonear(1),
+            0,
+        );
+        contract.set_penny_auction(Some(penny::PennyAuction {
+            fee: NearToken::from_millinear(100),
+            increment: NearToken::from_near(1),
+            countdown: U64(500),
+        }));
+
+        // Enough to outbid in a regular auction, but not to cover the fee
+        set_context("bob.near", net_of_receipt(NearToken::from_near(1)), 100);
+        let accepted = contract.try_bid();
+        assert!(matches!(accepted, PromiseOrValue::Value(false)));
+        assert_eq!(contract.get_bid_count(), U64(0));
+    }
+
+    #[test]
+    fn bids_must_clear_the_minimum_increment() {
+        let mut contract = new_contract();
+        set_context(
+            contract.get_owner().as_str(),
+            NearToken::from_yoctonear(1),
+            0,
+        );
+        contract.set_min_increment(Some(increments::BidIncrement::BasisPoints(500)));
+
+        let reserved = storage_management::storage_cost(receipts::MINT_BYTES);
+        set_context(
+            "bob.near",
+            NearToken::from_near(1).saturating_add(reserved),
+            100,
+        );
+        let _ = contract.bid();
+        let min_next_bid = contract.get_min_next_bid();
+        assert_eq!(min_next_bid, NearToken::from_millinear(1_050));
+
+        let short = min_next_bid.saturating_sub(NearToken::from_yoctonear(1));
+        set_context("alice.near", short.saturating_add(reserved), 200);
+        let accepted = contract.try_bid();
+        assert!(matches!(accepted, PromiseOrValue::Value(false)));
+
+        set_context("alice.near", min_next_bid.saturating_add(reserved), 200);
+        let _ = contract.bid();
+        assert_eq!(contract.get_highest_bid().bid, min_next_bid);
+    }
+
+    #[test]
+    fn registered_bidders_pay_storage_from_their_balance() {
+        let mut contract = new_contract();
+        set_context("bob.near", NearToken::from_near(1), 100);
+        contract.storage_deposit(None, None);
+        let available = contract
+            .storage_balance_of("bob.near".parse().unwrap())
+            .unwrap()
+            .available;
+
+        set_context("bob.near", NearToken::from_near(1), 100);
+        let _ = contract.bid();
+        assert_eq!(contract.get_highest_bid().bid, NearToken::from_near(1));
+        assert!(
+            contract
+                .storage_balance_of("bob.near".parse().unwrap())
+                .unwrap()
+                .available
+                < available
+        );
+    }
+
+    #[test]
+    fn try_bid_sends_back_a_deposit_below_the_storage_cost() {
+        let mut contract = new_contract();
+        let deposit = storage_management::storage_cost(receipts::MINT_BYTES);
+        set_context("bob.near", deposit, 100);
+
+        assert!(matches!(contract.try_bid(), PromiseOrValue::Value(false)));
+        assert_eq!(
+            created_transfers(),
+            vec![("bob.near".parse().unwrap(), deposit)]
+        );
+        assert_eq!(contract.get_receipt_count("bob.near".parse().unwrap()), 0);
+    }
+
+    #[test]
+    fn higher_bid_outbids() {
+        let mut contract = new_contract();
+        set_context("bob.near", NearToken::from_near(1), 100);
+        let _ = contract.bid();
+        set_context("carol.near", NearToken::from_near(2), 200);
+        let _ = contract.bid();
+
+        let highest_bid = contract.get_highest_bid();
+        assert_eq!(
+            highest_bid.bidder,
+            "carol.near".parse::<AccountId>().unwrap()
+        );
+        assert_eq!(highest_bid.bid, net_of_receipt(NearToken::from_near(2)));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_BID_TOO_LOW")]
+    fn lower_bid_is_rejected() {
+        let mut contract = new_contract();
+        set_context("bob.near", NearToken::from_near(2), 100);
+        let _ = contract.bid();
+        set_context("carol.near", NearToken::from_near(1), 200);
+        let _ = contract.bid();
+    }
+
+    #[test]
+    fn try_bid_sends_a_low_bid_back() {
+        let mut contract = new_contract();
+        set_context("bob.near", NearToken::from_near(2), 100);
+        let _ = contract.bid();
+        set_context("carol.near", NearToken::from_near(1), 200);
+
+        assert!(matches!(contract.try_bid(), PromiseOrValue::Value(false)));
+        assert_eq!(
+            created_transfers(),
+            vec![("carol.near".parse().unwrap(), NearToken::from_near(1))]
+        );
+        assert_eq!(contract.get_highest_bid().bidder.as_str(), "bob.near");
+        assert_eq!(contract.get_bid_count(), U64(1));
+    }
+
+    #[test]
+    fn try_bid_sends_the_deposit_back_while_paused() {
+        let mut contract = new_contract();
+        set_context("bob.near", NearToken::from_yoctonear(1), 100);
+        contract.pause();
+        set_context("carol.near", NearToken::from_near(1), 200);
+
+        assert!(matches!(contract.try_bid(), PromiseOrValue::Value(false)));
+        assert_eq!(
+            created_transfers(),
+            vec![("carol.near".parse().unwrap(), NearToken::from_near(1))]
+        );
+    }
+
+    #[test]
+    fn try_bid_accepts_a_higher_bid() {
+        let mut contract = new_contract();
+        set_context("bob.near", NearToken::from_near(2), 100);
+
+        assert!(matches!(contract.try_bid(), PromiseOrValue::Promise(_)));
+        assert_eq!(contract.get_highest_bid().bidder.as_str(), "bob.near");
+    }
+
+    #[test]
+    fn try_bid_sends_back_a_bid_that_overflows_the_volume() {
+        let mut contract = new_contract();
+        {
+            let _session = WriteBackSession::begin();
+            StorageCell::new(StorageKey::TotalVolume).set(NearToken::from_yoctonear(u128::MAX));
+        }
+        set_context("bob.near", NearToken::from_near(1), 100);
+
+        assert!(matches!(contract.try_bid(), PromiseOrValue::Value(false)));
+        assert_eq!(
+            created_transfers(),
+            vec![("bob.near".parse().unwrap(), NearToken::from_near(1))]
+        );
+        assert_eq!(
+            contract.get_total_volume(),
+            NearToken::from_yoctonear(u128::MAX)
+        );
+        assert_eq!(contract.get_bid_count(), U64(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_AUCTION_ENDED")]
+    fn bid_after_the_end_is_rejected() {
+        let mut contract = new_contract();
+        set_context("bob.near", NearToken::from_near(1), 1000);
+        let _ = contract.bid();
+    }
+
+    /// Runs `resolve_claim` as if the payout `claim` sent had ended in
+    /// `result`.
+    fn resolve_claim_with(contract: &mut Contract, result: PromiseResult) -> Option<Promise> {
+        let winning_bid = contract.get_highest_bid();
+        testing_env!(
+            VMContextBuilder::new()
+                .predecessor_account_id(env::current_account_id())
+                .block_timestamp(1001)
+                .build(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![result],
+        );
+        contract.resolve_claim(winning_bid)
+    }
+
+    #[test]
+    fn claim_marks_the_auction_claimed() {
+        let mut contract = new_contract();
+        set_context("bob.near", NearToken::from_near(1), 100);
+        let _ = contract.bid();
+        set_context("auctioneer.near", NearToken::from_yoctonear(1), 1001);
+        let _ = contract.claim();
+        assert_eq!(contract.get_claim_status(), ClaimStatus::Pending);
+        assert!(!contract.get_claimed());
+
+        resolve_claim_with(&mut contract, PromiseResult::Successful(vec![]));
+        assert_eq!(contract.get_claim_status(), ClaimStatus::Claimed);
+        assert!(contract.get_claimed());
+    }
+
+    #[test]
+    fn failed_payout_reopens_the_claim() {
+        let mut contract = new_contract();
+        set_context("bob.near", NearToken::from_near(1), 100);
+        let _ = contract.bid();
+        set_context("auctioneer.near", NearToken::from_yoctonear(1), 1001);
+        let _ = contract.claim();
+
+        assert!(resolve_claim_with(&mut contract, PromiseResult::Failed).is_none());
+        assert_eq!(contract.get_claim_status(), ClaimStatus::Open);
+
+        set_context("auctioneer.near", NearToken::from_yoctonear(1), 1002);
+        let _ = contract.claim();
+        assert_eq!(contract.get_claim_status(), ClaimStatus::Pending);
+    }
+
+    #[test]
+    fn result_is_only_published_after_a_successful_payout() {
+        let mut contract = new_contract();
+        set_context(
+            contract.get_owner().as_str(),
+            NearToken::from_yoctonear(1),
+            0,
+        );
+        contract.set_social_db(Some("social.near".parse().unwrap()));
+
+        set_context("auctioneer.near", NearToken::from_yoctonear(1), 1001);
+        let _ = contract.claim();
+        assert!(resolve_claim_with(&mut contract, PromiseResult::Failed).is_none());
+
+        set_context("auctioneer.near", NearToken::from_yoctonear(1), 1001);
+        let _ = contract.claim();
+        assert!(resolve_claim_with(&mut contract, PromiseResult::Successful(vec![])).is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_CLAIM_PENDING")]
+    fn claim_is_rejected_while_the_payout_is_pending() {
+        let mut contract = new_contract();
+        set_context("auctioneer.near", NearToken::from_yoctonear(1), 1001);
+        let _ = contract.claim();
+        let _ = contract.claim();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_AUCTION_NOT_ENDED")]
+    fn claim_before_the_end_is_rejected() {
+        let mut contract = new_contract();
+        set_context("auctioneer.near", NearToken::from_yoctonear(1), 1000);
+        let _ = contract.claim();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ALREADY_CLAIMED")]
+    fn double_claim_is_rejected() {
+        let mut contract = new_contract();
+        set_context("auctioneer.near", NearToken::from_yoctonear(1), 1001);
+        let _ = contract.claim();
+        resolve_claim_with(&mut contract, PromiseResult::Successful(vec![]));
+        set_context("auctioneer.near", NearToken::from_yoctonear(1), 1001);
+        let _ = contract.claim();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ALREADY_INITIALIZED")]
+    fn init_refuses_to_overwrite_state() {
+        new_contract();
+        Contract::init(U64::from(2000), "bob.near".parse().unwrap(), None, None);
+    }
+
+    #[cfg(not(feature = "minimal"))]
+    #[test]
+    #[should_panic(expected = "Missing value in storage: auction_config")]
+    fn getters_name_the_missing_key_before_init() {
+        Contract {}.get_auctioneer();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_END_TIME_TOO_SOON")]
+    fn init_rejects_a_past_end_time() {
+        set_context("bob.near", NearToken::from_yoctonear(0), 1000);
+        new_contract();
+    }
+
+    #[test]
+    fn init_honours_the_minimum_duration() {
+        set_context("bob.near", NearToken::from_yoctonear(0), 1000);
+        let contract = Contract::init(
+            U64(1501),
+            "auctioneer.near".parse().unwrap(),
+            None,
+            Some(U64(500)),
+        );
+        assert_eq!(contract.get_auction_end_time(), U64(1501));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_END_TIME_TOO_SOON")]
+    fn init_rejects_an_end_time_within_the_minimum_duration() {
+        set_context("bob.near", NearToken::from_yoctonear(0), 1000);
+        Contract::init(
+            U64(1500),
+            "auctioneer.near".parse().unwrap(),
+            None,
+            Some(U64(500)),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_AUCTIONEER_IS_CONTRACT")]
+    fn init_rejects_the_contract_as_auctioneer() {
+        Contract::init(U64(1000), env::current_account_id(), None, None);
+    }
+
+    #[test]
+    fn try_getters_return_none_before_init() {
+        let contract = Contract {};
+        assert!(contract.try_get_highest_bid().is_none());
+        assert_eq!(contract.try_get_auction_end_time(), None);
+        assert_eq!(contract.try_get_auctioneer(), None);
+        assert_eq!(contract.try_get_claimed(), None);
+
+        let contract = new_contract();
+        assert_eq!(contract.try_get_auction_end_time(), Some(U64::from(1000)));
+        assert_eq!(contract.try_get_claimed(), Some(false));
+    }
+
+    #[test]
+    fn clear_reports_freed_storage() {
+        let mut contract = new_contract();
+        contract.fill_vector(1000, 0, None);
+        // The compressed vector takes a fraction of its 1000 raw bytes
+        let min_vector_bytes = if cfg!(feature = "compression") {
+            100
+        } else {
+            1000
+        };
+        assert!(contract.clear_vector() >= min_vector_bytes);
+        assert!(contract.get_vector().is_empty());
+        assert_eq!(contract.clear_vector(), 0);
+
+        testing_env!(VMContextBuilder::new().build());
+        contract.fill_sdk_vector(1000, 0, None);
+        testing_env!(VMContextBuilder::new().build());
+        assert!(contract.clear_sdk_vector() >= 1000);
+        assert!(contract.get_sdk_vector().is_empty());
+
+        testing_env!(VMContextBuilder::new().build());
+        contract.fill_sdk_iterable_map(1000, 0, None);
+        testing_env!(VMContextBuilder::new().build());
+        assert!(contract.clear_sdk_iterable_map() >= 1000);
+
+        testing_env!(VMContextBuilder::new().build());
+        contract.fill_sdk_vector(1000, 0, None);
+        assert_eq!(contract.get_sdk_vector().len(), 1000);
+    }
+
+    #[test]
+    fn lookup_map_reads_back_filled_range() {
+        let mut contract = new_contract();
+        assert!(contract.read_lookup_map(10, 0).is_empty());
+
+        contract.fill_lookup_map(10, 5, None);
+        assert_eq!(contract.read_lookup_map(10, 0), vec![5, 6, 7, 8, 9]);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_FILL_RANGE_OVERFLOW")]
+    fn fills_past_u32_max_are_rejected() {
+        let mut contract = new_contract();
+        contract.fill_lookup_map(2, u32::MAX, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_BENCHMARKER")]
+    fn only_the_benchmarker_fills_the_state() {
+        let mut contract = new_contract();
+        set_context("mallory.near", NearToken::from_yoctonear(0), 0);
+        contract.fill_sdk_vector(10, 0, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_BENCHMARKER")]
+    fn only_the_benchmarker_grows_the_big_value() {
+        let mut contract = new_contract();
+        set_context("mallory.near", NearToken::from_yoctonear(0), 0);
+        contract.set_big_value(4_000_000);
+    }
+
+    #[test]
+    fn clearing_the_benchmark_state_frees_every_fill() {
+        let mut contract = new_contract();
+        // The first clear writes the key index `init` leaves out, which stays
+        contract.clear_benchmark_state();
+        let storage_before = env::storage_usage();
+        contract.fill_vector(100, 0, None);
+        contract.fill_sdk_vector(100, 0, None);
+        contract.fill_sdk_iterable_map(100, 0, None);
+        contract.fill_lookup_map(100, 0, None);
+        contract.fill_tree_map(100, 0);
+        contract.fill_lookup_set(100, 0);
+        contract.fill_iterable_set(100, 0);
+        contract.fill_nested_map(3, 10, 0);
+        contract.set_big_value(1_000);
+        contract.write_blob(1);
+        contract.fill_record_blob(10, 0);
+        contract.fill_record_keys(10, 0);
+
+        let filled = env::storage_usage() - storage_before;
+        assert_eq!(contract.clear_benchmark_state(), filled);
+        assert_eq!(env::storage_usage(), storage_before);
+        assert!(contract.get_sdk_vector().is_empty());
+        assert!(contract.read_nested_map(0).is_empty());
+        assert_eq!(contract.big_value_len(), 0);
+        assert_eq!(contract.read_record_keys(0), None);
+
+        contract.fill_sdk_vector(10, 0, None);
+        assert_eq!(contract.get_sdk_vector().len(), 10);
+    }
+
+    #[test]
+    fn big_value_is_only_loaded_on_demand() {
+        let mut contract = new_contract();
+        contract.set_big_value(10_000);
+        assert_eq!(contract.big_value_len(), 10_000);
+
+        testing_env!(VMContextBuilder::new().build());
+        contract.touch_state();
+        assert!(env::used_gas() < near_sdk::Gas::from_tgas(1));
+    }
+
+    #[test]
+    fn sets_report_membership() {
+        let mut contract = new_contract();
+        contract.fill_lookup_set(5, 0);
+        contract.fill_iterable_set(3, 2);
+        assert_eq!(contract.contains_many(10), (5, 3));
+    }
+
+    #[test]
+    fn tree_map_ranges_are_ordered() {
+        let mut contract = new_contract();
+        contract.fill_tree_map(10, 20);
+        contract.fill_tree_map(10, 0);
+        assert_eq!(contract.range_tree_map(8, 22), vec![8, 9, 20, 21]);
+        assert!(contract.range_tree_map(300, 400).is_empty());
+    }
+
+    #[test]
+    fn removals_report_freed_storage() {
+        let mut contract = new_contract();
+        contract.fill_sdk_vector(10, 0, None);
+        contract.fill_sdk_iterable_map(10, 0, None);
+
+        assert!(contract.drain_sdk_vector(4) > 0);
+        assert_eq!(contract.get_sdk_vector(), (0..6).collect::<Vec<u8>>());
+        assert!(contract.remove_from_iterable_map(4) > 0);
+
+        // Asking for more than is left stops at empty
+        assert!(contract.drain_sdk_vector(100) > 0);
+        assert_eq!(contract.drain_sdk_vector(1), 0);
+        assert!(contract.remove_from_iterable_map(100) > 0);
+        assert_eq!(contract.remove_from_iterable_map(1), 0);
+    }
+
+    #[test]
+    fn nested_vectors_keep_their_own_elements() {
+        let mut contract = new_contract();
+        contract.fill_nested_map(2, 3, 0);
+        contract.fill_nested_map(1, 2, 10);
+
+        assert_eq!(contract.read_nested_map(0), vec![0, 1, 2, 10, 11]);
+        assert_eq!(contract.read_nested_map(1), vec![0, 1, 2]);
+        assert!(contract.read_nested_map(2).is_empty());
+        assert!(contract.read_nested_map(300).is_empty());
+    }
+
+    #[test]
+    fn seeded_fills_do_not_depend_on_batching() {
+        let mut contract = new_contract();
+        contract.fill_vector(30, 0, Some(7));
+        contract.fill_vector(70, 30, Some(7));
+        let expected: Vec<u8> = (0..100).map(|i| testdata::value(7, i)).collect();
+        assert_eq!(contract.get_vector(), expected);
+
+        contract.fill_lookup_map(1, 0, Some(7));
+        assert_eq!(
+            contract.read_lookup_map(1, testdata::key(7, 0).into()),
+            vec![testdata::value(7, 0)]
+        );
+    }
+
+    #[test]
+    fn random_access_reads_stay_in_bounds() {
+        let mut contract = new_contract();
+        assert!(contract.random_access_sdk_vector(10).is_empty());
+
+        contract.fill_vector(50, 0, None);
+        contract.fill_sdk_vector(50, 0, None);
+        contract.fill_sdk_iterable_map(50, 0, None);
+        assert!(contract.random_access_vector(20).iter().all(|v| *v < 50));
+        assert!(contract
+            .random_access_sdk_vector(20)
+            .iter()
+            .all(|v| *v < 50));
+        assert_eq!(contract.random_access_sdk_vector(20).len(), 20);
+        assert!(contract
+            .random_access_sdk_iterable_map(20)
+            .iter()
+            .all(|v| *v < 50));
+    }
+
+    #[test]
+    fn auction_info_tracks_the_end_time() {
+        let auctioneer: AccountId = "auctioneer.near".parse().unwrap();
+        let contract = new_contract();
+
+        let info = contract.get_auction_info();
+        assert_eq!(info.auctioneer, auctioneer);
+        assert_eq!(info.auction_end_time, U64::from(1000));
+        assert!(!info.claimed);
+        assert_eq!(info.time_remaining, U64(1000 - env::block_timestamp()));
+        assert!(!info.has_ended);
+
+        testing_env!(VMContextBuilder::new().block_timestamp(1000).build());
+        let info = contract.get_auction_info();
+        assert_eq!(info.time_remaining, U64(0));
+        assert!(info.has_ended);
+    }
+
+    #[test]
+    fn bids_are_counted_and_summed() {
+        let mut contract = new_contract();
+        for near in [1, 3] {
+            testing_env!(VMContextBuilder::new()
+                .attached_deposit(NearToken::from_near(near))
+                .build());
+            let _ = contract.bid();
+        }
+        assert_eq!(contract.get_bid_count(), U64(2));
+        assert_eq!(
+            contract.get_total_volume(),
+            net_of_receipt(NearToken::from_near(1))
+                .saturating_add(net_of_receipt(NearToken::from_near(3)))
+        );
+    }
+
+    #[test]
+    fn relayed_bids_belong_to_the_sender() {
+        let mut contract = new_contract();
+        // A delegate action runs with the relayer as signer and the sender
+        // as predecessor
+        testing_env!(VMContextBuilder::new()
+            .signer_account_id("relayer.near".parse().unwrap())
+            .predecessor_account_id("bob.near".parse().unwrap())
+            .attached_deposit(NearToken::from_near(1))
+            .build());
+        let _ = contract.bid();
+        assert_eq!(
+            contract.get_highest_bid().bidder,
+            "bob.near".parse::<AccountId>().unwrap()
+        );
+    }
+
+    #[test]
+    fn auctioneer_can_extend_a_running_auction() {
+        let auctioneer: AccountId = "auctioneer.near".parse().unwrap();
+        let mut contract = new_contract();
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(auctioneer)
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .block_timestamp(500)
+            .build());
+
+        contract.extend_end_time(U64(2000));
+        assert_eq!(contract.get_auction_end_time(), U64(2000));
+        assert_eq!(
+            near_sdk::test_utils::get_logs(),
+            vec![
+                r#"EVENT_JSON:{"standard":"auction","version":"1.0.0","event":"end_time_extended","data":{"old_end_time":"1000","new_end_time":"2000"}}"#
+            ]
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_END_TIME_NOT_LATER")]
+    fn end_time_cannot_move_earlier() {
+        let auctioneer: AccountId = "auctioneer.near".parse().unwrap();
+        let mut contract = new_contract();
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(auctioneer)
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .build());
+        contract.extend_end_time(U64(900));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_AUCTIONEER")]
+    fn others_cannot_extend_the_auction() {
+        let mut contract = new_contract();
+        testing_env!(VMContextBuilder::new()
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .build());
+        contract.extend_end_time(U64(2000));
+    }
+
+    #[test]
+    #[should_panic(expected = "Requires attached deposit of exactly 1 yoctoNEAR")]
+    fn claim_requires_one_yocto() {
+        let mut contract = new_contract();
+        testing_env!(VMContextBuilder::new().block_timestamp(2000).build());
+        let _ = contract.claim();
+    }
+
+    #[test]
+    fn owner_can_replace_the_auctioneer() {
+        let owner: AccountId = "owner.near".parse().unwrap();
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(owner)
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .build());
+        let mut contract = new_contract();
+
+        contract.set_auctioneer("bob.near".parse().unwrap());
+        assert_eq!(
+            contract.get_auctioneer(),
+            "bob.near".parse::<AccountId>().unwrap()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ALREADY_CLAIMED")]
+    fn auctioneer_is_fixed_once_claimed() {
+        let auctioneer: AccountId = "auctioneer.near".parse().unwrap();
+        let mut contract = new_contract();
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(auctioneer.clone())
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .block_timestamp(2000)
+            .build());
+        let _ = contract.claim();
+        resolve_claim_with(&mut contract, PromiseResult::Successful(vec![]));
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(auctioneer)
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .build());
+        contract.set_auctioneer("bob.near".parse().unwrap());
+    }
+
+    #[test]
+    fn time_views_follow_the_block_timestamp() {
+        let contract = new_contract();
+        testing_env!(VMContextBuilder::new().block_timestamp(400).build());
+        assert_eq!(contract.get_time_remaining(), U64(600));
+        assert!(!contract.has_ended());
+
+        testing_env!(VMContextBuilder::new().block_timestamp(2000).build());
+        assert_eq!(contract.get_time_remaining(), U64(0));
+        assert!(contract.has_ended());
+    }
+
+    #[test]
+    fn vector_ranges_are_clamped() {
+        let mut contract = new_contract();
+        assert!(contract.get_sdk_vector_range(0, 10).is_empty());
+
+        contract.fill_vector(25, 0, None);
+        contract.fill_sdk_vector(25, 0, None);
+        assert_eq!(contract.get_vector_range(5, 3), vec![5, 6, 7]);
+        assert_eq!(contract.get_sdk_vector_range(5, 3), vec![5, 6, 7]);
+        assert_eq!(contract.get_vector_range(20, u32::MAX).len(), 5);
+        assert_eq!(contract.get_sdk_vector_range(20, u32::MAX).len(), 5);
+        assert!(contract.get_sdk_vector_range(30, 10).is_empty());
+    }
+
+    #[test]
+    fn sdk_vector_is_read_from_the_element_keys_under_its_prefix() {
+        let mut contract = new_contract();
+        contract.fill_sdk_vector(20, 0, Some(7));
+        let expected: Vec<u8> = (0..20).map(|i| testdata::value(7, i)).collect();
+        assert_eq!(contract.get_sdk_vector(), expected);
+
+        // Each element sits under the header's prefix, at its borsh index
+        let prefix = StorageKey::SdkVector.items_prefix();
+        for (index, value) in (0u32..).zip(&expected) {
+            let key = [prefix.clone(), borsh::to_vec(&index).unwrap()].concat();
+            let stored: u8 = borsh::from_slice(&env::storage_read(&key).unwrap()).unwrap();
+            assert_eq!(stored, *value);
+        }
+    }
+
+    #[test]
+    fn iterable_map_entries_are_paged() {
+        let mut contract = new_contract();
+        assert!(contract.get_sdk_iterable_map_entries(0, 10).is_empty());
+
+        contract.fill_sdk_iterable_map(25, 0, None);
+        assert_eq!(
+            contract.get_sdk_iterable_map_entries(0, 3),
+            vec![(0, 0), (1, 1), (2, 2)]
+        );
+        assert_eq!(contract.get_sdk_iterable_map_entries(20, 10).len(), 5);
+        assert!(contract.get_sdk_iterable_map_entries(25, 10).is_empty());
+    }
+
+    /// Outgoing transfers scheduled by the last call, as (receiver, amount).
+    fn created_transfers() -> Vec<(AccountId, NearToken)> {
+        near_sdk::test_utils::get_created_receipts()
+            .into_iter()
+            .flat_map(|receipt| {
+                let receiver_id = receipt.receiver_id;
+                receipt
+                    .actions
+                    .into_iter()
+                    .filter_map(move |action| match action {
+                        MockAction::Transfer { deposit, .. } => {
+                            Some((receiver_id.clone(), deposit))
+                        }
+                        _ => None,
+                    })
+            })
+            .collect()
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(32))]
+
+        /// Random bid sequences from a handful of bidders, with amounts in
+        /// milliNEAR so that plenty of them are too low.
+        #[test]
+        fn bid_sequences_keep_the_invariants(
+            bids in prop::collection::vec((0..4usize, 1..5_000u128), 1..30)
+        ) {
+            // What the contract holds besides the highest bid, e.g. for storage
+            const RESERVE: NearToken = NearToken::from_near(100);
+            let bidders = ["alice.near", "bob.near", "carol.near", "dave.near"];
+
+            env::set_blockchain_interface(MockedBlockchain::default());
+            let mut contract = new_contract();
+            // Storage balances pay for the receipts, so every deposit is bid in full
+            for bidder in bidders {
+                testing_env!(VMContextBuilder::new()
+                    .predecessor_account_id(bidder.parse().unwrap())
+                    .attached_deposit(NearToken::from_near(1))
+                    .build());
+                contract.storage_deposit(None, None);
+            }
+            let mut refunded = NearToken::from_yoctonear(0);
+            for (bidder, millinear) in bids {
+                let bidder: AccountId = bidders[bidder].parse().unwrap();
+                let deposit = NearToken::from_millinear(millinear);
+                let last = contract.get_highest_bid();
+                testing_env!(VMContextBuilder::new()
+                    .predecessor_account_id(bidder.clone())
+                    .attached_deposit(deposit)
+                    // The attached deposit is added on top, as in the runtime
+                    .account_balance(RESERVE.saturating_add(last.bid))
+                    .block_timestamp(100)
+                    .build());
+
+                if deposit <= last.bid {
+                    prop_assert!(place_bid(bidder, deposit).is_err());
+                    let highest = contract.get_highest_bid();
+                    prop_assert_eq!(highest.bid, last.bid);
+                    prop_assert_eq!(highest.bidder, last.bidder);
+                    prop_assert!(created_transfers().is_empty());
+                    continue;
+                }
+
+                let _ = contract.bid();
+                let highest = contract.get_highest_bid();
+                prop_assert!(highest.bid > last.bid);
+                prop_assert_eq!(&highest.bidder, &bidder);
+                // The outbid bidder gets exactly their bid back, and only they do
+                prop_assert_eq!(created_transfers(), vec![(last.bidder, last.bid)]);
+                refunded = refunded.saturating_add(last.bid);
+                prop_assert_eq!(env::account_balance(), RESERVE.saturating_add(highest.bid));
+            }
+
+            // Every accepted bid was either refunded or is the highest one;
+            // the initial 1 yoctoNEAR bid was refunded without being counted
+            prop_assert_eq!(
+                contract.get_total_volume().saturating_add(NearToken::from_yoctonear(1)),
+                refunded.saturating_add(contract.get_highest_bid().bid)
+            );
+        }
     }
 }
@@ -0,0 +1,80 @@
+//! Storage reads straight through the host functions, enabled with the
+//! `unsafe-lowlevel` feature, plus methods that measure them against the
+//! `env` wrappers.
+
+use near_sdk::json_types::U64;
+use near_sdk::{env, near, sys};
+
+use crate::storage::StorageKey;
+use crate::{Contract, ContractExt};
+
+/// Register used by this module; the SDK keeps `u64::MAX - 2` for itself.
+const REGISTER: u64 = 0;
+
+/// Same contract as `env::storage_read`.
+pub fn storage_read(key: &[u8]) -> Option<Vec<u8>> {
+    let mut buffer = Vec::new();
+    storage_read_into(key, &mut buffer).then_some(buffer)
+}
+
+/// Reads the value under `key` into `buffer`, reusing its allocation, and
+/// returns whether the key exists. `buffer` is left empty if it doesn't.
+pub fn storage_read_into(key: &[u8], buffer: &mut Vec<u8>) -> bool {
+    buffer.clear();
+    // SAFETY: `key` is a live slice for the duration of the call, and the
+    // buffer is grown to the register length before the host writes into it.
+    unsafe {
+        if sys::storage_read(key.len() as u64, key.as_ptr() as u64, REGISTER) == 0 {
+            return false;
+        }
+        let len = sys::register_len(REGISTER) as usize;
+        buffer.reserve(len);
+        sys::read_register(REGISTER, buffer.as_mut_ptr() as u64);
+        buffer.set_len(len);
+    }
+    true
+}
+
+#[near]
+impl Contract {
+    /// Gas burnt reading the highest bid `iterations` times with
+    /// `env::storage_read`.
+    pub fn bench_env_read(&self, iterations: u32) -> U64 {
+        let key = StorageKey::HighestBid.to_bytes();
+        let gas_before = env::used_gas();
+        for _ in 0..iterations {
+            env::storage_read(&key);
+        }
+        U64(env::used_gas().as_gas() - gas_before.as_gas())
+    }
+
+    /// Gas burnt reading the highest bid `iterations` times through the
+    /// register path, reusing one buffer.
+    pub fn bench_register_read(&self, iterations: u32) -> U64 {
+        let key = StorageKey::HighestBid.to_bytes();
+        let mut buffer = Vec::new();
+        let gas_before = env::used_gas();
+        for _ in 0..iterations {
+            storage_read_into(&key, &mut buffer);
+        }
+        U64(env::used_gas().as_gas() - gas_before.as_gas())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_env_reads() {
+        env::storage_write(b"key", b"value");
+        assert_eq!(storage_read(b"key"), env::storage_read(b"key"));
+        assert_eq!(storage_read(b"missing"), None);
+
+        let mut buffer = b"stale bytes".to_vec();
+        assert!(storage_read_into(b"key", &mut buffer));
+        assert_eq!(buffer, b"value");
+        assert!(!storage_read_into(b"missing", &mut buffer));
+        assert!(buffer.is_empty());
+    }
+}
@@ -0,0 +1,158 @@
+//! Checked yoctoNEAR arithmetic. Release builds keep `overflow-checks`, but
+//! an overflow there panics without saying what went wrong; these return an
+//! `AuctionError` instead, so callers can surface it or refund the deposit.
+
+use near_sdk::NearToken;
+
+use crate::error::AuctionError;
+
+/// Basis points in a whole: 10 000 bps is 100%.
+pub const BPS_DENOMINATOR: u32 = 10_000;
+
+pub fn add(a: NearToken, b: NearToken) -> Result<NearToken, AuctionError> {
+    a.checked_add(b).ok_or(AuctionError::AmountOverflow)
+}
+
+/// Total of `amounts`, e.g. every bid that made up the volume.
+pub fn sum(amounts: impl IntoIterator<Item = NearToken>) -> Result<NearToken, AuctionError> {
+    amounts
+        .into_iter()
+        .try_fold(NearToken::from_yoctonear(0), add)
+}
+
+/// `count` times `amount`, e.g. the price of several units.
+pub fn times(amount: NearToken, count: u32) -> Result<NearToken, AuctionError> {
+    amount
+        .checked_mul(count.into())
+        .ok_or(AuctionError::AmountOverflow)
+}
+
+/// How much `to` adds on top of `from`.
+pub fn increment(from: NearToken, to: NearToken) -> Result<NearToken, AuctionError> {
+    to.checked_sub(from).ok_or(AuctionError::AmountUnderflow)
+}
+
+/// `basis_points` of `amount`, rounded down. More than 10 000 bps is fine,
+/// e.g. for a minimum next bid above the current one.
+pub fn percent_of(amount: NearToken, basis_points: u32) -> Result<NearToken, AuctionError> {
+    let scaled = amount
+        .as_yoctonear()
+        .checked_mul(basis_points.into())
+        .ok_or(AuctionError::AmountOverflow)?;
+    Ok(NearToken::from_yoctonear(
+        scaled / u128::from(BPS_DENOMINATOR),
+    ))
+}
+
+/// `basis_points` of `amount`, rounded up, e.g. for a minimum increment that
+/// must never fall short of its percentage. Splitting `amount` into whole
+/// ten-thousandths and a remainder first means only a result that doesn't
+/// fit in a `u128` overflows, not the intermediate product.
+pub fn percent_of_rounded_up(
+    amount: NearToken,
+    basis_points: u32,
+) -> Result<NearToken, AuctionError> {
+    let denominator = u128::from(BPS_DENOMINATOR);
+    let (whole, rest) = (
+        amount.as_yoctonear() / denominator,
+        amount.as_yoctonear() % denominator,
+    );
+    let rest = (rest * u128::from(basis_points)).div_ceil(denominator);
+    whole
+        .checked_mul(basis_points.into())
+        .and_then(|whole| whole.checked_add(rest))
+        .map(NearToken::from_yoctonear)
+        .ok_or(AuctionError::AmountOverflow)
+}
+
+/// Splits `amount` into a fee of `fee_basis_points` and what is left. The
+/// fee rounds down, so the two always add back up to `amount`.
+pub fn split_fee(
+    amount: NearToken,
+    fee_basis_points: u32,
+) -> Result<(NearToken, NearToken), AuctionError> {
+    let fee = percent_of(amount, fee_basis_points)?;
+    let rest = increment(fee, amount)?;
+    Ok((fee, rest))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX: NearToken = NearToken::from_yoctonear(u128::MAX);
+
+    fn yocto(amount: u128) -> NearToken {
+        NearToken::from_yoctonear(amount)
+    }
+
+    #[test]
+    fn sums_up_to_the_largest_amount() {
+        assert_eq!(add(MAX, yocto(0)), Ok(MAX));
+        assert_eq!(sum([yocto(u128::MAX - 1), yocto(1)]), Ok(MAX));
+        assert_eq!(sum([]), Ok(yocto(0)));
+        assert_eq!(add(MAX, yocto(1)), Err(AuctionError::AmountOverflow));
+        assert_eq!(sum([MAX, yocto(1)]), Err(AuctionError::AmountOverflow));
+    }
+
+    #[test]
+    fn multiples_overflow_explicitly() {
+        assert_eq!(times(yocto(7), 3), Ok(yocto(21)));
+        assert_eq!(times(MAX, 1), Ok(MAX));
+        assert_eq!(times(MAX, 0), Ok(yocto(0)));
+        assert_eq!(times(MAX, 2), Err(AuctionError::AmountOverflow));
+    }
+
+    #[test]
+    fn increments_never_go_negative() {
+        assert_eq!(increment(yocto(0), MAX), Ok(MAX));
+        assert_eq!(increment(MAX, MAX), Ok(yocto(0)));
+        assert_eq!(
+            increment(yocto(1), yocto(0)),
+            Err(AuctionError::AmountUnderflow)
+        );
+    }
+
+    #[test]
+    fn percentages_round_down_and_overflow_explicitly() {
+        assert_eq!(percent_of(yocto(10_000), 250), Ok(yocto(250)));
+        assert_eq!(percent_of(yocto(9_999), 1), Ok(yocto(0)));
+        assert_eq!(percent_of(yocto(100), 15_000), Ok(yocto(150)));
+        assert_eq!(percent_of(MAX, 0), Ok(yocto(0)));
+        assert_eq!(percent_of(MAX, 1), Ok(yocto(u128::MAX / 10_000)));
+        assert_eq!(percent_of(MAX, 2), Err(AuctionError::AmountOverflow));
+        assert_eq!(
+            percent_of(yocto(u128::MAX / 10_000 + 1), BPS_DENOMINATOR),
+            Err(AuctionError::AmountOverflow)
+        );
+    }
+
+    #[test]
+    fn rounded_up_percentages_only_overflow_on_the_result() {
+        assert_eq!(percent_of_rounded_up(yocto(10_000), 250), Ok(yocto(250)));
+        assert_eq!(percent_of_rounded_up(yocto(9_999), 1), Ok(yocto(1)));
+        assert_eq!(percent_of_rounded_up(yocto(10_001), 500), Ok(yocto(501)));
+        assert_eq!(percent_of_rounded_up(yocto(0), 500), Ok(yocto(0)));
+        assert_eq!(percent_of_rounded_up(MAX, 0), Ok(yocto(0)));
+        assert_eq!(percent_of_rounded_up(MAX, BPS_DENOMINATOR), Ok(MAX));
+        assert_eq!(
+            percent_of_rounded_up(MAX, BPS_DENOMINATOR + 1),
+            Err(AuctionError::AmountOverflow)
+        );
+    }
+
+    #[test]
+    fn fee_splits_add_back_up() {
+        assert_eq!(split_fee(yocto(1_001), 500), Ok((yocto(50), yocto(951))));
+        assert_eq!(
+            split_fee(yocto(7), BPS_DENOMINATOR),
+            Ok((yocto(7), yocto(0)))
+        );
+        assert_eq!(split_fee(MAX, 0), Ok((yocto(0), MAX)));
+        assert_eq!(
+            split_fee(yocto(10_000), BPS_DENOMINATOR + 1),
+            Err(AuctionError::AmountUnderflow)
+        );
+        assert_eq!(split_fee(MAX, 500), Err(AuctionError::AmountOverflow));
+    }
+}
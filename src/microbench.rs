@@ -0,0 +1,304 @@
+//! Storage micro-benchmarks measured from inside the contract with
+//! `env::used_gas` and `env::storage_usage`, isolating one storage parameter
+//! at a time.
+
+use near_sdk::{borsh, serde_json};
+#[cfg(any(test, not(feature = "minimal")))]
+use near_sdk::{env, json_types::U64, near};
+
+use crate::storage::{self, StorageKey};
+use crate::Bid;
+#[cfg(any(test, not(feature = "minimal")))]
+use crate::{
+    auction_state::{self, AuctionConfig},
+    error::AuctionError,
+    highest_bid,
+    profile::GasProfile,
+    storage::{StorageCell, WriteBackSession},
+    Contract, ContractExt,
+};
+
+/// Key lengths compared by `bench_key_lengths`.
+#[cfg(any(test, not(feature = "minimal")))]
+const KEY_LENGTHS: [u32; 3] = [1, 16, 64];
+/// Length of the scratch key both bid encodings are measured under, so the
+/// keys' own lengths don't tip the comparison.
+#[cfg(any(test, not(feature = "minimal")))]
+const ENCODING_KEY_LEN: u32 = 32;
+
+#[cfg(any(test, not(feature = "minimal")))]
+#[near(serializers = [json])]
+pub struct KeyLengthCost {
+    pub key_len: u32,
+    pub write_gas: U64,
+    pub read_gas: U64,
+    pub storage_bytes: U64,
+}
+
+#[cfg(any(test, not(feature = "minimal")))]
+#[near(serializers = [json])]
+pub struct EncodingCost {
+    pub bytes: u32,
+    pub write_gas: U64,
+    pub read_gas: U64,
+}
+
+#[cfg(any(test, not(feature = "minimal")))]
+#[near(serializers = [json])]
+pub struct ConfigLayoutCost {
+    pub keys: u32,
+    pub write_gas: U64,
+    pub read_gas: U64,
+}
+
+/// Writes `bid` under both encoding benchmark keys.
+pub fn write_bid_encodings(bid: &Bid) {
+    storage::write(
+        &StorageKey::BidBorsh.to_bytes(),
+        &borsh::to_vec(bid).unwrap(),
+    );
+    storage::write(
+        &StorageKey::BidJson.to_bytes(),
+        &serde_json::to_vec(bid).unwrap(),
+    );
+}
+
+/// Encodes the highest bid with `encode`, round-trips it through `decode`
+/// and reports what each direction cost, then stores it under `key`. The
+/// measured write and read go straight to `env` under a scratch key: the
+/// envelope, index and cache upkeep of `storage` partly scale with the
+/// payload and would skew the comparison.
+#[cfg(any(test, not(feature = "minimal")))]
+fn bench_bid_encoding(
+    key: StorageKey,
+    encode: impl FnOnce(&Bid) -> Vec<u8>,
+    decode: impl FnOnce(&[u8]) -> Bid,
+) -> EncodingCost {
+    let bid = highest_bid::load();
+    let scratch = scratch_key(ENCODING_KEY_LEN);
+    let mut encoded = vec![];
+    let write_gas = measure(|| {
+        encoded = encode(&bid);
+        env::storage_write(&scratch, &encoded);
+    });
+    let read_gas = measure(|| {
+        decode(&env::storage_read(&scratch).unwrap());
+    });
+    env::storage_remove(&scratch);
+    storage::write(&key.to_bytes(), &encoded);
+    EncodingCost {
+        bytes: encoded.len() as u32,
+        write_gas,
+        read_gas,
+    }
+}
+
+/// A key of `len` bytes that can't collide with the contract's own keys:
+/// every `key!` starts with the length of its namespace, and none is 255
+/// bytes long.
+#[cfg(any(test, not(feature = "minimal")))]
+fn scratch_key(len: u32) -> Vec<u8> {
+    vec![0xff; len as usize]
+}
+
+/// Gas burnt by `f`.
+#[cfg(any(test, not(feature = "minimal")))]
+fn measure(f: impl FnOnce()) -> U64 {
+    let before = env::used_gas().as_gas();
+    f();
+    U64(env::used_gas().as_gas() - before)
+}
+
+#[cfg(any(test, not(feature = "minimal")))]
+#[near]
+impl Contract {
+    /// Writes the same `value_len`-byte value under a 1, 16 and 64-byte key,
+    /// reads it back and reports what each cost. The keys go straight to
+    /// `env` and are removed again, so no state is left behind.
+    pub fn bench_key_lengths(&mut self, value_len: u32) -> Vec<KeyLengthCost> {
+        crate::benchmarker::only_benchmarker();
+        let value = vec![0u8; value_len as usize];
+        KEY_LENGTHS
+            .into_iter()
+            .map(|key_len| {
+                let key = scratch_key(key_len);
+                let storage_before = env::storage_usage();
+                let write_gas = measure(|| {
+                    env::storage_write(&key, &value);
+                });
+                let storage_bytes = U64(env::storage_usage() - storage_before);
+                let read_gas = measure(|| {
+                    env::storage_read(&key);
+                });
+                env::storage_remove(&key);
+                KeyLengthCost {
+                    key_len,
+                    write_gas,
+                    read_gas,
+                    storage_bytes,
+                }
+            })
+            .collect()
+    }
+
+    /// Stores the highest bid borsh-encoded and reads it back.
+    pub fn store_bid_borsh(&mut self) -> EncodingCost {
+        crate::benchmarker::only_benchmarker();
+        bench_bid_encoding(
+            StorageKey::BidBorsh,
+            |bid| borsh::to_vec(bid).unwrap(),
+            |bytes| borsh::from_slice(bytes).unwrap(),
+        )
+    }
+
+    /// Stores the highest bid as JSON and reads it back.
+    pub fn store_bid_json(&mut self) -> EncodingCost {
+        crate::benchmarker::only_benchmarker();
+        bench_bid_encoding(
+            StorageKey::BidJson,
+            |bid| serde_json::to_vec(bid).unwrap(),
+            |bytes| serde_json::from_slice(bytes).unwrap(),
+        )
+    }
+
+    /// Writes the auction config once as three values under a key each, as it
+    /// was stored before v12, and once packed under a single key, then reads
+    /// each layout back. The scratch keys are removed again afterwards.
+    pub fn bench_config_layout(&mut self) -> Vec<ConfigLayoutCost> {
+        crate::benchmarker::only_benchmarker();
+        let config = auction_state::config();
+        let separate = [
+            borsh::to_vec(&config.auction_end_time).unwrap(),
+            borsh::to_vec(&config.auctioneer).unwrap(),
+            borsh::to_vec(&config.is_claimed()).unwrap(),
+        ];
+        let separate_keys = [17, 18, 19].map(scratch_key);
+        let packed_key = scratch_key(20);
+
+        let separate_cost = ConfigLayoutCost {
+            keys: separate_keys.len() as u32,
+            write_gas: measure(|| {
+                for (key, value) in separate_keys.iter().zip(&separate) {
+                    env::storage_write(key, value);
+                }
+            }),
+            read_gas: measure(|| {
+                let [end_time, auctioneer, claimed] = separate_keys
+                    .clone()
+                    .map(|key| env::storage_read(&key).unwrap());
+                let _: (u64, near_sdk::AccountId, bool) = (
+                    borsh::from_slice(&end_time).unwrap(),
+                    borsh::from_slice(&auctioneer).unwrap(),
+                    borsh::from_slice(&claimed).unwrap(),
+                );
+            }),
+        };
+        let packed_cost = ConfigLayoutCost {
+            keys: 1,
+            write_gas: measure(|| {
+                env::storage_write(&packed_key, &borsh::to_vec(&config).unwrap());
+            }),
+            read_gas: measure(|| {
+                let bytes = env::storage_read(&packed_key).unwrap();
+                let _: AuctionConfig = borsh::from_slice(&bytes).unwrap();
+            }),
+        };
+
+        for key in separate_keys.iter().chain([&packed_key]) {
+            env::storage_remove(key);
+        }
+        vec![separate_cost, packed_cost]
+    }
+
+    /// Replaces the blob with a synthetic payload of `size_kb` KiB.
+    pub fn write_blob(&mut self, size_kb: u32) {
+        let _session = WriteBackSession::begin();
+        crate::benchmarker::only_benchmarker();
+        let mut profile = GasProfile::start("write_blob");
+
+        let len = size_kb
+            .checked_mul(1024)
+            .unwrap_or_else(|| AuctionError::BlobTooLarge.panic());
+        let payload: Vec<u8> = (0..len).map(|i| i as u8).collect();
+        profile.checkpoint("logic");
+        StorageCell::new(StorageKey::Blob).set(payload);
+        profile.checkpoint("serialize");
+        storage::flush();
+        profile.checkpoint("write");
+        profile.finish();
+    }
+
+    /// Loads the blob and returns its length in bytes.
+    pub fn read_blob(&self) -> u32 {
+        let mut profile = GasProfile::start("read_blob");
+
+        let len = StorageCell::<Vec<u8>>::new(StorageKey::Blob)
+            .try_get()
+            .map_or(0, |blob| blob.len() as u32);
+        profile.checkpoint("read");
+        profile.finish();
+        len
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::new_contract;
+
+    #[test]
+    fn longer_keys_cost_more() {
+        let mut contract = new_contract();
+        let storage_before = env::storage_usage();
+        let costs = contract.bench_key_lengths(100);
+
+        assert_eq!(costs.len(), KEY_LENGTHS.len());
+        assert_eq!(costs[2].storage_bytes.0 - costs[0].storage_bytes.0, 63);
+        assert!(costs[2].write_gas.0 > costs[0].write_gas.0);
+        assert!(costs[2].read_gas.0 > costs[0].read_gas.0);
+        assert_eq!(env::storage_usage(), storage_before);
+    }
+
+    #[test]
+    fn json_bids_take_more_room() {
+        let mut contract = new_contract();
+        let borsh = contract.store_bid_borsh();
+        let json = contract.store_bid_json();
+        assert!(json.bytes > borsh.bytes);
+        assert!(json.write_gas.0 > 0 && json.read_gas.0 > 0);
+        assert!(!env::storage_has_key(&scratch_key(ENCODING_KEY_LEN)));
+        assert_eq!(
+            storage::read(&StorageKey::BidBorsh.to_bytes()),
+            Some(borsh::to_vec(&contract.get_highest_bid()).unwrap())
+        );
+    }
+
+    #[test]
+    fn packed_config_reads_cheaper() {
+        let mut contract = new_contract();
+        let storage_before = env::storage_usage();
+        let costs = contract.bench_config_layout();
+
+        assert_eq!((costs[0].keys, costs[1].keys), (3, 1));
+        assert!(costs[1].read_gas.0 < costs[0].read_gas.0);
+        assert!(costs[1].write_gas.0 < costs[0].write_gas.0);
+        assert_eq!(env::storage_usage(), storage_before);
+    }
+
+    #[test]
+    fn blob_round_trips() {
+        let mut contract = new_contract();
+        assert_eq!(contract.read_blob(), 0);
+        contract.write_blob(4);
+        assert_eq!(contract.read_blob(), 4096);
+        contract.write_blob(1);
+        assert_eq!(contract.read_blob(), 1024);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_BLOB_TOO_LARGE")]
+    fn blobs_past_u32_max_are_rejected() {
+        let mut contract = new_contract();
+        contract.write_blob(4_194_304);
+    }
+}
@@ -0,0 +1,756 @@
+use near_sdk::borsh;
+use near_sdk::json_types::U64;
+use near_sdk::{
+    assert_one_yocto, env, near, require, AccountId, Gas, GasWeight, NearToken, Promise,
+};
+
+use crate::auction_state::{AuctionConfig, ClaimStatus};
+use crate::chunked::ChunkedVector;
+use crate::dutch_auctions::{self, DutchInventory};
+use crate::emergency::EMERGENCY_DELAY;
+use crate::error::AuctionError;
+use crate::owner::only_owner;
+use crate::storage::{self, key, StorageCell, StorageKey, WriteBackSession};
+use crate::storage_management::total_available;
+use crate::{assets, envelope, highest_bid, math, sweep, ttl, Bid, Contract, ContractExt};
+
+/// Layout version written by `init` and by every successful `migrate`.
+///
+/// * v1: flat keys (`b"highest_bid"`, `b"s"`, ...), `Bid` with or without the
+///   block metadata fields.
+/// * v2: namespaced keys built with `key!`.
+/// * v3: SDK collection headers and elements moved to the reserved `coll`
+///   namespace.
+/// * v4: top-level keys tracked in the key index.
+/// * v5: every value wrapped in an integrity envelope.
+/// * v6: the benchmark vector split into fixed-size chunks.
+/// * v7: bid count and total volume tracked by `bid`.
+/// * v8: an owner separate from the auctioneer.
+/// * v9: a guardian and timelock for emergency withdrawals.
+/// * v10: a marker that stops `init` from running again.
+/// * v11: the highest bid split into its bidder and amount and its metadata.
+/// * v12: the end time, auctioneer and claimed flag packed into one
+///   `AuctionConfig`.
+/// * v13: the key index split into one record per key.
+/// * v14: claimed clearing-price refunds tracked in each `DutchInventory`.
+/// * v15: a running total of what registered storage balances hold.
+/// * v16: foreign payouts as EIP-1559 transactions.
+/// * v17: a count of the accounts each NFT auction owes tokens.
+/// * v18: values with a TTL sealed as expiring.
+///
+/// Steps up to v5 move bare bytes; the v5 step seals whatever they produced.
+pub const STATE_VERSION: u32 = 18;
+
+/// The original `Bid` layout, before the block metadata fields were added.
+#[near(serializers = [borsh])]
+struct BidV1 {
+    bidder: AccountId,
+    bid: NearToken,
+}
+
+/// `DutchInventory` until v14, before it tracked claimed refunds.
+#[near(serializers = [borsh])]
+struct DutchInventoryV13 {
+    remaining: u32,
+    last_price: Option<NearToken>,
+    proceeds: NearToken,
+}
+
+impl From<BidV1> for Bid {
+    fn from(bid: BidV1) -> Self {
+        Bid {
+            bidder: bid.bidder,
+            bid: bid.bid,
+            bid_time: U64(0),
+            bid_block_height: U64(0),
+            bid_block_timestamp: U64(0),
+            bid_epoch_height: U64(0),
+            premium: false,
+        }
+    }
+}
+
+#[near]
+impl Contract {
+    #[private]
+    pub fn migrate(&mut self) {
+        let _session = WriteBackSession::begin();
+
+        let from = stored_version();
+        // `upgrade` runs this after every deploy, including fixes that keep
+        // the layout
+        if from == STATE_VERSION {
+            env::log_str(&format!("State is already at v{STATE_VERSION}"));
+            return;
+        }
+        require!(
+            from < STATE_VERSION,
+            AuctionError::StateNewerThanCode.as_str()
+        );
+
+        if from < 2 {
+            migrate_v1_to_v2();
+        }
+        if from < 3 {
+            migrate_v2_to_v3();
+        }
+        if from < 5 {
+            migrate_v4_to_v5();
+        }
+        // Every later step writes through `storage`, which expects the v13
+        // index, so it is split as soon as it is sealed
+        if from < 13 {
+            storage::split_legacy_key_index();
+        }
+        if from < 6 {
+            migrate_v5_to_v6();
+        }
+        if from < 7 {
+            migrate_v6_to_v7();
+        }
+        if from < 8 {
+            migrate_v7_to_v8();
+        }
+        if from < 9 {
+            migrate_v8_to_v9();
+        }
+        if from < 10 {
+            migrate_v9_to_v10();
+        }
+        if from < 11 {
+            migrate_v10_to_v11();
+        }
+        if from < 12 {
+            migrate_v11_to_v12();
+        }
+        if from < 14 {
+            migrate_v13_to_v14();
+        }
+        if from < 15 {
+            migrate_v14_to_v15();
+        }
+        if from < 16 {
+            migrate_v15_to_v16();
+        }
+        if from < 17 {
+            migrate_v16_to_v17();
+        }
+        if from < 18 {
+            migrate_v17_to_v18();
+        }
+        // The index only tracks keys written through `storage`, so backfill it
+        // once every key has reached its current location.
+        storage::rebuild_key_index();
+
+        StorageCell::new(StorageKey::StateVersion).set(STATE_VERSION);
+        env::log_str(&format!("Migrated state from v{from} to v{STATE_VERSION}"));
+    }
+
+    /// Deploys the wasm passed as the raw call input, then runs `migrate` on
+    /// it with all the remaining gas. Both actions share one receipt, so if
+    /// the migration panics, e.g. because the new code is older than the
+    /// stored state, the old code stays in place.
+    #[payable]
+    pub fn upgrade(&mut self) -> Promise {
+        assert_one_yocto();
+        only_owner();
+
+        let code = env::input().unwrap_or_else(|| AuctionError::MissingCode.panic());
+        require!(!code.is_empty(), AuctionError::MissingCode.as_str());
+        env::log_str(&format!("Upgrading to {} bytes of new code", code.len()));
+
+        Promise::new(env::current_account_id())
+            .deploy_contract(code)
+            .function_call_weight(
+                "migrate",
+                vec![],
+                NearToken::from_yoctonear(0),
+                Gas::from_gas(0),
+                GasWeight(1),
+            )
+    }
+}
+
+/// Before v5 the version was stored as a bare borsh `u32`; since then it is
+/// sealed like every other value.
+fn stored_version() -> u32 {
+    match env::storage_read(&StorageKey::StateVersion.to_bytes()) {
+        None => 1,
+        Some(bytes) if bytes.len() == 4 => borsh::from_slice(&bytes).unwrap(),
+        Some(_) => *StorageCell::<u32>::new(StorageKey::StateVersion).get(),
+    }
+}
+
+fn migrate_v1_to_v2() {
+    if let Some(bytes) = env::storage_read(b"highest_bid") {
+        let bid = borsh::from_slice::<Bid>(&bytes)
+            .or_else(|_| borsh::from_slice::<BidV1>(&bytes).map(Bid::from))
+            .unwrap_or_else(|_| AuctionError::UndecodableValue.panic_with("v1 highest_bid"));
+        env::storage_write(
+            &StorageKey::HighestBid.to_bytes(),
+            &borsh::to_vec(&bid).unwrap(),
+        );
+        env::storage_remove(b"highest_bid");
+    }
+
+    // Collection headers carry their own element prefix, so moving the header
+    // is enough to keep the elements reachable.
+    let moved_keys: [(&[u8], Vec<u8>); 6] = [
+        (b"auction_end_time", key!("auction", "end_time")),
+        (b"auctioneer", key!("auction", "auctioneer")),
+        (b"claimed", key!("auction", "claimed")),
+        (b"vector", key!("bench", "vector")),
+        (b"s", key!("bench", "sdk_vector")),
+        (b"i", key!("bench", "sdk_iterable_map")),
+    ];
+    for (old_key, new_key) in moved_keys {
+        move_key(old_key, &new_key);
+    }
+}
+
+fn migrate_v2_to_v3() {
+    move_key(
+        &key!("bench", "sdk_vector"),
+        &StorageKey::SdkVector.to_bytes(),
+    );
+    move_key(
+        &key!("bench", "sdk_iterable_map"),
+        &StorageKey::SdkIterableMap.to_bytes(),
+    );
+}
+
+fn migrate_v4_to_v5() {
+    let mut keys: Vec<Vec<u8>> = StorageKey::ALL
+        .into_iter()
+        .filter(|key| *key != StorageKey::StateVersion)
+        .map(StorageKey::to_bytes)
+        .chain([
+            key!("bench", "vector"),
+            key!("auction", "end_time"),
+            key!("auction", "auctioneer"),
+            key!("auction", "claimed"),
+        ])
+        .collect();
+    // A v4 index also lists raw and TTL entries written through `set_raw*`
+    if let Some(bytes) = env::storage_read(&StorageKey::KeyIndex.to_bytes()) {
+        keys.extend(borsh::from_slice::<Vec<Vec<u8>>>(&bytes).unwrap_or_default());
+    }
+    keys.sort();
+    keys.dedup();
+    for key in keys {
+        if let Some(bytes) = env::storage_read(&key) {
+            env::storage_write(&key, &envelope::seal(&bytes));
+        }
+    }
+}
+
+fn migrate_v5_to_v6() {
+    let blob_key = key!("bench", "vector");
+    if let Some(bytes) = storage::read(&blob_key) {
+        // The legacy layouts store it plain, whatever features this build has
+        let blob: Vec<u8> = storage::decode(&blob_key, Some(bytes));
+        let mut vector = ChunkedVector::new(StorageKey::Vector);
+        for value in blob {
+            vector.push(value);
+        }
+        storage::remove(&blob_key);
+    }
+    // Rebuilding the index only sees keys that reached storage
+    storage::flush();
+}
+
+/// Bids placed before v7 weren't counted, so both totals start from zero.
+fn migrate_v6_to_v7() {
+    StorageCell::new(StorageKey::BidCount).set(U64(0));
+    StorageCell::new(StorageKey::TotalVolume).set(NearToken::from_yoctonear(0));
+    storage::flush();
+}
+
+/// `migrate` can only be called by the contract account, so it starts out as
+/// the owner.
+fn migrate_v7_to_v8() {
+    StorageCell::new(StorageKey::Owner).set(env::current_account_id());
+    storage::flush();
+}
+
+/// Like the owner, the guardian starts out as the contract account.
+fn migrate_v8_to_v9() {
+    StorageCell::new(StorageKey::Guardian).set(env::current_account_id());
+    StorageCell::new(StorageKey::EmergencyDelay).set(EMERGENCY_DELAY);
+    storage::flush();
+}
+
+/// Anything old enough to migrate has been initialized.
+fn migrate_v9_to_v10() {
+    StorageCell::new(StorageKey::StateInitialized).set(true);
+    storage::flush();
+}
+
+/// The whole `Bid` was stored under `highest_bid` until v11.
+fn migrate_v10_to_v11() {
+    let key = StorageKey::HighestBid.to_bytes();
+    if let Some(bytes) = storage::read(&key) {
+        highest_bid::store(storage::decode::<Bid>(&key, Some(bytes)));
+        storage::flush();
+    }
+}
+
+/// The end time, auctioneer and claimed flag had a key each until v12.
+fn migrate_v11_to_v12() {
+    let keys = [
+        key!("auction", "end_time"),
+        key!("auction", "auctioneer"),
+        key!("auction", "claimed"),
+    ];
+    let [end_time, auctioneer, claimed] = &keys;
+    if !storage::has_key(auctioneer) {
+        return;
+    }
+    StorageCell::new(StorageKey::AuctionConfig).set(AuctionConfig {
+        auction_end_time: storage::read_key(end_time),
+        auctioneer: storage::read_key(auctioneer),
+        claim: match storage::read_key(claimed) {
+            true => ClaimStatus::Claimed,
+            false => ClaimStatus::Open,
+        },
+    });
+    for key in &keys {
+        storage::remove(key);
+    }
+    storage::flush();
+}
+
+/// Every Dutch auction's inventory gains the refunds claimed from it, which
+/// are worked out from the purchase records still on file.
+fn migrate_v13_to_v14() {
+    let count = StorageCell::<u32>::new(StorageKey::DutchAuctionCount)
+        .try_get()
+        .copied()
+        .unwrap_or(0);
+    let indexed_keys = storage::indexed_keys();
+    for auction_id in 0..count {
+        let key = dutch_auctions::inventory_key(auction_id);
+        let Some(bytes) = storage::read(&key) else {
+            continue;
+        };
+        let legacy: DutchInventoryV13 = storage::decode(&key, Some(bytes));
+        StorageCell::new(key).set(DutchInventory {
+            remaining: legacy.remaining,
+            last_price: legacy.last_price,
+            proceeds: legacy.proceeds,
+            refunded: NearToken::from_yoctonear(0),
+        });
+        dutch_auctions::backfill_refunded(auction_id, &indexed_keys);
+    }
+    storage::flush();
+}
+
+/// Registered balances can't be enumerated, so their running total starts
+/// out as everything in the balance that no other ledger accounts for. That
+/// never leaves a deposit out; the sweep just can't take what was dust
+/// before the upgrade. Without any registration the total is zero anyway.
+fn migrate_v14_to_v15() {
+    if !storage::has_key(&StorageKey::StorageBalances.to_bytes()) {
+        return;
+    }
+    let total = math::add(total_available(), sweep::dust()).unwrap_or_else(|err| err.panic());
+    StorageCell::new(StorageKey::StorageAvailable).set(total);
+    storage::flush();
+}
+
+/// Until v16 a foreign payout named a chain and address, and what got
+/// signed was a hash no chain could execute. There is no transaction to
+/// build from that, so the auctioneer has to choose the payout again, and a
+/// claim that only got such a signature is reopened since nothing was paid.
+fn migrate_v15_to_v16() {
+    storage::remove(&StorageKey::ForeignPayout.to_bytes());
+    let request = StorageKey::SignRequest.to_bytes();
+    if storage::has_key(&request) {
+        storage::remove(&request);
+        StorageCell::<AuctionConfig>::new(StorageKey::AuctionConfig)
+            .get_mut()
+            .claim = ClaimStatus::Open;
+        env::log_str("Reopened the claim, whose foreign payout was never executable");
+    }
+    storage::flush();
+}
+
+fn migrate_v16_to_v17() {
+    let count = StorageCell::<u32>::new(StorageKey::NftAuctionCount)
+        .try_get()
+        .copied()
+        .unwrap_or(0);
+    let indexed_keys = storage::indexed_keys();
+    for auction_id in 0..count {
+        assets::backfill_owed_count(auction_id, &indexed_keys);
+    }
+    storage::flush();
+}
+
+fn migrate_v17_to_v18() {
+    ttl::mark_expiring_values();
+    storage::flush();
+}
+
+fn move_key(old_key: &[u8], new_key: &[u8]) {
+    if let Some(bytes) = env::storage_read(old_key) {
+        env::storage_write(new_key, &bytes);
+        env::storage_remove(old_key);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::store;
+    use near_sdk::test_utils::{get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+
+    use super::*;
+    use crate::test_utils::new_contract;
+
+    #[test]
+    fn migrates_v1_layout() {
+        let alice: AccountId = "alice.near".parse().unwrap();
+        let v1_bid = BidV1 {
+            bidder: alice.clone(),
+            bid: NearToken::from_near(1),
+        };
+        env::storage_write(b"highest_bid", &borsh::to_vec(&v1_bid).unwrap());
+        env::storage_write(b"auction_end_time", &borsh::to_vec(&U64(1000)).unwrap());
+        env::storage_write(b"auctioneer", &borsh::to_vec(&alice).unwrap());
+        env::storage_write(b"claimed", &borsh::to_vec(&false).unwrap());
+        let sdk_vector: store::Vector<u8> = store::Vector::new(b"s");
+        env::storage_write(b"s", &borsh::to_vec(&sdk_vector).unwrap());
+
+        let mut contract = Contract {};
+        contract.migrate();
+
+        let bid = contract.get_highest_bid();
+        assert_eq!(bid.bidder, alice);
+        assert_eq!(bid.bid, NearToken::from_near(1));
+        assert_eq!(bid.bid_time, U64(0));
+        assert_eq!(contract.get_auction_end_time(), U64(1000));
+        assert_eq!(contract.get_auctioneer(), alice);
+        assert_eq!(contract.get_bid_count(), U64(0));
+        assert_eq!(contract.get_owner(), env::current_account_id());
+        assert!(!env::storage_has_key(b"highest_bid"));
+        assert!(env::storage_has_key(&StorageKey::SdkVector.to_bytes()));
+        assert!(storage::indexed_keys().contains(&StorageKey::AuctionConfig.to_bytes()));
+        assert_eq!(
+            *StorageCell::<u32>::new(StorageKey::StateVersion).get(),
+            STATE_VERSION
+        );
+    }
+
+    #[test]
+    fn seals_v4_layout() {
+        let alice: AccountId = "alice.near".parse().unwrap();
+        let claimed = key!("auction", "claimed");
+        let auctioneer = key!("auction", "auctioneer");
+        let end_time = key!("auction", "end_time");
+        env::storage_write(&end_time, &borsh::to_vec(&1000u64).unwrap());
+        env::storage_write(&claimed, &borsh::to_vec(&true).unwrap());
+        env::storage_write(&auctioneer, &borsh::to_vec(&alice).unwrap());
+        env::storage_write(b"raw", b"value");
+        let index: Vec<Vec<u8>> = vec![auctioneer, claimed, end_time, b"raw".to_vec()];
+        env::storage_write(
+            &StorageKey::KeyIndex.to_bytes(),
+            &borsh::to_vec(&index).unwrap(),
+        );
+        env::storage_write(
+            &StorageKey::StateVersion.to_bytes(),
+            &borsh::to_vec(&4u32).unwrap(),
+        );
+
+        let mut contract = Contract {};
+        contract.migrate();
+
+        assert!(contract.get_claimed());
+        assert_eq!(contract.get_auctioneer(), alice);
+        assert!(contract.verify_state().is_empty());
+        assert!(storage::indexed_keys().contains(&b"raw".to_vec()));
+        assert_eq!(stored_version(), STATE_VERSION);
+    }
+
+    #[test]
+    fn splits_v10_highest_bid() {
+        let bid = Bid {
+            bidder: "bob.near".parse().unwrap(),
+            bid: NearToken::from_near(2),
+            bid_time: U64(10),
+            bid_block_height: U64(20),
+            bid_block_timestamp: U64(10),
+            bid_epoch_height: U64(30),
+            premium: false,
+        };
+        storage::write(
+            &StorageKey::HighestBid.to_bytes(),
+            &borsh::to_vec(&bid).unwrap(),
+        );
+        storage::write(
+            &StorageKey::StateVersion.to_bytes(),
+            &borsh::to_vec(&10u32).unwrap(),
+        );
+
+        let mut contract = Contract {};
+        contract.migrate();
+
+        let migrated = contract.get_highest_bid();
+        assert_eq!(migrated.bidder, bid.bidder);
+        assert_eq!(migrated.bid, bid.bid);
+        assert_eq!(migrated.bid_block_height, U64(20));
+        assert_eq!(migrated.bid_epoch_height, U64(30));
+        assert!(storage::indexed_keys().contains(&StorageKey::HighestBidMetadata.to_bytes()));
+    }
+
+    #[test]
+    fn packs_v11_config() {
+        let alice: AccountId = "alice.near".parse().unwrap();
+        storage::write(
+            &key!("auction", "end_time"),
+            &borsh::to_vec(&1000u64).unwrap(),
+        );
+        storage::write(
+            &key!("auction", "auctioneer"),
+            &borsh::to_vec(&alice).unwrap(),
+        );
+        storage::write(&key!("auction", "claimed"), &borsh::to_vec(&true).unwrap());
+        storage::write(
+            &StorageKey::StateVersion.to_bytes(),
+            &borsh::to_vec(&11u32).unwrap(),
+        );
+
+        let mut contract = Contract {};
+        contract.migrate();
+
+        assert_eq!(contract.get_auction_end_time(), U64(1000));
+        assert_eq!(contract.get_auctioneer(), alice);
+        assert!(contract.get_claimed());
+        assert!(!env::storage_has_key(&key!("auction", "auctioneer")));
+        assert!(!storage::indexed_keys().contains(&key!("auction", "claimed")));
+    }
+
+    #[test]
+    fn splits_v12_key_index() {
+        let seal = |value: Vec<u8>| envelope::seal(&value);
+        let config = StorageKey::AuctionConfig.to_bytes();
+        let auction_config = AuctionConfig {
+            auction_end_time: 1000,
+            auctioneer: "alice.near".parse().unwrap(),
+            claim: ClaimStatus::Open,
+        };
+        env::storage_write(&config, &seal(borsh::to_vec(&auction_config).unwrap()));
+        env::storage_write(b"raw", &seal(b"value".to_vec()));
+        let index: Vec<Vec<u8>> = vec![config.clone(), b"raw".to_vec(), b"gone".to_vec()];
+        env::storage_write(
+            &StorageKey::KeyIndex.to_bytes(),
+            &seal(borsh::to_vec(&index).unwrap()),
+        );
+        env::storage_write(
+            &StorageKey::StateVersion.to_bytes(),
+            &seal(borsh::to_vec(&12u32).unwrap()),
+        );
+
+        let mut contract = Contract {};
+        contract.migrate();
+
+        let keys = storage::indexed_keys();
+        assert!(keys.contains(&config));
+        assert!(keys.contains(&b"raw".to_vec()));
+        assert!(!keys.contains(&b"gone".to_vec()));
+        assert_eq!(storage::indexed_key_count() as usize, keys.len());
+        assert!(contract.verify_state().is_empty());
+    }
+
+    #[test]
+    fn tracks_v13_dutch_refunds() {
+        let mut contract = new_contract();
+        let call_as = |account_id: &str, deposit: NearToken, block_timestamp: u64| {
+            testing_env!(VMContextBuilder::new()
+                .predecessor_account_id(account_id.parse().unwrap())
+                .attached_deposit(deposit)
+                .block_timestamp(block_timestamp)
+                .build());
+        };
+        call_as("seller.near", NearToken::from_near(1), 0);
+        let auction_id = contract.start_dutch_auction(dutch_auctions::DutchAuctionArgs {
+            units: 10,
+            start_price: NearToken::from_near(10),
+            floor_price: NearToken::from_near(2),
+            end_time: U64(800),
+            clearing_price: true,
+        });
+        call_as("bob.near", NearToken::from_near(21), 0);
+        let _ = contract.buy(auction_id, 2);
+        call_as("alice.near", NearToken::from_near(19), 400);
+        let _ = contract.buy(auction_id, 3);
+        // Bob claims the 8 NEAR he paid above the clearing price of 6
+        call_as("bob.near", NearToken::from_yoctonear(0), 900);
+        let _ = contract.claim_dutch_refund(auction_id);
+
+        let legacy = DutchInventoryV13 {
+            remaining: 5,
+            last_price: Some(NearToken::from_near(6)),
+            proceeds: NearToken::from_near(38),
+        };
+        storage::write(
+            &dutch_auctions::inventory_key(auction_id),
+            &borsh::to_vec(&legacy).unwrap(),
+        );
+        storage::write(
+            &StorageKey::StateVersion.to_bytes(),
+            &borsh::to_vec(&13u32).unwrap(),
+        );
+        contract.migrate();
+
+        let inventory = contract.get_dutch_inventory(auction_id).unwrap();
+        assert_eq!(inventory.proceeds, NearToken::from_near(38));
+        assert_eq!(inventory.refunded, NearToken::from_near(8));
+    }
+
+    #[test]
+    fn backfills_v14_storage_total() {
+        let mut contract = new_contract();
+        testing_env!(VMContextBuilder::new()
+            .attached_deposit(NearToken::from_near(1))
+            .build());
+        let deposit = contract.storage_deposit(None, None).available;
+        // Deployed before the total was kept
+        storage::remove(&StorageKey::StorageAvailable.to_bytes());
+        storage::write(
+            &StorageKey::StateVersion.to_bytes(),
+            &borsh::to_vec(&14u32).unwrap(),
+        );
+        testing_env!(VMContextBuilder::new().build());
+        assert!(total_available().is_zero());
+
+        contract.migrate();
+
+        assert!(total_available() >= deposit);
+        assert!(contract.get_dust().is_zero());
+    }
+
+    #[test]
+    fn reopens_v15_foreign_payouts() {
+        let mut contract = new_contract();
+        // The v15 layouts, with a request signed after a claim
+        storage::write(&StorageKey::ForeignPayout.to_bytes(), b"ethereum 0xabc");
+        storage::write(&StorageKey::SignRequest.to_bytes(), b"signed");
+        StorageCell::<AuctionConfig>::new(StorageKey::AuctionConfig)
+            .get_mut()
+            .claim = ClaimStatus::Claimed;
+        storage::write(
+            &StorageKey::StateVersion.to_bytes(),
+            &borsh::to_vec(&15u32).unwrap(),
+        );
+
+        contract.migrate();
+
+        assert!(contract.get_foreign_payout().is_none());
+        assert!(contract.get_sign_request().is_none());
+        assert!(!contract.get_claimed());
+    }
+
+    #[test]
+    fn counts_v16_owed_tokens() {
+        let mut contract = new_contract();
+        // Two accounts owed by auction 1, written before the count was kept
+        StorageCell::new(StorageKey::NftAuctionCount).set(2u32);
+        for account in ["bob.near", "carol.near"] {
+            StorageCell::new(key!("auction", "nft", 1u32, "owed", account))
+                .set(NearToken::from_yoctonear(5));
+        }
+        storage::write(
+            &StorageKey::StateVersion.to_bytes(),
+            &borsh::to_vec(&16u32).unwrap(),
+        );
+
+        contract.migrate();
+
+        assert!(!assets::has_owed(0));
+        assert!(assets::has_owed(1));
+        let _ = contract.retry_asset_transfer(1, "bob.near".parse().unwrap());
+        assert!(assets::has_owed(1));
+        let _ = contract.retry_asset_transfer(1, "carol.near".parse().unwrap());
+        assert!(!assets::has_owed(1));
+    }
+
+    #[test]
+    fn seals_v17_ttl_values() {
+        let mut contract = new_contract();
+        testing_env!(VMContextBuilder::new().block_timestamp(100).build());
+        // Written before values with a TTL were sealed as expiring
+        storage::write(b"old", b"a");
+        storage::write(
+            &key!("ttl", b"old".as_slice()),
+            &borsh::to_vec(&50u64).unwrap(),
+        );
+        storage::write(
+            &StorageKey::StateVersion.to_bytes(),
+            &borsh::to_vec(&17u32).unwrap(),
+        );
+        assert!(storage::has_key(b"old"));
+
+        contract.migrate();
+
+        assert!(!storage::has_key(b"old"));
+    }
+
+    #[test]
+    fn chunks_v5_vector() {
+        let blob: Vec<u8> = (0..1000).map(|i| i as u8).collect();
+        storage::write(&key!("bench", "vector"), &borsh::to_vec(&blob).unwrap());
+        storage::write(
+            &StorageKey::StateVersion.to_bytes(),
+            &borsh::to_vec(&5u32).unwrap(),
+        );
+
+        let mut contract = Contract {};
+        contract.migrate();
+
+        assert_eq!(contract.get_vector(), blob);
+        assert!(!env::storage_has_key(&key!("bench", "vector")));
+        assert!(storage::indexed_keys().contains(&StorageKey::Vector.chunk_key(1)));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER")]
+    fn only_the_owner_can_upgrade() {
+        let mut contract = new_contract();
+        let mut context = VMContextBuilder::new()
+            .predecessor_account_id("alice.near".parse().unwrap())
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .build();
+        context.input = b"\0asm".as_slice().into();
+        testing_env!(context);
+        let _ = contract.upgrade();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_MISSING_CODE")]
+    fn upgrade_needs_code() {
+        let mut contract = new_contract();
+        testing_env!(VMContextBuilder::new()
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .build());
+        let _ = contract.upgrade();
+    }
+
+    #[test]
+    fn current_layout_is_left_alone() {
+        let mut contract = new_contract();
+        contract.migrate();
+        assert_eq!(
+            get_logs().last(),
+            Some(&format!("State is already at v{STATE_VERSION}"))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_STATE_NEWER_THAN_CODE")]
+    fn rejects_newer_layout() {
+        let mut contract = new_contract();
+        StorageCell::new(StorageKey::StateVersion).set(STATE_VERSION + 1);
+        contract.migrate();
+    }
+}
@@ -0,0 +1,819 @@
+//! Auctions started by transferring an NFT to the contract (NEP-171
+//! `nft_on_transfer`). The token is the prize, the account it came from is
+//! the auctioneer, and the transfer `msg` carries the auction parameters.
+//! Each one lives under its own key next to the main auction, which is
+//! unaffected. An auction settles in NEAR unless `msg` names a NEP-141
+//! token, in which case bids arrive through `ft_on_transfer` instead.
+//! Starting one stores its records at the sender's expense, out of their
+//! storage balance. Claimed auctions can be archived down to a single
+//! summary record.
+
+use near_sdk::json_types::{U128, U64};
+use near_sdk::serde_json::{self, json};
+use near_sdk::{
+    assert_one_yocto, env, near, require, AccountId, Gas, NearToken, Promise, PromiseOrValue,
+};
+
+use crate::assets::{self, Asset};
+use crate::auction_state::ClaimStatus;
+use crate::error::AuctionError;
+use crate::events::AuctionEvent;
+use crate::increments::{self, BidIncrement};
+use crate::owner::only_owner;
+use crate::storage::{key, StorageCell, StorageKey, WriteBackSession};
+use crate::{math, pause, receipts, storage_management, Bid, Contract, ContractExt};
+
+/// Gas for the `nft_transfer` that hands the prize out.
+const NFT_TRANSFER_GAS: Gas = Gas::from_tgas(20);
+/// Gas reserved for `resolve_nft_claim`, which starts the payout.
+const RESOLVE_NFT_CLAIM_GAS: Gas = Gas::from_tgas(25);
+
+/// What `msg` must hold when transferring a token to start an auction.
+#[near(serializers = [json])]
+pub struct NftAuctionArgs {
+    pub end_time: U64,
+    /// What bids are paid in; NEAR when left out.
+    #[serde(default)]
+    pub asset: Asset,
+    /// How much each bid must beat the last by; any amount when left out.
+    #[serde(default)]
+    pub min_increment: Option<BidIncrement>,
+}
+
+/// What `msg` must hold when transferring tokens to bid on an auction.
+#[near(serializers = [json])]
+pub struct NftBidArgs {
+    pub auction_id: u32,
+}
+
+#[near(serializers = [json, borsh])]
+#[derive(Clone)]
+pub struct NftAuction {
+    pub nft_contract: AccountId,
+    pub token_id: String,
+    pub auctioneer: AccountId,
+    pub auction_end_time: U64,
+    /// In the smallest unit of the auction's asset.
+    pub highest_bid: Bid,
+    /// Encodes like the `claimed: bool` it replaced.
+    pub claim: ClaimStatus,
+}
+
+/// What is left of an auction once it is archived.
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct ArchivedNftAuction {
+    pub nft_contract: AccountId,
+    pub token_id: String,
+    pub auctioneer: AccountId,
+    /// `None` when nobody bid and the token went back to the auctioneer.
+    pub winner: Option<AccountId>,
+    /// In the smallest unit of `asset`.
+    pub price: NearToken,
+    pub asset: Asset,
+    pub auction_end_time: U64,
+}
+
+fn auction_key(auction_id: u32) -> Vec<u8> {
+    key!("auction", "nft", auction_id)
+}
+
+fn archive_key(auction_id: u32) -> Vec<u8> {
+    key!("auction", "nft", auction_id, "archived")
+}
+
+fn increment_key(auction_id: u32) -> Vec<u8> {
+    key!("auction", "nft", auction_id, "increment")
+}
+
+fn min_increment(auction_id: u32) -> Option<BidIncrement> {
+    StorageCell::<BidIncrement>::new(increment_key(auction_id))
+        .try_get()
+        .cloned()
+}
+
+fn load(auction_id: u32) -> StorageCell<NftAuction> {
+    let auction = StorageCell::<NftAuction>::new(auction_key(auction_id));
+    require!(
+        auction.try_get().is_some(),
+        AuctionError::NoSuchAuction.as_str()
+    );
+    auction
+}
+
+/// The NEAR the contract holds for unclaimed auctions: the highest bid of
+/// each one settling in NEAR, once someone has bid.
+pub fn held() -> NearToken {
+    let count = StorageCell::<u32>::new(StorageKey::NftAuctionCount)
+        .try_get()
+        .copied()
+        .unwrap_or(0);
+    let bids = (0..count).filter_map(|auction_id| {
+        let auction = StorageCell::<NftAuction>::new(auction_key(auction_id));
+        let auction = auction.try_get()?;
+        let unpaid = auction.claim != ClaimStatus::Claimed
+            && auction.highest_bid.bidder != env::current_account_id()
+            && assets::asset(auction_id) == Asset::Near;
+        unpaid.then_some(auction.highest_bid.bid)
+    });
+    math::sum(bids).unwrap_or_else(|err| err.panic())
+}
+
+/// Replaces claimed auction `auction_id` with its summary and removes its
+/// detail keys. Auctions still running, unclaimed, or owing tokens to
+/// someone are left alone; returns whether it was archived.
+fn try_archive(auction_id: u32) -> bool {
+    let mut auction = StorageCell::<NftAuction>::new(auction_key(auction_id));
+    let archivable = auction.try_get().is_some_and(|auction| {
+        auction.claim == ClaimStatus::Claimed && !assets::has_owed(auction_id)
+    });
+    if !archivable {
+        return false;
+    }
+    let NftAuction {
+        nft_contract,
+        token_id,
+        auctioneer,
+        auction_end_time,
+        highest_bid,
+        ..
+    } = auction.get().clone();
+    let winner = (highest_bid.bidder != env::current_account_id()).then_some(highest_bid.bidder);
+    StorageCell::new(archive_key(auction_id)).set(ArchivedNftAuction {
+        nft_contract,
+        token_id,
+        auctioneer,
+        price: winner
+            .as_ref()
+            .map_or(NearToken::from_yoctonear(0), |_| highest_bid.bid),
+        winner,
+        asset: assets::asset(auction_id),
+        auction_end_time,
+    });
+    auction.remove();
+    assets::forget(auction_id);
+    StorageCell::<BidIncrement>::new(increment_key(auction_id)).remove();
+    AuctionEvent::NftAuctionArchived { auction_id }.emit();
+    true
+}
+
+/// Makes `bid` by `bidder` the highest bid on auction `auction_id` and
+/// returns the one it replaced.
+fn outbid(auction_id: u32, auction: &mut NftAuction, bidder: AccountId, bid: NearToken) -> Bid {
+    require!(
+        env::block_timestamp() < auction.auction_end_time.0,
+        AuctionError::AuctionEnded.as_str()
+    );
+    let min_bid =
+        increments::min_next_bid(min_increment(auction_id).as_ref(), auction.highest_bid.bid)
+            .unwrap_or_else(|err| err.panic());
+    require!(bid >= min_bid, AuctionError::BidTooLow.as_str());
+    let last_bid = auction.highest_bid.clone();
+    auction.highest_bid = Bid {
+        bidder,
+        bid,
+        bid_time: U64::from(env::block_timestamp()),
+        bid_block_height: U64::from(env::block_height()),
+        bid_block_timestamp: U64::from(env::block_timestamp()),
+        bid_epoch_height: U64::from(env::epoch_height()),
+        premium: false,
+    };
+    last_bid
+}
+
+/// Sends the prize token to `receiver_id`.
+fn transfer_nft(auction: &NftAuction, receiver_id: AccountId) -> Promise {
+    Promise::new(auction.nft_contract.clone()).function_call(
+        "nft_transfer".to_string(),
+        serde_json::to_vec(&json!({
+            "receiver_id": receiver_id,
+            "token_id": auction.token_id,
+        }))
+        .unwrap(),
+        NearToken::from_yoctonear(1),
+        NFT_TRANSFER_GAS,
+    )
+}
+
+#[near]
+impl Contract {
+    /// NEP-171 receiver: starts an auction for the token that was just
+    /// transferred. `msg` is the JSON form of `NftAuctionArgs`; anything
+    /// else panics, so the NFT contract returns the token. So does a
+    /// `sender_id` whose storage balance doesn't cover the auction.
+    pub fn nft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        previous_owner_id: AccountId,
+        token_id: String,
+        msg: String,
+    ) -> PromiseOrValue<bool> {
+        let _session = WriteBackSession::begin();
+        pause::assert_not_paused();
+
+        let Ok(args) = serde_json::from_str::<NftAuctionArgs>(&msg) else {
+            AuctionError::InvalidAuctionMsg.panic();
+        };
+        require!(
+            args.end_time.0 > env::block_timestamp(),
+            AuctionError::EndTimeInPast.as_str()
+        );
+        require!(
+            args.min_increment
+                .as_ref()
+                .is_none_or(BidIncrement::is_valid),
+            AuctionError::InvalidMinIncrement.as_str()
+        );
+
+        storage_management::charge_balance(&sender_id.clone(), || {
+            let mut count = StorageCell::<u32>::new(StorageKey::NftAuctionCount);
+            let auction_id = count.try_get().copied().unwrap_or(0);
+            count.set(auction_id + 1);
+
+            let auction = NftAuction {
+                nft_contract: env::predecessor_account_id(),
+                token_id,
+                auctioneer: previous_owner_id,
+                auction_end_time: args.end_time,
+                highest_bid: Bid {
+                    bidder: env::current_account_id(),
+                    bid: NearToken::from_yoctonear(1),
+                    bid_time: U64::from(env::block_timestamp()),
+                    bid_block_height: U64::from(env::block_height()),
+                    bid_block_timestamp: U64::from(env::block_timestamp()),
+                    bid_epoch_height: U64::from(env::epoch_height()),
+                    premium: false,
+                },
+                claim: ClaimStatus::Open,
+            };
+            AuctionEvent::NftAuctionStarted {
+                auction_id,
+                nft_contract: auction.nft_contract.clone(),
+                token_id: auction.token_id.clone(),
+                sender_id,
+                auction_end_time: auction.auction_end_time,
+            }
+            .emit();
+            StorageCell::new(auction_key(auction_id)).set(auction);
+            assets::record(auction_id, args.asset);
+            if let Some(min_increment) = args.min_increment {
+                StorageCell::new(increment_key(auction_id)).set(min_increment);
+            }
+        });
+
+        // Keep the token
+        PromiseOrValue::Value(false)
+    }
+
+    #[payable]
+    pub fn bid_on_nft(&mut self, auction_id: u32) -> Promise {
+        let _session = WriteBackSession::begin();
+        pause::assert_not_paused();
+
+        require!(
+            assets::asset(auction_id) == Asset::Near,
+            AuctionError::AssetMismatch.as_str()
+        );
+        let mut auction = load(auction_id);
+        let auction = auction.get_mut();
+        let bidder = env::predecessor_account_id();
+        let prepaid = storage_management::required_deposit(&bidder, receipts::MINT_BYTES);
+        let bid = math::increment(prepaid, env::attached_deposit())
+            .unwrap_or_else(|_| AuctionError::DepositBelowStorageCost.panic());
+        let last_bid = outbid(auction_id, auction, bidder.clone(), bid);
+        let unused = receipts::mint(&auction.highest_bid, Some(auction_id), prepaid);
+
+        let refund = assets::transfer(auction_id, last_bid.bidder, last_bid.bid);
+        storage_management::refund_unused(refund, bidder, unused)
+    }
+
+    /// NEP-141 receiver: bids the transferred tokens on the auction named in
+    /// `msg` (the JSON form of `NftBidArgs`). Panics when the tokens aren't
+    /// the ones the auction settles in, so the token contract returns them.
+    /// The receipt's storage comes out of the bidder's storage balance, or
+    /// the contract's if they have none.
+    pub fn ft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        amount: U128,
+        msg: String,
+    ) -> PromiseOrValue<U128> {
+        let _session = WriteBackSession::begin();
+        pause::assert_not_paused();
+
+        let Ok(args) = serde_json::from_str::<NftBidArgs>(&msg) else {
+            AuctionError::InvalidBidMsg.panic();
+        };
+        let token = Asset::Ft {
+            contract_id: env::predecessor_account_id(),
+        };
+        require!(
+            assets::asset(args.auction_id) == token,
+            AuctionError::AssetMismatch.as_str()
+        );
+        let mut auction = load(args.auction_id);
+        let auction = auction.get_mut();
+        let last_bid = outbid(
+            args.auction_id,
+            auction,
+            sender_id,
+            NearToken::from_yoctonear(amount.0),
+        );
+        receipts::mint(
+            &auction.highest_bid,
+            Some(args.auction_id),
+            NearToken::from_yoctonear(0),
+        );
+
+        // The placeholder bid the auction starts with was never paid
+        if last_bid.bidder != env::current_account_id() {
+            assets::transfer(args.auction_id, last_bid.bidder, last_bid.bid).detach();
+        }
+        // Keep all of it; the result is read as the amount to return
+        PromiseOrValue::Value(U128(0))
+    }
+
+    /// Sends the token to the winner, or back to the auctioneer if nobody
+    /// bid. The auctioneer is paid by `resolve_nft_claim` once the token has
+    /// moved.
+    #[payable]
+    pub fn claim_nft(&mut self, auction_id: u32) -> Promise {
+        let _session = WriteBackSession::begin();
+
+        assert_one_yocto();
+        let mut auction = load(auction_id);
+        let auction = auction.get_mut();
+        require!(
+            env::block_timestamp() > auction.auction_end_time.0,
+            AuctionError::AuctionNotEnded.as_str()
+        );
+        auction.claim.assert_open();
+        // Claimed only once `resolve_nft_claim` sees the token arrive
+        auction.claim = ClaimStatus::Pending;
+
+        let winner = auction.highest_bid.bidder.clone();
+        let receiver_id = if winner == env::current_account_id() {
+            auction.auctioneer.clone()
+        } else {
+            winner
+        };
+        transfer_nft(auction, receiver_id).then(
+            Self::ext(env::current_account_id())
+                .with_static_gas(RESOLVE_NFT_CLAIM_GAS)
+                .resolve_nft_claim(auction_id),
+        )
+    }
+
+    /// Marks auction `auction_id` claimed and pays the auctioneer once the
+    /// token has been transferred. If the transfer failed, the claim is
+    /// reopened so `claim_nft` can be called again, and nobody is paid.
+    #[private]
+    pub fn resolve_nft_claim(&mut self, auction_id: u32) -> Option<Promise> {
+        let _session = WriteBackSession::begin();
+
+        let mut auction = load(auction_id);
+        let auction = auction.get_mut();
+        if env::promise_result_checked(0, 0).is_err() {
+            auction.claim = ClaimStatus::Open;
+            env::log_str(&format!(
+                "The transfer of the token failed, auction {auction_id} can be claimed again"
+            ));
+            return None;
+        }
+        auction.claim = ClaimStatus::Claimed;
+
+        // The placeholder bid the auction starts with was never paid
+        (auction.highest_bid.bidder != env::current_account_id()).then(|| {
+            assets::transfer(
+                auction_id,
+                auction.auctioneer.clone(),
+                auction.highest_bid.bid,
+            )
+        })
+    }
+
+    pub fn get_nft_auction(&self, auction_id: u32) -> Option<NftAuction> {
+        StorageCell::<NftAuction>::new(auction_key(auction_id))
+            .try_get()
+            .cloned()
+    }
+
+    pub fn get_nft_min_increment(&self, auction_id: u32) -> Option<BidIncrement> {
+        min_increment(auction_id)
+    }
+
+    /// The smallest bid auction `auction_id` accepts right now, in its
+    /// asset. Bids in NEAR also pay for their receipt's storage on top.
+    pub fn get_min_next_nft_bid(&self, auction_id: u32) -> NearToken {
+        let auction = load(auction_id);
+        increments::min_next_bid(
+            min_increment(auction_id).as_ref(),
+            auction.get().highest_bid.bid,
+        )
+        .unwrap_or_else(|err| err.panic())
+    }
+
+    /// Archives claimed auction `auction_id`, reclaiming the storage of its
+    /// details. Its receipts keep pointing at it, and
+    /// `get_archived_auction` still reports how it ended.
+    #[payable]
+    pub fn archive_auction(&mut self, auction_id: u32) {
+        let _session = WriteBackSession::begin();
+
+        assert_one_yocto();
+        only_owner();
+        require!(
+            try_archive(auction_id),
+            AuctionError::NotArchivable.as_str()
+        );
+    }
+
+    /// Looks at up to `limit` auctions, picking up where the last call
+    /// stopped and wrapping around, and archives those that can be. Returns
+    /// how many it archived.
+    #[payable]
+    pub fn prune(&mut self, limit: u32) -> u32 {
+        let _session = WriteBackSession::begin();
+
+        assert_one_yocto();
+        only_owner();
+        let count = self.get_nft_auction_count();
+        if count == 0 {
+            return 0;
+        }
+        let mut cursor = StorageCell::<u32>::new(StorageKey::NftPruneCursor);
+        let start = cursor.try_get().copied().unwrap_or(0) % count;
+        let mut archived = 0;
+        for n in 0..limit.min(count) {
+            if try_archive((start + n) % count) {
+                archived += 1;
+            }
+        }
+        cursor.set((start + limit.min(count)) % count);
+        archived
+    }
+
+    pub fn get_archived_auction(&self, auction_id: u32) -> Option<ArchivedNftAuction> {
+        StorageCell::<ArchivedNftAuction>::new(archive_key(auction_id))
+            .try_get()
+            .cloned()
+    }
+
+    /// Number of auctions started with `nft_on_transfer`; their ids are
+    /// `0..count`.
+    pub fn get_nft_auction_count(&self) -> u32 {
+        StorageCell::<u32>::new(StorageKey::NftAuctionCount)
+            .try_get()
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::{test_vm_config, testing_env, PromiseResult, RuntimeFeesConfig};
+
+    use super::*;
+    use crate::storage;
+    use crate::storage_management::storage_cost;
+    use crate::test_utils::new_contract;
+
+    fn call_at(account_id: &str, deposit: NearToken, block_timestamp: u64) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account_id.parse().unwrap())
+            .attached_deposit(deposit)
+            .block_timestamp(block_timestamp)
+            .build());
+    }
+
+    fn start_nft_auction() -> Contract {
+        start_nft_auction_with(r#"{"end_time":"500"}"#)
+    }
+
+    fn start_nft_auction_with(msg: &str) -> Contract {
+        let mut contract = new_contract();
+        call_at("carol.near", NearToken::from_near(1), 0);
+        contract.storage_deposit(None, None);
+        call_at("nft.near", NearToken::from_yoctonear(1), 0);
+        let kept = contract.nft_on_transfer(
+            "carol.near".parse().unwrap(),
+            "carol.near".parse().unwrap(),
+            "token-1".to_string(),
+            msg.to_string(),
+        );
+        assert!(matches!(kept, PromiseOrValue::Value(false)));
+        contract
+    }
+
+    fn start_usdc_auction() -> Contract {
+        start_nft_auction_with(r#"{"end_time":"500","asset":{"Ft":{"contract_id":"usdc.near"}}}"#)
+    }
+
+    fn bid_tokens(contract: &mut Contract, token: &str, bidder: &str, amount: u128) {
+        call_at(token, NearToken::from_yoctonear(0), 100);
+        let unused = contract.ft_on_transfer(
+            bidder.parse().unwrap(),
+            U128(amount),
+            r#"{"auction_id":0}"#.to_string(),
+        );
+        assert!(matches!(unused, PromiseOrValue::Value(U128(0))));
+    }
+
+    fn resolve_claim_with(contract: &mut Contract, result: PromiseResult) -> Option<Promise> {
+        testing_env!(
+            VMContextBuilder::new()
+                .predecessor_account_id(env::current_account_id())
+                .block_timestamp(600)
+                .build(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![result],
+        );
+        contract.resolve_nft_claim(0)
+    }
+
+    #[test]
+    fn nft_transfer_starts_an_auction() {
+        let mut contract = start_nft_auction();
+        assert_eq!(contract.get_nft_auction_count(), 1);
+        let auction = contract.get_nft_auction(0).unwrap();
+        assert_eq!(
+            auction.nft_contract,
+            "nft.near".parse::<AccountId>().unwrap()
+        );
+        assert_eq!(auction.token_id, "token-1");
+        assert_eq!(
+            auction.auctioneer,
+            "carol.near".parse::<AccountId>().unwrap()
+        );
+
+        call_at("bob.near", NearToken::from_near(1), 100);
+        let _ = contract.bid_on_nft(0);
+        call_at("bob.near", NearToken::from_yoctonear(1), 600);
+        let _ = contract.claim_nft(0);
+        assert_eq!(
+            contract.get_nft_auction(0).unwrap().claim,
+            ClaimStatus::Pending
+        );
+        assert!(resolve_claim_with(&mut contract, PromiseResult::Successful(vec![])).is_some());
+
+        let auction = contract.get_nft_auction(0).unwrap();
+        assert_eq!(
+            auction.highest_bid.bidder,
+            "bob.near".parse::<AccountId>().unwrap()
+        );
+        assert_eq!(auction.claim, ClaimStatus::Claimed);
+        // The main auction is untouched
+        assert!(!contract.get_claimed());
+    }
+
+    #[test]
+    fn starting_is_charged_to_the_senders_storage_balance() {
+        let contract = start_nft_auction();
+        let balance = contract
+            .storage_balance_of("carol.near".parse().unwrap())
+            .unwrap();
+        let registration = contract.storage_balance_bounds().min;
+        assert!(balance.available < balance.total.saturating_sub(registration));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_REGISTERED")]
+    fn unregistered_senders_cannot_start_auctions() {
+        let mut contract = new_contract();
+        call_at("nft.near", NearToken::from_yoctonear(1), 0);
+        let _ = contract.nft_on_transfer(
+            "mallory.near".parse().unwrap(),
+            "carol.near".parse().unwrap(),
+            "token-1".to_string(),
+            r#"{"end_time":"500"}"#.to_string(),
+        );
+    }
+
+    #[test]
+    fn failed_token_transfers_reopen_the_claim() {
+        let mut contract = start_nft_auction();
+        claim_after_bidding(&mut contract);
+        assert!(resolve_claim_with(&mut contract, PromiseResult::Failed).is_none());
+        assert_eq!(
+            contract.get_nft_auction(0).unwrap().claim,
+            ClaimStatus::Open
+        );
+        // The winning bid is still held for the next attempt
+        assert_eq!(
+            held(),
+            NearToken::from_near(1).saturating_sub(storage_cost(receipts::MINT_BYTES))
+        );
+
+        call_at("bob.near", NearToken::from_yoctonear(1), 700);
+        let _ = contract.claim_nft(0);
+        assert_eq!(
+            contract.get_nft_auction(0).unwrap().claim,
+            ClaimStatus::Pending
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_CLAIM_PENDING")]
+    fn pending_claims_cannot_be_repeated() {
+        let mut contract = start_nft_auction();
+        claim_after_bidding(&mut contract);
+        let _ = contract.claim_nft(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INVALID_AUCTION_MSG")]
+    fn malformed_msg_rejects_the_token() {
+        let mut contract = new_contract();
+        let _ = contract.nft_on_transfer(
+            "carol.near".parse().unwrap(),
+            "carol.near".parse().unwrap(),
+            "token-1".to_string(),
+            "start please".to_string(),
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_AUCTION_ENDED")]
+    fn nft_auction_rejects_late_bids() {
+        let mut contract = start_nft_auction();
+        call_at("bob.near", NearToken::from_near(1), 500);
+        let _ = contract.bid_on_nft(0);
+    }
+
+    #[test]
+    fn token_auctions_take_bids_in_their_token() {
+        let mut contract = start_usdc_auction();
+        assert_eq!(
+            contract.get_nft_auction_asset(0),
+            Asset::Ft {
+                contract_id: "usdc.near".parse().unwrap()
+            }
+        );
+
+        bid_tokens(&mut contract, "usdc.near", "bob.near", 5_000);
+        bid_tokens(&mut contract, "usdc.near", "dave.near", 6_000);
+
+        let auction = contract.get_nft_auction(0).unwrap();
+        assert_eq!(
+            auction.highest_bid.bidder,
+            "dave.near".parse::<AccountId>().unwrap()
+        );
+        assert_eq!(auction.highest_bid.bid.as_yoctonear(), 6_000);
+        assert_eq!(
+            contract.get_receipts("dave.near".parse().unwrap(), 0, 1)[0].amount,
+            NearToken::from_yoctonear(6_000)
+        );
+    }
+
+    #[test]
+    fn failed_token_refunds_are_owed() {
+        let mut contract = start_usdc_auction();
+        let bob: AccountId = "bob.near".parse().unwrap();
+        assert_eq!(contract.get_asset_owed(0, bob.clone()), U128(0));
+
+        testing_env!(
+            VMContextBuilder::new()
+                .predecessor_account_id(env::current_account_id())
+                .build(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        contract.resolve_asset_transfer(0, bob.clone(), NearToken::from_yoctonear(2_000));
+        contract.resolve_asset_transfer(0, bob.clone(), NearToken::from_yoctonear(3_000));
+        contract.resolve_asset_transfer(
+            0,
+            "carol.near".parse().unwrap(),
+            NearToken::from_yoctonear(1_000),
+        );
+        assert_eq!(contract.get_asset_owed(0, bob.clone()), U128(5_000));
+        assert!(assets::has_owed(0));
+
+        let _ = contract.retry_asset_transfer(0, bob.clone());
+        assert_eq!(contract.get_asset_owed(0, bob), U128(0));
+        assert!(assets::has_owed(0));
+        let _ = contract.retry_asset_transfer(0, "carol.near".parse().unwrap());
+        assert!(!assets::has_owed(0));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ASSET_MISMATCH")]
+    fn token_auctions_reject_near_bids() {
+        let mut contract = start_usdc_auction();
+        call_at("bob.near", NearToken::from_near(1), 100);
+        let _ = contract.bid_on_nft(0);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ASSET_MISMATCH")]
+    fn token_auctions_reject_other_tokens() {
+        let mut contract = start_usdc_auction();
+        bid_tokens(&mut contract, "usdt.near", "bob.near", 5_000);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_ASSET_MISMATCH")]
+    fn near_auctions_reject_tokens() {
+        let mut contract = start_nft_auction();
+        bid_tokens(&mut contract, "usdc.near", "bob.near", 5_000);
+    }
+
+    const TEN_PERCENT_USDC: &str = r#"{
+        "end_time": "500",
+        "asset": {"Ft": {"contract_id": "usdc.near"}},
+        "min_increment": {"BasisPoints": 1000}
+    }"#;
+
+    #[test]
+    fn percentage_increments_set_the_next_bid() {
+        let mut contract = start_nft_auction_with(TEN_PERCENT_USDC);
+        bid_tokens(&mut contract, "usdc.near", "bob.near", 1_001);
+        assert_eq!(
+            contract.get_min_next_nft_bid(0),
+            NearToken::from_yoctonear(1_102)
+        );
+        bid_tokens(&mut contract, "usdc.near", "dave.near", 1_102);
+        assert_eq!(
+            contract.get_nft_auction(0).unwrap().highest_bid.bidder,
+            "dave.near".parse::<AccountId>().unwrap()
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_BID_TOO_LOW")]
+    fn bids_below_the_increment_are_rejected() {
+        let mut contract = start_nft_auction_with(TEN_PERCENT_USDC);
+        bid_tokens(&mut contract, "usdc.near", "bob.near", 1_001);
+        bid_tokens(&mut contract, "usdc.near", "dave.near", 1_101);
+    }
+
+    fn claim_after_bidding(contract: &mut Contract) {
+        call_at("bob.near", NearToken::from_near(1), 100);
+        let _ = contract.bid_on_nft(0);
+        call_at("bob.near", NearToken::from_yoctonear(1), 600);
+        let _ = contract.claim_nft(0);
+    }
+
+    #[test]
+    fn archiving_keeps_a_summary_and_frees_the_details() {
+        let mut contract =
+            start_nft_auction_with(r#"{"end_time":"500","min_increment":{"BasisPoints":1000}}"#);
+        claim_after_bidding(&mut contract);
+        let _ = resolve_claim_with(&mut contract, PromiseResult::Successful(vec![]));
+        let auction = contract.get_nft_auction(0).unwrap();
+
+        call_at("bob.near", NearToken::from_yoctonear(1), 700);
+        let before = env::storage_usage();
+        contract.archive_auction(0);
+        storage::flush();
+        assert!(env::storage_usage() < before);
+
+        assert!(contract.get_nft_auction(0).is_none());
+        assert_eq!(contract.get_nft_min_increment(0), None);
+        assert_eq!(
+            contract.get_archived_auction(0),
+            Some(ArchivedNftAuction {
+                nft_contract: auction.nft_contract,
+                token_id: auction.token_id,
+                auctioneer: auction.auctioneer,
+                winner: Some("bob.near".parse().unwrap()),
+                price: auction.highest_bid.bid,
+                asset: Asset::Near,
+                auction_end_time: auction.auction_end_time,
+            })
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_ARCHIVABLE")]
+    fn unclaimed_auctions_are_not_archived() {
+        let mut contract = start_nft_auction();
+        call_at("bob.near", NearToken::from_yoctonear(1), 600);
+        contract.archive_auction(0);
+    }
+
+    #[test]
+    fn prune_archives_claimed_auctions_in_batches() {
+        let mut contract = start_nft_auction();
+        call_at("nft.near", NearToken::from_yoctonear(1), 0);
+        let _ = contract.nft_on_transfer(
+            "carol.near".parse().unwrap(),
+            "carol.near".parse().unwrap(),
+            "token-2".to_string(),
+            r#"{"end_time":"5000"}"#.to_string(),
+        );
+        claim_after_bidding(&mut contract);
+        let _ = resolve_claim_with(&mut contract, PromiseResult::Successful(vec![]));
+
+        call_at("bob.near", NearToken::from_yoctonear(1), 700);
+        // Auction 1 is still running, so only auction 0 goes
+        assert_eq!(contract.prune(1), 1);
+        assert_eq!(contract.prune(5), 0);
+        assert!(contract.get_archived_auction(0).is_some());
+        assert!(contract.get_nft_auction(1).is_some());
+    }
+}
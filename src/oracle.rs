@@ -0,0 +1,177 @@
+//! Optional reserve price denominated in USD. When one is configured, `bid`
+//! asks the price oracle for the current NEAR price and only accepts the bid
+//! in `resolve_bid`, once it is known to be worth at least the reserve.
+
+use near_sdk::json_types::U128;
+use near_sdk::serde_json;
+use near_sdk::{assert_one_yocto, env, near, AccountId, Gas, NearToken, Promise, PromiseOrValue};
+
+use crate::error::AuctionError;
+use crate::owner::only_owner;
+use crate::storage::{StorageCell, StorageKey, WriteBackSession};
+use crate::{accept_or_refund, place_bid, Contract, ContractExt};
+
+/// Gas for the oracle's price query.
+const GET_PRICE_GAS: Gas = Gas::from_tgas(10);
+/// Gas reserved for `resolve_bid`, which stores the bid and schedules the
+/// refund of the previous one.
+const RESOLVE_BID_GAS: Gas = Gas::from_tgas(30);
+
+/// Longest oracle response `resolve_bid` will read.
+const MAX_PRICE_LEN: usize = 64;
+
+#[near(serializers = [json, borsh])]
+#[derive(Clone)]
+pub struct UsdReserve {
+    pub oracle: AccountId,
+    /// Minimum bid, in millionths of a dollar.
+    pub min_usd: U128,
+}
+
+pub fn usd_reserve() -> Option<UsdReserve> {
+    StorageCell::<UsdReserve>::new(StorageKey::UsdReserve)
+        .try_get()
+        .cloned()
+}
+
+/// Asks the oracle for `get_near_usd_price`, the USD price of one NEAR in
+/// millionths of a dollar, then finishes the bid in `resolve_bid`.
+pub fn price_bid(reserve: UsdReserve, bidder: AccountId, bid: NearToken) -> Promise {
+    Promise::new(reserve.oracle)
+        .function_call(
+            "get_near_usd_price".to_string(),
+            vec![],
+            NearToken::from_yoctonear(0),
+            GET_PRICE_GAS,
+        )
+        .then(
+            Contract::ext(env::current_account_id())
+                .with_static_gas(RESOLVE_BID_GAS)
+                .resolve_bid(bidder, bid, reserve.min_usd),
+        )
+}
+
+/// Whether `bid` is worth at least `min_usd` at `price`, both in millionths
+/// of a dollar. Saturates, since a bid that overflows clears any reserve.
+fn meets_reserve(bid: NearToken, price: u128, min_usd: u128) -> bool {
+    let one_near = NearToken::from_near(1).as_yoctonear();
+    bid.as_yoctonear().saturating_mul(price) / one_near >= min_usd
+}
+
+#[near]
+impl Contract {
+    /// Accepts the bid if the oracle answered and the bid clears the reserve.
+    /// Otherwise the deposit goes back to the bidder; panicking here would
+    /// leave it stuck in the contract.
+    #[private]
+    pub fn resolve_bid(
+        &mut self,
+        bidder: AccountId,
+        bid: NearToken,
+        min_usd: U128,
+    ) -> PromiseOrValue<bool> {
+        let _session = WriteBackSession::begin();
+
+        let price = env::promise_result_checked(0, MAX_PRICE_LEN)
+            .ok()
+            .and_then(|bytes| serde_json::from_slice::<U128>(&bytes).ok());
+        let result = match price {
+            None => Err(AuctionError::NoOraclePrice),
+            Some(price) if !meets_reserve(bid, price.0, min_usd.0) => {
+                Err(AuctionError::BelowUsdReserve)
+            }
+            Some(_) => place_bid(bidder.clone(), bid),
+        };
+        accept_or_refund(bidder, bid, result)
+    }
+
+    /// Requires bids to be worth at least `min_usd` according to `oracle`.
+    /// `None` goes back to accepting any higher bid.
+    #[payable]
+    pub fn set_usd_reserve(&mut self, reserve: Option<UsdReserve>) {
+        let _session = WriteBackSession::begin();
+
+        assert_one_yocto();
+        only_owner();
+        let mut cell = StorageCell::<UsdReserve>::new(StorageKey::UsdReserve);
+        match reserve {
+            Some(reserve) => cell.set(reserve),
+            None => cell.remove(),
+        }
+    }
+
+    pub fn get_usd_reserve(&self) -> Option<UsdReserve> {
+        usd_reserve()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::json_types::U64;
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::{test_vm_config, testing_env, PromiseResult, RuntimeFeesConfig};
+
+    use super::*;
+    use crate::test_utils::new_contract;
+
+    /// A `resolve_bid` context in which the oracle returned `price`.
+    fn resolve_with(price: Option<u128>) {
+        let result = match price {
+            Some(price) => PromiseResult::Successful(serde_json::to_vec(&U128(price)).unwrap()),
+            None => PromiseResult::Failed,
+        };
+        testing_env!(
+            VMContextBuilder::new()
+                .predecessor_account_id(env::current_account_id())
+                .block_timestamp(100)
+                .build(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![result],
+        );
+    }
+
+    #[test]
+    fn reserve_is_compared_in_usd() {
+        // $3.50 per NEAR
+        let price = 3_500_000;
+        assert!(meets_reserve(NearToken::from_near(2), price, 7_000_000));
+        assert!(!meets_reserve(NearToken::from_near(2), price, 7_000_001));
+        // Saturates instead of overflowing
+        assert!(meets_reserve(
+            NearToken::from_yoctonear(u128::MAX),
+            price,
+            100_000_000_000_000
+        ));
+    }
+
+    #[test]
+    fn bid_is_accepted_once_priced() {
+        let mut contract = new_contract();
+        resolve_with(Some(3_500_000));
+        let bob: AccountId = "bob.near".parse().unwrap();
+        let _ = contract.resolve_bid(bob.clone(), NearToken::from_near(2), U128(5_000_000));
+        assert_eq!(contract.get_highest_bid().bidder, bob);
+    }
+
+    #[test]
+    fn bid_below_the_reserve_is_sent_back() {
+        let mut contract = new_contract();
+        resolve_with(Some(3_500_000));
+        let _ = contract.resolve_bid(
+            "bob.near".parse().unwrap(),
+            NearToken::from_near(1),
+            U128(5_000_000),
+        );
+        assert_eq!(contract.get_highest_bid().bidder, env::current_account_id());
+
+        resolve_with(None);
+        let _ = contract.resolve_bid(
+            "bob.near".parse().unwrap(),
+            NearToken::from_near(2),
+            U128(5_000_000),
+        );
+        assert_eq!(contract.get_bid_count(), U64(0));
+    }
+}
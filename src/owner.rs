@@ -0,0 +1,127 @@
+//! The account allowed to administer the contract. It is separate from the
+//! auctioneer, who only receives the winning bid, and is handed over in two
+//! steps so a typo in `propose_owner` can't lock everyone out.
+
+use near_sdk::{assert_one_yocto, env, near, require, AccountId};
+
+use crate::error::AuctionError;
+use crate::storage::{StorageCell, StorageKey, WriteBackSession};
+use crate::{Contract, ContractExt};
+
+/// Panics unless the caller is the current owner.
+pub fn only_owner() {
+    let owner = StorageCell::<AccountId>::new(StorageKey::Owner);
+    require!(
+        env::predecessor_account_id() == *owner.get(),
+        AuctionError::NotOwner.as_str()
+    );
+}
+
+#[near]
+impl Contract {
+    /// Names `new_owner` as the next owner. Ownership only moves once they
+    /// call `accept_ownership`; proposing again replaces the pending owner.
+    #[payable]
+    pub fn propose_owner(&mut self, new_owner: AccountId) {
+        let _session = WriteBackSession::begin();
+
+        assert_one_yocto();
+        only_owner();
+
+        env::log_str(&format!("Proposed {new_owner} as the new owner"));
+        StorageCell::new(StorageKey::PendingOwner).set(new_owner);
+    }
+
+    /// Makes the pending owner the owner. Only they can call this.
+    #[payable]
+    pub fn accept_ownership(&mut self) {
+        let _session = WriteBackSession::begin();
+
+        assert_one_yocto();
+        let mut pending_owner = StorageCell::<AccountId>::new(StorageKey::PendingOwner);
+        require!(
+            pending_owner.try_get() == Some(&env::predecessor_account_id()),
+            AuctionError::NotPendingOwner.as_str()
+        );
+        let new_owner = env::predecessor_account_id();
+        pending_owner.remove();
+
+        env::log_str(&format!("Ownership transferred to {new_owner}"));
+        StorageCell::new(StorageKey::Owner).set(new_owner);
+    }
+
+    pub fn get_owner(&self) -> AccountId {
+        StorageCell::<AccountId>::new(StorageKey::Owner)
+            .get()
+            .clone()
+    }
+
+    pub fn get_pending_owner(&self) -> Option<AccountId> {
+        StorageCell::<AccountId>::new(StorageKey::PendingOwner)
+            .try_get()
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::{testing_env, NearToken};
+
+    use super::*;
+    use crate::test_utils::new_contract;
+
+    fn call_as(account_id: &str) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account_id.parse().unwrap())
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .build());
+    }
+
+    #[test]
+    fn ownership_moves_once_accepted() {
+        call_as("owner.near");
+        let mut contract = new_contract();
+        assert_eq!(
+            contract.get_owner(),
+            "owner.near".parse::<AccountId>().unwrap()
+        );
+
+        contract.propose_owner("bob.near".parse().unwrap());
+        assert_eq!(
+            contract.get_owner(),
+            "owner.near".parse::<AccountId>().unwrap()
+        );
+        assert_eq!(
+            contract.get_pending_owner(),
+            Some("bob.near".parse().unwrap())
+        );
+
+        call_as("bob.near");
+        contract.accept_ownership();
+        assert_eq!(
+            contract.get_owner(),
+            "bob.near".parse::<AccountId>().unwrap()
+        );
+        assert_eq!(contract.get_pending_owner(), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER")]
+    fn auctioneer_cannot_propose_owner() {
+        call_as("owner.near");
+        let mut contract = new_contract();
+        call_as("auctioneer.near");
+        contract.propose_owner("auctioneer.near".parse().unwrap());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_PENDING_OWNER")]
+    fn others_cannot_accept_ownership() {
+        call_as("owner.near");
+        let mut contract = new_contract();
+        contract.propose_owner("bob.near".parse().unwrap());
+        call_as("carol.near");
+        contract.accept_ownership();
+    }
+}
@@ -0,0 +1,77 @@
+//! Reads the bidder and amount of the last bid straight from its stored
+//! bytes. Borsh writes the account id as a `u32` length and its bytes, then
+//! the amount as 16 little-endian bytes, so both sit at offsets known from
+//! that length alone. A whole `Bid` starts the same way as a `HotBid`, so
+//! this works on either encoding.
+
+use near_sdk::{AccountId, NearToken};
+
+use crate::highest_bid::HotBid;
+
+/// The leading fields of an encoded bid, borrowing the bidder from it.
+pub struct BidPrefix<'a> {
+    pub bidder: &'a str,
+    pub bid: NearToken,
+}
+
+impl BidPrefix<'_> {
+    pub fn to_hot_bid(&self) -> Option<HotBid> {
+        Some(HotBid {
+            bidder: self.bidder.parse::<AccountId>().ok()?,
+            bid: self.bid,
+        })
+    }
+}
+
+/// `None` if `bytes` is too short or the bidder isn't UTF-8.
+pub fn bid_prefix(bytes: &[u8]) -> Option<BidPrefix<'_>> {
+    let len = u32::from_le_bytes(bytes.get(..4)?.try_into().ok()?) as usize;
+    let bid_start = len.checked_add(4)?;
+    let bidder = std::str::from_utf8(bytes.get(4..bid_start)?).ok()?;
+    let bid = bytes.get(bid_start..bid_start.checked_add(16)?)?;
+    Some(BidPrefix {
+        bidder,
+        bid: NearToken::from_yoctonear(u128::from_le_bytes(bid.try_into().ok()?)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::borsh;
+    use near_sdk::json_types::U64;
+
+    use super::*;
+    use crate::Bid;
+
+    fn bid() -> Bid {
+        Bid {
+            bidder: "bob.near".parse().unwrap(),
+            bid: NearToken::from_near(3),
+            bid_time: U64(10),
+            bid_block_height: U64(20),
+            bid_block_timestamp: U64(10),
+            bid_epoch_height: U64(30),
+            premium: false,
+        }
+    }
+
+    #[test]
+    fn reads_the_prefix_of_either_encoding() {
+        let (hot, _) = bid().split();
+        for bytes in [borsh::to_vec(&hot).unwrap(), borsh::to_vec(&bid()).unwrap()] {
+            let prefix = bid_prefix(&bytes).unwrap();
+            assert_eq!(prefix.bidder, "bob.near");
+            assert_eq!(prefix.bid, NearToken::from_near(3));
+            let decoded = prefix.to_hot_bid().unwrap();
+            assert_eq!(decoded.bidder, hot.bidder);
+        }
+    }
+
+    #[test]
+    fn truncated_bytes_are_rejected() {
+        let bytes = borsh::to_vec(&bid().split().0).unwrap();
+        assert!(bid_prefix(&bytes[..bytes.len() - 1]).is_none());
+        assert!(bid_prefix(&bytes[..3]).is_none());
+        assert!(bid_prefix(&[0xff, 0xff, 0xff, 0xff]).is_none());
+    }
+}
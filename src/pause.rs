@@ -0,0 +1,122 @@
+//! Owner-controlled circuit breaker for `bid` and the benchmark fills.
+
+use near_sdk::{assert_one_yocto, env, near, require};
+
+use crate::error::AuctionError;
+use crate::events::AuctionEvent;
+use crate::owner::only_owner;
+use crate::storage::{StorageCell, StorageKey, WriteBackSession};
+use crate::{Contract, ContractExt};
+
+/// Panics while the contract is paused.
+pub fn assert_not_paused() {
+    require!(!currently_paused(), AuctionError::Paused.as_str());
+}
+
+/// State written before the flag existed reads as not paused.
+pub fn currently_paused() -> bool {
+    StorageCell::<bool>::new(StorageKey::Paused)
+        .try_get()
+        .copied()
+        .unwrap_or(false)
+}
+
+fn set_paused(paused: bool) {
+    let _session = WriteBackSession::begin();
+
+    assert_one_yocto();
+    only_owner();
+    require!(
+        currently_paused() != paused,
+        AuctionError::PauseUnchanged.as_str()
+    );
+
+    StorageCell::new(StorageKey::Paused).set(paused);
+    let by = env::predecessor_account_id();
+    if paused {
+        AuctionEvent::Paused { by }.emit();
+    } else {
+        AuctionEvent::Unpaused { by }.emit();
+    }
+}
+
+#[near]
+impl Contract {
+    #[payable]
+    pub fn pause(&mut self) {
+        set_paused(true);
+    }
+
+    #[payable]
+    pub fn unpause(&mut self) {
+        set_paused(false);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        currently_paused()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::{get_logs, VMContextBuilder};
+    use near_sdk::testing_env;
+    use near_sdk::{AccountId, NearToken};
+
+    use super::*;
+    use crate::test_utils::new_contract;
+
+    fn init() -> Contract {
+        let owner: AccountId = "owner.near".parse().unwrap();
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(owner)
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .build());
+        new_contract()
+    }
+
+    #[test]
+    fn pausing_blocks_fills_until_unpaused() {
+        let mut contract = init();
+        contract.pause();
+        assert!(contract.is_paused());
+        assert_eq!(
+            get_logs(),
+            vec![
+                r#"EVENT_JSON:{"standard":"auction","version":"1.0.0","event":"paused","data":{"by":"owner.near"}}"#
+            ]
+        );
+
+        contract.unpause();
+        assert!(!contract.is_paused());
+        contract.fill_vector(10, 0, None);
+        assert_eq!(contract.get_vector().len(), 10);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PAUSED")]
+    fn paused_contract_rejects_fills() {
+        let mut contract = init();
+        contract.pause();
+        contract.fill_sdk_vector(10, 0, None);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PAUSED")]
+    fn paused_contract_rejects_bids() {
+        let mut contract = init();
+        contract.pause();
+        let _ = contract.bid();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER")]
+    fn only_the_owner_can_pause() {
+        let mut contract = init();
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id("auctioneer.near".parse().unwrap())
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .build());
+        contract.pause();
+    }
+}
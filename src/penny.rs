@@ -0,0 +1,216 @@
+//! Optional penny-auction mode. Every bid raises the price by a fixed
+//! increment and pays a flat fee on top, which the contract keeps, and the
+//! countdown restarts with each bid, so the auction only ends once nobody
+//! has bid for a whole countdown.
+
+use near_sdk::json_types::U64;
+use near_sdk::{assert_one_yocto, env, near, require, NearToken};
+
+use crate::auction_state::AuctionState;
+use crate::error::AuctionError;
+use crate::math;
+use crate::owner::only_owner;
+use crate::storage::{StorageCell, StorageKey, WriteBackSession};
+use crate::{time_remaining, Contract, ContractExt};
+
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct PennyAuction {
+    /// Charged on top of the price for every bid and never refunded.
+    pub fee: NearToken,
+    /// How much each bid raises the price.
+    pub increment: NearToken,
+    /// How long after a bid the auction ends at the earliest, in
+    /// nanoseconds.
+    pub countdown: U64,
+}
+
+/// What a penny bid's deposit, less receipt storage, pays for.
+#[derive(Debug, PartialEq)]
+pub struct PennyBid {
+    /// The new highest bid, which is refunded once someone outbids it.
+    pub price: NearToken,
+    /// Fees collected so far, including this bid's.
+    pub fees: NearToken,
+    /// Whatever the deposit held beyond the price and fee, sent back.
+    pub excess: NearToken,
+}
+
+#[near(serializers = [json])]
+pub struct PennyAuctionInfo {
+    pub current_price: NearToken,
+    /// The price the next bid sets.
+    pub next_price: NearToken,
+    /// What the next bid must attach: the next price plus the fee, not
+    /// counting storage for its receipt.
+    pub next_bid_deposit: NearToken,
+    pub auction_end_time: U64,
+    pub time_remaining: U64,
+    pub collected_fees: NearToken,
+}
+
+pub fn penny_auction() -> Option<PennyAuction> {
+    StorageCell::<PennyAuction>::new(StorageKey::PennyAuction)
+        .try_get()
+        .cloned()
+}
+
+fn collected_fees() -> NearToken {
+    StorageCell::<NearToken>::new(StorageKey::PennyFees)
+        .try_get()
+        .copied()
+        .unwrap_or(NearToken::from_yoctonear(0))
+}
+
+impl PennyAuction {
+    /// The next price after `current_price`, and the deposit that sets it.
+    fn next(&self, current_price: NearToken) -> Result<(NearToken, NearToken), AuctionError> {
+        let price = math::add(current_price, self.increment)?;
+        Ok((price, math::add(price, self.fee)?))
+    }
+
+    /// Splits the `deposit` outbidding `current_price`. Any deposit that
+    /// covers the next price and the fee is enough; bidding never skips
+    /// ahead by more than one increment.
+    pub fn split_bid(
+        &self,
+        current_price: NearToken,
+        deposit: NearToken,
+    ) -> Result<PennyBid, AuctionError> {
+        let (price, required) = self.next(current_price)?;
+        let excess = math::increment(required, deposit).map_err(|_| AuctionError::BidTooLow)?;
+        Ok(PennyBid {
+            price,
+            fees: math::add(collected_fees(), self.fee)?,
+            excess,
+        })
+    }
+
+    /// The end time after a bid now: at least a whole countdown away, but
+    /// never earlier than it already was.
+    pub fn end_time_after_bid(&self, auction_end_time: u64) -> u64 {
+        auction_end_time.max(env::block_timestamp().saturating_add(self.countdown.0))
+    }
+}
+
+/// Records the fees `split_bid` worked out, once the bid is accepted.
+pub fn collect_fees(bid: &PennyBid) {
+    StorageCell::new(StorageKey::PennyFees).set(bid.fees);
+}
+
+#[near]
+impl Contract {
+    /// Turns the auction into a penny auction, or back into a regular one
+    /// with `None`. Only possible before the first bid, so every bidder
+    /// plays by the same rules.
+    #[payable]
+    pub fn set_penny_auction(&mut self, penny_auction: Option<PennyAuction>) {
+        let _session = WriteBackSession::begin();
+
+        assert_one_yocto();
+        only_owner();
+        require!(
+            StorageCell::<U64>::new(StorageKey::BidCount).get().0 == 0,
+            AuctionError::PennyAuctionAfterBids.as_str()
+        );
+        let mut cell = StorageCell::<PennyAuction>::new(StorageKey::PennyAuction);
+        match penny_auction {
+            Some(penny_auction) => {
+                require!(
+                    !penny_auction.increment.is_zero() && penny_auction.countdown.0 > 0,
+                    AuctionError::InvalidPennyAuction.as_str()
+                );
+                cell.set(penny_auction);
+            }
+            None => cell.remove(),
+        }
+    }
+
+    pub fn get_penny_auction(&self) -> Option<PennyAuction> {
+        penny_auction()
+    }
+
+    /// The current price and countdown of a penny auction, or `None` for a
+    /// regular one.
+    pub fn get_penny_auction_info(&self) -> Option<PennyAuctionInfo> {
+        let penny_auction = penny_auction()?;
+        let state = AuctionState::new();
+        let current_price = state.highest_bid().bid;
+        let (next_price, next_bid_deposit) = penny_auction.next(current_price).ok()?;
+        let auction_end_time = state.config.get().auction_end_time;
+        Some(PennyAuctionInfo {
+            current_price,
+            next_price,
+            next_bid_deposit,
+            auction_end_time: U64(auction_end_time),
+            time_remaining: time_remaining(auction_end_time),
+            collected_fees: collected_fees(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::{testing_env, AccountId};
+
+    use super::*;
+    use crate::test_utils::new_contract;
+
+    fn near(amount: u128) -> NearToken {
+        NearToken::from_near(amount)
+    }
+
+    fn rules() -> PennyAuction {
+        PennyAuction {
+            fee: near(1),
+            increment: near(2),
+            countdown: U64(50),
+        }
+    }
+
+    #[test]
+    fn bids_pay_the_next_price_plus_the_fee() {
+        new_contract();
+        let penny_auction = rules();
+
+        assert_eq!(
+            penny_auction.split_bid(near(4), near(7)),
+            Ok(PennyBid {
+                price: near(6),
+                fees: near(1),
+                excess: near(0),
+            })
+        );
+        assert_eq!(
+            penny_auction.split_bid(near(4), near(10)).unwrap().excess,
+            near(3)
+        );
+        assert_eq!(
+            penny_auction.split_bid(near(4), near(6)),
+            Err(AuctionError::BidTooLow)
+        );
+    }
+
+    #[test]
+    fn countdown_never_brings_the_end_forward() {
+        testing_env!(VMContextBuilder::new().block_timestamp(100).build());
+        let penny_auction = rules();
+        assert_eq!(penny_auction.end_time_after_bid(120), 150);
+        assert_eq!(penny_auction.end_time_after_bid(1000), 1000);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_PENNY_AUCTION_AFTER_BIDS")]
+    fn rules_are_fixed_once_bidding_starts() {
+        let mut contract = new_contract();
+        let owner: AccountId = contract.get_owner();
+        StorageCell::new(StorageKey::BidCount).set(U64(1));
+
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(owner)
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .build());
+        contract.set_penny_auction(Some(rules()));
+    }
+}
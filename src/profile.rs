@@ -0,0 +1,79 @@
+use near_sdk::env;
+use near_sdk::serde_json::{Map, Value};
+
+/// Records `env::used_gas()` at named checkpoints so a method can report how
+/// much gas each of its phases burnt. The breakdown is only logged with the
+/// `gas-profile` feature, so regular builds don't pay for the log line.
+pub struct GasProfile {
+    method: &'static str,
+    last: u64,
+    phases: Vec<(&'static str, u64)>,
+}
+
+impl GasProfile {
+    pub fn start(method: &'static str) -> Self {
+        Self {
+            method,
+            last: env::used_gas().as_gas(),
+            phases: vec![],
+        }
+    }
+
+    /// Attributes the gas burnt since the previous checkpoint to `phase`.
+    pub fn checkpoint(&mut self, phase: &'static str) {
+        let now = env::used_gas().as_gas();
+        self.phases.push((phase, now - self.last));
+        self.last = now;
+    }
+
+    /// Logs `gas_profile {"method": .., "<phase>": <gas>, ..}`, and keeps the
+    /// breakdown for `take_last` when built for benchmarks.
+    pub fn finish(self) {
+        if !cfg!(any(feature = "gas-profile", feature = "bench")) {
+            return;
+        }
+        let mut breakdown = Map::new();
+        breakdown.insert("method".into(), self.method.into());
+        for (phase, gas) in self.phases {
+            breakdown.insert(phase.into(), gas.into());
+        }
+        let breakdown = Value::Object(breakdown);
+        if cfg!(feature = "gas-profile") {
+            env::log_str(&format!("gas_profile {breakdown}"));
+        }
+        #[cfg(feature = "bench")]
+        LAST.set(Some(breakdown));
+    }
+}
+
+#[cfg(feature = "bench")]
+thread_local! {
+    static LAST: std::cell::Cell<Option<Value>> = const { std::cell::Cell::new(None) };
+}
+
+/// The breakdown of the most recently finished profile, if any.
+#[cfg(all(feature = "bench", not(feature = "minimal")))]
+pub fn take_last() -> Option<Value> {
+    LAST.take()
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    use super::*;
+
+    #[test]
+    fn attributes_gas_to_phases() {
+        testing_env!(VMContextBuilder::new().build());
+        let mut profile = GasProfile::start("test");
+        env::storage_write(b"key", &[0; 100]);
+        profile.checkpoint("write");
+        profile.checkpoint("nothing");
+
+        assert_eq!(profile.phases[0].0, "write");
+        assert!(profile.phases[0].1 > 0);
+        assert!(profile.phases[1].1 < profile.phases[0].1);
+    }
+}
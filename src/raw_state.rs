@@ -0,0 +1,486 @@
+use near_sdk::json_types::{Base64VecU8, U64};
+use near_sdk::serde_json::{self, json, Value};
+use near_sdk::{assert_one_yocto, borsh, env, near, store, AccountId, NearToken};
+
+use crate::approvals::ClaimApprovers;
+use crate::auction_state::AuctionConfig;
+use crate::chain_signatures::{ForeignPayout, SignRequest};
+use crate::emergency::Emergency;
+use crate::highest_bid::{self, HotBid};
+use crate::increments::BidIncrement;
+use crate::oracle::UsdReserve;
+use crate::owner::only_owner;
+use crate::penny::PennyAuction;
+use crate::records::Record;
+use crate::staking::StakingEscrow;
+use crate::storage::{self, key, StorageKey, WriteBackSession};
+use crate::storage_management::StorageBalance;
+use crate::{ttl, Bid, Contract, ContractExt};
+
+/// Bytes the runtime charges for every storage record on top of its key and
+/// value (`storage_num_extra_bytes_record` in the protocol config).
+const STORAGE_RECORD_OVERHEAD: u64 = 40;
+
+#[near(serializers = [json])]
+pub struct IntegrityFailure {
+    pub key: Base64VecU8,
+    pub reason: String,
+}
+
+/// `env::storage_usage` split by the namespace of each indexed key.
+#[near(serializers = [json])]
+pub struct StorageUsage {
+    pub total: U64,
+    /// Keys under the `auction` namespace, plus bid receipts (`receipt`).
+    pub auction: U64,
+    /// Benchmark values and SDK collection headers (`bench` and `coll`).
+    pub benchmark: U64,
+    /// Admin settings, the state version and the key index (`meta`).
+    pub metadata: U64,
+    /// Everything else: the account and contract code, SDK collection
+    /// elements, which aren't indexed, and raw keys.
+    pub other: U64,
+}
+
+#[near(serializers = [json])]
+pub struct StateEntry {
+    pub name: String,
+    pub key: Base64VecU8,
+    pub value: Option<Base64VecU8>,
+    pub decoded: Option<Value>,
+}
+
+#[near]
+impl Contract {
+    pub fn get_raw(&self, key: Base64VecU8) -> Option<Base64VecU8> {
+        ttl::peek(&key.0).map(Base64VecU8::from)
+    }
+
+    pub fn has_key(&self, key: Base64VecU8) -> bool {
+        storage::has_key(&key.0)
+    }
+
+    /// Pages through the key index, i.e. every top-level key this contract
+    /// has written and not removed since. Removing a key moves the last one
+    /// into its place, so pages can shift between calls.
+    pub fn list_keys(&self, from: u32, limit: u32) -> Vec<Base64VecU8> {
+        storage::indexed_keys_page(from, limit)
+            .into_iter()
+            .map(Base64VecU8::from)
+            .collect()
+    }
+
+    pub fn dump_state(&self) -> Vec<StateEntry> {
+        StorageKey::ALL
+            .into_iter()
+            .map(|key| {
+                let raw_key = key.to_bytes();
+                let value = storage::peek(&raw_key);
+                StateEntry {
+                    name: key.name().to_string(),
+                    decoded: value.as_deref().and_then(|bytes| decode(key, bytes)),
+                    key: raw_key.into(),
+                    value: value.map(Base64VecU8::from),
+                }
+            })
+            .collect()
+    }
+
+    /// Storage bytes taken by every registered key that is currently set:
+    /// value length plus key length plus the per-record overhead.
+    pub fn get_key_sizes(&self) -> Vec<(String, u64)> {
+        StorageKey::ALL
+            .into_iter()
+            .filter_map(|key| {
+                let raw_key = key.to_bytes();
+                env::storage_has_key(&raw_key)
+                    .then(|| (key.name().to_string(), record_size(&raw_key)))
+            })
+            .collect()
+    }
+
+    /// Splits the account's storage usage between auction state, benchmark
+    /// data and metadata, to cross-check the storage staked for it.
+    pub fn get_storage_usage(&self) -> StorageUsage {
+        let total = env::storage_usage();
+        let [mut auction, mut benchmark, mut metadata] = [0; 3];
+        metadata += record_size(&StorageKey::KeyIndex.to_bytes());
+        for raw_key in storage::indexed_keys() {
+            // The index itself keeps two records per key under `meta`
+            for record in storage::index_records(&raw_key) {
+                metadata += record_size(&record);
+            }
+            let size = record_size(&raw_key);
+            if raw_key.starts_with(&key!("auction")) || raw_key.starts_with(&key!("receipt")) {
+                auction += size;
+            } else if raw_key.starts_with(&key!("bench")) || raw_key.starts_with(&key!("coll")) {
+                benchmark += size;
+            } else if raw_key.starts_with(&key!("meta")) {
+                metadata += size;
+            }
+        }
+        StorageUsage {
+            total: U64(total),
+            auction: U64(auction),
+            benchmark: U64(benchmark),
+            metadata: U64(metadata),
+            other: U64(total - auction - benchmark - metadata),
+        }
+    }
+
+    /// Checks the integrity envelope of every indexed and registered key and
+    /// returns the ones that fail.
+    pub fn verify_state(&self) -> Vec<IntegrityFailure> {
+        let mut keys = storage::indexed_keys();
+        keys.extend(StorageKey::ALL.into_iter().map(StorageKey::to_bytes));
+        keys.sort();
+        keys.dedup();
+        keys.into_iter()
+            .filter_map(|key| {
+                storage::verify(&key).err().map(|err| IntegrityFailure {
+                    key: key.into(),
+                    reason: err.to_string(),
+                })
+            })
+            .collect()
+    }
+
+    /// Overwrites any key with arbitrary bytes, bypassing every ledger, so
+    /// only the owner may call it. Any TTL the key had is dropped.
+    #[payable]
+    pub fn set_raw(&mut self, key: Base64VecU8, value: Base64VecU8) {
+        let _session = WriteBackSession::begin();
+
+        assert_one_yocto();
+        only_owner();
+
+        storage::write(&key.0, &value.0);
+        ttl::clear(&key.0);
+    }
+
+    /// Like `set_raw`, but the entry reads as absent once `expires_at`
+    /// (nanoseconds) has passed and can then be reclaimed by `purge_expired`.
+    #[payable]
+    pub fn set_raw_with_ttl(&mut self, key: Base64VecU8, value: Base64VecU8, expires_at: U64) {
+        let _session = WriteBackSession::begin();
+
+        assert_one_yocto();
+        only_owner();
+
+        ttl::write_with_ttl(&key.0, &value.0, expires_at.0);
+    }
+
+    /// Writes every entry like `set_raw`, dropping any TTL the keys had.
+    #[private]
+    pub fn patch_state(&mut self, entries: Vec<(Base64VecU8, Base64VecU8)>) {
+        let _session = WriteBackSession::begin();
+
+        let entries: Vec<(&[u8], Vec<u8>)> = entries
+            .iter()
+            .map(|(key, value)| (key.0.as_slice(), value.0.clone()))
+            .collect();
+        storage::write_many(&entries);
+        for (key, _) in &entries {
+            ttl::clear(key);
+        }
+        env::log_str(&format!("Patched {} keys", entries.len()));
+    }
+}
+
+/// Storage bytes taken by the record under `key`, zero if there is none.
+fn record_size(key: &[u8]) -> u64 {
+    env::storage_read(key).map_or(0, |value| {
+        value.len() as u64 + key.len() as u64 + STORAGE_RECORD_OVERHEAD
+    })
+}
+
+/// Decodes the borsh value stored under `key` into JSON, or `None` when the
+/// bytes don't match the type registered for that key.
+fn decode(key: StorageKey, bytes: &[u8]) -> Option<Value> {
+    match key {
+        StorageKey::HighestBid => to_json::<HotBid>(bytes),
+        StorageKey::HighestBidMetadata => highest_bid::decode_metadata(bytes).map(|metadata| {
+            json!({
+                "bid_time": U64(metadata.bid_time),
+                "bid_block_height": U64(metadata.bid_block_height),
+                "bid_block_timestamp": U64(metadata.bid_block_timestamp),
+                "bid_epoch_height": U64(metadata.bid_epoch_height),
+                "premium": metadata.premium,
+            })
+        }),
+        StorageKey::AuctionConfig => borsh::from_slice::<AuctionConfig>(bytes)
+            .ok()
+            .map(|config| {
+                json!({
+                    "auction_end_time": U64(config.auction_end_time),
+                    "auctioneer": config.auctioneer,
+                    "claim": config.claim,
+                })
+            }),
+        StorageKey::BidCount => to_json::<U64>(bytes),
+        StorageKey::TotalVolume => to_json::<NearToken>(bytes),
+        StorageKey::Refunds => borsh::from_slice::<store::IterableMap<AccountId, NearToken>>(bytes)
+            .ok()
+            .map(|ledger| json!({ "len": ledger.len() })),
+        StorageKey::StorageBalances => {
+            borsh::from_slice::<store::LookupMap<AccountId, StorageBalance>>(bytes)
+                .ok()
+                .map(|_| json!({}))
+        }
+        StorageKey::ClaimApprovers => to_json::<ClaimApprovers>(bytes),
+        StorageKey::ClaimApprovals => to_json::<Vec<AccountId>>(bytes),
+        StorageKey::NftAuctionCount
+        | StorageKey::NftPruneCursor
+        | StorageKey::DutchAuctionCount => to_json::<u32>(bytes),
+        StorageKey::UsdReserve => to_json::<UsdReserve>(bytes),
+        StorageKey::PennyAuction => to_json::<PennyAuction>(bytes),
+        StorageKey::PennyFees | StorageKey::StorageAvailable | StorageKey::RefundsInFlight => {
+            to_json::<NearToken>(bytes)
+        }
+        StorageKey::MinIncrement => to_json::<BidIncrement>(bytes),
+        StorageKey::WnearContract => to_json::<AccountId>(bytes),
+        StorageKey::UnsettledWnear => to_json::<NearToken>(bytes),
+        StorageKey::StakingEscrow => to_json::<StakingEscrow>(bytes),
+        StorageKey::ForeignPayout => to_json::<ForeignPayout>(bytes),
+        StorageKey::SignRequest => to_json::<SignRequest>(bytes),
+        StorageKey::ReceiptCount => to_json::<U64>(bytes),
+        StorageKey::Vector => to_json::<u32>(bytes),
+        StorageKey::SdkVector => borsh::from_slice::<store::Vector<u8>>(bytes)
+            .ok()
+            .map(|vector| json!({ "len": vector.len() })),
+        StorageKey::SdkIterableMap => borsh::from_slice::<store::IterableMap<u8, u8>>(bytes)
+            .ok()
+            .map(|map| json!({ "len": map.len() })),
+        // A lookup map can't count its entries, so only report that it parses
+        StorageKey::SdkLookupMap => borsh::from_slice::<store::LookupMap<u8, u8>>(bytes)
+            .ok()
+            .map(|_| json!({})),
+        StorageKey::SdkTreeMap => borsh::from_slice::<store::TreeMap<u8, u8>>(bytes)
+            .ok()
+            .map(|map| json!({ "len": map.len() })),
+        StorageKey::SdkLookupSet => borsh::from_slice::<store::LookupSet<u8>>(bytes)
+            .ok()
+            .map(|_| json!({})),
+        StorageKey::SdkIterableSet => borsh::from_slice::<store::IterableSet<u8>>(bytes)
+            .ok()
+            .map(|set| json!({ "len": set.len() })),
+        StorageKey::SdkNestedMap => {
+            borsh::from_slice::<store::IterableMap<u8, store::Vector<u8>>>(bytes)
+                .ok()
+                .map(|map| json!({ "len": map.len() }))
+        }
+        StorageKey::BigValue => borsh::from_slice::<Vec<u8>>(bytes)
+            .ok()
+            .map(|value| json!({ "len": value.len() })),
+        StorageKey::RecordBlob => borsh::from_slice::<Vec<Record>>(bytes)
+            .ok()
+            .map(|records| json!({ "len": records.len() })),
+        StorageKey::RecordEntries => to_json::<u32>(bytes),
+        StorageKey::Blob => borsh::from_slice::<Vec<u8>>(bytes)
+            .ok()
+            .map(|blob| json!({ "len": blob.len() })),
+        StorageKey::BidBorsh => to_json::<Bid>(bytes),
+        // Already JSON, so only check that it parses
+        StorageKey::BidJson => serde_json::from_slice::<Value>(bytes).ok(),
+        StorageKey::Owner
+        | StorageKey::PendingOwner
+        | StorageKey::Guardian
+        | StorageKey::SocialDb
+        | StorageKey::Benchmarker => to_json::<AccountId>(bytes),
+        StorageKey::Paused | StorageKey::StateInitialized => to_json::<bool>(bytes),
+        StorageKey::EmergencyDelay => to_json::<U64>(bytes),
+        StorageKey::Emergency => to_json::<Emergency>(bytes),
+        StorageKey::StateVersion => to_json::<u32>(bytes),
+        StorageKey::KeyIndex => to_json::<u32>(bytes),
+    }
+}
+
+fn to_json<T: borsh::BorshDeserialize + near_sdk::serde::Serialize>(bytes: &[u8]) -> Option<Value> {
+    borsh::from_slice::<T>(bytes)
+        .ok()
+        .and_then(|value| serde_json::to_value(value).ok())
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    use super::*;
+    use crate::auction_state::{self, ClaimStatus};
+    use crate::test_utils::new_contract;
+
+    #[test]
+    fn owner_can_patch_raw_keys() {
+        let mut contract = new_contract();
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id("bob.near".parse().unwrap())
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .build());
+
+        let key = Base64VecU8::from(StorageKey::AuctionConfig.to_bytes());
+        let mut config = auction_state::config();
+        assert_eq!(
+            contract.get_raw(key.clone()),
+            Some(borsh::to_vec(&config).unwrap().into())
+        );
+
+        config.claim = ClaimStatus::Claimed;
+        contract.set_raw(key.clone(), borsh::to_vec(&config).unwrap().into());
+        assert!(contract.get_claimed());
+        assert!(contract.has_key(key));
+        assert!(!contract.has_key(b"missing".to_vec().into()));
+    }
+
+    #[test]
+    fn patch_state_writes_every_entry() {
+        let mut contract = new_contract();
+        let config = AuctionConfig {
+            auction_end_time: 5,
+            auctioneer: "bob.near".parse().unwrap(),
+            claim: ClaimStatus::Claimed,
+        };
+        contract.patch_state(vec![
+            (
+                StorageKey::AuctionConfig.to_bytes().into(),
+                borsh::to_vec(&config).unwrap().into(),
+            ),
+            (
+                StorageKey::BidCount.to_bytes().into(),
+                borsh::to_vec(&U64(3)).unwrap().into(),
+            ),
+        ]);
+        assert!(contract.get_claimed());
+        assert_eq!(contract.get_auction_end_time(), U64(5));
+        assert_eq!(contract.get_bid_count(), U64(3));
+    }
+
+    #[test]
+    fn patch_state_drops_ttls() {
+        let mut contract = new_contract();
+        testing_env!(VMContextBuilder::new().block_timestamp(100).build());
+        ttl::write_with_ttl(b"key", b"a", 200);
+        contract.patch_state(vec![(b"key".to_vec().into(), b"b".to_vec().into())]);
+
+        testing_env!(VMContextBuilder::new().block_timestamp(300).build());
+        assert_eq!(
+            contract.get_raw(b"key".to_vec().into()),
+            Some(b"b".to_vec().into())
+        );
+        // No expiry record is left for `purge_expired` to find
+        assert_eq!(contract.purge_expired(10), 0);
+    }
+
+    #[test]
+    fn list_keys_pages_through_the_index() {
+        new_contract();
+        let contract = Contract {};
+        let all = contract.list_keys(0, 100);
+        // `init` writes neither the index itself nor the keys that only exist
+        // while something is pending or configured
+        let unset = [
+            StorageKey::KeyIndex,
+            StorageKey::PendingOwner,
+            StorageKey::Emergency,
+            StorageKey::RefundsInFlight,
+            StorageKey::StorageAvailable,
+            StorageKey::ClaimApprovers,
+            StorageKey::ClaimApprovals,
+            StorageKey::NftAuctionCount,
+            StorageKey::NftPruneCursor,
+            StorageKey::DutchAuctionCount,
+            StorageKey::UsdReserve,
+            StorageKey::PennyAuction,
+            StorageKey::PennyFees,
+            StorageKey::MinIncrement,
+            StorageKey::WnearContract,
+            StorageKey::UnsettledWnear,
+            StorageKey::StakingEscrow,
+            StorageKey::ForeignPayout,
+            StorageKey::SignRequest,
+            StorageKey::SocialDb,
+            StorageKey::Benchmarker,
+            StorageKey::ReceiptCount,
+        ];
+        assert_eq!(all.len(), StorageKey::ALL.len() - unset.len());
+        assert!(!all.contains(&StorageKey::PendingOwner.to_bytes().into()));
+        assert!(all.contains(&StorageKey::HighestBid.to_bytes().into()));
+        assert_eq!(contract.list_keys(2, 3), all[2..5].to_vec());
+    }
+
+    #[test]
+    fn verify_state_reports_corrupted_keys() {
+        let contract = new_contract();
+        assert!(contract.verify_state().is_empty());
+
+        let key = StorageKey::HighestBid.to_bytes();
+        let bytes = env::storage_read(&key).unwrap();
+        env::storage_write(&key, &bytes[..bytes.len() - 1]);
+
+        let failures = contract.verify_state();
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].key, key.into());
+        assert_eq!(failures[0].reason, "checksum mismatch");
+    }
+
+    #[test]
+    fn dump_state_decodes_known_keys() {
+        let contract = new_contract();
+        let dump = contract.dump_state();
+        assert_eq!(dump.len(), StorageKey::ALL.len());
+
+        let config = dump.iter().find(|e| e.name == "auction_config").unwrap();
+        assert_eq!(
+            config.decoded,
+            Some(json!({
+                "auction_end_time": "1000",
+                "auctioneer": "auctioneer.near",
+                "claim": "Open",
+            }))
+        );
+        let sdk_vector = dump.iter().find(|e| e.name == "sdk_vector").unwrap();
+        assert_eq!(sdk_vector.decoded, Some(json!({ "len": 0 })));
+    }
+
+    #[test]
+    fn key_sizes_include_key_and_record_overhead() {
+        let mut contract = new_contract();
+        contract.clear_vector();
+        let sizes = contract.get_key_sizes();
+
+        assert!(!sizes.iter().any(|(name, _)| name == "vector"));
+        let (_, paused) = sizes.iter().find(|(name, _)| name == "paused").unwrap();
+        let key_len = StorageKey::Paused.to_bytes().len() as u64;
+        let value_len = crate::envelope::seal(&[0]).len() as u64;
+        assert_eq!(*paused, value_len + key_len + STORAGE_RECORD_OVERHEAD);
+    }
+
+    #[test]
+    fn storage_usage_adds_up() {
+        let mut contract = new_contract();
+        let before = contract.get_storage_usage();
+        contract.write_blob(1);
+        let after = contract.get_storage_usage();
+
+        assert_eq!(after.total.0, env::storage_usage());
+        assert_eq!(
+            after.total.0,
+            after.auction.0 + after.benchmark.0 + after.metadata.0 + after.other.0
+        );
+        assert!(after.benchmark.0 >= before.benchmark.0 + 1024);
+        assert_eq!(after.auction, before.auction);
+        assert_eq!(after.other, before.other);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER")]
+    fn others_cannot_write_raw_keys() {
+        let auctioneer: AccountId = "auctioneer.near".parse().unwrap();
+        let mut contract = new_contract();
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(auctioneer)
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .build());
+        contract.set_raw(b"claimed".to_vec().into(), vec![1].into());
+    }
+}
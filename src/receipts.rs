@@ -0,0 +1,164 @@
+//! Receipts for accepted bids. Every bid mints one with the next token id,
+//! stored per bidder under `receipt/<account>/<n>` next to a per-bidder
+//! count, so a bidder can prove they took part even after being outbid.
+
+use near_sdk::json_types::U64;
+use near_sdk::{near, AccountId, NearToken};
+
+use crate::storage::{key, StorageCell, StorageKey};
+use crate::storage_management::charge_deposit;
+use crate::{Bid, Contract, ContractExt};
+
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct BidReceipt {
+    pub token_id: U64,
+    pub owner_id: AccountId,
+    /// The NFT auction the bid was placed in, `None` for the main auction.
+    pub nft_auction_id: Option<u32>,
+    pub amount: NearToken,
+    pub bid_time: U64,
+}
+
+fn count_key(account_id: &AccountId) -> Vec<u8> {
+    key!("receipt", account_id, "count")
+}
+
+fn receipt_key(account_id: &AccountId, n: u32) -> Vec<u8> {
+    key!("receipt", account_id, n)
+}
+
+fn receipt_count(account_id: &AccountId) -> u32 {
+    StorageCell::<u32>::new(count_key(account_id))
+        .try_get()
+        .copied()
+        .unwrap_or(0)
+}
+
+/// Most bytes one `mint` adds: the receipt and, on a bidder's first bid,
+/// their count, with room for the longest account id, plus two key index
+/// records for each. The very first mint also creates the total, which
+/// makes 1123 bytes at worst.
+pub const MINT_BYTES: u64 = 1200;
+
+/// Mints a receipt for `bid`, paying for its storage out of `prepaid` or,
+/// when that is zero, the bidder's NEP-145 deposit. Returns what is left of
+/// `prepaid`.
+pub fn mint(bid: &Bid, nft_auction_id: Option<u32>, prepaid: NearToken) -> NearToken {
+    let owner_id = bid.bidder.clone();
+    let ((), unused) = charge_deposit(&owner_id.clone(), prepaid, || {
+        let mut total = StorageCell::<U64>::new(StorageKey::ReceiptCount);
+        let token_id = total.try_get().copied().unwrap_or(U64(0));
+        total.set(U64(token_id.0 + 1));
+
+        let mut count = StorageCell::<u32>::new(count_key(&owner_id));
+        let n = count.try_get().copied().unwrap_or(0);
+        count.set(n + 1);
+
+        StorageCell::new(receipt_key(&owner_id, n)).set(BidReceipt {
+            token_id,
+            owner_id,
+            nft_auction_id,
+            amount: bid.bid,
+            bid_time: bid.bid_time,
+        });
+    });
+    unused
+}
+
+#[near]
+impl Contract {
+    /// Number of receipts minted so far across all bidders.
+    pub fn get_total_receipts(&self) -> U64 {
+        StorageCell::<U64>::new(StorageKey::ReceiptCount)
+            .try_get()
+            .copied()
+            .unwrap_or(U64(0))
+    }
+
+    pub fn get_receipt_count(&self, account_id: AccountId) -> u32 {
+        receipt_count(&account_id)
+    }
+
+    /// Up to `limit` of `account_id`'s receipts, oldest first, skipping the
+    /// first `from`.
+    pub fn get_receipts(&self, account_id: AccountId, from: u32, limit: u32) -> Vec<BidReceipt> {
+        let count = receipt_count(&account_id);
+        (from..from.saturating_add(limit).min(count))
+            .filter_map(|n| {
+                StorageCell::<BidReceipt>::new(receipt_key(&account_id, n))
+                    .try_get()
+                    .cloned()
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::{env, testing_env};
+
+    use super::*;
+    use crate::test_utils::new_contract;
+
+    fn bid_as(account_id: &str, near: u128) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account_id.parse().unwrap())
+            .attached_deposit(NearToken::from_near(near))
+            .build());
+    }
+
+    #[test]
+    fn every_accepted_bid_mints_a_receipt() {
+        let mut contract = new_contract();
+        let bob: AccountId = "bob.near".parse().unwrap();
+        bid_as("bob.near", 1);
+        let _ = contract.bid();
+        bid_as("carol.near", 2);
+        let _ = contract.bid();
+        bid_as("bob.near", 3);
+        let _ = contract.bid();
+
+        assert_eq!(contract.get_total_receipts(), U64(3));
+        assert_eq!(contract.get_receipt_count(bob.clone()), 2);
+        let receipts = contract.get_receipts(bob.clone(), 0, 10);
+        assert_eq!(
+            receipts.iter().map(|r| r.token_id).collect::<Vec<_>>(),
+            vec![U64(0), U64(2)]
+        );
+        let reserved = crate::storage_management::storage_cost(MINT_BYTES);
+        assert_eq!(
+            receipts[1].amount,
+            NearToken::from_near(3).saturating_sub(reserved)
+        );
+        assert_eq!(contract.get_receipts(bob, 1, 10), receipts[1..].to_vec());
+    }
+
+    #[test]
+    fn mint_bytes_cover_a_first_receipt_for_the_longest_account_id() {
+        new_contract();
+        let mut bid = crate::highest_bid::load();
+        bid.bidder = "a".repeat(64).parse().unwrap();
+        bid.bid = NearToken::from_near(1);
+
+        let before = env::storage_usage();
+        let unused = mint(&bid, Some(u32::MAX), NearToken::from_near(1));
+        let added = env::storage_usage() - before;
+        assert!(added <= MINT_BYTES, "{added} bytes");
+        assert_eq!(
+            unused,
+            NearToken::from_near(1).saturating_sub(crate::storage_management::storage_cost(added))
+        );
+    }
+
+    #[test]
+    fn rejected_bids_mint_nothing() {
+        let mut contract = new_contract();
+        bid_as("bob.near", 2);
+        let _ = contract.bid();
+        assert!(crate::place_bid("carol.near".parse().unwrap(), NearToken::from_near(1)).is_err());
+        assert_eq!(contract.get_total_receipts(), U64(1));
+        assert_eq!(contract.get_receipt_count("carol.near".parse().unwrap()), 0);
+    }
+}
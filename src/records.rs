@@ -0,0 +1,160 @@
+//! The same record set stored two ways: as one borsh blob under a single key,
+//! and as one key per record. Filling, reading one record and updating one
+//! record are implemented for both so their gas can be compared directly.
+
+use near_sdk::json_types::U64;
+use near_sdk::near;
+
+#[cfg(any(test, not(feature = "minimal")))]
+use crate::{
+    pause,
+    profile::GasProfile,
+    storage::{self, StorageCell, StorageKey, WriteBackSession},
+    Contract, ContractExt,
+};
+
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct Record {
+    pub id: u32,
+    pub amount: U64,
+    pub label: String,
+}
+
+#[cfg(any(test, not(feature = "minimal")))]
+impl Record {
+    fn new(id: u32) -> Self {
+        Self {
+            id,
+            amount: U64(id.into()),
+            label: format!("record-{id}"),
+        }
+    }
+}
+
+#[cfg(any(test, not(feature = "minimal")))]
+fn entry(index: u32) -> StorageCell<Record> {
+    StorageCell::new(StorageKey::RecordEntries.entry_key(index))
+}
+
+#[cfg(any(test, not(feature = "minimal")))]
+#[near]
+impl Contract {
+    /// Appends records `start..start + count` to the blob. Every call reads
+    /// and rewrites the whole set.
+    pub fn fill_record_blob(&mut self, count: u32, start: u32) {
+        let _session = WriteBackSession::begin();
+        pause::assert_not_paused();
+        crate::benchmarker::only_benchmarker();
+        let mut profile = GasProfile::start("fill_record_blob");
+
+        {
+            let mut records = StorageCell::<Vec<Record>>::new(StorageKey::RecordBlob);
+            let records = records.get_mut_or_insert_with(Vec::new);
+            profile.checkpoint("read");
+
+            records.extend(crate::fill_range(start, count).map(Record::new));
+            profile.checkpoint("logic");
+        }
+
+        storage::flush();
+        profile.checkpoint("write");
+        profile.finish();
+    }
+
+    /// Writes records `start..start + count` under a key each and raises the
+    /// stored count to cover them. Every new key is also added to the key
+    /// index, which is part of what this layout costs.
+    pub fn fill_record_keys(&mut self, count: u32, start: u32) {
+        let _session = WriteBackSession::begin();
+        pause::assert_not_paused();
+        crate::benchmarker::only_benchmarker();
+        let mut profile = GasProfile::start("fill_record_keys");
+
+        {
+            let mut len = StorageCell::<u32>::new(StorageKey::RecordEntries);
+            let len = len.get_mut_or_insert_with(|| 0);
+            profile.checkpoint("read");
+
+            let ids = crate::fill_range(start, count);
+            *len = (*len).max(ids.end);
+            for id in ids {
+                entry(id).set(Record::new(id));
+            }
+            profile.checkpoint("logic");
+        }
+
+        storage::flush();
+        profile.checkpoint("write");
+        profile.finish();
+    }
+
+    /// Record `index` of the blob. Deserializes the whole set to find it.
+    pub fn read_record_blob(&self, index: u32) -> Option<Record> {
+        StorageCell::<Vec<Record>>::new(StorageKey::RecordBlob)
+            .try_get()
+            .and_then(|records| records.get(index as usize).cloned())
+    }
+
+    /// Record `index`, read from its own key.
+    pub fn read_record_keys(&self, index: u32) -> Option<Record> {
+        entry(index).try_get().cloned()
+    }
+
+    /// Sets the amount of record `index` in the blob, rewriting the whole set.
+    pub fn update_record_blob(&mut self, index: u32, amount: U64) {
+        let _session = WriteBackSession::begin();
+        crate::benchmarker::only_benchmarker();
+
+        let mut records = StorageCell::<Vec<Record>>::new(StorageKey::RecordBlob);
+        if records.try_get().is_none() {
+            return;
+        }
+        if let Some(record) = records.get_mut().get_mut(index as usize) {
+            record.amount = amount;
+        }
+    }
+
+    /// Sets the amount of record `index`, rewriting only its key.
+    pub fn update_record_keys(&mut self, index: u32, amount: U64) {
+        let _session = WriteBackSession::begin();
+        crate::benchmarker::only_benchmarker();
+
+        let mut record = entry(index);
+        if record.try_get().is_some() {
+            record.get_mut().amount = amount;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::new_contract;
+
+    #[test]
+    fn both_layouts_hold_the_same_records() {
+        let mut contract = new_contract();
+        contract.fill_record_blob(10, 0);
+        contract.fill_record_keys(10, 0);
+        contract.fill_record_blob(5, 10);
+        contract.fill_record_keys(5, 10);
+
+        for index in [0, 7, 14] {
+            assert_eq!(contract.read_record_blob(index), Some(Record::new(index)));
+            assert_eq!(contract.read_record_keys(index), Some(Record::new(index)));
+        }
+        assert_eq!(contract.read_record_blob(15), None);
+        assert_eq!(contract.read_record_keys(15), None);
+        assert_eq!(
+            *StorageCell::<u32>::new(StorageKey::RecordEntries).get(),
+            15
+        );
+
+        contract.update_record_blob(3, U64(99));
+        contract.update_record_keys(3, U64(99));
+        assert_eq!(contract.read_record_blob(3).unwrap().amount, U64(99));
+        assert_eq!(contract.read_record_keys(3).unwrap().amount, U64(99));
+        assert_eq!(contract.read_record_keys(4), Some(Record::new(4)));
+    }
+}
@@ -0,0 +1,226 @@
+//! Ledger of refunds that could not be delivered. Outbid bidders are paid
+//! back with a plain transfer; when that transfer fails the amount is
+//! recorded here instead of being lost, paid for out of the bidder's storage
+//! deposit when they have one. `retry_refund` pays it out again. Until
+//! `resolve_refund` settles a transfer its amount is kept as in flight, so a
+//! bounce that comes back before the callback is still accounted for.
+
+use near_sdk::{env, near, store, AccountId, Gas, NearToken, Promise};
+
+use crate::error::AuctionError;
+use crate::math;
+use crate::storage::{new_collection, StorageCell, StorageKey, WriteBackSession};
+use crate::storage_management::charge_storage;
+use crate::{Contract, ContractExt};
+
+/// Gas reserved for `resolve_refund`.
+const RESOLVE_REFUND_GAS: Gas = Gas::from_tgas(5);
+
+type Ledger = store::IterableMap<AccountId, NearToken>;
+
+/// Transfers `amount` back to `account_id`, recording it as owed if the
+/// transfer fails.
+pub fn refund(account_id: AccountId, amount: NearToken) -> Promise {
+    set_in_flight(math::add(in_flight(), amount).unwrap_or_else(|err| err.panic()));
+    Promise::new(account_id.clone()).transfer(amount).then(
+        Contract::ext(env::current_account_id())
+            .with_static_gas(RESOLVE_REFUND_GAS)
+            .resolve_refund(account_id, amount),
+    )
+}
+
+/// Everything the ledger owes, across all accounts.
+pub fn total_owed() -> NearToken {
+    StorageCell::<Ledger>::new(StorageKey::Refunds)
+        .try_get()
+        .map(|ledger| math::sum(ledger.values().copied()).unwrap_or_else(|err| err.panic()))
+        .unwrap_or(NearToken::from_yoctonear(0))
+}
+
+/// Refunds sent whose `resolve_refund` hasn't run yet, added up.
+pub fn in_flight() -> NearToken {
+    StorageCell::<NearToken>::new(StorageKey::RefundsInFlight)
+        .try_get()
+        .copied()
+        .unwrap_or(NearToken::from_yoctonear(0))
+}
+
+fn set_in_flight(total: NearToken) {
+    let mut cell = StorageCell::<NearToken>::new(StorageKey::RefundsInFlight);
+    if total.is_zero() {
+        cell.remove();
+    } else {
+        cell.set(total);
+    }
+}
+
+/// Adds `amount` to what the ledger owes `account_id`.
+fn record_owed(account_id: AccountId, amount: NearToken) {
+    let mut ledger = StorageCell::<Ledger>::new(StorageKey::Refunds);
+    let ledger = ledger.get_mut_or_insert_with(|| new_collection(StorageKey::Refunds));
+    let owed = ledger
+        .entry(account_id)
+        .or_insert(NearToken::from_yoctonear(0));
+    *owed = math::add(*owed, amount).unwrap_or_else(|err| err.panic());
+}
+
+#[near]
+impl Contract {
+    #[private]
+    pub fn resolve_refund(&mut self, account_id: AccountId, amount: NearToken) {
+        let _session = WriteBackSession::begin();
+        // Saturating, since refunds sent before the total was kept aren't in it
+        set_in_flight(in_flight().saturating_sub(amount));
+        if env::promise_result_checked(0, 0).is_ok() {
+            return;
+        }
+        env::log_str(&format!("Refund of {amount} to {account_id} failed"));
+        charge_storage(&account_id.clone(), || record_owed(account_id, amount));
+    }
+
+    /// Sends `account_id` everything failed refunds left owed to it. Anyone
+    /// can call this since the money only ever goes to `account_id`; if the
+    /// transfer fails again the amount is recorded again.
+    pub fn retry_refund(&mut self, account_id: AccountId) -> Promise {
+        let _session = WriteBackSession::begin();
+
+        let mut ledger = StorageCell::<Ledger>::new(StorageKey::Refunds);
+        let Some(amount) = ledger
+            .get_mut_or_insert_with(|| new_collection(StorageKey::Refunds))
+            .remove(&account_id)
+        else {
+            AuctionError::NothingOwed.panic();
+        };
+        env::log_str(&format!("Retrying refund of {amount} to {account_id}"));
+        refund(account_id, amount)
+    }
+
+    /// What the contract still owes `account_id` from failed refunds.
+    pub fn get_refund_owed(&self, account_id: AccountId) -> NearToken {
+        StorageCell::<Ledger>::new(StorageKey::Refunds)
+            .try_get()
+            .and_then(|ledger| ledger.get(&account_id).copied())
+            .unwrap_or(NearToken::from_yoctonear(0))
+    }
+
+    /// Up to `limit` pending refunds, skipping the first `from`.
+    pub fn get_all_refunds(&self, from: u32, limit: u32) -> Vec<(AccountId, NearToken)> {
+        let ledger = StorageCell::<Ledger>::new(StorageKey::Refunds);
+        let Some(ledger) = ledger.try_get() else {
+            return vec![];
+        };
+        ledger
+            .iter()
+            .skip(from as usize)
+            .take(limit as usize)
+            .map(|(account_id, amount)| (account_id.clone(), *amount))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::{test_vm_config, testing_env, PromiseResult, RuntimeFeesConfig};
+
+    use super::*;
+    use crate::test_utils::new_contract;
+
+    fn resolve_with(contract: &mut Contract, result: PromiseResult, account_id: &AccountId) {
+        testing_env!(
+            VMContextBuilder::new()
+                .predecessor_account_id(env::current_account_id())
+                .build(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![result],
+        );
+        contract.resolve_refund(account_id.clone(), NearToken::from_near(1));
+    }
+
+    #[test]
+    fn failed_refunds_add_up() {
+        let contract = new_contract();
+        let bob: AccountId = "bob.near".parse().unwrap();
+        let carol: AccountId = "carol.near".parse().unwrap();
+        assert_eq!(
+            contract.get_refund_owed(bob.clone()),
+            NearToken::from_near(0)
+        );
+
+        {
+            let _session = WriteBackSession::begin();
+            record_owed(bob.clone(), NearToken::from_near(1));
+            record_owed(carol.clone(), NearToken::from_near(2));
+            record_owed(bob.clone(), NearToken::from_near(3));
+        }
+
+        assert_eq!(
+            contract.get_refund_owed(bob.clone()),
+            NearToken::from_near(4)
+        );
+        assert_eq!(
+            contract.get_all_refunds(0, 10),
+            vec![
+                (bob, NearToken::from_near(4)),
+                (carol.clone(), NearToken::from_near(2))
+            ]
+        );
+        assert_eq!(
+            contract.get_all_refunds(1, 10),
+            vec![(carol, NearToken::from_near(2))]
+        );
+    }
+
+    #[test]
+    fn retrying_clears_the_debt() {
+        let mut contract = new_contract();
+        let bob: AccountId = "bob.near".parse().unwrap();
+        {
+            let _session = WriteBackSession::begin();
+            record_owed(bob.clone(), NearToken::from_near(1));
+        }
+
+        let _ = contract.retry_refund(bob.clone());
+        assert_eq!(
+            contract.get_refund_owed(bob.clone()),
+            NearToken::from_near(0)
+        );
+        assert!(contract.get_all_refunds(0, 10).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOTHING_OWED")]
+    fn retrying_without_a_debt_fails() {
+        let mut contract = new_contract();
+        let _ = contract.retry_refund("bob.near".parse().unwrap());
+    }
+
+    #[test]
+    fn refunds_stay_in_flight_until_resolved() {
+        let mut contract = new_contract();
+        let bob: AccountId = "bob.near".parse().unwrap();
+        let carol: AccountId = "carol.near".parse().unwrap();
+        let before = in_flight();
+
+        let _ = refund(bob.clone(), NearToken::from_near(1));
+        let _ = refund(carol.clone(), NearToken::from_near(1));
+        assert_eq!(
+            in_flight(),
+            math::add(before, NearToken::from_near(2)).unwrap()
+        );
+
+        resolve_with(&mut contract, PromiseResult::Successful(vec![]), &bob);
+        assert_eq!(
+            in_flight(),
+            math::add(before, NearToken::from_near(1)).unwrap()
+        );
+        assert_eq!(contract.get_refund_owed(bob), NearToken::from_near(0));
+
+        // a bounce moves the amount from in flight to owed, never dropping it
+        resolve_with(&mut contract, PromiseResult::Failed, &carol);
+        assert_eq!(in_flight(), before);
+        assert_eq!(contract.get_refund_owed(carol), NearToken::from_near(1));
+    }
+}
@@ -0,0 +1,114 @@
+//! Optional publishing of the auction result to SocialDB. When the owner
+//! names a SocialDB contract, a successful `claim` is followed by a `set`
+//! that writes the result under this contract's namespace, where social
+//! graph frontends can find it.
+
+use near_sdk::serde_json::{self, json};
+use near_sdk::{assert_one_yocto, env, near, AccountId, Gas, NearToken, Promise};
+
+use crate::owner::only_owner;
+use crate::storage::{StorageCell, StorageKey, WriteBackSession};
+use crate::{Bid, Contract, ContractExt};
+
+/// Attached to `set` to pay for the storage the result takes on SocialDB.
+const SET_DEPOSIT: NearToken = NearToken::from_millinear(10);
+/// Gas for `set` on the SocialDB contract.
+const SET_GAS: Gas = Gas::from_tgas(20);
+/// Gas `publish` needs from the method calling it to schedule the `set`.
+const PUBLISH_GAS: Gas = Gas::from_tgas(30);
+
+fn social_db() -> Option<AccountId> {
+    StorageCell::<AccountId>::new(StorageKey::SocialDb)
+        .try_get()
+        .cloned()
+}
+
+/// Gas to reserve for a later `publish`: none unless publishing is
+/// configured.
+pub fn publish_gas() -> Gas {
+    match social_db() {
+        Some(_) => PUBLISH_GAS,
+        None => Gas::from_gas(0),
+    }
+}
+
+/// The `set` that writes `winning_bid` to SocialDB, or `None` when
+/// publishing isn't configured.
+pub fn publish(winning_bid: &Bid) -> Option<Promise> {
+    let social_db = social_db()?;
+    let result = json!({
+        "winner": winning_bid.bidder,
+        "amount": winning_bid.bid,
+        "bid_time": winning_bid.bid_time,
+        "claimed_at": env::block_timestamp().to_string(),
+    });
+    let args = json!({
+        "data": {
+            env::current_account_id(): {
+                "auction": { "result": result.to_string() }
+            }
+        }
+    });
+    Some(Promise::new(social_db).function_call(
+        "set".to_string(),
+        serde_json::to_vec(&args).unwrap(),
+        SET_DEPOSIT,
+        SET_GAS,
+    ))
+}
+
+#[near]
+impl Contract {
+    /// Publishes the result to `social_db` (`social.near` on mainnet) after
+    /// the claim, or stops publishing with `None`.
+    #[payable]
+    pub fn set_social_db(&mut self, social_db: Option<AccountId>) {
+        let _session = WriteBackSession::begin();
+
+        assert_one_yocto();
+        only_owner();
+        let mut cell = StorageCell::<AccountId>::new(StorageKey::SocialDb);
+        match social_db {
+            Some(social_db) => cell.set(social_db),
+            None => cell.remove(),
+        }
+    }
+
+    pub fn get_social_db(&self) -> Option<AccountId> {
+        social_db()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    use super::*;
+    use crate::test_utils::new_contract;
+
+    #[test]
+    fn result_is_only_published_once_configured() {
+        let mut contract = new_contract();
+        let winning_bid = contract.get_highest_bid();
+        assert!(publish(&winning_bid).is_none());
+
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(contract.get_owner())
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .build());
+        contract.set_social_db(Some("social.near".parse().unwrap()));
+        assert!(publish(&winning_bid).is_some());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER")]
+    fn only_the_owner_configures_publishing() {
+        let mut contract = new_contract();
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id("auctioneer.near".parse().unwrap())
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .build());
+        contract.set_social_db(Some("social.near".parse().unwrap()));
+    }
+}
@@ -0,0 +1,319 @@
+//! Optional staking of the escrowed highest bid. While the mode is on, every
+//! accepted bid stakes what it adds on top of the previous one with the
+//! configured pool, so the pool always holds the current highest bid. Near
+//! the end it is unstaked, withdrawn once the pool releases it, and only
+//! then can the auction be claimed. Rewards stay with the contract.
+
+use near_sdk::json_types::U64;
+use near_sdk::{assert_one_yocto, env, near, require, AccountId, Gas, NearToken, Promise};
+
+use crate::auction_state;
+use crate::error::AuctionError;
+use crate::math;
+use crate::owner::only_owner;
+use crate::storage::{StorageCell, StorageKey, WriteBackSession};
+use crate::{Contract, ContractExt};
+
+/// Epochs a staking pool keeps unstaked funds locked.
+const NUM_EPOCHS_TO_UNLOCK: u64 = 4;
+/// How long before the end unstaking may start: four epochs of roughly
+/// twelve hours, so the funds unlock around when the auction ends.
+const UNSTAKE_LEAD: u64 = NUM_EPOCHS_TO_UNLOCK * 12 * 60 * 60 * 1_000_000_000;
+
+/// Gas for each call into the staking pool.
+const POOL_GAS: Gas = Gas::from_tgas(50);
+/// Gas reserved for the callbacks of those calls.
+const RESOLVE_GAS: Gas = Gas::from_tgas(5);
+
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Debug, PartialEq)]
+pub enum EscrowPhase {
+    /// Bids are staked as they come in.
+    Staking,
+    /// `unstake_all` went through in `epoch_height`.
+    Unstaking { epoch_height: U64 },
+    /// Everything is back in the contract's balance.
+    Withdrawn,
+}
+
+#[near(serializers = [json, borsh])]
+#[derive(Clone)]
+pub struct StakingEscrow {
+    pub pool: AccountId,
+    /// What the pool confirmed staking so far, without rewards.
+    pub staked: NearToken,
+    pub phase: EscrowPhase,
+}
+
+fn pool_call(pool: AccountId, method: &str, deposit: NearToken) -> Promise {
+    Promise::new(pool).function_call(method.to_string(), b"{}".to_vec(), deposit, POOL_GAS)
+}
+
+fn callback() -> ContractExt {
+    Contract::ext(env::current_account_id()).with_static_gas(RESOLVE_GAS)
+}
+
+/// Stakes `amount` of a new bid, if bids are being staked.
+pub fn stake_increment(amount: NearToken) {
+    let escrow = StorageCell::<StakingEscrow>::new(StorageKey::StakingEscrow);
+    let Some(escrow) = escrow.try_get() else {
+        return;
+    };
+    if escrow.phase != EscrowPhase::Staking || amount.is_zero() {
+        return;
+    }
+    pool_call(escrow.pool.clone(), "deposit_and_stake", amount)
+        .then(callback().resolve_stake(amount))
+        .detach();
+}
+
+/// Panics while the highest bid is still with the staking pool.
+pub fn assert_escrow_released() {
+    let escrow = StorageCell::<StakingEscrow>::new(StorageKey::StakingEscrow);
+    require!(
+        escrow
+            .try_get()
+            .is_none_or(|escrow| escrow.phase == EscrowPhase::Withdrawn),
+        AuctionError::StillStaked.as_str()
+    );
+}
+
+fn escrow_in(phase: fn(&EscrowPhase) -> bool, err: AuctionError) -> StorageCell<StakingEscrow> {
+    let escrow = StorageCell::<StakingEscrow>::new(StorageKey::StakingEscrow);
+    require!(
+        escrow.try_get().is_some_and(|escrow| phase(&escrow.phase)),
+        err.as_str()
+    );
+    escrow
+}
+
+#[near]
+impl Contract {
+    /// Stakes bids with `pool` from now on, or stops with `None`. Only
+    /// possible before the first bid, so the pool holds the whole highest
+    /// bid or none of it.
+    #[payable]
+    pub fn set_staking_pool(&mut self, pool: Option<AccountId>) {
+        let _session = WriteBackSession::begin();
+
+        assert_one_yocto();
+        only_owner();
+        require!(
+            StorageCell::<U64>::new(StorageKey::BidCount).get().0 == 0,
+            AuctionError::StakingAfterBids.as_str()
+        );
+
+        let mut escrow = StorageCell::<StakingEscrow>::new(StorageKey::StakingEscrow);
+        match pool {
+            Some(pool) => escrow.set(StakingEscrow {
+                pool,
+                staked: NearToken::from_yoctonear(0),
+                phase: EscrowPhase::Staking,
+            }),
+            None => escrow.remove(),
+        }
+    }
+
+    /// Starts unstaking the highest bid. Anyone can call it once the auction
+    /// is within the pool's unlock period of its end; bids placed after
+    /// that are kept in the contract.
+    pub fn unstake_escrow(&mut self) -> Promise {
+        let _session = WriteBackSession::begin();
+
+        let escrow = escrow_in(
+            |phase| *phase == EscrowPhase::Staking,
+            AuctionError::NotStaking,
+        );
+        require!(
+            env::block_timestamp().saturating_add(UNSTAKE_LEAD)
+                >= auction_state::config().auction_end_time,
+            AuctionError::TooEarlyToUnstake.as_str()
+        );
+
+        pool_call(
+            escrow.get().pool.clone(),
+            "unstake_all",
+            NearToken::from_yoctonear(0),
+        )
+        .then(callback().resolve_unstake())
+    }
+
+    /// Withdraws the unstaked bid once the pool has released it.
+    pub fn withdraw_escrow(&mut self) -> Promise {
+        let _session = WriteBackSession::begin();
+
+        let escrow = escrow_in(
+            |phase| matches!(phase, EscrowPhase::Unstaking { .. }),
+            AuctionError::NotUnstaking,
+        );
+        let EscrowPhase::Unstaking { epoch_height } = escrow.get().phase else {
+            unreachable!();
+        };
+        let release_epoch = epoch_height.0 + NUM_EPOCHS_TO_UNLOCK;
+        if env::epoch_height() < release_epoch {
+            AuctionError::StillUnbonding.panic_with(format!("epoch {release_epoch}"));
+        }
+
+        pool_call(
+            escrow.get().pool.clone(),
+            "withdraw_all",
+            NearToken::from_yoctonear(0),
+        )
+        .then(callback().resolve_withdraw())
+    }
+
+    /// A failed stake leaves the amount in the contract's balance, which is
+    /// just as good for paying out.
+    #[private]
+    pub fn resolve_stake(&mut self, amount: NearToken) {
+        if env::promise_result_checked(0, 0).is_err() {
+            env::log_str(&format!("Staking {amount} failed"));
+            return;
+        }
+        let _session = WriteBackSession::begin();
+        let mut escrow = StorageCell::<StakingEscrow>::new(StorageKey::StakingEscrow);
+        let escrow = escrow.get_mut();
+        escrow.staked = math::add(escrow.staked, amount).unwrap_or_else(|err| err.panic());
+    }
+
+    #[private]
+    pub fn resolve_unstake(&mut self) {
+        require!(
+            env::promise_result_checked(0, 0).is_ok(),
+            AuctionError::UnstakeFailed.as_str()
+        );
+        let _session = WriteBackSession::begin();
+        StorageCell::<StakingEscrow>::new(StorageKey::StakingEscrow)
+            .get_mut()
+            .phase = EscrowPhase::Unstaking {
+            epoch_height: U64(env::epoch_height()),
+        };
+    }
+
+    #[private]
+    pub fn resolve_withdraw(&mut self) {
+        require!(
+            env::promise_result_checked(0, 0).is_ok(),
+            AuctionError::WithdrawFailed.as_str()
+        );
+        let _session = WriteBackSession::begin();
+        StorageCell::<StakingEscrow>::new(StorageKey::StakingEscrow)
+            .get_mut()
+            .phase = EscrowPhase::Withdrawn;
+    }
+
+    pub fn get_staking_escrow(&self) -> Option<StakingEscrow> {
+        StorageCell::<StakingEscrow>::new(StorageKey::StakingEscrow)
+            .try_get()
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::{test_vm_config, testing_env, PromiseResult, RuntimeFeesConfig};
+
+    use super::*;
+    use crate::ClaimStatus;
+
+    fn call_at(account_id: &str, deposit: NearToken, block_timestamp: u64, epoch_height: u64) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account_id.parse().unwrap())
+            .attached_deposit(deposit)
+            .block_timestamp(block_timestamp)
+            .epoch_height(epoch_height)
+            .build());
+    }
+
+    /// A callback context in which the pool call succeeded.
+    fn resolve_at(block_timestamp: u64, epoch_height: u64) {
+        testing_env!(
+            VMContextBuilder::new()
+                .predecessor_account_id(env::current_account_id())
+                .block_timestamp(block_timestamp)
+                .epoch_height(epoch_height)
+                .build(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+    }
+
+    fn init_staking() -> Contract {
+        call_at("owner.near", NearToken::from_yoctonear(1), 0, 0);
+        let mut contract = Contract::init(
+            U64(UNSTAKE_LEAD * 2),
+            "auctioneer.near".parse().unwrap(),
+            None,
+            None,
+        );
+        contract.set_staking_pool(Some("pool.near".parse().unwrap()));
+        contract
+    }
+
+    #[test]
+    fn escrow_is_unstaked_and_withdrawn_before_the_claim() {
+        let mut contract = init_staking();
+        call_at("bob.near", NearToken::from_near(5), 10, 0);
+        let _ = contract.bid();
+
+        call_at("carol.near", NearToken::from_yoctonear(0), UNSTAKE_LEAD, 1);
+        let _ = contract.unstake_escrow();
+        resolve_at(UNSTAKE_LEAD, 1);
+        contract.resolve_unstake();
+        assert_eq!(
+            contract.get_staking_escrow().unwrap().phase,
+            EscrowPhase::Unstaking {
+                epoch_height: U64(1)
+            }
+        );
+
+        call_at(
+            "carol.near",
+            NearToken::from_yoctonear(0),
+            UNSTAKE_LEAD * 3,
+            5,
+        );
+        let _ = contract.withdraw_escrow();
+        resolve_at(UNSTAKE_LEAD * 3, 5);
+        contract.resolve_withdraw();
+
+        call_at(
+            "auctioneer.near",
+            NearToken::from_yoctonear(1),
+            UNSTAKE_LEAD * 3,
+            5,
+        );
+        let _ = contract.claim();
+        assert_eq!(contract.get_claim_status(), ClaimStatus::Pending);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_STILL_STAKED")]
+    fn claim_waits_for_the_escrow() {
+        let mut contract = init_staking();
+        call_at("bob.near", NearToken::from_near(5), 10, 0);
+        let _ = contract.bid();
+        call_at(
+            "auctioneer.near",
+            NearToken::from_yoctonear(1),
+            UNSTAKE_LEAD * 3,
+            5,
+        );
+        let _ = contract.claim();
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_STILL_UNBONDING")]
+    fn withdrawal_waits_for_the_unlock() {
+        let mut contract = init_staking();
+        call_at("carol.near", NearToken::from_yoctonear(0), UNSTAKE_LEAD, 1);
+        let _ = contract.unstake_escrow();
+        resolve_at(UNSTAKE_LEAD, 1);
+        contract.resolve_unstake();
+        call_at("carol.near", NearToken::from_yoctonear(0), UNSTAKE_LEAD, 4);
+        let _ = contract.withdraw_escrow();
+    }
+}
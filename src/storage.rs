@@ -0,0 +1,1111 @@
+use std::any::Any;
+use std::cell::{OnceCell, RefCell};
+use std::collections::BTreeMap;
+use std::rc::Rc;
+
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::{env, store, AccountId, IntoStorageKey};
+
+use crate::envelope::{self, IntegrityError};
+use crate::error::AuctionError;
+#[cfg(feature = "unsafe-lowlevel")]
+use crate::lowlevel::storage_read;
+use crate::ttl;
+#[cfg(not(feature = "unsafe-lowlevel"))]
+use near_sdk::env::storage_read;
+
+/// Builds a storage key from a namespace followed by any number of segments:
+/// `key!("auction", id, "highest_bid")`. Every segment is length-prefixed, so
+/// `("ab", "c")` and `("a", "bc")` never produce the same bytes.
+macro_rules! key {
+    ($namespace:expr $(, $segment:expr)* $(,)?) => {
+        $crate::storage::KeyBuilder::new($namespace)
+            $(.push($segment))*
+            .build()
+    };
+}
+pub(crate) use key;
+
+/// Anything that can be used as one segment of a composed key.
+pub trait KeySegment {
+    fn segment_bytes(&self) -> Vec<u8>;
+}
+
+impl KeySegment for str {
+    fn segment_bytes(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+}
+
+impl KeySegment for String {
+    fn segment_bytes(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+}
+
+impl KeySegment for AccountId {
+    fn segment_bytes(&self) -> Vec<u8> {
+        self.as_bytes().to_vec()
+    }
+}
+
+impl KeySegment for [u8] {
+    fn segment_bytes(&self) -> Vec<u8> {
+        self.to_vec()
+    }
+}
+
+impl KeySegment for u32 {
+    fn segment_bytes(&self) -> Vec<u8> {
+        self.to_le_bytes().to_vec()
+    }
+}
+
+impl KeySegment for u64 {
+    fn segment_bytes(&self) -> Vec<u8> {
+        self.to_le_bytes().to_vec()
+    }
+}
+
+impl<T: KeySegment + ?Sized> KeySegment for &T {
+    fn segment_bytes(&self) -> Vec<u8> {
+        (**self).segment_bytes()
+    }
+}
+
+pub struct KeyBuilder(Vec<u8>);
+
+impl KeyBuilder {
+    pub fn new(namespace: impl KeySegment) -> Self {
+        Self(vec![]).push(namespace)
+    }
+
+    pub fn push(mut self, segment: impl KeySegment) -> Self {
+        let bytes = segment.segment_bytes();
+        let len =
+            u8::try_from(bytes.len()).unwrap_or_else(|_| AuctionError::KeySegmentTooLong.panic());
+        self.0.push(len);
+        self.0.extend_from_slice(&bytes);
+        self
+    }
+
+    pub fn build(self) -> Vec<u8> {
+        self.0
+    }
+}
+
+/// Every top-level key written by the contract. Reads and writes go through
+/// this enum so the whole key layout can be audited in one place.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StorageKey {
+    HighestBid,
+    HighestBidMetadata,
+    AuctionConfig,
+    BidCount,
+    TotalVolume,
+    Refunds,
+    RefundsInFlight,
+    StorageBalances,
+    StorageAvailable,
+    ClaimApprovers,
+    ClaimApprovals,
+    NftAuctionCount,
+    NftPruneCursor,
+    DutchAuctionCount,
+    UsdReserve,
+    PennyAuction,
+    PennyFees,
+    MinIncrement,
+    WnearContract,
+    UnsettledWnear,
+    StakingEscrow,
+    ForeignPayout,
+    SignRequest,
+    ReceiptCount,
+    Vector,
+    SdkVector,
+    SdkIterableMap,
+    SdkLookupMap,
+    SdkTreeMap,
+    SdkLookupSet,
+    SdkIterableSet,
+    SdkNestedMap,
+    BigValue,
+    RecordBlob,
+    RecordEntries,
+    Blob,
+    BidBorsh,
+    BidJson,
+    Owner,
+    PendingOwner,
+    Paused,
+    Guardian,
+    SocialDb,
+    Benchmarker,
+    EmergencyDelay,
+    Emergency,
+    StateInitialized,
+    StateVersion,
+    KeyIndex,
+}
+
+impl StorageKey {
+    pub const ALL: [StorageKey; 49] = [
+        StorageKey::HighestBid,
+        StorageKey::HighestBidMetadata,
+        StorageKey::AuctionConfig,
+        StorageKey::BidCount,
+        StorageKey::TotalVolume,
+        StorageKey::Refunds,
+        StorageKey::RefundsInFlight,
+        StorageKey::StorageBalances,
+        StorageKey::StorageAvailable,
+        StorageKey::ClaimApprovers,
+        StorageKey::ClaimApprovals,
+        StorageKey::NftAuctionCount,
+        StorageKey::NftPruneCursor,
+        StorageKey::DutchAuctionCount,
+        StorageKey::UsdReserve,
+        StorageKey::PennyAuction,
+        StorageKey::PennyFees,
+        StorageKey::MinIncrement,
+        StorageKey::WnearContract,
+        StorageKey::UnsettledWnear,
+        StorageKey::StakingEscrow,
+        StorageKey::ForeignPayout,
+        StorageKey::SignRequest,
+        StorageKey::ReceiptCount,
+        StorageKey::Vector,
+        StorageKey::SdkVector,
+        StorageKey::SdkIterableMap,
+        StorageKey::SdkLookupMap,
+        StorageKey::SdkTreeMap,
+        StorageKey::SdkLookupSet,
+        StorageKey::SdkIterableSet,
+        StorageKey::SdkNestedMap,
+        StorageKey::BigValue,
+        StorageKey::RecordBlob,
+        StorageKey::RecordEntries,
+        StorageKey::Blob,
+        StorageKey::BidBorsh,
+        StorageKey::BidJson,
+        StorageKey::Owner,
+        StorageKey::PendingOwner,
+        StorageKey::Paused,
+        StorageKey::Guardian,
+        StorageKey::SocialDb,
+        StorageKey::Benchmarker,
+        StorageKey::EmergencyDelay,
+        StorageKey::Emergency,
+        StorageKey::StateInitialized,
+        StorageKey::StateVersion,
+        StorageKey::KeyIndex,
+    ];
+
+    pub const fn name(self) -> &'static str {
+        match self {
+            StorageKey::HighestBid => "highest_bid",
+            StorageKey::HighestBidMetadata => "highest_bid_metadata",
+            StorageKey::AuctionConfig => "auction_config",
+            StorageKey::BidCount => "bid_count",
+            StorageKey::TotalVolume => "total_volume",
+            StorageKey::Refunds => "refunds",
+            StorageKey::RefundsInFlight => "refunds_in_flight",
+            StorageKey::StorageBalances => "storage_balances",
+            StorageKey::StorageAvailable => "storage_available",
+            StorageKey::ClaimApprovers => "claim_approvers",
+            StorageKey::ClaimApprovals => "claim_approvals",
+            StorageKey::NftAuctionCount => "nft_auction_count",
+            StorageKey::NftPruneCursor => "nft_prune_cursor",
+            StorageKey::DutchAuctionCount => "dutch_auction_count",
+            StorageKey::UsdReserve => "usd_reserve",
+            StorageKey::PennyAuction => "penny_auction",
+            StorageKey::PennyFees => "penny_fees",
+            StorageKey::MinIncrement => "min_increment",
+            StorageKey::WnearContract => "wnear_contract",
+            StorageKey::UnsettledWnear => "unsettled_wnear",
+            StorageKey::StakingEscrow => "staking_escrow",
+            StorageKey::ForeignPayout => "foreign_payout",
+            StorageKey::SignRequest => "sign_request",
+            StorageKey::ReceiptCount => "receipt_count",
+            StorageKey::Vector => "vector",
+            StorageKey::SdkVector => "sdk_vector",
+            StorageKey::SdkIterableMap => "sdk_iterable_map",
+            StorageKey::SdkLookupMap => "sdk_lookup_map",
+            StorageKey::SdkTreeMap => "sdk_tree_map",
+            StorageKey::SdkLookupSet => "sdk_lookup_set",
+            StorageKey::SdkIterableSet => "sdk_iterable_set",
+            StorageKey::SdkNestedMap => "sdk_nested_map",
+            StorageKey::BigValue => "big_value",
+            StorageKey::RecordBlob => "record_blob",
+            StorageKey::RecordEntries => "record_entries",
+            StorageKey::Blob => "blob",
+            StorageKey::BidBorsh => "bid_borsh",
+            StorageKey::BidJson => "bid_json",
+            StorageKey::Owner => "owner",
+            StorageKey::PendingOwner => "pending_owner",
+            StorageKey::Paused => "paused",
+            StorageKey::Guardian => "guardian",
+            StorageKey::SocialDb => "social_db",
+            StorageKey::Benchmarker => "benchmarker",
+            StorageKey::EmergencyDelay => "emergency_delay",
+            StorageKey::Emergency => "emergency",
+            StorageKey::StateInitialized => "state_initialized",
+            StorageKey::StateVersion => "state_version",
+            StorageKey::KeyIndex => "key_index",
+        }
+    }
+
+    pub fn to_bytes(self) -> Vec<u8> {
+        match self {
+            StorageKey::HighestBid => key!("auction", "highest_bid"),
+            StorageKey::HighestBidMetadata => key!("auction", "highest_bid_metadata"),
+            StorageKey::AuctionConfig => key!("auction", "config"),
+            StorageKey::BidCount => key!("auction", "bid_count"),
+            StorageKey::TotalVolume => key!("auction", "total_volume"),
+            StorageKey::Refunds => key!("coll", "header", "refunds"),
+            StorageKey::RefundsInFlight => key!("auction", "refunds_in_flight"),
+            StorageKey::StorageBalances => key!("coll", "header", "storage_balances"),
+            StorageKey::StorageAvailable => key!("meta", "storage_available"),
+            StorageKey::ClaimApprovers => key!("auction", "claim_approvers"),
+            StorageKey::ClaimApprovals => key!("auction", "claim_approvals"),
+            StorageKey::NftAuctionCount => key!("auction", "nft_auction_count"),
+            StorageKey::NftPruneCursor => key!("auction", "nft_prune_cursor"),
+            StorageKey::DutchAuctionCount => key!("auction", "dutch_auction_count"),
+            StorageKey::UsdReserve => key!("auction", "usd_reserve"),
+            StorageKey::PennyAuction => key!("auction", "penny_auction"),
+            StorageKey::PennyFees => key!("auction", "penny_fees"),
+            StorageKey::MinIncrement => key!("auction", "min_increment"),
+            StorageKey::WnearContract => key!("auction", "wnear_contract"),
+            StorageKey::UnsettledWnear => key!("auction", "unsettled_wnear"),
+            StorageKey::StakingEscrow => key!("auction", "staking_escrow"),
+            StorageKey::ForeignPayout => key!("auction", "foreign_payout"),
+            StorageKey::SignRequest => key!("auction", "sign_request"),
+            StorageKey::ReceiptCount => key!("auction", "receipt_count"),
+            StorageKey::Vector => key!("bench", "vector", "len"),
+            StorageKey::SdkVector => key!("coll", "header", "sdk_vector"),
+            StorageKey::SdkIterableMap => key!("coll", "header", "sdk_iterable_map"),
+            StorageKey::SdkLookupMap => key!("coll", "header", "sdk_lookup_map"),
+            StorageKey::SdkTreeMap => key!("coll", "header", "sdk_tree_map"),
+            StorageKey::SdkLookupSet => key!("coll", "header", "sdk_lookup_set"),
+            StorageKey::SdkIterableSet => key!("coll", "header", "sdk_iterable_set"),
+            StorageKey::SdkNestedMap => key!("coll", "header", "sdk_nested_map"),
+            StorageKey::BigValue => key!("bench", "big_value"),
+            StorageKey::RecordBlob => key!("bench", "record_blob"),
+            StorageKey::RecordEntries => key!("bench", "record_entries", "len"),
+            StorageKey::Blob => key!("bench", "blob"),
+            StorageKey::BidBorsh => key!("bench", "bid", "borsh"),
+            StorageKey::BidJson => key!("bench", "bid", "json"),
+            StorageKey::Owner => key!("meta", "owner"),
+            StorageKey::PendingOwner => key!("meta", "pending_owner"),
+            StorageKey::Paused => key!("meta", "paused"),
+            StorageKey::Guardian => key!("meta", "guardian"),
+            StorageKey::SocialDb => key!("meta", "social_db"),
+            StorageKey::Benchmarker => key!("meta", "benchmarker"),
+            StorageKey::EmergencyDelay => key!("meta", "emergency_delay"),
+            StorageKey::Emergency => key!("meta", "emergency"),
+            StorageKey::StateInitialized => key!("meta", "state_initialized"),
+            StorageKey::StateVersion => key!("meta", "state_version"),
+            StorageKey::KeyIndex => key!("meta", "__keys"),
+        }
+    }
+}
+
+impl StorageKey {
+    /// Prefix for the elements of the SDK collection whose header is stored
+    /// under this key. Headers and elements live in sibling `coll` namespaces,
+    /// so neither is ever a prefix of the other.
+    pub fn items_prefix(self) -> Vec<u8> {
+        key!("coll", "items", self.name())
+    }
+
+    /// Element prefix for the inner collection stored under `outer` in the
+    /// nested collection whose header is under this key. Every inner
+    /// collection needs its own prefix, or they would all share elements.
+    #[cfg(any(test, not(feature = "minimal")))]
+    pub fn nested_prefix(self, outer: u32) -> Vec<u8> {
+        key!("coll", "nested", self.name(), outer)
+    }
+
+    /// Key of entry `n` of the per-key record set whose count is stored
+    /// under this key.
+    #[cfg(any(test, not(feature = "minimal")))]
+    pub fn entry_key(self, n: u32) -> Vec<u8> {
+        key!("bench", self.name(), "entry", n)
+    }
+
+    /// Key of chunk `n` of the chunked vector whose length is stored under
+    /// this key.
+    pub fn chunk_key(self, n: u32) -> Vec<u8> {
+        key!("bench", self.name(), "chunk", n)
+    }
+}
+
+impl IntoStorageKey for StorageKey {
+    fn into_storage_key(self) -> Vec<u8> {
+        self.to_bytes()
+    }
+}
+
+/// SDK collections that can be created from a bare element prefix.
+pub trait Collection: BorshSerialize + BorshDeserialize {
+    fn with_prefix(prefix: Vec<u8>) -> Self;
+}
+
+impl<T: BorshSerialize + BorshDeserialize> Collection for store::Vector<T> {
+    fn with_prefix(prefix: Vec<u8>) -> Self {
+        store::Vector::new(prefix)
+    }
+}
+
+impl<K, V> Collection for store::IterableMap<K, V>
+where
+    K: BorshSerialize + BorshDeserialize + Ord,
+    V: BorshSerialize + BorshDeserialize,
+{
+    fn with_prefix(prefix: Vec<u8>) -> Self {
+        store::IterableMap::new(prefix)
+    }
+}
+
+impl<K, V> Collection for store::LookupMap<K, V>
+where
+    K: BorshSerialize + Ord,
+    V: BorshSerialize + BorshDeserialize,
+{
+    fn with_prefix(prefix: Vec<u8>) -> Self {
+        store::LookupMap::new(prefix)
+    }
+}
+
+impl<K, V> Collection for store::TreeMap<K, V>
+where
+    K: BorshSerialize + BorshDeserialize + Ord,
+    V: BorshSerialize + BorshDeserialize,
+{
+    fn with_prefix(prefix: Vec<u8>) -> Self {
+        store::TreeMap::new(prefix)
+    }
+}
+
+impl<T: BorshSerialize + BorshDeserialize> Collection for store::LookupSet<T> {
+    fn with_prefix(prefix: Vec<u8>) -> Self {
+        store::LookupSet::new(prefix)
+    }
+}
+
+impl<T: BorshSerialize + BorshDeserialize + Ord> Collection for store::IterableSet<T> {
+    fn with_prefix(prefix: Vec<u8>) -> Self {
+        store::IterableSet::new(prefix)
+    }
+}
+
+/// Creates an empty collection whose header belongs under `key`, using the
+/// element prefix reserved for it.
+pub fn new_collection<C: Collection>(key: StorageKey) -> C {
+    C::with_prefix(key.items_prefix())
+}
+
+/// A key touched during the current write-back session. Values are kept
+/// sealed, exactly as they are or will be stored.
+struct CachedEntry {
+    /// Bytes found in storage when the key was first read, or `None` if the
+    /// key was written without being read first.
+    original: Option<Option<Vec<u8>>>,
+    current: Option<Vec<u8>>,
+    /// `current` as deserialized by `load`, dropped whenever it changes.
+    decoded: Option<Rc<dyn Any>>,
+}
+
+impl CachedEntry {
+    fn is_dirty(&self) -> bool {
+        self.original.as_ref() != Some(&self.current)
+    }
+}
+
+#[derive(Default)]
+struct WriteBackCache {
+    depth: u32,
+    entries: BTreeMap<Vec<u8>, CachedEntry>,
+}
+
+thread_local! {
+    static CACHE: RefCell<WriteBackCache> = RefCell::default();
+}
+
+/// Routes every `read`/`write`/`remove` through an in-memory cache until the
+/// session is dropped, then writes back only the keys whose bytes changed.
+/// Every `&mut self` method opens one first thing, so it is dropped after all
+/// the cells the method created.
+#[must_use]
+pub struct WriteBackSession(());
+
+impl WriteBackSession {
+    pub fn begin() -> Self {
+        CACHE.with_borrow_mut(|cache| cache.depth += 1);
+        Self(())
+    }
+}
+
+impl Drop for WriteBackSession {
+    fn drop(&mut self) {
+        let outermost = CACHE.with_borrow_mut(|cache| {
+            cache.depth -= 1;
+            cache.depth == 0
+        });
+        if outermost {
+            flush();
+            CACHE.with_borrow_mut(|cache| cache.entries.clear());
+        }
+    }
+}
+
+fn in_session() -> bool {
+    CACHE.with_borrow(|cache| cache.depth > 0)
+}
+
+/// Reads the physical value under `key`, envelope included, panicking if
+/// the envelope doesn't check out.
+fn read_enveloped(key: &[u8]) -> Option<Vec<u8>> {
+    let bytes = storage_read(key)?;
+    if let Err(err) = envelope::open(&bytes) {
+        AuctionError::IntegrityCheckFailed.panic_with(err);
+    }
+    Some(bytes)
+}
+
+/// Reads the physical value under `key` and strips its integrity envelope,
+/// panicking if the envelope doesn't check out.
+fn read_sealed(key: &[u8]) -> Option<Vec<u8>> {
+    read_enveloped(key).map(|bytes| envelope::payload(&bytes).to_vec())
+}
+
+/// Returns the unwrapped payload for values written through this module and
+/// the bytes as stored for anything else (SDK collection elements, `STATE`).
+pub fn peek(key: &[u8]) -> Option<Vec<u8>> {
+    env::storage_read(key).map(unwrap_stored)
+}
+
+/// `peek` for bytes already read from storage.
+pub fn unwrap_stored(bytes: Vec<u8>) -> Vec<u8> {
+    match envelope::open(&bytes) {
+        Ok(payload) => payload.to_vec(),
+        Err(_) => bytes,
+    }
+}
+
+/// Checks the envelope of the value stored under `key`, if any.
+pub fn verify(key: &[u8]) -> Result<(), IntegrityError> {
+    match env::storage_read(key) {
+        Some(bytes) => envelope::open(&bytes).map(|_| ()),
+        None => Ok(()),
+    }
+}
+
+/// Reads the value under `key`, which is absent once a TTL set with
+/// `ttl::write_with_ttl` has passed, even before `purge_expired` removes it.
+pub fn read(key: &[u8]) -> Option<Vec<u8>> {
+    let sealed = read_current(key)?;
+    if is_expired(key, &sealed) {
+        return None;
+    }
+    Some(envelope::payload(&sealed).to_vec())
+}
+
+/// Only values sealed as expiring have an expiry record to look up.
+fn is_expired(key: &[u8], sealed: &[u8]) -> bool {
+    envelope::is_expiring(sealed) && ttl::has_expired(key)
+}
+
+/// The sealed value under `key`, as the current session sees it.
+fn read_current(key: &[u8]) -> Option<Vec<u8>> {
+    if !in_session() {
+        return read_enveloped(key);
+    }
+    CACHE.with_borrow_mut(|cache| {
+        let entry = cache.entries.entry(key.to_vec()).or_insert_with(|| {
+            let value = read_enveloped(key);
+            CachedEntry {
+                original: Some(value.clone()),
+                current: value,
+                decoded: None,
+            }
+        });
+        entry.current.clone()
+    })
+}
+
+/// Whether the value under `key` was written with `write_expiring`.
+pub fn is_expiring(key: &[u8]) -> bool {
+    read_current(key).is_some_and(|sealed| envelope::is_expiring(&sealed))
+}
+
+/// Whether `read` would find a value under `key`.
+pub fn has_key(key: &[u8]) -> bool {
+    read_current(key).is_some_and(|sealed| !is_expired(key, &sealed))
+}
+
+pub fn write(key: &[u8], value: &[u8]) {
+    set_current(key, Some(envelope::seal(value)));
+}
+
+/// Writes `value` marked as having an expiry record, for
+/// `ttl::write_with_ttl`. A later `write` drops the mark.
+pub fn write_expiring(key: &[u8], value: &[u8]) {
+    set_current(key, Some(envelope::seal_expiring(value)));
+}
+
+pub fn remove(key: &[u8]) {
+    set_current(key, None);
+}
+
+fn set_current(key: &[u8], value: Option<Vec<u8>>) {
+    if !in_session() {
+        if !holds(key, &value) {
+            apply(vec![(key.to_vec(), value)]);
+        }
+        return;
+    }
+    CACHE.with_borrow_mut(|cache| {
+        let entry = cache.entries.entry(key.to_vec()).or_insert(CachedEntry {
+            original: None,
+            current: None,
+            decoded: None,
+        });
+        entry.current = value;
+        entry.decoded = None;
+    });
+}
+
+/// Pulls `keys` into the session cache in one pass, so the `load`s that
+/// follow don't touch storage.
+pub fn prefetch(keys: &[&[u8]]) {
+    for key in keys {
+        read(key);
+    }
+}
+
+/// Reads and deserializes the value under `key`. Within a session the value
+/// is kept next to its cached bytes, so however many helpers load a key
+/// during one call, it is read and deserialized once.
+pub fn try_load<T: BorshDeserialize + Clone + 'static>(key: &[u8]) -> Option<T> {
+    if !in_session() {
+        return read(key).map(|bytes| decode(key, Some(bytes)));
+    }
+    let cached = CACHE.with_borrow(|cache| {
+        let decoded = cache.entries.get(key)?.decoded.as_ref()?;
+        decoded.downcast_ref::<T>().cloned()
+    });
+    if cached.is_some() {
+        return cached;
+    }
+    let value: T = decode(key, Some(read(key)?));
+    CACHE.with_borrow_mut(|cache| {
+        if let Some(entry) = cache.entries.get_mut(key) {
+            entry.decoded = Some(Rc::new(value.clone()));
+        }
+    });
+    Some(value)
+}
+
+/// `try_load` for keys that must exist.
+pub fn load<T: BorshDeserialize + Clone + 'static>(key: &[u8]) -> T {
+    try_load(key).unwrap_or_else(|| panic_missing(key))
+}
+
+/// Reads and deserializes the value under `key`, which must exist. Unlike
+/// `load` the decoded value isn't kept in the session cache.
+pub fn read_key<T: BorshDeserialize>(key: &[u8]) -> T {
+    decode(key, read(key))
+}
+
+pub fn write_many(entries: &[(&[u8], Vec<u8>)]) {
+    for (key, value) in entries {
+        write(key, value);
+    }
+}
+
+/// Deserializes a value fetched with `read`, panicking with the name of
+/// `key` when it is missing or malformed.
+pub fn decode<T: BorshDeserialize>(key: &[u8], value: Option<Vec<u8>>) -> T {
+    let bytes = value.unwrap_or_else(|| panic_missing(key));
+    borsh::from_slice(&bytes).unwrap_or_else(|_| panic_undecodable(key))
+}
+
+pub fn panic_missing(key: &[u8]) -> ! {
+    AuctionError::MissingValue.panic_with(key_name(key))
+}
+
+pub fn panic_undecodable(key: &[u8]) -> ! {
+    AuctionError::UndecodableValue.panic_with(key_name(key))
+}
+
+/// The name `key` is registered under, or its bytes with anything
+/// unprintable escaped for chunk, TTL and raw keys.
+pub fn key_name(key: &[u8]) -> String {
+    StorageKey::ALL
+        .into_iter()
+        .find(|known| known.to_bytes() == key)
+        .map_or_else(
+            || key.escape_ascii().to_string(),
+            |known| known.name().to_string(),
+        )
+}
+
+/// Writes back every changed key of the current session without ending it,
+/// for methods that need to observe `env::storage_usage` mid-call.
+pub fn flush() {
+    let dirty: Vec<(Vec<u8>, Option<Vec<u8>>)> = CACHE.with_borrow_mut(|cache| {
+        let mut dirty = vec![];
+        for (key, entry) in cache
+            .entries
+            .iter_mut()
+            .filter(|(_, entry)| entry.is_dirty())
+        {
+            // A key written without being read first may already hold these
+            // bytes, such as a collection header that didn't change
+            let changed = entry.original.is_some() || !holds(key, &entry.current);
+            entry.original = Some(entry.current.clone());
+            if changed {
+                dirty.push((key.clone(), entry.current.clone()));
+            }
+        }
+        dirty
+    });
+    apply(dirty);
+}
+
+/// Whether storage already has `value` under `key`, or no value when it is
+/// `None`, so writing it would change nothing.
+fn holds(key: &[u8], value: &Option<Vec<u8>>) -> bool {
+    env::storage_read(key) == *value
+}
+
+/// Writes changes straight to storage and keeps the key index in sync. The
+/// index is only touched when a key is created or deleted.
+fn apply(changes: Vec<(Vec<u8>, Option<Vec<u8>>)>) {
+    for (key, value) in changes {
+        match value {
+            Some(value) => {
+                if !env::storage_write(&key, &value) {
+                    index_insert(&key);
+                }
+            }
+            None => {
+                if env::storage_remove(&key) {
+                    index_remove(&key);
+                }
+            }
+        }
+    }
+}
+
+/// Where the index lists the key at `position`.
+fn index_entry_key(position: u32) -> Vec<u8> {
+    key!("meta", "__keys", "entry", position)
+}
+
+/// Where the index records the position of `key`, which is hashed so long
+/// keys aren't stored twice.
+fn index_slot_key(key: &[u8]) -> Vec<u8> {
+    key!("meta", "__keys", "slot", env::sha256_array(key).as_slice())
+}
+
+/// The records the index keeps for `key` on top of its count: the entry
+/// listing it and the slot pointing back at that entry. None if unlisted.
+pub fn index_records(key: &[u8]) -> Vec<Vec<u8>> {
+    index_position(key).map_or_else(Vec::new, |position| {
+        vec![index_entry_key(position), index_slot_key(key)]
+    })
+}
+
+fn index_position(key: &[u8]) -> Option<u32> {
+    let slot = index_slot_key(key);
+    read_sealed(&slot).map(|bytes| decode(&slot, Some(bytes)))
+}
+
+fn write_sealed<T: BorshSerialize>(key: &[u8], value: &T) {
+    env::storage_write(key, &envelope::seal(&borsh::to_vec(value).unwrap()));
+}
+
+/// How many keys the index lists.
+pub fn indexed_key_count() -> u32 {
+    let index_key = StorageKey::KeyIndex.to_bytes();
+    read_sealed(&index_key)
+        .map(|bytes| decode(&index_key, Some(bytes)))
+        .unwrap_or_default()
+}
+
+/// Appends `key` to the index unless it is listed already. The index lives in
+/// one record per key, so this costs the same however many keys it lists.
+fn index_insert(key: &[u8]) {
+    if index_position(key).is_some() {
+        return;
+    }
+    let count = indexed_key_count();
+    write_sealed(&index_entry_key(count), &key.to_vec());
+    write_sealed(&index_slot_key(key), &count);
+    write_sealed(&StorageKey::KeyIndex.to_bytes(), &(count + 1));
+}
+
+/// Drops `key` from the index by moving the last entry into its place.
+fn index_remove(key: &[u8]) {
+    let Some(position) = index_position(key) else {
+        return;
+    };
+    let last = indexed_key_count() - 1;
+    if position != last {
+        let last_entry = index_entry_key(last);
+        let moved: Vec<u8> = decode(&last_entry, read_sealed(&last_entry));
+        write_sealed(&index_entry_key(position), &moved);
+        write_sealed(&index_slot_key(&moved), &position);
+    }
+    env::storage_remove(&index_entry_key(last));
+    env::storage_remove(&index_slot_key(key));
+    write_sealed(&StorageKey::KeyIndex.to_bytes(), &last);
+}
+
+/// Up to `limit` indexed keys starting at position `from`, in the index's own
+/// order, which changes as keys are removed.
+pub fn indexed_keys_page(from: u32, limit: u32) -> Vec<Vec<u8>> {
+    let end = indexed_key_count().min(from.saturating_add(limit));
+    (from..end)
+        .map(|position| {
+            let entry = index_entry_key(position);
+            decode(&entry, read_sealed(&entry))
+        })
+        .collect()
+}
+
+/// Every top-level key written through this module, sorted. NEAR contracts
+/// can't scan storage by prefix, so this index is the only way to enumerate
+/// the raw-key state on-chain. SDK collection elements are not included.
+/// Reads one record per key, so change methods shouldn't need it.
+pub fn indexed_keys() -> Vec<Vec<u8>> {
+    let mut keys = indexed_keys_page(0, u32::MAX);
+    keys.sort();
+    keys
+}
+
+/// Rebuilds the key index from the registered keys plus whatever it already
+/// lists, keeping only the keys that currently exist.
+pub fn rebuild_key_index() {
+    for key in indexed_keys() {
+        if !env::storage_has_key(&key) {
+            index_remove(&key);
+        }
+    }
+    for key in StorageKey::ALL
+        .into_iter()
+        .filter(|key| *key != StorageKey::KeyIndex)
+        .map(StorageKey::to_bytes)
+        .filter(|key| env::storage_has_key(key))
+    {
+        index_insert(&key);
+    }
+}
+
+/// Moves a v12 index, one sorted `Vec` of every key under `KeyIndex`, into
+/// one record per key. A count is four bytes long, and so is an empty `Vec`,
+/// which reads the same, so those are left as they are.
+pub fn split_legacy_key_index() {
+    let index_key = StorageKey::KeyIndex.to_bytes();
+    let Some(bytes) = read_sealed(&index_key).filter(|bytes| bytes.len() != 4) else {
+        return;
+    };
+    let keys: Vec<Vec<u8>> = decode(&index_key, Some(bytes));
+    env::storage_remove(&index_key);
+    for key in keys {
+        if env::storage_has_key(&key) {
+            index_insert(&key);
+        }
+    }
+}
+
+/// A single borsh value living under its own key. The value is read on first
+/// access, cached for the rest of the call and written back (on `flush` or
+/// drop) only if it was mutated.
+pub struct StorageCell<T: BorshSerialize + BorshDeserialize> {
+    key: Vec<u8>,
+    value: OnceCell<T>,
+    dirty: bool,
+}
+
+impl<T: BorshSerialize + BorshDeserialize> StorageCell<T> {
+    pub fn new(key: impl IntoStorageKey) -> Self {
+        Self {
+            key: key.into_storage_key(),
+            value: OnceCell::new(),
+            dirty: false,
+        }
+    }
+
+    pub fn get(&self) -> &T {
+        self.value.get_or_init(|| read_key(&self.key))
+    }
+
+    pub fn try_get(&self) -> Option<&T> {
+        if self.value.get().is_none() && !has_key(&self.key) {
+            return None;
+        }
+        Some(self.get())
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        self.get();
+        self.dirty = true;
+        self.value.get_mut().unwrap()
+    }
+
+    pub fn get_mut_or_insert_with(&mut self, f: impl FnOnce() -> T) -> &mut T {
+        if self.try_get().is_none() {
+            self.value = OnceCell::from(f());
+        }
+        self.dirty = true;
+        self.value.get_mut().unwrap()
+    }
+
+    pub fn set(&mut self, value: T) {
+        self.value = OnceCell::from(value);
+        self.dirty = true;
+    }
+
+    pub fn flush(&mut self) {
+        if let Some((key, value)) = self.take_change() {
+            write(&key, &value);
+        }
+    }
+
+    /// The key and serialized value to write if the cell was mutated since
+    /// it was last flushed, leaving it clean. For owners that batch the
+    /// writes of several cells.
+    pub fn take_change(&mut self) -> Option<(Vec<u8>, Vec<u8>)> {
+        if !std::mem::take(&mut self.dirty) {
+            return None;
+        }
+        let value = self.value.get()?;
+        Some((self.key.clone(), borsh::to_vec(value).unwrap()))
+    }
+
+    pub fn remove(&mut self) {
+        remove(&self.key);
+        self.value = OnceCell::new();
+        self.dirty = false;
+    }
+}
+
+impl<T: BorshSerialize + BorshDeserialize> Drop for StorageCell<T> {
+    fn drop(&mut self) {
+        self.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_unique() {
+        let keys = StorageKey::ALL;
+        for (i, a) in keys.iter().enumerate() {
+            for b in &keys[i + 1..] {
+                assert_ne!(a.to_bytes(), b.to_bytes());
+            }
+        }
+    }
+
+    #[test]
+    fn segments_are_length_prefixed() {
+        assert_eq!(key!("ab", "c"), b"\x02ab\x01c".to_vec());
+        assert_ne!(key!("ab", "c"), key!("a", "bc"));
+        assert_ne!(key!("auction", 1u64), key!("auction", 1u32));
+    }
+
+    #[test]
+    fn collection_headers_and_items_do_not_overlap() {
+        for key in [
+            StorageKey::SdkVector,
+            StorageKey::SdkIterableMap,
+            StorageKey::SdkLookupMap,
+            StorageKey::SdkTreeMap,
+            StorageKey::SdkLookupSet,
+            StorageKey::SdkIterableSet,
+            StorageKey::SdkNestedMap,
+            StorageKey::Refunds,
+            StorageKey::StorageBalances,
+        ] {
+            let header = key.to_bytes();
+            let items = key.items_prefix();
+            let nested = key.nested_prefix(0);
+            assert!(!items.starts_with(&header));
+            assert!(!header.starts_with(&items));
+            assert!(!nested.starts_with(&items));
+            assert!(!nested.starts_with(&header));
+        }
+    }
+
+    #[test]
+    fn session_writes_back_changed_keys_on_drop() {
+        let paused = StorageKey::Paused.to_bytes();
+        let owner = StorageKey::Owner.to_bytes();
+        write(&paused, &[0]);
+
+        let session = WriteBackSession::begin();
+        assert_eq!(read(&paused), Some(vec![0]));
+        write(&paused, &[1]);
+        write(&owner, b"bob");
+        remove(&owner);
+        assert_eq!(read(&paused), Some(vec![1]));
+        assert!(!has_key(&owner));
+        assert_eq!(env::storage_read(&paused), Some(envelope::seal(&[0])));
+        drop(session);
+
+        assert_eq!(env::storage_read(&paused), Some(envelope::seal(&[1])));
+        assert!(!env::storage_has_key(&owner));
+    }
+
+    #[test]
+    fn session_skips_unchanged_keys() {
+        let paused = StorageKey::Paused.to_bytes();
+        write(&paused, &[0]);
+
+        let _session = WriteBackSession::begin();
+        read(&paused);
+        write(&paused, &[1]);
+        write(&paused, &[0]);
+        CACHE.with_borrow(|cache| assert!(!cache.entries[&paused].is_dirty()));
+    }
+
+    #[test]
+    fn writes_of_the_stored_bytes_are_skipped() {
+        let paused = StorageKey::Paused.to_bytes();
+        write(&paused, &[0]);
+
+        // Neither value is read first, so only storage can tell them apart
+        let gas_to_write = |value: u8| {
+            let before = env::used_gas();
+            {
+                let _session = WriteBackSession::begin();
+                write(&paused, &[value]);
+            }
+            env::used_gas().saturating_sub(before)
+        };
+        let unchanged = gas_to_write(0);
+        let changed = gas_to_write(1);
+        assert!(unchanged < changed);
+        assert_eq!(env::storage_read(&paused), Some(envelope::seal(&[1])));
+    }
+
+    #[test]
+    fn key_index_tracks_created_and_removed_keys() {
+        let paused = StorageKey::Paused.to_bytes();
+        let owner = StorageKey::Owner.to_bytes();
+        write(&paused, &[0]);
+        write(&paused, &[1]);
+        {
+            let _session = WriteBackSession::begin();
+            write(&owner, b"bob");
+            write(b"scratch", b"tmp");
+            remove(b"scratch");
+        }
+        let mut expected = vec![paused.clone(), owner.clone()];
+        expected.sort();
+        assert_eq!(indexed_keys(), expected);
+
+        remove(&paused);
+        assert_eq!(indexed_keys(), vec![owner.clone()]);
+        assert_eq!(indexed_keys_page(0, 10), vec![owner]);
+        assert_eq!(indexed_key_count(), 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_INTEGRITY_CHECK_FAILED")]
+    fn read_rejects_tampered_values() {
+        let paused = StorageKey::Paused.to_bytes();
+        write(&paused, &[0]);
+        let mut bytes = env::storage_read(&paused).unwrap();
+        *bytes.last_mut().unwrap() = 1;
+        env::storage_write(&paused, &bytes);
+        read(&paused);
+    }
+
+    #[cfg(not(feature = "minimal"))]
+    #[test]
+    #[should_panic(expected = "Missing value in storage: owner")]
+    fn missing_values_name_their_key() {
+        read_key::<AccountId>(&StorageKey::Owner.to_bytes());
+    }
+
+    #[cfg(not(feature = "minimal"))]
+    #[test]
+    #[should_panic(expected = "Cannot deserialize value: emergency_delay")]
+    fn undecodable_values_name_their_key() {
+        let delay = StorageKey::EmergencyDelay.to_bytes();
+        write(&delay, &[1, 2]);
+        StorageCell::<u64>::new(StorageKey::EmergencyDelay).get();
+    }
+
+    // The mocked host quotes the message, doubling its backslashes
+    #[cfg(not(feature = "minimal"))]
+    #[test]
+    #[should_panic(expected = r"Missing value in storage: \\x05bench\\x06vector")]
+    fn unregistered_keys_are_escaped() {
+        load::<u32>(&key!("bench", "vector"));
+    }
+
+    #[test]
+    fn prefetched_keys_are_served_from_the_session() {
+        let paused = StorageKey::Paused.to_bytes();
+        let owner = StorageKey::Owner.to_bytes();
+        write_many(&[(&paused, vec![1]), (&owner, b"bob".to_vec())]);
+
+        let _session = WriteBackSession::begin();
+        prefetch(&[&owner, b"missing", &paused]);
+        env::storage_remove(&paused);
+        assert!(load::<bool>(&paused));
+        assert_eq!(read(&owner), Some(b"bob".to_vec()));
+        assert_eq!(try_load::<bool>(b"missing"), None);
+    }
+
+    #[test]
+    fn loads_are_decoded_once_per_session() {
+        let delay = StorageKey::EmergencyDelay.to_bytes();
+        write(&delay, &borsh::to_vec(&1000u64).unwrap());
+
+        let _session = WriteBackSession::begin();
+        assert_eq!(load::<u64>(&delay), 1000);
+        let decoded = |key: &[u8]| {
+            CACHE.with_borrow(|cache| cache.entries[key].decoded.clone().map(|rc| Rc::as_ptr(&rc)))
+        };
+        let first = decoded(&delay).expect("the value is memoized");
+        assert_eq!(load::<u64>(&delay), 1000);
+        assert_eq!(decoded(&delay), Some(first));
+
+        // Writing drops the decoded value, so the next load sees the new bytes
+        write(&delay, &borsh::to_vec(&2000u64).unwrap());
+        assert!(decoded(&delay).is_none());
+        assert_eq!(load::<u64>(&delay), 2000);
+    }
+
+    #[test]
+    fn cell_writes_back_only_when_mutated() {
+        let mut paused = StorageCell::<bool>::new(StorageKey::Paused);
+        paused.set(false);
+        drop(paused);
+        assert!(env::storage_has_key(&StorageKey::Paused.to_bytes()));
+
+        let paused = StorageCell::<bool>::new(StorageKey::Paused);
+        assert!(!paused.get());
+        assert!(!paused.dirty);
+
+        let mut paused = StorageCell::<bool>::new(StorageKey::Paused);
+        *paused.get_mut() = true;
+        drop(paused);
+        assert!(*StorageCell::<bool>::new(StorageKey::Paused).get());
+
+        StorageCell::<bool>::new(StorageKey::Paused).remove();
+        assert!(StorageCell::<bool>::new(StorageKey::Paused)
+            .try_get()
+            .is_none());
+    }
+}
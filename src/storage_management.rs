@@ -0,0 +1,303 @@
+//! NEP-145 storage management. Accounts deposit NEAR up front, and records
+//! the contract creates on their behalf are paid for out of that deposit
+//! instead of the contract's own balance.
+
+use near_sdk::{assert_one_yocto, env, near, require, store, AccountId, NearToken, Promise};
+
+use crate::error::AuctionError;
+use crate::storage::{self, new_collection, StorageCell, StorageKey, WriteBackSession};
+use crate::{Contract, ContractExt};
+
+/// Bytes locked by registering: one balance entry, with room for the
+/// longest account id.
+const REGISTRATION_BYTES: u64 = 250;
+
+type Balances = store::LookupMap<AccountId, StorageBalance>;
+
+#[near(serializers = [json, borsh])]
+#[derive(Clone, Debug, PartialEq)]
+pub struct StorageBalance {
+    pub total: NearToken,
+    pub available: NearToken,
+}
+
+#[near(serializers = [json])]
+pub struct StorageBalanceBounds {
+    pub min: NearToken,
+    pub max: Option<NearToken>,
+}
+
+fn registration_cost() -> NearToken {
+    storage_cost(REGISTRATION_BYTES)
+}
+
+/// What `bytes` of storage lock up at the current byte cost.
+pub fn storage_cost(bytes: u64) -> NearToken {
+    env::storage_byte_cost().saturating_mul(bytes.into())
+}
+
+fn balance_of(account_id: &AccountId) -> Option<StorageBalance> {
+    StorageCell::<Balances>::new(StorageKey::StorageBalances)
+        .try_get()
+        .and_then(|balances| balances.get(account_id).cloned())
+}
+
+/// What every registered account could withdraw right now. Kept as a running
+/// total since the balances themselves can't be enumerated.
+pub fn total_available() -> NearToken {
+    StorageCell::<NearToken>::new(StorageKey::StorageAvailable)
+        .try_get()
+        .copied()
+        .unwrap_or(NearToken::from_yoctonear(0))
+}
+
+fn set_balance(account_id: AccountId, balance: StorageBalance) {
+    let before = balance_of(&account_id).map_or(NearToken::from_yoctonear(0), |b| b.available);
+    // No account's available balance exceeds the total, so neither side can
+    // go out of range
+    let total = total_available()
+        .saturating_sub(before)
+        .saturating_add(balance.available);
+    StorageCell::new(StorageKey::StorageAvailable).set(total);
+
+    let mut balances = StorageCell::<Balances>::new(StorageKey::StorageBalances);
+    balances
+        .get_mut_or_insert_with(|| new_collection(StorageKey::StorageBalances))
+        .insert(account_id, balance);
+}
+
+/// Runs `f`, which creates records on behalf of `account_id`, and charges
+/// the storage it added to that account's deposit. Whatever the deposit
+/// can't cover, or all of it for unregistered accounts, falls to the
+/// contract. Never panics, so it is safe to use in callbacks.
+pub fn charge_storage<R>(account_id: &AccountId, f: impl FnOnce() -> R) -> R {
+    let before = env::storage_usage();
+    let result = f();
+    storage::flush();
+    let added = env::storage_usage().saturating_sub(before);
+
+    if let Some(mut balance) = balance_of(account_id) {
+        let cost = env::storage_byte_cost().saturating_mul(added.into());
+        balance.available = balance.available.saturating_sub(cost);
+        set_balance(account_id.clone(), balance);
+    }
+    result
+}
+
+/// Runs `f` like `charge_storage`, but panics unless `account_id`'s storage
+/// balance covers everything `f` adds, for calls that can't take a deposit.
+pub fn charge_balance<R>(account_id: &AccountId, f: impl FnOnce() -> R) -> R {
+    let Some(mut balance) = balance_of(account_id) else {
+        AuctionError::NotRegistered.panic_with(account_id);
+    };
+    let before = env::storage_usage();
+    let result = f();
+    storage::flush();
+    let cost = storage_cost(env::storage_usage().saturating_sub(before));
+    require!(
+        balance.available >= cost,
+        AuctionError::StorageNotCovered.as_str()
+    );
+    balance.available = balance.available.saturating_sub(cost);
+    set_balance(account_id.clone(), balance);
+    result
+}
+
+/// How much of the attached deposit a call that adds up to `bytes` of
+/// records for `account_id` has to set aside: nothing when the account's
+/// storage balance covers them, their full cost otherwise.
+pub fn required_deposit(account_id: &AccountId, bytes: u64) -> NearToken {
+    let cost = storage_cost(bytes);
+    match balance_of(account_id) {
+        Some(balance) if balance.available >= cost => NearToken::from_yoctonear(0),
+        _ => cost,
+    }
+}
+
+/// Runs `f` like `charge_storage`, except that with a `prepaid` part of the
+/// attached deposit (see `required_deposit`) the storage `f` adds is paid
+/// out of that instead. Returns what `f` returned and what is left of
+/// `prepaid`, for the caller to send back.
+pub fn charge_deposit<R>(
+    account_id: &AccountId,
+    prepaid: NearToken,
+    f: impl FnOnce() -> R,
+) -> (R, NearToken) {
+    if prepaid.is_zero() {
+        return (charge_storage(account_id, f), prepaid);
+    }
+    let before = env::storage_usage();
+    let result = f();
+    storage::flush();
+    let added = env::storage_usage().saturating_sub(before);
+    (result, prepaid.saturating_sub(storage_cost(added)))
+}
+
+/// `promise`, followed by sending `unused` back to `account_id` when there
+/// is any.
+pub fn refund_unused(promise: Promise, account_id: AccountId, unused: NearToken) -> Promise {
+    if unused.is_zero() {
+        return promise;
+    }
+    promise.and(Promise::new(account_id).transfer(unused))
+}
+
+#[near]
+impl Contract {
+    /// Registers `account_id` (the caller by default) and adds the attached
+    /// deposit to its balance. With `registration_only`, anything above the
+    /// registration cost is sent back.
+    #[payable]
+    pub fn storage_deposit(
+        &mut self,
+        account_id: Option<AccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        let _session = WriteBackSession::begin();
+
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+        let mut deposit = env::attached_deposit();
+        let min = registration_cost();
+
+        let balance = match balance_of(&account_id) {
+            Some(balance) if registration_only == Some(true) => {
+                // Already registered, so the whole deposit goes back
+                Promise::new(env::predecessor_account_id())
+                    .transfer(deposit)
+                    .detach();
+                return balance;
+            }
+            Some(balance) => StorageBalance {
+                total: balance.total.saturating_add(deposit),
+                available: balance.available.saturating_add(deposit),
+            },
+            None => {
+                if deposit < min {
+                    AuctionError::StorageDepositTooLow.panic_with(min);
+                }
+                if registration_only == Some(true) {
+                    let excess = deposit.saturating_sub(min);
+                    if !excess.is_zero() {
+                        Promise::new(env::predecessor_account_id())
+                            .transfer(excess)
+                            .detach();
+                    }
+                    deposit = min;
+                }
+                StorageBalance {
+                    total: deposit,
+                    available: deposit.saturating_sub(min),
+                }
+            }
+        };
+        set_balance(account_id, balance.clone());
+        balance
+    }
+
+    /// Sends `amount` of the caller's available balance back to them, or
+    /// all of it when `amount` is omitted.
+    #[payable]
+    pub fn storage_withdraw(&mut self, amount: Option<NearToken>) -> StorageBalance {
+        let _session = WriteBackSession::begin();
+
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+        let Some(mut balance) = balance_of(&account_id) else {
+            AuctionError::NotRegistered.panic_with(account_id);
+        };
+        let amount = amount.unwrap_or(balance.available);
+        require!(
+            amount <= balance.available,
+            AuctionError::StorageBalanceTooLow.as_str()
+        );
+
+        balance.total = balance.total.saturating_sub(amount);
+        balance.available = balance.available.saturating_sub(amount);
+        set_balance(account_id.clone(), balance.clone());
+        if !amount.is_zero() {
+            Promise::new(account_id).transfer(amount).detach();
+        }
+        balance
+    }
+
+    pub fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: registration_cost(),
+            max: None,
+        }
+    }
+
+    pub fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        balance_of(&account_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    use super::*;
+    use crate::test_utils::new_contract;
+
+    fn call_with(deposit: NearToken) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id("bob.near".parse().unwrap())
+            .attached_deposit(deposit)
+            .build());
+    }
+
+    #[test]
+    fn deposits_and_withdrawals_update_the_balance() {
+        let mut contract = new_contract();
+        let bob: AccountId = "bob.near".parse().unwrap();
+        let min = contract.storage_balance_bounds().min;
+        assert_eq!(contract.storage_balance_of(bob.clone()), None);
+
+        call_with(NearToken::from_near(1));
+        let balance = contract.storage_deposit(None, None);
+        assert_eq!(balance.total, NearToken::from_near(1));
+        assert_eq!(
+            balance.available,
+            NearToken::from_near(1).saturating_sub(min)
+        );
+
+        call_with(NearToken::from_yoctonear(1));
+        let balance = contract.storage_withdraw(Some(NearToken::from_millinear(100)));
+        assert_eq!(balance.total, NearToken::from_millinear(900));
+        assert_eq!(contract.storage_balance_of(bob), Some(balance));
+    }
+
+    #[test]
+    fn registration_only_keeps_the_minimum() {
+        let mut contract = new_contract();
+        call_with(NearToken::from_near(1));
+        let balance = contract.storage_deposit(None, Some(true));
+        assert_eq!(balance.total, contract.storage_balance_bounds().min);
+        assert!(balance.available.is_zero());
+    }
+
+    #[test]
+    fn records_are_charged_to_the_account() {
+        let mut contract = new_contract();
+        let bob: AccountId = "bob.near".parse().unwrap();
+        call_with(NearToken::from_near(1));
+        let before = contract.storage_deposit(None, None);
+
+        let _session = WriteBackSession::begin();
+        charge_storage(&bob, || {
+            storage::write(b"bob-record", &[0; 100]);
+        });
+        let after = contract.storage_balance_of(bob).unwrap();
+        assert_eq!(after.total, before.total);
+        assert!(after.available < before.available);
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_STORAGE_DEPOSIT_TOO_LOW")]
+    fn registration_needs_the_minimum() {
+        let mut contract = new_contract();
+        call_with(NearToken::from_yoctonear(1));
+        contract.storage_deposit(None, None);
+    }
+}
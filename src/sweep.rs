@@ -0,0 +1,136 @@
+//! Sweeping the balance nobody is owed. Years of rounding, failed promises
+//! and stray transfers leave NEAR in the account that no ledger accounts
+//! for; the owner can send it elsewhere so the books keep reconciling.
+//! Everything still owed is worked out from the ledgers themselves, so a
+//! sweep can never touch a bid, a refund or a storage deposit. Penny fees
+//! and staking rewards belong to the contract and are swept with the rest.
+
+use near_sdk::{assert_one_yocto, env, near, require, AccountId, NearToken, Promise};
+
+use crate::auction_state::{self, AuctionState};
+use crate::error::AuctionError;
+use crate::owner::only_owner;
+use crate::storage::WriteBackSession;
+use crate::storage_management::{storage_cost, total_available};
+use crate::{chain_signatures, dutch_auctions, math, nft_auctions, refunds, Contract, ContractExt};
+
+/// The highest bid of the main auction, until its payout went through or,
+/// for a payout on another chain, was signed.
+fn highest_bid_held() -> NearToken {
+    let unclaimed = auction_state::try_config().is_some_and(|config| !config.is_claimed());
+    let held = unclaimed || chain_signatures::payout_unsigned();
+    let state = AuctionState::new();
+    let highest_bid = state.highest_bid();
+    if held && highest_bid.bidder != env::current_account_id() {
+        highest_bid.bid
+    } else {
+        NearToken::from_yoctonear(0)
+    }
+}
+
+/// Everything the balance has to cover: bids, refunds and storage deposits
+/// still owed, refunds whose transfer hasn't been resolved yet, and the
+/// stake for the contract's own storage.
+pub fn accounted_balance() -> NearToken {
+    math::sum([
+        highest_bid_held(),
+        nft_auctions::held(),
+        dutch_auctions::held(),
+        refunds::total_owed(),
+        refunds::in_flight(),
+        total_available(),
+        storage_cost(env::storage_usage()),
+    ])
+    .unwrap_or_else(|err| err.panic())
+}
+
+/// What the balance holds beyond `accounted_balance`. While the highest bid
+/// is staked it is missing from the balance, which only makes this smaller.
+pub(crate) fn dust() -> NearToken {
+    env::account_balance().saturating_sub(accounted_balance())
+}
+
+#[near]
+impl Contract {
+    /// Sends the balance no ledger accounts for to `to`. Reads the running
+    /// totals of every NFT and Dutch auction, so it gets more expensive as
+    /// they pile up, but not with the bids and purchases in them.
+    #[payable]
+    pub fn sweep_dust(&mut self, to: AccountId) -> Promise {
+        let _session = WriteBackSession::begin();
+
+        assert_one_yocto();
+        only_owner();
+        let amount = dust();
+        require!(!amount.is_zero(), AuctionError::NoDust.as_str());
+        env::log_str(&format!("Sweeping {amount} to {to}"));
+        Promise::new(to).transfer(amount)
+    }
+
+    /// What `sweep_dust` would send right now.
+    pub fn get_dust(&self) -> NearToken {
+        dust()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    use super::*;
+    use crate::test_utils::new_contract;
+
+    fn call_as(account_id: &AccountId, deposit: NearToken, balance: NearToken) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account_id.clone())
+            .attached_deposit(deposit)
+            .account_balance(balance)
+            .block_timestamp(100)
+            .build());
+    }
+
+    #[test]
+    fn only_unaccounted_balance_is_swept() {
+        let mut contract = new_contract();
+        let bob: AccountId = "bob.near".parse().unwrap();
+
+        call_as(&bob, NearToken::from_near(2), NearToken::from_near(100));
+        contract.storage_deposit(None, None);
+        let _ = contract.bid();
+
+        call_as(
+            &bob,
+            NearToken::from_yoctonear(0),
+            NearToken::from_near(100),
+        );
+        // the initial bid's refund stays owed until its callback has run
+        let owed = math::sum([
+            contract.get_highest_bid().bid,
+            refunds::in_flight(),
+            contract.storage_balance_of(bob.clone()).unwrap().available,
+            storage_cost(env::storage_usage()),
+        ])
+        .unwrap();
+        assert_eq!(accounted_balance(), owed);
+        assert_eq!(
+            contract.get_dust(),
+            NearToken::from_near(100).saturating_sub(owed)
+        );
+        call_as(&bob, NearToken::from_yoctonear(0), owed);
+        assert!(contract.get_dust().is_zero());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_OWNER")]
+    fn only_the_owner_sweeps() {
+        let mut contract = new_contract();
+        let mallory: AccountId = "mallory.near".parse().unwrap();
+        call_as(
+            &mallory,
+            NearToken::from_yoctonear(1),
+            NearToken::from_near(100),
+        );
+        let _ = contract.sweep_dust(mallory.clone());
+    }
+}
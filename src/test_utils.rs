@@ -0,0 +1,10 @@
+//! Setup shared by the unit tests.
+
+use near_sdk::json_types::U64;
+
+use crate::Contract;
+
+/// A contract ending at 1000 ns, auctioned by `auctioneer.near`.
+pub fn new_contract() -> Contract {
+    Contract::init(U64(1000), "auctioneer.near".parse().unwrap(), None, None)
+}
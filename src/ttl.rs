@@ -0,0 +1,169 @@
+use near_sdk::{borsh, env, near};
+
+use crate::envelope;
+use crate::error::AuctionError;
+use crate::storage::{self, key, WriteBackSession};
+use crate::{Contract, ContractExt};
+
+/// Expiry timestamps live next to the data under `key!("ttl", key)`, so the
+/// key index doubles as the list of entries that may need purging. Keys
+/// longer than one key segment can't have one.
+fn ttl_key(key: &[u8]) -> Option<Vec<u8>> {
+    (key.len() <= u8::MAX as usize).then(|| key!("ttl", key))
+}
+
+/// Whether the TTL recorded for `key` has passed. `storage` only asks for
+/// values sealed as expiring, which expiry records never are.
+pub fn has_expired(key: &[u8]) -> bool {
+    ttl_key(key).is_some_and(|ttl_key| is_expired(&ttl_key))
+}
+
+fn is_expired(ttl_key: &[u8]) -> bool {
+    storage::read(ttl_key)
+        .map(|bytes| storage::decode::<u64>(ttl_key, Some(bytes)) <= env::block_timestamp())
+        .unwrap_or(false)
+}
+
+pub fn write_with_ttl(key: &[u8], value: &[u8], expires_at: u64) {
+    let Some(ttl_key) = ttl_key(key) else {
+        AuctionError::KeyTooLongForTtl.panic();
+    };
+    storage::write_expiring(key, value);
+    storage::write(&ttl_key, &borsh::to_vec(&expires_at).unwrap());
+}
+
+/// Drops the expiry record of `key`, if it had one. Call it after a plain
+/// `storage::write`, which already stops the value from expiring.
+pub fn clear(key: &[u8]) {
+    if let Some(ttl_key) = ttl_key(key) {
+        storage::remove(&ttl_key);
+    }
+}
+
+/// Seals every value that has an expiry record as expiring, for state
+/// written before `storage` only looked up the TTL of such values.
+pub(crate) fn mark_expiring_values() {
+    let prefix = key!("ttl");
+    for ttl_key in storage::indexed_keys() {
+        if !ttl_key.starts_with(&prefix) {
+            continue;
+        }
+        // Skip the length byte that precedes the wrapped key segment
+        let key = &ttl_key[prefix.len() + 1..];
+        if let Some(value) = storage::read(key) {
+            storage::write_expiring(key, &value);
+        }
+    }
+}
+
+/// Like `storage::peek`, but an entry whose TTL has passed reads as absent
+/// even before `purge_expired` reclaims it.
+pub fn peek(key: &[u8]) -> Option<Vec<u8>> {
+    let bytes = env::storage_read(key)?;
+    if envelope::is_expiring(&bytes) && has_expired(key) {
+        return None;
+    }
+    Some(storage::unwrap_stored(bytes))
+}
+
+#[near]
+impl Contract {
+    /// Removes up to `limit` expired entries together with their expiry
+    /// metadata and returns how many were purged.
+    pub fn purge_expired(&mut self, limit: u32) -> u32 {
+        let _session = WriteBackSession::begin();
+
+        let prefix = key!("ttl");
+        let expired: Vec<Vec<u8>> = storage::indexed_keys()
+            .into_iter()
+            .filter(|key| key.starts_with(&prefix) && is_expired(key))
+            .take(limit as usize)
+            .collect();
+        for ttl_key in &expired {
+            // Skip the length byte that precedes the wrapped key segment
+            let key = &ttl_key[prefix.len() + 1..];
+            // A plain write since the TTL was set keeps the value
+            if storage::is_expiring(key) {
+                storage::remove(key);
+            }
+            storage::remove(ttl_key);
+        }
+        expired.len() as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
+    use super::*;
+    use crate::storage::{StorageCell, StorageKey};
+
+    #[test]
+    fn expired_entries_read_as_absent_and_get_purged() {
+        testing_env!(VMContextBuilder::new().block_timestamp(100).build());
+        write_with_ttl(b"old", b"a", 50);
+        write_with_ttl(b"fresh", b"b", 500);
+        write_with_ttl(b"stale", b"c", 100);
+
+        assert_eq!(peek(b"old"), None);
+        assert_eq!(peek(b"fresh"), Some(b"b".to_vec()));
+
+        let mut contract = Contract {};
+        assert_eq!(contract.purge_expired(1), 1);
+        assert_eq!(contract.purge_expired(10), 1);
+        assert_eq!(contract.purge_expired(10), 0);
+        assert!(!env::storage_has_key(b"old"));
+        assert!(!env::storage_has_key(&ttl_key(b"stale").unwrap()));
+        assert!(env::storage_has_key(b"fresh"));
+    }
+
+    #[test]
+    fn every_read_skips_expired_entries() {
+        testing_env!(VMContextBuilder::new().block_timestamp(100).build());
+        let paused = StorageKey::Paused.to_bytes();
+        write_with_ttl(&paused, &borsh::to_vec(&true).unwrap(), 50);
+
+        assert_eq!(storage::read(&paused), None);
+        assert!(!storage::has_key(&paused));
+        assert!(StorageCell::<bool>::new(StorageKey::Paused)
+            .try_get()
+            .is_none());
+
+        clear(&paused);
+        assert_eq!(
+            StorageCell::<bool>::new(StorageKey::Paused).try_get(),
+            Some(&true)
+        );
+    }
+
+    #[test]
+    fn plain_writes_stop_expiring_and_survive_purges() {
+        testing_env!(VMContextBuilder::new().block_timestamp(100).build());
+        write_with_ttl(b"key", b"a", 50);
+        storage::write(b"key", b"b");
+
+        // The stale expiry record is no longer looked up
+        assert_eq!(storage::read(b"key"), Some(b"b".to_vec()));
+        assert_eq!(peek(b"key"), Some(b"b".to_vec()));
+        let mut contract = Contract {};
+        assert_eq!(contract.purge_expired(10), 1);
+        assert_eq!(storage::read(b"key"), Some(b"b".to_vec()));
+    }
+
+    #[test]
+    fn keys_too_long_for_a_ttl_read_normally() {
+        let key = [7; 300];
+        storage::write(&key, b"a");
+        clear(&key);
+        assert_eq!(peek(&key), Some(b"a".to_vec()));
+        assert!(storage::has_key(&key));
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_KEY_TOO_LONG_FOR_TTL")]
+    fn keys_too_long_for_a_ttl_are_rejected() {
+        write_with_ttl(&[7; 300], b"a", 50);
+    }
+}
@@ -0,0 +1,218 @@
+//! Optional settlement in wrapped NEAR. When the auctioneer names a wNEAR
+//! contract, `claim` deposits the winning bid into it with `near_deposit`
+//! and sends the wNEAR on with `ft_transfer`, checking each step in a
+//! callback.
+
+use near_sdk::json_types::U128;
+use near_sdk::serde_json::{self, json};
+use near_sdk::{assert_one_yocto, env, near, require, AccountId, Gas, NearToken, Promise};
+
+use crate::auction_state;
+use crate::error::AuctionError;
+use crate::storage::{StorageCell, StorageKey, WriteBackSession};
+use crate::{Contract, ContractExt};
+
+/// Gas for `near_deposit` on the wNEAR contract.
+const NEAR_DEPOSIT_GAS: Gas = Gas::from_tgas(10);
+/// Gas for `ft_transfer` on the wNEAR contract.
+const FT_TRANSFER_GAS: Gas = Gas::from_tgas(10);
+/// Gas reserved for `resolve_wnear_transfer`.
+const RESOLVE_TRANSFER_GAS: Gas = Gas::from_tgas(5);
+/// Gas reserved for `resolve_wnear_deposit`, which schedules the transfer
+/// and its callback.
+const RESOLVE_DEPOSIT_GAS: Gas = Gas::from_tgas(30);
+
+fn wnear_contract() -> Option<AccountId> {
+    StorageCell::<AccountId>::new(StorageKey::WnearContract)
+        .try_get()
+        .cloned()
+}
+
+/// Pays `amount` to `auctioneer`: as wNEAR if settlement in wNEAR was
+/// requested, as a plain transfer otherwise.
+pub fn pay_auctioneer(auctioneer: AccountId, amount: NearToken) -> Promise {
+    let Some(wnear) = wnear_contract() else {
+        return Promise::new(auctioneer).transfer(amount);
+    };
+    Promise::new(wnear.clone())
+        .function_call(
+            "near_deposit".to_string(),
+            b"{}".to_vec(),
+            amount,
+            NEAR_DEPOSIT_GAS,
+        )
+        .then(
+            Contract::ext(env::current_account_id())
+                .with_static_gas(RESOLVE_DEPOSIT_GAS)
+                .resolve_wnear_deposit(wnear, auctioneer, amount),
+        )
+}
+
+fn transfer_wnear(wnear: AccountId, auctioneer: AccountId, amount: NearToken) -> Promise {
+    let args = json!({
+        "receiver_id": auctioneer,
+        "amount": U128(amount.as_yoctonear()),
+    });
+    Promise::new(wnear.clone())
+        .function_call(
+            "ft_transfer".to_string(),
+            serde_json::to_vec(&args).unwrap(),
+            NearToken::from_yoctonear(1),
+            FT_TRANSFER_GAS,
+        )
+        .then(
+            Contract::ext(env::current_account_id())
+                .with_static_gas(RESOLVE_TRANSFER_GAS)
+                .resolve_wnear_transfer(wnear, auctioneer, amount),
+        )
+}
+
+#[near]
+impl Contract {
+    /// Moves the freshly minted wNEAR on to the auctioneer. If the deposit
+    /// failed the NEAR came back with the refund, so it is paid out as is.
+    #[private]
+    pub fn resolve_wnear_deposit(
+        &mut self,
+        wnear: AccountId,
+        auctioneer: AccountId,
+        amount: NearToken,
+    ) -> Promise {
+        if env::promise_result_checked(0, 0).is_err() {
+            env::log_str(&format!(
+                "near_deposit on {wnear} failed, paying {amount} in NEAR"
+            ));
+            return Promise::new(auctioneer).transfer(amount);
+        }
+        transfer_wnear(wnear, auctioneer, amount)
+    }
+
+    /// Records the wNEAR as unsettled when `ft_transfer` failed, usually
+    /// because the auctioneer has no storage on the wNEAR contract yet.
+    #[private]
+    pub fn resolve_wnear_transfer(
+        &mut self,
+        wnear: AccountId,
+        auctioneer: AccountId,
+        amount: NearToken,
+    ) {
+        if env::promise_result_checked(0, 0).is_ok() {
+            return;
+        }
+        let _session = WriteBackSession::begin();
+        env::log_str(&format!(
+            "ft_transfer of {amount} wNEAR on {wnear} to {auctioneer} failed"
+        ));
+        StorageCell::new(StorageKey::UnsettledWnear).set(amount);
+    }
+
+    /// Asks for the winning bid in wNEAR from `wnear_contract`, or in NEAR
+    /// again with `None`. Only the auctioneer can choose, before the claim.
+    #[payable]
+    pub fn set_wnear_settlement(&mut self, wnear_contract: Option<AccountId>) {
+        let _session = WriteBackSession::begin();
+
+        assert_one_yocto();
+        let config = auction_state::config();
+        require!(
+            env::predecessor_account_id() == config.auctioneer,
+            AuctionError::NotAuctioneer.as_str()
+        );
+        config.assert_unclaimed();
+
+        let mut cell = StorageCell::<AccountId>::new(StorageKey::WnearContract);
+        match wnear_contract {
+            Some(wnear_contract) => cell.set(wnear_contract),
+            None => cell.remove(),
+        }
+    }
+
+    /// Sends wNEAR whose `ft_transfer` failed to the auctioneer again, e.g.
+    /// after they registered with the wNEAR contract.
+    #[payable]
+    pub fn retry_wnear_settlement(&mut self) -> Promise {
+        let _session = WriteBackSession::begin();
+
+        assert_one_yocto();
+        let mut unsettled = StorageCell::<NearToken>::new(StorageKey::UnsettledWnear);
+        let Some(amount) = unsettled.try_get().copied() else {
+            AuctionError::NoUnsettledWnear.panic();
+        };
+        let Some(wnear) = wnear_contract() else {
+            AuctionError::WnearNotConfigured.panic();
+        };
+        unsettled.remove();
+
+        transfer_wnear(wnear, auction_state::config().auctioneer, amount)
+    }
+
+    pub fn get_wnear_settlement(&self) -> Option<AccountId> {
+        wnear_contract()
+    }
+
+    pub fn get_unsettled_wnear(&self) -> NearToken {
+        StorageCell::<NearToken>::new(StorageKey::UnsettledWnear)
+            .try_get()
+            .copied()
+            .unwrap_or(NearToken::from_yoctonear(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::{test_vm_config, testing_env, PromiseResult, RuntimeFeesConfig};
+
+    use super::*;
+    use crate::test_utils::new_contract;
+
+    fn call_as(account_id: &str, block_timestamp: u64) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(account_id.parse().unwrap())
+            .attached_deposit(NearToken::from_yoctonear(1))
+            .block_timestamp(block_timestamp)
+            .build());
+    }
+
+    #[test]
+    fn auctioneer_chooses_wnear_and_failed_transfers_are_kept() {
+        let mut contract = new_contract();
+        call_as("auctioneer.near", 0);
+        contract.set_wnear_settlement(Some("wrap.near".parse().unwrap()));
+        assert_eq!(
+            contract.get_wnear_settlement(),
+            Some("wrap.near".parse().unwrap())
+        );
+
+        call_as("auctioneer.near", 2000);
+        let _ = contract.claim();
+
+        testing_env!(
+            VMContextBuilder::new()
+                .predecessor_account_id(env::current_account_id())
+                .build(),
+            test_vm_config(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        contract.resolve_wnear_transfer(
+            "wrap.near".parse().unwrap(),
+            "auctioneer.near".parse().unwrap(),
+            NearToken::from_near(1),
+        );
+        assert_eq!(contract.get_unsettled_wnear(), NearToken::from_near(1));
+
+        call_as("auctioneer.near", 3000);
+        let _ = contract.retry_wnear_settlement();
+        assert!(contract.get_unsettled_wnear().is_zero());
+    }
+
+    #[test]
+    #[should_panic(expected = "ERR_NOT_AUCTIONEER")]
+    fn others_cannot_choose_the_settlement() {
+        let mut contract = new_contract();
+        call_as("bob.near", 0);
+        contract.set_wnear_settlement(Some("wrap.near".parse().unwrap()));
+    }
+}
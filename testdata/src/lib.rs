@@ -0,0 +1,58 @@
+//! Deterministic fill data shared by every benchmark contract, so contracts
+//! given the same seed write byte-identical data. Each item is a pure
+//! function of the seed and its index: a fill split across several calls
+//! produces exactly the data of a single call.
+
+/// Separates the key and account id streams from the value stream, so a key
+/// isn't simply the value at the same index.
+const KEY_STREAM: u64 = 0x6b65_7973;
+const ACCOUNT_STREAM: u64 = 0x6163_6374;
+
+/// SplitMix64 applied to the `index`-th step from `seed`.
+fn mix(seed: u64, index: u32) -> u64 {
+    let mut z = seed.wrapping_add((u64::from(index) + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Value of element `index`.
+pub fn value(seed: u64, index: u32) -> u8 {
+    mix(seed, index) as u8
+}
+
+/// Map key of element `index`.
+pub fn key(seed: u64, index: u32) -> u8 {
+    mix(seed ^ KEY_STREAM, index) as u8
+}
+
+/// Name of the `index`-th generated account, valid as a sub-account segment.
+pub fn account_id(seed: u64, index: u32) -> String {
+    format!("user-{:012x}", mix(seed ^ ACCOUNT_STREAM, index) >> 16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn same_seed_same_data() {
+        let first: Vec<u8> = (0..100).map(|i| value(7, i)).collect();
+        let second: Vec<u8> = (0..100).map(|i| value(7, i)).collect();
+        assert_eq!(first, second);
+        assert_ne!(first, (0..100).map(|i| value(8, i)).collect::<Vec<u8>>());
+        assert_ne!(first, (0..100).map(|i| key(7, i)).collect::<Vec<u8>>());
+    }
+
+    #[test]
+    fn account_ids_are_distinct_and_well_formed() {
+        let ids: Vec<String> = (0..50).map(|i| account_id(7, i)).collect();
+        for (i, id) in ids.iter().enumerate() {
+            assert_eq!(id.len(), "user-".len() + 12);
+            assert!(id
+                .bytes()
+                .all(|b| b.is_ascii_lowercase() || b.is_ascii_digit() || b == b'-'));
+            assert!(!ids[i + 1..].contains(id));
+        }
+    }
+}
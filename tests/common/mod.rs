@@ -0,0 +1,211 @@
+//! Sandbox setup shared by the integration tests: building the contracts,
+//! starting a sandbox, creating accounts and deploying with `init`.
+//!
+//! ```ignore
+//! let fixture = Fixture::builder()
+//!     .account("alice")
+//!     .contract("contract", contract_wasm()?)
+//!     .start()
+//!     .await?;
+//! let contract = fixture.contract("contract");
+//! ```
+
+// Each test binary uses a different part of the fixture
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use near_api::types::transaction::result::ExecutionSuccess;
+use near_api::{AccountId, NearToken};
+use near_sdk::serde_json::json;
+
+#[derive(near_sdk::serde::Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+pub struct Bid {
+    pub bidder: AccountId,
+    pub bid: NearToken,
+}
+
+/// What `Fixture::register_storage` deposits on the account's behalf.
+pub const STORAGE_DEPOSIT: NearToken = NearToken::from_millinear(100);
+
+/// The auction contract at the crate root.
+pub fn contract_wasm() -> testresult::TestResult<Vec<u8>> {
+    build(Default::default())
+}
+
+/// The auction contract built with `features`, comma separated.
+pub fn contract_wasm_with_features(features: &str) -> testresult::TestResult<Vec<u8>> {
+    build(
+        cargo_near_build::BuildOpts::builder()
+            .features(features)
+            .build(),
+    )
+}
+
+/// The slim production build: the `minimal` feature under the `minimal`
+/// profile.
+pub fn contract_wasm_minimal() -> testresult::TestResult<Vec<u8>> {
+    build(
+        cargo_near_build::BuildOpts::builder()
+            .features("minimal")
+            .profile("minimal".to_string())
+            .build(),
+    )
+}
+
+/// One of the comparison contracts under `tests/`, e.g. `default-contract`.
+pub fn nested_wasm(dir: &str) -> testresult::TestResult<Vec<u8>> {
+    build(
+        cargo_near_build::BuildOpts::builder()
+            .manifest_path(format!("tests/{dir}/Cargo.toml"))
+            .build(),
+    )
+}
+
+fn build(opts: cargo_near_build::BuildOpts) -> testresult::TestResult<Vec<u8>> {
+    let wasm_path = cargo_near_build::build_with_cli(opts)?;
+    Ok(std::fs::read(wasm_path)?)
+}
+
+#[derive(Default)]
+pub struct FixtureBuilder {
+    accounts: Vec<String>,
+    contracts: Vec<(String, Vec<u8>)>,
+}
+
+impl FixtureBuilder {
+    /// Creates `<name>.sandbox` with 10 NEAR.
+    pub fn account(mut self, name: &str) -> Self {
+        self.accounts.push(name.to_string());
+        self
+    }
+
+    /// Deploys `wasm` to `<name>.sandbox` and calls `init` on it.
+    pub fn contract(mut self, name: &str, wasm: Vec<u8>) -> Self {
+        self.contracts.push((name.to_string(), wasm));
+        self
+    }
+
+    pub async fn start(self) -> testresult::TestResult<Fixture> {
+        let sandbox = near_sandbox::Sandbox::start_sandbox().await?;
+        let network = near_api::NetworkConfig::from_rpc_url("sandbox", sandbox.rpc_addr.parse()?);
+        let signer = near_api::Signer::from_secret_key(
+            near_sandbox::config::DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY
+                .parse()
+                .unwrap(),
+        )?;
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::SystemTime::UNIX_EPOCH)?
+            .as_secs();
+        let a_minute_from_now = (now + 60) * 1000000000;
+
+        let auctioneer = create_subaccount(&sandbox, "auctioneer.sandbox").await?;
+        let mut fixture = Fixture {
+            sandbox,
+            network,
+            signer,
+            auctioneer,
+            end_time: a_minute_from_now,
+            accounts: HashMap::new(),
+        };
+        for name in self.accounts {
+            fixture.create_account(&name).await?;
+        }
+        for (name, wasm) in self.contracts {
+            fixture.deploy(&name, wasm).await?;
+        }
+        Ok(fixture)
+    }
+}
+
+/// A running sandbox with an auctioneer and whatever accounts and contracts
+/// the builder asked for. Every account shares the genesis key.
+pub struct Fixture {
+    pub sandbox: near_sandbox::Sandbox,
+    pub network: near_api::NetworkConfig,
+    pub signer: Arc<near_api::Signer>,
+    pub auctioneer: near_api::Account,
+    /// `end_time` passed to `init`, in nanoseconds.
+    pub end_time: u64,
+    accounts: HashMap<String, near_api::Account>,
+}
+
+impl Fixture {
+    pub fn builder() -> FixtureBuilder {
+        FixtureBuilder::default()
+    }
+
+    /// An account or contract created by the builder or afterwards.
+    pub fn account(&self, name: &str) -> &near_api::Account {
+        self.accounts
+            .get(name)
+            .unwrap_or_else(|| panic!("no account named {name} in the fixture"))
+    }
+
+    pub fn contract(&self, name: &str) -> near_api::Contract {
+        self.account(name).as_contract()
+    }
+
+    pub async fn create_account(
+        &mut self,
+        name: &str,
+    ) -> testresult::TestResult<near_api::Account> {
+        let account = create_subaccount(&self.sandbox, &format!("{name}.sandbox")).await?;
+        self.accounts.insert(name.to_string(), account.clone());
+        Ok(account)
+    }
+
+    /// Gives `account_id` a storage balance on `contract`, so the receipts
+    /// of its bids there are paid from it and the bids count in full.
+    pub async fn register_storage(
+        &self,
+        contract: &near_api::Contract,
+        account_id: &AccountId,
+    ) -> testresult::TestResult<()> {
+        contract
+            .call_function("storage_deposit", ())
+            .transaction()
+            .deposit(STORAGE_DEPOSIT)
+            .with_signer(account_id.clone(), self.signer.clone())
+            .send_to(&self.network)
+            .await?
+            .assert_success();
+        Ok(())
+    }
+
+    /// Deploys `wasm` to a new `<name>.sandbox` and initializes it with the
+    /// fixture's end time and auctioneer.
+    pub async fn deploy(
+        &mut self,
+        name: &str,
+        wasm: Vec<u8>,
+    ) -> testresult::TestResult<ExecutionSuccess> {
+        let account = self.create_account(name).await?;
+        let result = near_api::Contract::deploy(account.account_id().clone())
+            .use_code(wasm)
+            .with_init_call(
+                "init",
+                json!({"end_time": self.end_time.to_string(), "auctioneer": self.auctioneer.account_id()}),
+            )?
+            .with_signer(self.signer.clone())
+            .send_to(&self.network)
+            .await?
+            .assert_success();
+        Ok(result)
+    }
+}
+
+async fn create_subaccount(
+    sandbox: &near_sandbox::Sandbox,
+    name: &str,
+) -> testresult::TestResult<near_api::Account> {
+    let account_id: AccountId = name.parse().unwrap();
+    sandbox
+        .create_account(account_id.clone())
+        .initial_balance(NearToken::from_near(10))
+        .send()
+        .await?;
+    Ok(near_api::Account(account_id))
+}
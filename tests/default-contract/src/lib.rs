@@ -1,7 +1,20 @@
 // Find all our documentation at https://docs.near.org
 use near_sdk::json_types::U64;
+use near_sdk::serde_json::Value;
 use near_sdk::{env, near, require, store, AccountId, NearToken, PanicOnDefault, Promise};
 
+/// The scenarios `run_benchmark` accepts: the main contract's, less the
+/// record blob this contract doesn't have.
+const SCENARIOS: [&str; 7] = [
+    "fill_vector",
+    "fill_sdk_vector",
+    "fill_sdk_iterable_map",
+    "fill_lookup_map",
+    "fill_tree_map",
+    "fill_lookup_set",
+    "fill_iterable_set",
+];
+
 #[near(serializers = [json, borsh])]
 #[derive(Clone)]
 pub struct Bid {
@@ -14,6 +27,16 @@ pub struct Bid {
     pub premium: bool,
 }
 
+/// The same result as the main contract's `run_benchmark`. This contract
+/// doesn't profile its gas, so `gas_profile` is always `null`.
+#[near(serializers = [json])]
+pub struct BenchResult {
+    pub name: String,
+    pub gas_profile: Value,
+    pub storage_before: U64,
+    pub storage_after: U64,
+}
+
 #[near(contract_state)]
 #[derive(PanicOnDefault)]
 pub struct Contract {
@@ -24,6 +47,13 @@ pub struct Contract {
     vector: Vec<u8>,
     sdk_vector: store::Vector<u8>,
     sdk_iterable_map: store::IterableMap<u8, u8>,
+    sdk_lookup_map: store::LookupMap<u8, u8>,
+    sdk_tree_map: store::TreeMap<u8, u8>,
+    sdk_lookup_set: store::LookupSet<u8>,
+    sdk_iterable_set: store::IterableSet<u8>,
+    sdk_nested_map: store::IterableMap<u8, store::Vector<u8>>,
+    big_value: store::LazyOption<Vec<u8>>,
+    blob: Vec<u8>,
 }
 
 #[near]
@@ -47,6 +77,13 @@ impl Contract {
             vector: vec![],
             sdk_vector: store::Vector::new(b"s"),
             sdk_iterable_map: store::IterableMap::new(b"i"),
+            sdk_lookup_map: store::LookupMap::new(b"l"),
+            sdk_tree_map: store::TreeMap::new(b"t"),
+            sdk_lookup_set: store::LookupSet::new(b"k"),
+            sdk_iterable_set: store::IterableSet::new(b"e"),
+            sdk_nested_map: store::IterableMap::new(b"n"),
+            big_value: store::LazyOption::new(b"b", Some(vec![])),
+            blob: vec![],
         }
     }
 
@@ -62,30 +99,26 @@ impl Contract {
         let bid = env::attached_deposit();
         let bidder = env::predecessor_account_id();
 
-        // Last bid
+        // Check if the deposit is higher than the current bid
+        require!(bid > self.highest_bid.bid, "You must place a higher bid");
+
+        // Update the highest bid, taking the last one out instead of cloning it
         let Bid {
             bidder: last_bidder,
             bid: last_bid,
-            bid_time: _last_bid_time,
-            bid_block_height: _last_bid_block_height,
-            bid_block_timestamp: _last_bid_block_timestamp,
-            bid_epoch_height: _last_bid_epoch_height,
-            premium: _last_premium,
-        } = self.highest_bid.clone();
-
-        // Check if the deposit is higher than the current bid
-        require!(bid > last_bid, "You must place a higher bid");
-
-        // Update the highest bid
-        self.highest_bid = Bid {
-            bidder,
-            bid,
-            bid_time: U64::from(env::block_timestamp()),
-            bid_block_height: U64::from(env::block_height()),
-            bid_block_timestamp: U64::from(env::block_timestamp()),
-            bid_epoch_height: U64::from(env::epoch_height()),
-            premium: false,
-        };
+            ..
+        } = std::mem::replace(
+            &mut self.highest_bid,
+            Bid {
+                bidder,
+                bid,
+                bid_time: U64::from(env::block_timestamp()),
+                bid_block_height: U64::from(env::block_height()),
+                bid_block_timestamp: U64::from(env::block_timestamp()),
+                bid_epoch_height: U64::from(env::epoch_height()),
+                premium: false,
+            },
+        );
 
         // Transfer tokens back to the last bidder
         Promise::new(last_bidder).transfer(last_bid)
@@ -104,41 +137,316 @@ impl Contract {
         Promise::new(self.auctioneer.clone()).transfer(self.highest_bid.bid)
     }
 
-    pub fn fill_vector(&mut self) {
-        for i in 0..1000 {
-            self.vector.push(i as u8);
+    // Fills are staked from the contract's balance, so only it can call them
+    #[private]
+    pub fn fill_vector(&mut self, count: u32, start: u32, seed: Option<u64>) {
+        for i in start..start + count {
+            self.vector.push(fill_item(seed, i).1);
         }
     }
 
-    pub fn fill_sdk_vector(&mut self) {
-        for i in 0..1000 {
-            self.sdk_vector.push(i as u8);
+    #[private]
+    pub fn fill_sdk_vector(&mut self, count: u32, start: u32, seed: Option<u64>) {
+        for i in start..start + count {
+            self.sdk_vector.push(fill_item(seed, i).1);
         }
     }
-    pub fn fill_sdk_iterable_map(&mut self) {
-        for i in 0..1000 {
-            self.sdk_iterable_map.insert(i as u8, i as u8);
+    #[private]
+    pub fn fill_sdk_iterable_map(&mut self, count: u32, start: u32, seed: Option<u64>) {
+        for i in start..start + count {
+            let (key, value) = fill_item(seed, i);
+            self.sdk_iterable_map.insert(key, value);
         }
     }
 
-    pub fn get_vector(&self) -> Vec<u8> {
-        self.vector.clone()
+    #[private]
+    pub fn fill_lookup_map(&mut self, count: u32, start: u32, seed: Option<u64>) {
+        for i in start..start + count {
+            let (key, value) = fill_item(seed, i);
+            self.sdk_lookup_map.insert(key, value);
+        }
+    }
+
+    pub fn read_lookup_map(&self, count: u32, start: u32) -> Vec<u8> {
+        (start..start + count)
+            .filter_map(|i| self.sdk_lookup_map.get(&(i as u8)).copied())
+            .collect()
+    }
+
+    #[private]
+    pub fn fill_tree_map(&mut self, count: u32, start: u32) {
+        for i in start..start + count {
+            self.sdk_tree_map.insert(i as u8, i as u8);
+        }
+    }
+
+    pub fn range_tree_map(&self, from: u32, to: u32) -> Vec<u8> {
+        let Ok(from) = u8::try_from(from) else {
+            return vec![];
+        };
+        self.sdk_tree_map
+            .range(from..)
+            .take_while(|(key, _)| u32::from(**key) < to)
+            .map(|(_, value)| *value)
+            .collect()
+    }
+
+    #[private]
+    pub fn fill_lookup_set(&mut self, count: u32, start: u32) {
+        for i in start..start + count {
+            self.sdk_lookup_set.insert(i as u8);
+        }
+    }
+
+    #[private]
+    pub fn fill_iterable_set(&mut self, count: u32, start: u32) {
+        for i in start..start + count {
+            self.sdk_iterable_set.insert(i as u8);
+        }
+    }
+
+    pub fn contains_many(&self, n: u32) -> (u32, u32) {
+        let lookup_hits = (0..n)
+            .filter(|i| self.sdk_lookup_set.contains(&(*i as u8)))
+            .count();
+        let iterable_hits = (0..n)
+            .filter(|i| self.sdk_iterable_set.contains(&(*i as u8)))
+            .count();
+        (lookup_hits as u32, iterable_hits as u32)
+    }
+
+    /// Pops up to `n` elements off the end of the SDK vector and returns how
+    /// many bytes of storage were freed, not counting the state rewrite.
+    #[private]
+    pub fn drain_sdk_vector(&mut self, n: u32) -> u64 {
+        let storage_before = env::storage_usage();
+        for _ in 0..n {
+            if self.sdk_vector.pop().is_none() {
+                break;
+            }
+        }
+        self.sdk_vector.flush();
+        storage_before - env::storage_usage()
+    }
+
+    /// Removes up to `n` entries of the SDK iterable map, in iteration order,
+    /// and returns how many bytes of storage were freed.
+    #[private]
+    pub fn remove_from_iterable_map(&mut self, n: u32) -> u64 {
+        let storage_before = env::storage_usage();
+        let keys: Vec<u8> = self
+            .sdk_iterable_map
+            .keys()
+            .take(n as usize)
+            .copied()
+            .collect();
+        for key in keys {
+            self.sdk_iterable_map.remove(&key);
+        }
+        self.sdk_iterable_map.flush();
+        storage_before - env::storage_usage()
+    }
+
+    /// Empties every collection the `fill_*` methods write to and returns
+    /// how many bytes of storage were freed, not counting the state rewrite.
+    #[private]
+    pub fn clear_benchmark_state(&mut self) -> u64 {
+        let storage_before = env::storage_usage();
+        self.vector.clear();
+        self.sdk_vector.clear();
+        self.sdk_vector.flush();
+        self.sdk_iterable_map.clear();
+        self.sdk_iterable_map.flush();
+        // Lookup collections can't be iterated, but fills only use byte keys
+        for key in 0..=u8::MAX {
+            self.sdk_lookup_map.remove(&key);
+            self.sdk_lookup_set.remove(&key);
+        }
+        self.sdk_lookup_map.flush();
+        self.sdk_tree_map.clear();
+        self.sdk_tree_map.flush();
+        self.sdk_iterable_set.clear();
+        self.sdk_iterable_set.flush();
+        for inner in self.sdk_nested_map.values_mut() {
+            inner.clear();
+            inner.flush();
+        }
+        self.sdk_nested_map.clear();
+        self.sdk_nested_map.flush();
+        storage_before - env::storage_usage()
+    }
+
+    /// Runs the scenario called `name` with the same sizes as the main
+    /// contract's `run_benchmark`. It loads and saves the state itself, like
+    /// any call would, so the storage after includes the state write.
+    #[private]
+    pub fn run_benchmark(name: String) -> BenchResult {
+        let storage_before = env::storage_usage();
+        let mut contract: Contract = env::state_read().expect("Contract is not initialized");
+        match name.as_str() {
+            "fill_vector" => contract.fill_vector(1000, 0, None),
+            "fill_sdk_vector" => contract.fill_sdk_vector(1000, 0, None),
+            "fill_sdk_iterable_map" => contract.fill_sdk_iterable_map(1000, 0, None),
+            "fill_lookup_map" => contract.fill_lookup_map(1000, 0, None),
+            "fill_tree_map" => contract.fill_tree_map(200, 0),
+            "fill_lookup_set" => contract.fill_lookup_set(100, 0),
+            "fill_iterable_set" => contract.fill_iterable_set(100, 0),
+            _ => env::panic_str("Unknown benchmark"),
+        }
+        env::state_write(&contract);
+        // Collections write their changes when dropped
+        drop(contract);
+        BenchResult {
+            name,
+            gas_profile: Value::Null,
+            storage_before: U64(storage_before),
+            storage_after: U64(env::storage_usage()),
+        }
+    }
+
+    pub fn list_benchmarks(&self) -> Vec<String> {
+        SCENARIOS.iter().map(|name| name.to_string()).collect()
+    }
+
+    /// Reads `n_accesses` pseudo-random elements of the vector. The whole
+    /// vector was already deserialized along with the rest of the state.
+    pub fn random_access_vector(&self, n_accesses: u32) -> Vec<u8> {
+        random_indices(n_accesses, self.vector.len() as u32)
+            .map(|index| self.vector[index as usize])
+            .collect()
+    }
+
+    pub fn random_access_sdk_vector(&self, n_accesses: u32) -> Vec<u8> {
+        random_indices(n_accesses, self.sdk_vector.len())
+            .filter_map(|index| self.sdk_vector.get(index).copied())
+            .collect()
+    }
+
+    pub fn random_access_sdk_iterable_map(&self, n_accesses: u32) -> Vec<u8> {
+        random_indices(n_accesses, u8::MAX as u32 + 1)
+            .filter_map(|key| self.sdk_iterable_map.get(&(key as u8)).copied())
+            .collect()
+    }
+
+    /// Appends `start..start + count` to the inner vectors under the keys
+    /// `0..outers`, creating the ones that don't exist yet.
+    #[private]
+    pub fn fill_nested_map(&mut self, outers: u32, count: u32, start: u32) {
+        for outer in 0..outers {
+            let inner = self
+                .sdk_nested_map
+                .entry(outer as u8)
+                // Every inner vector needs a prefix of its own
+                .or_insert_with(|| store::Vector::new(vec![b'N', outer as u8]));
+            for i in start..start + count {
+                inner.push(i as u8);
+            }
+        }
+    }
+
+    pub fn read_nested_map(&self, outer: u32) -> Vec<u8> {
+        u8::try_from(outer)
+            .ok()
+            .and_then(|outer| self.sdk_nested_map.get(&outer))
+            .map(|inner| inner.iter().copied().collect())
+            .unwrap_or_default()
+    }
+
+    /// Replaces the big value with `size` bytes. It sits behind a
+    /// `LazyOption`, so methods that don't touch it never load or store it.
+    #[private]
+    pub fn set_big_value(&mut self, size: u32) {
+        let value: Vec<u8> = (0..size).map(|i| i as u8).collect();
+        self.big_value.set(Some(value));
+    }
+
+    pub fn big_value_len(&self) -> u32 {
+        self.big_value
+            .get()
+            .as_ref()
+            .map_or(0, |value| value.len() as u32)
+    }
+
+    /// Replaces the blob with a synthetic payload of `size_kb` KiB. It is a
+    /// plain field, so it is written with the rest of the state.
+    #[private]
+    pub fn write_blob(&mut self, size_kb: u32) {
+        self.blob = (0..size_kb * 1024).map(|i| i as u8).collect();
+    }
+
+    pub fn read_blob(&self) -> u32 {
+        self.blob.len() as u32
+    }
+
+    /// Clones the highest bid `n` times, as the getters used to, to measure
+    /// the cost of cloning against `borrow_highest_bid`.
+    pub fn clone_highest_bid(&self, n: u32) -> u32 {
+        (0..n)
+            .map(|_| std::hint::black_box(self.highest_bid.clone()).bidder.len() as u32)
+            .sum()
+    }
+
+    /// `clone_highest_bid` with a reference in place of every clone.
+    pub fn borrow_highest_bid(&self, n: u32) -> u32 {
+        (0..n)
+            .map(|_| std::hint::black_box(&self.highest_bid).bidder.len() as u32)
+            .sum()
+    }
+
+    /// A mutating call that changes nothing, to measure the fixed cost of
+    /// loading and saving the contract state.
+    #[private]
+    pub fn touch_state(&mut self) {}
+
+    // Views return references where they can: the SDK serializes the
+    // result straight from the borrowed field
+
+    pub fn get_vector(&self) -> &[u8] {
+        &self.vector
+    }
+
+    /// Up to `limit` elements of the vector starting at `from`.
+    pub fn get_vector_range(&self, from: u32, limit: u32) -> Vec<u8> {
+        self.vector
+            .iter()
+            .skip(from as usize)
+            .take(limit as usize)
+            .copied()
+            .collect()
     }
 
     pub fn get_sdk_vector(&self) -> Vec<u8> {
-        self.sdk_vector.iter().cloned().collect::<Vec<u8>>()
+        self.sdk_vector.iter().copied().collect::<Vec<u8>>()
+    }
+
+    /// Up to `limit` elements of the SDK vector starting at `from`.
+    pub fn get_sdk_vector_range(&self, from: u32, limit: u32) -> Vec<u8> {
+        (from..from.saturating_add(limit).min(self.sdk_vector.len()))
+            .filter_map(|index| self.sdk_vector.get(index).copied())
+            .collect()
+    }
+
+    /// Up to `limit` entries of the SDK iterable map in insertion order,
+    /// skipping the first `from`.
+    pub fn get_sdk_iterable_map_entries(&self, from: u32, limit: u32) -> Vec<(u8, u8)> {
+        self.sdk_iterable_map
+            .iter()
+            .skip(from as usize)
+            .take(limit as usize)
+            .map(|(key, value)| (*key, *value))
+            .collect()
     }
 
-    pub fn get_highest_bid(&self) -> Bid {
-        self.highest_bid.clone()
+    pub fn get_highest_bid(&self) -> &Bid {
+        &self.highest_bid
     }
 
     pub fn get_auction_end_time(&self) -> U64 {
         self.auction_end_time
     }
 
-    pub fn get_auctioneer(&self) -> AccountId {
-        self.auctioneer.clone()
+    pub fn get_auctioneer(&self) -> &AccountId {
+        &self.auctioneer
     }
 
     pub fn get_claimed(&self) -> bool {
@@ -146,15 +454,42 @@ impl Contract {
     }
 }
 
+/// Key and value of element `i` of a fill: `i` itself, or the shared seeded
+/// test data when a seed is given.
+fn fill_item(seed: Option<u64>, i: u32) -> (u8, u8) {
+    match seed {
+        Some(seed) => (testdata::key(seed, i), testdata::value(seed, i)),
+        None => (i as u8, i as u8),
+    }
+}
+
+/// `n` pseudo-random indices below `len`, seeded from `env::random_seed` so
+/// every call in the same block touches the same indices.
+fn random_indices(n: u32, len: u32) -> impl Iterator<Item = u32> {
+    let seed = env::random_seed_array();
+    // xorshift64 must not start from zero
+    let mut state = u64::from_le_bytes(seed[..8].try_into().unwrap()) | 1;
+    let n = if len == 0 { 0 } else { n };
+    (0..n).map(move |_| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        (state % u64::from(len)) as u32
+    })
+}
+
 #[cfg(test)]
 mod tests {
+    use near_sdk::test_utils::VMContextBuilder;
+    use near_sdk::testing_env;
+
     use super::*;
 
     #[test]
     fn init_contract() {
         let end_time: U64 = U64::from(1000);
         let alice: AccountId = "alice.near".parse().unwrap();
-        let contract = Contract::init(end_time.clone(), alice.clone());
+        let contract = Contract::init(end_time, alice.clone());
 
         let default_bid = contract.get_highest_bid();
         assert_eq!(default_bid.bidder, env::current_account_id());
@@ -164,9 +499,115 @@ mod tests {
         assert_eq!(auction_end_time, end_time);
 
         let auctioneer = contract.get_auctioneer();
-        assert_eq!(auctioneer, alice);
+        assert_eq!(*auctioneer, alice);
 
         let claimed = contract.get_claimed();
-        assert_eq!(claimed, false);
+        assert!(!claimed);
+    }
+
+    fn set_context(predecessor: &str, deposit: NearToken, block_timestamp: u64) {
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(predecessor.parse().unwrap())
+            .attached_deposit(deposit)
+            .block_timestamp(block_timestamp)
+            .build());
+    }
+
+    #[test]
+    fn every_benchmark_grows_the_storage() {
+        env::state_write(&Contract::init(
+            U64::from(1000),
+            "alice.near".parse().unwrap(),
+        ));
+        set_context(
+            env::current_account_id().as_str(),
+            NearToken::from_yoctonear(0),
+            0,
+        );
+        for name in SCENARIOS {
+            let result = Contract::run_benchmark(name.to_string());
+            assert!(result.storage_after.0 > result.storage_before.0, "{name}");
+        }
+    }
+
+    #[test]
+    fn bid_records_the_bidder_and_deposit() {
+        let mut contract = Contract::init(U64::from(1000), "alice.near".parse().unwrap());
+        set_context("bob.near", NearToken::from_near(1), 100);
+        let _ = contract.bid();
+
+        let highest_bid = contract.get_highest_bid();
+        assert_eq!(highest_bid.bidder, "bob.near".parse::<AccountId>().unwrap());
+        assert_eq!(highest_bid.bid, NearToken::from_near(1));
+        assert_eq!(highest_bid.bid_time, U64(100));
+    }
+
+    #[test]
+    fn higher_bid_outbids() {
+        let mut contract = Contract::init(U64::from(1000), "alice.near".parse().unwrap());
+        set_context("bob.near", NearToken::from_near(1), 100);
+        let _ = contract.bid();
+        set_context("carol.near", NearToken::from_near(2), 200);
+        let _ = contract.bid();
+
+        let highest_bid = contract.get_highest_bid();
+        assert_eq!(
+            highest_bid.bidder,
+            "carol.near".parse::<AccountId>().unwrap()
+        );
+        assert_eq!(highest_bid.bid, NearToken::from_near(2));
+    }
+
+    #[test]
+    #[should_panic(expected = "You must place a higher bid")]
+    fn lower_bid_is_rejected() {
+        let mut contract = Contract::init(U64::from(1000), "alice.near".parse().unwrap());
+        set_context("bob.near", NearToken::from_near(2), 100);
+        let _ = contract.bid();
+        set_context("carol.near", NearToken::from_near(1), 200);
+        let _ = contract.bid();
+    }
+
+    #[test]
+    #[should_panic(expected = "Auction has ended")]
+    fn bid_after_the_end_is_rejected() {
+        let mut contract = Contract::init(U64::from(1000), "alice.near".parse().unwrap());
+        set_context("bob.near", NearToken::from_near(1), 1000);
+        let _ = contract.bid();
+    }
+
+    #[test]
+    fn claim_marks_the_auction_claimed() {
+        let mut contract = Contract::init(U64::from(1000), "alice.near".parse().unwrap());
+        set_context("bob.near", NearToken::from_near(1), 100);
+        let _ = contract.bid();
+        set_context("alice.near", NearToken::from_yoctonear(0), 1001);
+        let _ = contract.claim();
+        assert!(contract.get_claimed());
+    }
+
+    #[test]
+    #[should_panic(expected = "Auction has not ended yet")]
+    fn claim_before_the_end_is_rejected() {
+        let mut contract = Contract::init(U64::from(1000), "alice.near".parse().unwrap());
+        set_context("alice.near", NearToken::from_yoctonear(0), 1000);
+        let _ = contract.claim();
+    }
+
+    #[test]
+    fn clone_and_borrow_benchmarks_agree() {
+        let contract = Contract::init(U64::from(1000), "alice.near".parse().unwrap());
+        let bidder_len = env::current_account_id().len() as u32;
+        assert_eq!(contract.clone_highest_bid(3), 3 * bidder_len);
+        assert_eq!(contract.borrow_highest_bid(3), 3 * bidder_len);
+    }
+
+    #[test]
+    #[should_panic(expected = "Auction has already been claimed")]
+    fn double_claim_is_rejected() {
+        let mut contract = Contract::init(U64::from(1000), "alice.near".parse().unwrap());
+        set_context("alice.near", NearToken::from_yoctonear(0), 1001);
+        let _ = contract.claim();
+        let _ = contract.claim();
     }
 }
@@ -0,0 +1,181 @@
+// Find all our documentation at https://docs.near.org
+use near_sdk::json_types::U64;
+use near_sdk::serde::{de::DeserializeOwned, Serialize};
+use near_sdk::{env, near, require, serde_json, AccountId, NearToken, Promise};
+
+#[near(serializers = [json])]
+#[derive(Clone)]
+pub struct Bid {
+    pub bidder: AccountId,
+    pub bid: NearToken,
+    pub bid_time: U64,
+    pub bid_block_height: U64,
+    pub bid_block_timestamp: U64,
+    pub bid_epoch_height: U64,
+    pub premium: bool,
+}
+
+#[near(contract_state)]
+#[derive(Default)]
+pub struct Contract {
+    // every field lives under its own key, serialized as JSON
+}
+
+const HIGHEST_BID: &[u8] = b"highest_bid";
+const AUCTION_END_TIME: &[u8] = b"auction_end_time";
+const AUCTIONEER: &[u8] = b"auctioneer";
+const CLAIMED: &[u8] = b"claimed";
+const VECTOR: &[u8] = b"vector";
+
+fn read<T: DeserializeOwned>(key: &[u8]) -> T {
+    let bytes =
+        env::storage_read(key).unwrap_or_else(|| env::panic_str("Missing value in storage"));
+    serde_json::from_slice(&bytes).unwrap_or_else(|_| env::panic_str("Cannot deserialize value"))
+}
+
+fn write<T: Serialize>(key: &[u8], value: &T) {
+    env::storage_write(key, &serde_json::to_vec(value).unwrap());
+}
+
+#[near]
+impl Contract {
+    #[init]
+    #[private] // only callable by the contract's account
+    pub fn init(end_time: U64, auctioneer: AccountId) -> Self {
+        let highest_bid = Bid {
+            bidder: env::current_account_id(),
+            bid: NearToken::from_yoctonear(1),
+            bid_time: U64::from(env::block_timestamp()),
+            bid_block_height: U64::from(env::block_height()),
+            bid_block_timestamp: U64::from(env::block_timestamp()),
+            bid_epoch_height: U64::from(env::epoch_height()),
+            premium: false,
+        };
+        write(HIGHEST_BID, &highest_bid);
+        write(AUCTION_END_TIME, &end_time);
+        write(AUCTIONEER, &auctioneer);
+        write(CLAIMED, &false);
+        write(VECTOR, &Vec::<u8>::new());
+
+        Self {}
+    }
+
+    #[payable]
+    pub fn bid(&mut self) -> Promise {
+        // Assert the auction is still ongoing
+        let auction_end_time: U64 = read(AUCTION_END_TIME);
+        require!(
+            env::block_timestamp() < auction_end_time.0,
+            "Auction has ended"
+        );
+
+        // Current bid
+        let bid = env::attached_deposit();
+        let bidder = env::predecessor_account_id();
+
+        // Last bid
+        let Bid {
+            bidder: last_bidder,
+            bid: last_bid,
+            bid_time: _last_bid_time,
+            bid_block_height: _last_bid_block_height,
+            bid_block_timestamp: _last_bid_block_timestamp,
+            bid_epoch_height: _last_bid_epoch_height,
+            premium: _last_premium,
+        } = read(HIGHEST_BID);
+
+        // Check if the deposit is higher than the current bid
+        require!(bid > last_bid, "You must place a higher bid");
+
+        // Update the highest bid
+        let highest_bid = Bid {
+            bidder,
+            bid,
+            bid_time: U64::from(env::block_timestamp()),
+            bid_block_height: U64::from(env::block_height()),
+            bid_block_timestamp: U64::from(env::block_timestamp()),
+            bid_epoch_height: U64::from(env::epoch_height()),
+            premium: false,
+        };
+        write(HIGHEST_BID, &highest_bid);
+
+        // Transfer tokens back to the last bidder
+        Promise::new(last_bidder).transfer(last_bid)
+    }
+
+    pub fn claim(&mut self) -> Promise {
+        let auction_end_time: U64 = read(AUCTION_END_TIME);
+        require!(
+            env::block_timestamp() > auction_end_time.0,
+            "Auction has not ended yet"
+        );
+
+        let claimed: bool = read(CLAIMED);
+        require!(!claimed, "Auction has already been claimed");
+        write(CLAIMED, &true);
+
+        // Transfer tokens to the auctioneer
+        let auctioneer: AccountId = read(AUCTIONEER);
+        let highest_bid: Bid = read(HIGHEST_BID);
+        Promise::new(auctioneer).transfer(highest_bid.bid)
+    }
+
+    pub fn fill_vector(&mut self, count: u32, start: u32) {
+        let mut vector: Vec<u8> = read(VECTOR);
+        for i in start..start + count {
+            vector.push(i as u8);
+        }
+        write(VECTOR, &vector);
+    }
+
+    pub fn get_vector(&self) -> Vec<u8> {
+        read(VECTOR)
+    }
+
+    pub fn get_highest_bid(&self) -> Bid {
+        read(HIGHEST_BID)
+    }
+
+    pub fn get_auction_end_time(&self) -> U64 {
+        read(AUCTION_END_TIME)
+    }
+
+    pub fn get_auctioneer(&self) -> AccountId {
+        read(AUCTIONEER)
+    }
+
+    pub fn get_claimed(&self) -> bool {
+        read(CLAIMED)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn init_contract() {
+        let end_time: U64 = U64::from(1000);
+        let alice: AccountId = "alice.near".parse().unwrap();
+        let contract = Contract::init(end_time, alice.clone());
+
+        let default_bid = contract.get_highest_bid();
+        assert_eq!(default_bid.bidder, env::current_account_id());
+        assert_eq!(default_bid.bid, NearToken::from_yoctonear(1));
+
+        assert_eq!(contract.get_auction_end_time(), end_time);
+        assert_eq!(contract.get_auctioneer(), alice);
+        assert!(!contract.get_claimed());
+        assert_eq!(env::storage_read(CLAIMED), Some(b"false".to_vec()));
+    }
+
+    #[test]
+    fn fill_vector_appends() {
+        let mut contract = Contract::init(U64::from(1000), "alice.near".parse().unwrap());
+        contract.fill_vector(1000, 0);
+        contract.fill_vector(10, 1000);
+        let vector = contract.get_vector();
+        assert_eq!(vector.len(), 1010);
+        assert_eq!(vector[1000], 1000u32 as u8);
+    }
+}
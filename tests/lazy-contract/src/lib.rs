@@ -0,0 +1,205 @@
+// Find all our documentation at https://docs.near.org
+use near_sdk::json_types::U64;
+use near_sdk::store::{IterableMap, Lazy, LookupMap, Vector};
+use near_sdk::{env, near, require, AccountId, NearToken, PanicOnDefault, Promise};
+
+#[near(serializers = [json, borsh])]
+#[derive(Clone)]
+pub struct Bid {
+    pub bidder: AccountId,
+    pub bid: NearToken,
+    pub bid_time: U64,
+    pub bid_block_height: U64,
+    pub bid_block_timestamp: U64,
+    pub bid_epoch_height: U64,
+    pub premium: bool,
+}
+
+// Every field lives under its own key and is only loaded when touched, so the
+// `STATE` record itself is just the list of those keys
+#[near(contract_state)]
+#[derive(PanicOnDefault)]
+pub struct Contract {
+    highest_bid: Lazy<Bid>,
+    auction_end_time: Lazy<U64>,
+    auctioneer: Lazy<AccountId>,
+    claimed: Lazy<bool>,
+    vector: Lazy<Vec<u8>>,
+    sdk_vector: Lazy<Vector<u8>>,
+    sdk_iterable_map: Lazy<IterableMap<u8, u8>>,
+    sdk_lookup_map: Lazy<LookupMap<u8, u8>>,
+}
+
+#[near]
+impl Contract {
+    #[init]
+    #[private] // only callable by the contract's account
+    pub fn init(end_time: U64, auctioneer: AccountId) -> Self {
+        let highest_bid = Bid {
+            bidder: env::current_account_id(),
+            bid: NearToken::from_yoctonear(1),
+            bid_time: U64::from(env::block_timestamp()),
+            bid_block_height: U64::from(env::block_height()),
+            bid_block_timestamp: U64::from(env::block_timestamp()),
+            bid_epoch_height: U64::from(env::epoch_height()),
+            premium: false,
+        };
+        Self {
+            highest_bid: Lazy::new(b"h", highest_bid),
+            auction_end_time: Lazy::new(b"a", end_time),
+            auctioneer: Lazy::new(b"u", auctioneer),
+            claimed: Lazy::new(b"c", false),
+            vector: Lazy::new(b"v", vec![]),
+            sdk_vector: Lazy::new(b"S", Vector::new(b"s")),
+            sdk_iterable_map: Lazy::new(b"I", IterableMap::new(b"i")),
+            sdk_lookup_map: Lazy::new(b"L", LookupMap::new(b"l")),
+        }
+    }
+
+    #[payable]
+    pub fn bid(&mut self) -> Promise {
+        // Assert the auction is still ongoing
+        require!(
+            env::block_timestamp() < self.auction_end_time.get().0,
+            "Auction has ended"
+        );
+
+        // Current bid
+        let bid = env::attached_deposit();
+        let bidder = env::predecessor_account_id();
+
+        // Last bid
+        let Bid {
+            bidder: last_bidder,
+            bid: last_bid,
+            ..
+        } = self.highest_bid.get().clone();
+
+        // Check if the deposit is higher than the current bid
+        require!(bid > last_bid, "You must place a higher bid");
+
+        // Update the highest bid
+        self.highest_bid.set(Bid {
+            bidder,
+            bid,
+            bid_time: U64::from(env::block_timestamp()),
+            bid_block_height: U64::from(env::block_height()),
+            bid_block_timestamp: U64::from(env::block_timestamp()),
+            bid_epoch_height: U64::from(env::epoch_height()),
+            premium: false,
+        });
+
+        // Transfer tokens back to the last bidder
+        Promise::new(last_bidder).transfer(last_bid)
+    }
+
+    pub fn claim(&mut self) -> Promise {
+        require!(
+            env::block_timestamp() > self.auction_end_time.get().0,
+            "Auction has not ended yet"
+        );
+
+        require!(!*self.claimed.get(), "Auction has already been claimed");
+        self.claimed.set(true);
+
+        // Transfer tokens to the auctioneer
+        Promise::new(self.auctioneer.get().clone()).transfer(self.highest_bid.get().bid)
+    }
+
+    pub fn fill_vector(&mut self, count: u32, start: u32, seed: Option<u64>) {
+        let vector = self.vector.get_mut();
+        for i in start..start + count {
+            vector.push(fill_item(seed, i).1);
+        }
+    }
+
+    pub fn fill_sdk_vector(&mut self, count: u32, start: u32, seed: Option<u64>) {
+        let sdk_vector = self.sdk_vector.get_mut();
+        for i in start..start + count {
+            sdk_vector.push(fill_item(seed, i).1);
+        }
+    }
+
+    pub fn fill_sdk_iterable_map(&mut self, count: u32, start: u32, seed: Option<u64>) {
+        let sdk_iterable_map = self.sdk_iterable_map.get_mut();
+        for i in start..start + count {
+            let (key, value) = fill_item(seed, i);
+            sdk_iterable_map.insert(key, value);
+        }
+    }
+
+    pub fn fill_lookup_map(&mut self, count: u32, start: u32, seed: Option<u64>) {
+        let sdk_lookup_map = self.sdk_lookup_map.get_mut();
+        for i in start..start + count {
+            let (key, value) = fill_item(seed, i);
+            sdk_lookup_map.insert(key, value);
+        }
+    }
+
+    pub fn get_vector(&self) -> Vec<u8> {
+        self.vector.get().clone()
+    }
+
+    pub fn get_sdk_vector(&self) -> Vec<u8> {
+        self.sdk_vector.get().iter().copied().collect()
+    }
+
+    pub fn get_highest_bid(&self) -> Bid {
+        self.highest_bid.get().clone()
+    }
+
+    pub fn get_auction_end_time(&self) -> U64 {
+        *self.auction_end_time.get()
+    }
+
+    pub fn get_auctioneer(&self) -> AccountId {
+        self.auctioneer.get().clone()
+    }
+
+    pub fn get_claimed(&self) -> bool {
+        *self.claimed.get()
+    }
+}
+
+/// Key and value of element `i` of a fill: `i` itself, or the shared seeded
+/// test data when a seed is given.
+fn fill_item(seed: Option<u64>, i: u32) -> (u8, u8) {
+    match seed {
+        Some(seed) => (testdata::key(seed, i), testdata::value(seed, i)),
+        None => (i as u8, i as u8),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn init_contract() {
+        let end_time: U64 = U64::from(1000);
+        let alice: AccountId = "alice.near".parse().unwrap();
+        let contract = Contract::init(end_time, alice.clone());
+
+        let default_bid = contract.get_highest_bid();
+        assert_eq!(default_bid.bidder, env::current_account_id());
+        assert_eq!(default_bid.bid, NearToken::from_yoctonear(1));
+
+        assert_eq!(contract.get_auction_end_time(), end_time);
+        assert_eq!(contract.get_auctioneer(), alice);
+        assert!(!contract.get_claimed());
+    }
+
+    #[test]
+    fn fields_are_written_to_their_own_keys() {
+        let mut contract = Contract::init(U64::from(1000), "alice.near".parse().unwrap());
+        contract.fill_vector(10, 0, None);
+        contract.fill_sdk_vector(10, 0, None);
+        drop(contract);
+
+        assert!(env::storage_has_key(b"v"));
+        assert!(env::storage_has_key(b"S"));
+        let vector: Vec<u8> =
+            near_sdk::borsh::from_slice(&env::storage_read(b"v").unwrap()).unwrap();
+        assert_eq!(vector, (0..10).collect::<Vec<u8>>());
+    }
+}
@@ -0,0 +1,29 @@
+// Find all our documentation at https://docs.near.org
+use near_sdk::{env, ext_contract, near, AccountId, Promise};
+
+#[ext_contract(ext_auction)]
+#[allow(dead_code)]
+trait Auction {
+    fn bid(&mut self);
+}
+
+#[near(contract_state)]
+#[derive(Default)]
+pub struct Contract {}
+
+#[near]
+impl Contract {
+    /// Bids the attached deposit on `auction`.
+    #[payable]
+    pub fn bid(&mut self, auction: AccountId) -> Promise {
+        ext_auction::ext(auction)
+            .with_attached_deposit(env::attached_deposit())
+            .bid()
+    }
+
+    /// Deletes this account, so transfers to it fail from now on.
+    #[private]
+    pub fn leave(&mut self, beneficiary: AccountId) -> Promise {
+        Promise::new(env::current_account_id()).delete_account(beneficiary)
+    }
+}
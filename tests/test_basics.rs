@@ -1,65 +1,52 @@
-use near_api::{AccountId, NearGas, NearToken};
+use near_api::{NearGas, NearToken};
 use near_sdk::serde_json::json;
 
+mod common;
+
+use common::{
+    contract_wasm, contract_wasm_minimal, contract_wasm_with_features, nested_wasm, Bid, Fixture,
+};
+
 #[derive(near_sdk::serde::Deserialize)]
 #[serde(crate = "near_sdk::serde")]
-pub struct Bid {
-    pub bidder: AccountId,
-    pub bid: NearToken,
+pub struct BenchResult {
+    pub name: String,
+    pub gas_profile: near_sdk::serde_json::Value,
+    pub storage_before: near_sdk::json_types::U64,
+    pub storage_after: near_sdk::json_types::U64,
 }
 
 #[tokio::test]
 async fn test_contract_is_operational() -> testresult::TestResult<()> {
-    let contract_wasm_path = cargo_near_build::build_with_cli(Default::default())?;
-    let contract_wasm = std::fs::read(contract_wasm_path)?;
-
-    let sandbox = near_sandbox::Sandbox::start_sandbox().await?;
-    let sandbox_network =
-        near_api::NetworkConfig::from_rpc_url("sandbox", sandbox.rpc_addr.parse()?);
-
-    // Create accounts
-    let alice = create_subaccount(&sandbox, "alice.sandbox").await?;
-    let bob = create_subaccount(&sandbox, "bob.sandbox").await?;
-    let auctioneer = create_subaccount(&sandbox, "auctioneer.sandbox").await?;
-    let contract = create_subaccount(&sandbox, "contract.sandbox")
-        .await?
-        .as_contract();
-
-    // Deploy and initialize contract
-    let signer = near_api::Signer::from_secret_key(
-        near_sandbox::config::DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY
-            .parse()
-            .unwrap(),
-    )?;
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::SystemTime::UNIX_EPOCH)?
-        .as_secs();
-    let a_minute_from_now = (now + 60) * 1000000000;
-    near_api::Contract::deploy(contract.account_id().clone())
-        .use_code(contract_wasm)
-        .with_init_call(
-            "init",
-            json!({"end_time": a_minute_from_now.to_string(), "auctioneer": auctioneer.account_id()}),
-        )?
-        .with_signer(signer.clone())
-        .send_to(&sandbox_network)
-        .await?
-        .assert_success();
+    let fixture = Fixture::builder()
+        .account("alice")
+        .account("bob")
+        .contract("contract", contract_wasm()?)
+        .start()
+        .await?;
+    let alice = fixture.account("alice");
+    let bob = fixture.account("bob");
+    let contract = fixture.contract("contract");
+    for account in [alice, bob] {
+        fixture
+            .register_storage(&contract, account.account_id())
+            .await?;
+    }
 
     // Alice makes first bid
-    let function = contract
+    contract
         .call_function("bid", ())
         .transaction()
         .deposit(NearToken::from_near(1))
-        .with_signer(alice.account_id().clone(), signer.clone())
-        .send_to(&sandbox_network)
+        .with_signer(alice.account_id().clone(), fixture.signer.clone())
+        .send_to(&fixture.network)
         .await?
         .assert_success();
 
     let highest_bid: Bid = contract
         .call_function("get_highest_bid", ())
         .read_only()
-        .fetch_from(&sandbox_network)
+        .fetch_from(&fixture.network)
         .await?
         .data;
     assert_eq!(highest_bid.bid, NearToken::from_near(1));
@@ -68,7 +55,7 @@ async fn test_contract_is_operational() -> testresult::TestResult<()> {
     let alice_balance = alice
         .tokens()
         .near_balance()
-        .fetch_from(&sandbox_network)
+        .fetch_from(&fixture.network)
         .await?
         .total;
 
@@ -77,15 +64,15 @@ async fn test_contract_is_operational() -> testresult::TestResult<()> {
         .call_function("bid", ())
         .transaction()
         .deposit(NearToken::from_near(2))
-        .with_signer(bob.account_id().clone(), signer.clone())
-        .send_to(&sandbox_network)
+        .with_signer(bob.account_id().clone(), fixture.signer.clone())
+        .send_to(&fixture.network)
         .await?
         .assert_success();
 
     let highest_bid: Bid = contract
         .call_function("get_highest_bid", ())
         .read_only()
-        .fetch_from(&sandbox_network)
+        .fetch_from(&fixture.network)
         .await?
         .data;
     assert_eq!(highest_bid.bid, NearToken::from_near(2));
@@ -95,7 +82,7 @@ async fn test_contract_is_operational() -> testresult::TestResult<()> {
     let new_alice_balance = alice
         .tokens()
         .near_balance()
-        .fetch_from(&sandbox_network)
+        .fetch_from(&fixture.network)
         .await?
         .total;
 
@@ -106,8 +93,8 @@ async fn test_contract_is_operational() -> testresult::TestResult<()> {
         .call_function("bid", ())
         .transaction()
         .deposit(NearToken::from_near(1))
-        .with_signer(alice.account_id().clone(), signer.clone())
-        .send_to(&sandbox_network)
+        .with_signer(alice.account_id().clone(), fixture.signer.clone())
+        .send_to(&fixture.network)
         .await?
         .assert_failure();
 
@@ -115,31 +102,40 @@ async fn test_contract_is_operational() -> testresult::TestResult<()> {
     contract
         .call_function("claim", ())
         .transaction()
+        .deposit(NearToken::from_yoctonear(1))
         .gas(NearGas::from_tgas(30))
-        .with_signer(auctioneer.account_id().clone(), signer.clone())
-        .send_to(&sandbox_network)
+        .with_signer(
+            fixture.auctioneer.account_id().clone(),
+            fixture.signer.clone(),
+        )
+        .send_to(&fixture.network)
         .await?
         .assert_failure();
 
     // Fast forward 200 blocks
     let blocks_to_advance = 200;
-    sandbox.fast_forward(blocks_to_advance).await?;
+    fixture.sandbox.fast_forward(blocks_to_advance).await?;
 
     // Auctioneer claims the auction
     contract
         .call_function("claim", ())
         .transaction()
+        .deposit(NearToken::from_yoctonear(1))
         .gas(NearGas::from_tgas(30))
-        .with_signer(auctioneer.account_id().clone(), signer.clone())
-        .send_to(&sandbox_network)
+        .with_signer(
+            fixture.auctioneer.account_id().clone(),
+            fixture.signer.clone(),
+        )
+        .send_to(&fixture.network)
         .await?
         .assert_success();
 
     // Checks the auctioneer has the correct balance
-    let auctioneer_balance = auctioneer
+    let auctioneer_balance = fixture
+        .auctioneer
         .tokens()
         .near_balance()
-        .fetch_from(&sandbox_network)
+        .fetch_from(&fixture.network)
         .await?
         .total;
     assert!(auctioneer_balance <= NearToken::from_near(12));
@@ -149,9 +145,13 @@ async fn test_contract_is_operational() -> testresult::TestResult<()> {
     contract
         .call_function("claim", ())
         .transaction()
+        .deposit(NearToken::from_yoctonear(1))
         .gas(NearGas::from_tgas(30))
-        .with_signer(auctioneer.account_id().clone(), signer.clone())
-        .send_to(&sandbox_network)
+        .with_signer(
+            fixture.auctioneer.account_id().clone(),
+            fixture.signer.clone(),
+        )
+        .send_to(&fixture.network)
         .await?
         .assert_failure();
 
@@ -160,85 +160,270 @@ async fn test_contract_is_operational() -> testresult::TestResult<()> {
         .call_function("bid", ())
         .transaction()
         .deposit(NearToken::from_near(1))
-        .with_signer(alice.account_id().clone(), signer.clone())
-        .send_to(&sandbox_network)
+        .with_signer(alice.account_id().clone(), fixture.signer.clone())
+        .send_to(&fixture.network)
         .await?
         .assert_failure();
 
     Ok(())
 }
 
+#[tokio::test]
+async fn test_relayed_bid() -> testresult::TestResult<()> {
+    let fixture = Fixture::builder()
+        .account("alice")
+        .account("relayer")
+        .contract("contract", contract_wasm()?)
+        .start()
+        .await?;
+    let alice = fixture.account("alice");
+    let relayer = fixture.account("relayer");
+    let contract = fixture.contract("contract");
+    fixture
+        .register_storage(&contract, alice.account_id())
+        .await?;
+
+    // Alice signs a NEP-366 delegate action for her bid...
+    let delegate_action = contract
+        .call_function("bid", ())
+        .transaction()
+        .deposit(NearToken::from_near(1))
+        .with_signer(alice.account_id().clone(), fixture.signer.clone())
+        .meta()
+        .presign_with(&fixture.network)
+        .await?
+        .transaction
+        .signed()
+        .expect("the delegate action was just signed");
+
+    // ...and the relayer submits it, paying for the gas
+    let relayer_balance = relayer
+        .tokens()
+        .near_balance()
+        .fetch_from(&fixture.network)
+        .await?
+        .total;
+    near_api::Transaction::construct(relayer.account_id().clone(), alice.account_id().clone())
+        .add_action(near_api::types::Action::Delegate(Box::new(delegate_action)))
+        .with_signer(fixture.signer.clone())
+        .send_to(&fixture.network)
+        .await?
+        .assert_success();
+
+    // The bid is Alice's, not the relayer's, and only Alice's deposit was used
+    let highest_bid: Bid = contract
+        .call_function("get_highest_bid", ())
+        .read_only()
+        .fetch_from(&fixture.network)
+        .await?
+        .data;
+    assert_eq!(highest_bid.bid, NearToken::from_near(1));
+    assert_eq!(&highest_bid.bidder, alice.account_id());
+
+    let new_relayer_balance = relayer
+        .tokens()
+        .near_balance()
+        .fetch_from(&fixture.network)
+        .await?
+        .total;
+    assert!(relayer_balance.saturating_sub(new_relayer_balance) < NearToken::from_millinear(100));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_partial_decode_in_bid() -> testresult::TestResult<()> {
+    let fixture = Fixture::builder()
+        .account("alice")
+        .account("bob")
+        .contract("contract", contract_wasm()?)
+        .contract(
+            "partial_contract",
+            contract_wasm_with_features("partial-decode")?,
+        )
+        .start()
+        .await?;
+
+    // The second bid reads the first one back, fully or only its prefix
+    let mut gas = vec![];
+    for name in ["contract", "partial_contract"] {
+        let contract = fixture.contract(name);
+        let mut last = None;
+        for bidder in ["alice", "bob"] {
+            fixture
+                .register_storage(&contract, fixture.account(bidder).account_id())
+                .await?;
+        }
+        for (bidder, near) in [("alice", 1), ("bob", 2)] {
+            let result = contract
+                .call_function("bid", ())
+                .transaction()
+                .deposit(NearToken::from_near(near))
+                .with_signer(
+                    fixture.account(bidder).account_id().clone(),
+                    fixture.signer.clone(),
+                )
+                .send_to(&fixture.network)
+                .await?
+                .assert_success();
+            last = Some(result.total_gas_burnt);
+        }
+        let highest_bid: Bid = contract
+            .call_function("get_highest_bid", ())
+            .read_only()
+            .fetch_from(&fixture.network)
+            .await?
+            .data;
+        assert_eq!(&highest_bid.bidder, fixture.account("bob").account_id());
+        assert_eq!(highest_bid.bid, NearToken::from_near(2));
+        gas.push(last.unwrap());
+    }
+    println!(
+        "bid_full_decode_gas: {:?} Ggas, bid_partial_decode_gas: {:?} Ggas",
+        gas[0].as_ggas(),
+        gas[1].as_ggas()
+    );
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_try_bid_returns_the_deposit() -> testresult::TestResult<()> {
+    let fixture = Fixture::builder()
+        .account("alice")
+        .account("bob")
+        .contract("contract", contract_wasm()?)
+        .start()
+        .await?;
+    let contract = fixture.contract("contract");
+    let bob = fixture.account("bob");
+
+    contract
+        .call_function("bid", ())
+        .transaction()
+        .deposit(NearToken::from_near(2))
+        .with_signer(
+            fixture.account("alice").account_id().clone(),
+            fixture.signer.clone(),
+        )
+        .send_to(&fixture.network)
+        .await?
+        .assert_success();
+
+    // Bob's bid is too low, so the call succeeds with `false` and sends his
+    // deposit back instead of failing
+    let balance_before = bob
+        .tokens()
+        .near_balance()
+        .fetch_from(&fixture.network)
+        .await?
+        .total;
+    let accepted: bool = contract
+        .call_function("try_bid", ())
+        .transaction()
+        .deposit(NearToken::from_near(1))
+        .with_signer(bob.account_id().clone(), fixture.signer.clone())
+        .send_to(&fixture.network)
+        .await?
+        .assert_success()
+        .json()?;
+    assert!(!accepted);
+    let balance_after = bob
+        .tokens()
+        .near_balance()
+        .fetch_from(&fixture.network)
+        .await?
+        .total;
+    // Only gas is spent
+    assert!(balance_after > balance_before.saturating_sub(NearToken::from_millinear(10)));
+
+    let highest_bid: Bid = contract
+        .call_function("get_highest_bid", ())
+        .read_only()
+        .fetch_from(&fixture.network)
+        .await?
+        .data;
+    assert_eq!(&highest_bid.bidder, fixture.account("alice").account_id());
+
+    Ok(())
+}
+
 #[tokio::test]
 async fn test_difference_between_contracts() -> testresult::TestResult<()> {
-    // Build our custom state contract
-    let contract_wasm_path = cargo_near_build::build_with_cli(Default::default())?;
-    let contract_wasm = std::fs::read(contract_wasm_path)?;
-
-    // Build default contract
-    let default_contract_wasm_path = cargo_near_build::build_with_cli(
-        cargo_near_build::BuildOpts::builder()
-            .manifest_path("tests/default-contract/Cargo.toml")
-            .build(),
-    )?;
-    let default_contract_wasm = std::fs::read(default_contract_wasm_path)?;
-
-    // Initialize sandbox
-    let sandbox = near_sandbox::Sandbox::start_sandbox().await?;
-    let sandbox_network =
-        near_api::NetworkConfig::from_rpc_url("sandbox", sandbox.rpc_addr.parse()?);
-
-    // Create accounts
-    let alice = create_subaccount(&sandbox, "alice.sandbox").await?;
-    let auctioneer = create_subaccount(&sandbox, "auctioneer.sandbox").await?;
-    let contract_account = create_subaccount(&sandbox, "contract.sandbox").await?;
-    let contract = contract_account.as_contract();
-    let default_contract_account = create_subaccount(&sandbox, "default_contract.sandbox").await?;
-    let default_contract = default_contract_account.as_contract();
+    let mut fixture = Fixture::builder().account("alice").start().await?;
+    let mut report = GasReport::default();
 
-    // Iinitialize parameters for the contracts
-    let signer = near_api::Signer::from_secret_key(
-        near_sandbox::config::DEFAULT_GENESIS_ACCOUNT_PRIVATE_KEY
-            .parse()
-            .unwrap(),
-    )?;
-    let now = std::time::SystemTime::now()
-        .duration_since(std::time::SystemTime::UNIX_EPOCH)?
-        .as_secs();
-    let a_minute_from_now = (now + 60) * 1000000000;
-
-    // Deploy our custom state contract with init call
-    let deploy_contract_result = near_api::Contract::deploy(contract.account_id().clone())
-        .use_code(contract_wasm.clone())
-        .with_init_call(
-            "init",
-            json!({"end_time": a_minute_from_now.to_string(), "auctioneer": auctioneer.account_id()}),
-        )?
-        .with_signer(signer.clone())
-        .send_to(&sandbox_network)
+    // Deploy our custom state contract, logging where each method spends its gas
+    let deploy_contract_result = fixture
+        .deploy(
+            "contract",
+            contract_wasm_with_features("gas-profile,bench")?,
+        )
         .await?;
     println!(
         "deploy_result_gas: {:?} Ggas",
         deploy_contract_result.total_gas_burnt.as_ggas()
     );
-    assert!(deploy_contract_result.is_success());
-
-    // Deploy default contract with init call
-    let deploy_default_contract_result = near_api::Contract::deploy(
-        default_contract.account_id().clone(),
-    )
-    .use_code(default_contract_wasm.clone())
-    .with_init_call(
-        "init",
-        json!({"end_time": a_minute_from_now.to_string(), "auctioneer": auctioneer.account_id()}),
-    )?
-    .with_signer(signer.clone())
-    .send_to(&sandbox_network)
-    .await?;
+    report.record_gas("contract", "deploy", deploy_contract_result.total_gas_burnt);
+
+    // Deploy default contract
+    let deploy_default_contract_result = fixture
+        .deploy("default_contract", nested_wasm("default-contract")?)
+        .await?;
     println!(
         "deploy_default_contract_result_gas: {:?} Ggas\n",
         deploy_default_contract_result.total_gas_burnt.as_ggas()
     );
-    assert!(deploy_default_contract_result.is_success());
+    report.record_gas(
+        "default_contract",
+        "deploy",
+        deploy_default_contract_result.total_gas_burnt,
+    );
+
+    // Deploy the slim production build next to the full one. It leaves out
+    // the benchmark methods, so it only takes part in the deploy comparison
+    let full_wasm_len = contract_wasm()?.len();
+    let minimal_wasm = contract_wasm_minimal()?;
+    println!(
+        "wasm_size: full {full_wasm_len} bytes, minimal {} bytes",
+        minimal_wasm.len()
+    );
+    assert!(minimal_wasm.len() < full_wasm_len);
+    let deploy_minimal_contract_result = fixture.deploy("minimal_contract", minimal_wasm).await?;
+    println!(
+        "deploy_minimal_contract_result_gas: {:?} Ggas\n",
+        deploy_minimal_contract_result.total_gas_burnt.as_ggas()
+    );
+    report.record_gas(
+        "minimal_contract",
+        "deploy",
+        deploy_minimal_contract_result.total_gas_burnt,
+    );
+    assert!(
+        deploy_minimal_contract_result.total_gas_burnt < deploy_contract_result.total_gas_burnt
+    );
+
+    // Deploy the contract that keeps every field behind `store::Lazy`
+    let deploy_lazy_contract_result = fixture
+        .deploy("lazy_contract", nested_wasm("lazy-contract")?)
+        .await?;
+    println!(
+        "deploy_lazy_contract_result_gas: {:?} Ggas\n",
+        deploy_lazy_contract_result.total_gas_burnt.as_ggas()
+    );
+    report.record_gas(
+        "lazy_contract",
+        "deploy",
+        deploy_lazy_contract_result.total_gas_burnt,
+    );
+
+    let alice = fixture.account("alice");
+    let contract_account = fixture.account("contract");
+    let contract = contract_account.as_contract();
+    let default_contract_account = fixture.account("default_contract");
+    let default_contract = default_contract_account.as_contract();
+    let lazy_contract_account = fixture.account("lazy_contract");
+    let lazy_contract = lazy_contract_account.as_contract();
 
     let deploy_gas_difference = deploy_contract_result
         .total_gas_burnt
@@ -259,177 +444,335 @@ async fn test_difference_between_contracts() -> testresult::TestResult<()> {
     let contract_storage_locked = contract_account
         .tokens()
         .near_balance()
-        .fetch_from(&sandbox_network)
+        .fetch_from(&fixture.network)
         .await?
         .storage_locked;
     println!(
         "contract_storage_locked: {:?}",
         contract_storage_locked.exact_amount_display()
     );
+    report.record_storage("contract", "after_deploy", contract_storage_locked);
 
     // Get the storage locked for default contract
     let default_contract_storage_locked = default_contract_account
         .tokens()
         .near_balance()
-        .fetch_from(&sandbox_network)
+        .fetch_from(&fixture.network)
         .await?
         .storage_locked;
     println!(
         "default_contract_storage_locked: {:?}\n",
         default_contract_storage_locked.exact_amount_display()
     );
+    report.record_storage(
+        "default_contract",
+        "after_deploy",
+        default_contract_storage_locked,
+    );
+
+    // Get the storage locked for lazy contract
+    let lazy_contract_storage_locked = lazy_contract_account
+        .tokens()
+        .near_balance()
+        .fetch_from(&fixture.network)
+        .await?
+        .storage_locked;
+    println!(
+        "lazy_contract_storage_locked: {:?}\n",
+        lazy_contract_storage_locked.exact_amount_display()
+    );
+    report.record_storage(
+        "lazy_contract",
+        "after_deploy",
+        lazy_contract_storage_locked,
+    );
 
     // Alice makes first bid in our custom state contract
     let bid_contract_result = contract
         .call_function("bid", ())
         .transaction()
         .deposit(NearToken::from_near(1))
-        .with_signer(alice.account_id().clone(), signer.clone())
-        .send_to(&sandbox_network)
+        .with_signer(alice.account_id().clone(), fixture.signer.clone())
+        .send_to(&fixture.network)
         .await?
         .assert_success();
     println!(
         "bid_contract_result_gas: {:?} Ggas",
         bid_contract_result.total_gas_burnt.as_ggas()
     );
+    report.record_gas("contract", "bid", bid_contract_result.total_gas_burnt);
+    print_gas_profile(&bid_contract_result);
 
     // Alice makes first bid in default contract
     let bid_default_contract_result = default_contract
         .call_function("bid", ())
         .transaction()
         .deposit(NearToken::from_near(1))
-        .with_signer(alice.account_id().clone(), signer.clone())
-        .send_to(&sandbox_network)
+        .with_signer(alice.account_id().clone(), fixture.signer.clone())
+        .send_to(&fixture.network)
         .await?
         .assert_success();
     println!(
         "bid_default_contract_result_gas: {:?} Ggas\n",
         bid_default_contract_result.total_gas_burnt.as_ggas()
     );
+    report.record_gas(
+        "default_contract",
+        "bid",
+        bid_default_contract_result.total_gas_burnt,
+    );
+
+    // Alice makes first bid in lazy contract
+    let bid_lazy_contract_result = lazy_contract
+        .call_function("bid", ())
+        .transaction()
+        .deposit(NearToken::from_near(1))
+        .with_signer(alice.account_id().clone(), fixture.signer.clone())
+        .send_to(&fixture.network)
+        .await?
+        .assert_success();
+    println!(
+        "bid_lazy_contract_result_gas: {:?} Ggas\n",
+        bid_lazy_contract_result.total_gas_burnt.as_ggas()
+    );
+    report.record_gas(
+        "lazy_contract",
+        "bid",
+        bid_lazy_contract_result.total_gas_burnt,
+    );
 
     // Fast forward 200 blocks
     let blocks_to_advance = 200;
-    sandbox.fast_forward(blocks_to_advance).await?;
+    fixture.sandbox.fast_forward(blocks_to_advance).await?;
 
     // Auctioneer claims the auction in our custom state contract
     let claim_contract_result = contract
         .call_function("claim", ())
         .transaction()
+        .deposit(NearToken::from_yoctonear(1))
         .gas(NearGas::from_tgas(30))
-        .with_signer(auctioneer.account_id().clone(), signer.clone())
-        .send_to(&sandbox_network)
+        .with_signer(
+            fixture.auctioneer.account_id().clone(),
+            fixture.signer.clone(),
+        )
+        .send_to(&fixture.network)
         .await?
         .assert_success();
     println!(
         "claim_contract_result_gas: {:?} Ggas",
         claim_contract_result.total_gas_burnt.as_ggas()
     );
+    report.record_gas("contract", "claim", claim_contract_result.total_gas_burnt);
+    print_gas_profile(&claim_contract_result);
 
     // Auctioneer claims the auction in default contract
     let claim_default_contract_result = default_contract
         .call_function("claim", ())
         .transaction()
         .gas(NearGas::from_tgas(30))
-        .with_signer(auctioneer.account_id().clone(), signer.clone())
-        .send_to(&sandbox_network)
+        .with_signer(
+            fixture.auctioneer.account_id().clone(),
+            fixture.signer.clone(),
+        )
+        .send_to(&fixture.network)
         .await?
         .assert_success();
     println!(
         "claim_default_contract_result_gas: {:?} Ggas\n",
         claim_default_contract_result.total_gas_burnt.as_ggas()
     );
+    report.record_gas(
+        "default_contract",
+        "claim",
+        claim_default_contract_result.total_gas_burnt,
+    );
 
-    // Fill vector in our custom state contract
-    let fill_vector_contract_result = contract
-        .call_function("fill_vector", ())
+    // Auctioneer claims the auction in lazy contract
+    let claim_lazy_contract_result = lazy_contract
+        .call_function("claim", ())
         .transaction()
         .gas(NearGas::from_tgas(30))
-        .with_signer(auctioneer.account_id().clone(), signer.clone())
-        .send_to(&sandbox_network)
+        .with_signer(
+            fixture.auctioneer.account_id().clone(),
+            fixture.signer.clone(),
+        )
+        .send_to(&fixture.network)
         .await?
         .assert_success();
     println!(
-        "fill_vector_contract_result_gas: {:?} Ggas",
-        fill_vector_contract_result.total_gas_burnt.as_ggas()
+        "claim_lazy_contract_result_gas: {:?} Ggas\n",
+        claim_lazy_contract_result.total_gas_burnt.as_ggas()
+    );
+    report.record_gas(
+        "lazy_contract",
+        "claim",
+        claim_lazy_contract_result.total_gas_burnt,
     );
 
-    // Fill vector in default contract
-    let fill_vector_default_contract_result = default_contract
-        .call_function("fill_vector", ())
+    // `claim` reads the end time, auctioneer and claimed flag from one key;
+    // compare that with the three keys they had before
+    let config_layouts: Vec<near_sdk::serde_json::Value> = contract
+        .call_function("bench_config_layout", ())
         .transaction()
         .gas(NearGas::from_tgas(30))
-        .with_signer(auctioneer.account_id().clone(), signer.clone())
-        .send_to(&sandbox_network)
+        .with_signer(contract.account_id().clone(), fixture.signer.clone())
+        .send_to(&fixture.network)
         .await?
-        .assert_success();
-    println!(
-        "fill_vector_default_contract_result_gas: {:?} Ggas\n",
-        fill_vector_default_contract_result
-            .total_gas_burnt
-            .as_ggas()
-    );
+        .assert_success()
+        .json()?;
+    let mut config_read_gas = Vec::new();
+    for (layout, cost) in ["separate_config", "packed_config"]
+        .iter()
+        .zip(&config_layouts)
+    {
+        println!(
+            "{layout}: {} keys, write {} gas, read {} gas",
+            cost["keys"], cost["write_gas"], cost["read_gas"]
+        );
+        let read_gas = NearGas::from_gas(cost["read_gas"].as_str().unwrap().parse()?);
+        report.record_gas("contract", &format!("{layout}_read"), read_gas);
+        config_read_gas.push(read_gas);
+    }
+    assert!(config_read_gas[1] < config_read_gas[0]);
 
-    // Fill sdk vector in our custom state contract
-    let fill_sdk_vector_contract_result = contract
-        .call_function("fill_sdk_vector", ())
-        .transaction()
-        .gas(NearGas::from_tgas(300))
-        .with_signer(auctioneer.account_id().clone(), signer.clone())
-        .send_to(&sandbox_network)
+    // Run every scenario both contracts have through `run_benchmark`; ours
+    // pays for its bookkeeping in each of them
+    let scenarios: Vec<String> = default_contract
+        .call_function("list_benchmarks", ())
+        .read_only()
+        .fetch_from(&fixture.network)
         .await?
-        .assert_success();
-    println!(
-        "fill_sdk_vector_contract_result_gas: {:?} Ggas",
-        fill_sdk_vector_contract_result.total_gas_burnt.as_ggas()
-    );
+        .data;
+    for scenario in &scenarios {
+        let mut scenario_gas = vec![];
+        for (name, bench_contract) in [
+            ("contract", &contract),
+            ("default_contract", &default_contract),
+        ] {
+            let (bench, gas_burnt) = run_benchmark(&fixture, bench_contract, scenario).await?;
+            print_bench_result(name, &bench, gas_burnt);
+            assert!(bench.storage_after.0 > bench.storage_before.0);
+            report.record_gas(name, scenario, gas_burnt);
+            scenario_gas.push(gas_burnt);
+        }
+        assert!(scenario_gas[0] > scenario_gas[1], "{scenario}");
+    }
+    println!();
 
-    // Fill sdk vector in default contract
-    let fill_sdk_vector_default_contract_result = default_contract
-        .call_function("fill_sdk_vector", ())
-        .transaction()
-        .gas(NearGas::from_tgas(300))
-        .with_signer(auctioneer.account_id().clone(), signer.clone())
-        .send_to(&sandbox_network)
-        .await?
-        .assert_success();
-    println!(
-        "fill_sdk_vector_default_contract_result_gas: {:?} Ggas\n",
-        fill_sdk_vector_default_contract_result
-            .total_gas_burnt
-            .as_ggas()
-    );
+    // Run the same fills in lazy contract
+    for method in [
+        "fill_vector",
+        "fill_sdk_vector",
+        "fill_sdk_iterable_map",
+        "fill_lookup_map",
+    ] {
+        let result = lazy_contract
+            .call_function(method, json!({"count": 1000, "start": 0}))
+            .transaction()
+            .gas(NearGas::from_tgas(300))
+            .with_signer(lazy_contract.account_id().clone(), fixture.signer.clone())
+            .send_to(&fixture.network)
+            .await?
+            .assert_success();
+        println!(
+            "{method}_lazy_contract_result_gas: {:?} Ggas",
+            result.total_gas_burnt.as_ggas()
+        );
+        report.record_gas("lazy_contract", method, result.total_gas_burnt);
+    }
+    println!();
 
-    // Fill sdk iterable map in our custom state contract
-    let fill_sdk_iterable_map_contract_result = contract
-        .call_function("fill_sdk_iterable_map", ())
-        .transaction()
-        .gas(NearGas::from_tgas(300))
-        .with_signer(auctioneer.account_id().clone(), signer.clone())
-        .send_to(&sandbox_network)
-        .await?
-        .assert_success();
-    println!(
-        "fill_sdk_iterable_map_contract_result_gas: {:?} Ggas",
-        fill_sdk_iterable_map_contract_result
-            .total_gas_burnt
-            .as_ggas()
-    );
+    // Range query on both tree maps
+    for (name, tree_map_contract) in [
+        ("contract", &contract),
+        ("default_contract", &default_contract),
+    ] {
+        let range: Vec<u8> = tree_map_contract
+            .call_function("range_tree_map", json!({"from": 50, "to": 150}))
+            .read_only()
+            .fetch_from(&fixture.network)
+            .await?
+            .data;
+        assert_eq!(range.len(), 100);
+        println!("range_tree_map on {name}: {} values", range.len());
+    }
 
-    // Fill sdk iterable map in default contract
-    let fill_sdk_iterable_map_default_contract_result = default_contract
-        .call_function("fill_sdk_iterable_map", ())
-        .transaction()
-        .gas(NearGas::from_tgas(300))
-        .with_signer(auctioneer.account_id().clone(), signer.clone())
-        .send_to(&sandbox_network)
-        .await?
-        .assert_success();
-    println!(
-        "fill_sdk_iterable_map_default_contract_result_gas: {:?} Ggas\n",
-        fill_sdk_iterable_map_default_contract_result
-            .total_gas_burnt
-            .as_ggas()
-    );
+    // Check membership in both sets of each contract
+    for (name, set_contract) in [
+        ("contract", &contract),
+        ("default_contract", &default_contract),
+    ] {
+        let result = set_contract
+            .call_function("contains_many", json!({"n": 200}))
+            .transaction()
+            .gas(NearGas::from_tgas(300))
+            .with_signer(set_contract.account_id().clone(), fixture.signer.clone())
+            .send_to(&fixture.network)
+            .await?
+            .assert_success();
+        println!(
+            "contains_many_{name}_result_gas: {:?} Ggas",
+            result.total_gas_burnt.as_ggas()
+        );
+        report.record_gas(name, "contains_many", result.total_gas_burnt);
+        print_gas_profile(&result);
+    }
+
+    // Fill the nested maps and read one inner vector back from each contract
+    for (name, nested_contract) in [
+        ("contract", &contract),
+        ("default_contract", &default_contract),
+    ] {
+        let result = nested_contract
+            .call_function(
+                "fill_nested_map",
+                json!({"outers": 10, "count": 20, "start": 0}),
+            )
+            .transaction()
+            .gas(NearGas::from_tgas(300))
+            .with_signer(nested_contract.account_id().clone(), fixture.signer.clone())
+            .send_to(&fixture.network)
+            .await?
+            .assert_success();
+        println!(
+            "fill_nested_map_{name}_result_gas: {:?} Ggas",
+            result.total_gas_burnt.as_ggas()
+        );
+        report.record_gas(name, "fill_nested_map", result.total_gas_burnt);
+        print_gas_profile(&result);
+
+        let inner: Vec<u8> = nested_contract
+            .call_function("read_nested_map", json!({"outer": 3}))
+            .read_only()
+            .fetch_from(&fixture.network)
+            .await?
+            .data;
+        assert_eq!(inner, (0..20).collect::<Vec<u8>>());
+    }
+
+    // What the clones the default contract's getters used to make cost
+    let mut view_gas = vec![];
+    for method in ["clone_highest_bid", "borrow_highest_bid"] {
+        let result = default_contract
+            .call_function(method, json!({"n": 1000}))
+            .transaction()
+            .gas(NearGas::from_tgas(300))
+            .with_signer(
+                fixture.auctioneer.account_id().clone(),
+                fixture.signer.clone(),
+            )
+            .send_to(&fixture.network)
+            .await?
+            .assert_success();
+        println!(
+            "{method}_default_contract_result_gas: {:?} Ggas",
+            result.total_gas_burnt.as_ggas()
+        );
+        report.record_gas("default_contract", method, result.total_gas_burnt);
+        view_gas.push(result.total_gas_burnt);
+    }
+    assert!(view_gas[1] < view_gas[0]);
+    println!();
 
     // Check that our custom state contract is more expensive to call methods than the default contract
     assert!(
@@ -438,50 +781,922 @@ async fn test_difference_between_contracts() -> testresult::TestResult<()> {
             && (bid_contract_result.total_gas_burnt > bid_default_contract_result.total_gas_burnt)
             && (claim_contract_result.total_gas_burnt
                 > claim_default_contract_result.total_gas_burnt)
-            && (fill_vector_contract_result.total_gas_burnt
-                > fill_vector_default_contract_result.total_gas_burnt)
-            && (fill_sdk_vector_contract_result.total_gas_burnt
-                > fill_sdk_vector_default_contract_result.total_gas_burnt)
-            && (fill_sdk_iterable_map_contract_result.total_gas_burnt
-                > fill_sdk_iterable_map_default_contract_result.total_gas_burnt)
     );
 
     // Get the storage locked for our custom state contract
     let contract_storage_locked = contract_account
         .tokens()
         .near_balance()
-        .fetch_from(&sandbox_network)
+        .fetch_from(&fixture.network)
         .await?
         .storage_locked;
     println!(
         "final_contract_storage_locked: {:?}",
         contract_storage_locked.exact_amount_display()
     );
+    report.record_storage("contract", "final", contract_storage_locked);
+
+    // Cross-check the account's storage usage against the contract's own
+    // breakdown of it
+    let storage_usage: near_sdk::serde_json::Value = contract
+        .call_function("get_storage_usage", ())
+        .read_only()
+        .fetch_from(&fixture.network)
+        .await?
+        .data;
+    println!("contract_storage_usage: {storage_usage}\n");
+    let account_storage_usage = contract_account
+        .tokens()
+        .near_balance()
+        .fetch_from(&fixture.network)
+        .await?
+        .storage_usage;
+    assert_eq!(
+        storage_usage["total"],
+        json!(account_storage_usage.to_string())
+    );
 
     // Get the storage locked for default contract
     let default_contract_storage_locked = default_contract_account
         .tokens()
         .near_balance()
-        .fetch_from(&sandbox_network)
+        .fetch_from(&fixture.network)
         .await?
         .storage_locked;
     println!(
         "final_default_contract_storage_locked: {:?}\n",
         default_contract_storage_locked.exact_amount_display()
     );
+    report.record_storage("default_contract", "final", default_contract_storage_locked);
+
+    // Get the storage locked for lazy contract
+    let lazy_contract_storage_locked = lazy_contract_account
+        .tokens()
+        .near_balance()
+        .fetch_from(&fixture.network)
+        .await?
+        .storage_locked;
+    println!(
+        "final_lazy_contract_storage_locked: {:?}\n",
+        lazy_contract_storage_locked.exact_amount_display()
+    );
+    report.record_storage("lazy_contract", "final", lazy_contract_storage_locked);
+
+    report.write()?;
+    report.assert_within_thresholds("contract");
+    report.assert_matches_snapshot();
+
+    Ok(())
+}
+
+#[test]
+fn test_thresholds_file_parses() {
+    // Catches a typo in benches/thresholds.toml without starting a sandbox
+    let mut report = GasReport::default();
+    report.record_gas("contract", "bid", NearGas::from_ggas(1));
+    report.record_gas("contract", "not_a_method", NearGas::from_tgas(300));
+    report.assert_within_thresholds("contract");
+}
+
+#[test]
+fn test_gas_snapshot_file_parses() {
+    let snapshot = read_gas_snapshot();
+    assert!(snapshot_tolerance(&snapshot) >= 0.0);
+    for (contract, methods) in snapshot.iter().filter(|(_, item)| item.is_table()) {
+        for (method, gas) in methods.as_table().unwrap() {
+            assert!(
+                gas.as_integer().is_some(),
+                "{contract}.{method} in benches/gas-snapshot.toml is not a gas amount"
+            );
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_fill_size_sweep() -> testresult::TestResult<()> {
+    let mut fixture = Fixture::builder().start().await?;
+
+    for (name, wasm) in [
+        ("contract", contract_wasm()?),
+        ("default_contract", nested_wasm("default-contract")?),
+    ] {
+        fixture.deploy(name, wasm).await?;
+        let contract = fixture.contract(name);
+
+        // Each run appends the next range, so later sizes land on a fuller
+        // collection; the SDK collections stop at 1000 to stay under 300 Tgas
+        let mut start = 0;
+        for count in [10, 100, 1000, 10000] {
+            for method in [
+                "fill_vector",
+                "fill_sdk_vector",
+                "fill_sdk_iterable_map",
+                "fill_lookup_map",
+            ] {
+                if count > 1000 && method != "fill_vector" {
+                    continue;
+                }
+                let result = contract
+                    .call_function(method, json!({"count": count, "start": start}))
+                    .transaction()
+                    .gas(NearGas::from_tgas(300))
+                    .with_signer(contract.account_id().clone(), fixture.signer.clone())
+                    .send_to(&fixture.network)
+                    .await?
+                    .assert_success();
+                println!(
+                    "{name} {method}({count}): {:?} Ggas",
+                    result.total_gas_burnt.as_ggas()
+                );
+            }
+            start += count;
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_big_value_loading() -> testresult::TestResult<()> {
+    let mut fixture = Fixture::builder().start().await?;
+
+    for (name, wasm) in [
+        ("contract", contract_wasm()?),
+        ("default_contract", nested_wasm("default-contract")?),
+    ] {
+        fixture.deploy(name, wasm).await?;
+        let contract = fixture.contract(name);
+
+        let mut gas = Vec::new();
+        for (method, args) in [
+            ("touch_state", json!({})),
+            ("set_big_value", json!({"size": 20000})),
+            ("touch_state", json!({})),
+            ("big_value_len", json!({})),
+        ] {
+            let result = contract
+                .call_function(method, args)
+                .transaction()
+                .gas(NearGas::from_tgas(300))
+                .with_signer(contract.account_id().clone(), fixture.signer.clone())
+                .send_to(&fixture.network)
+                .await?
+                .assert_success();
+            println!(
+                "{name} {method}: {:?} Ggas",
+                result.total_gas_burnt.as_ggas()
+            );
+            gas.push(result.total_gas_burnt);
+        }
+
+        // Calls that leave the big value alone don't pay for its size, while
+        // reading it does
+        let untouched_growth = gas[2].saturating_sub(gas[0]);
+        assert!(untouched_growth < NearGas::from_ggas(100));
+        assert!(gas[3] > gas[2]);
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_random_access() -> testresult::TestResult<()> {
+    let mut fixture = Fixture::builder().start().await?;
+
+    let methods = [
+        "random_access_vector",
+        "random_access_sdk_vector",
+        "random_access_sdk_iterable_map",
+    ];
+    let mut growth = Vec::new();
+    for (name, wasm) in [
+        ("contract", contract_wasm()?),
+        ("default_contract", nested_wasm("default-contract")?),
+    ] {
+        fixture.deploy(name, wasm).await?;
+        let contract = fixture.contract(name);
+
+        // Measure the same number of point reads on a small and a ten times
+        // bigger collection
+        let mut gas = Vec::new();
+        let mut len = 0;
+        for target in [1000, 10000] {
+            while len < target {
+                for method in ["fill_vector", "fill_sdk_vector", "fill_sdk_iterable_map"] {
+                    contract
+                        .call_function(method, json!({"count": 1000, "start": len}))
+                        .transaction()
+                        .gas(NearGas::from_tgas(300))
+                        .with_signer(contract.account_id().clone(), fixture.signer.clone())
+                        .send_to(&fixture.network)
+                        .await?
+                        .assert_success();
+                }
+                len += 1000;
+            }
+
+            let mut round = Vec::new();
+            for method in methods {
+                let result = contract
+                    .call_function(method, json!({"n_accesses": 10}))
+                    .transaction()
+                    .gas(NearGas::from_tgas(300))
+                    .with_signer(
+                        fixture.auctioneer.account_id().clone(),
+                        fixture.signer.clone(),
+                    )
+                    .send_to(&fixture.network)
+                    .await?
+                    .assert_success();
+                println!(
+                    "{name} {method} at {len} elements: {:?} Ggas",
+                    result.total_gas_burnt.as_ggas()
+                );
+                round.push(result.total_gas_burnt);
+            }
+            gas.push(round);
+        }
+        growth.push(
+            (0..methods.len())
+                .map(|i| gas[1][i].saturating_sub(gas[0][i]))
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    // A point read into the state blob pays for deserializing every element,
+    // while the SDK vector only loads the elements it touches
+    let default_growth = &growth[1];
+    assert!(default_growth[0] > growth[0][1]);
+    assert!(default_growth[0] > NearGas::from_ggas(10));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_removal_refunds() -> testresult::TestResult<()> {
+    let mut fixture = Fixture::builder().start().await?;
+
+    for (name, wasm) in [
+        ("contract", contract_wasm()?),
+        ("default_contract", nested_wasm("default-contract")?),
+    ] {
+        fixture.deploy(name, wasm).await?;
+        let account = fixture.account(name);
+        let contract = account.as_contract();
+
+        for method in ["fill_sdk_vector", "fill_sdk_iterable_map"] {
+            contract
+                .call_function(method, json!({"count": 200, "start": 0}))
+                .transaction()
+                .gas(NearGas::from_tgas(300))
+                .with_signer(contract.account_id().clone(), fixture.signer.clone())
+                .send_to(&fixture.network)
+                .await?
+                .assert_success();
+        }
+
+        for method in ["drain_sdk_vector", "remove_from_iterable_map"] {
+            let locked_before = account
+                .tokens()
+                .near_balance()
+                .fetch_from(&fixture.network)
+                .await?
+                .storage_locked;
+            let result = contract
+                .call_function(method, json!({"n": 100}))
+                .transaction()
+                .gas(NearGas::from_tgas(300))
+                .with_signer(contract.account_id().clone(), fixture.signer.clone())
+                .send_to(&fixture.network)
+                .await?
+                .assert_success();
+            let freed: u64 = result.json()?;
+            let locked_after = account
+                .tokens()
+                .near_balance()
+                .fetch_from(&fixture.network)
+                .await?
+                .storage_locked;
+            println!(
+                "{name} {method}: {:?} Ggas, {freed} bytes freed, {:?} unlocked",
+                result.total_gas_burnt.as_ggas(),
+                locked_before
+                    .saturating_sub(locked_after)
+                    .exact_amount_display()
+            );
+
+            // Every removal frees storage, and the account gets the stake back
+            assert!(freed > 0);
+            assert!(locked_after < locked_before);
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_blob_vs_per_entry_keys() -> testresult::TestResult<()> {
+    let fixture = Fixture::builder()
+        .contract("contract", contract_wasm()?)
+        .start()
+        .await?;
+    let contract = fixture.contract("contract");
+
+    let mut gas = Vec::new();
+    for layout in ["blob", "keys"] {
+        // 1000 records, written in batches to stay under the gas limit
+        let mut fill_gas = NearGas::from_gas(0);
+        for start in (0..1000).step_by(250) {
+            let result = contract
+                .call_function(
+                    &format!("fill_record_{layout}"),
+                    json!({"count": 250, "start": start}),
+                )
+                .transaction()
+                .gas(NearGas::from_tgas(300))
+                .with_signer(contract.account_id().clone(), fixture.signer.clone())
+                .send_to(&fixture.network)
+                .await?
+                .assert_success();
+            fill_gas = fill_gas.saturating_add(result.total_gas_burnt);
+        }
+
+        let mut layout_gas = vec![fill_gas];
+        for (method, args) in [
+            ("read_record", json!({"index": 500})),
+            ("update_record", json!({"index": 500, "amount": "7"})),
+        ] {
+            let result = contract
+                .call_function(&format!("{method}_{layout}"), args)
+                .transaction()
+                .gas(NearGas::from_tgas(300))
+                .with_signer(contract.account_id().clone(), fixture.signer.clone())
+                .send_to(&fixture.network)
+                .await?
+                .assert_success();
+            layout_gas.push(result.total_gas_burnt);
+        }
+        println!(
+            "{layout}: fill {:?} Ggas, read one {:?} Ggas, update one {:?} Ggas",
+            layout_gas[0].as_ggas(),
+            layout_gas[1].as_ggas(),
+            layout_gas[2].as_ggas()
+        );
+        gas.push(layout_gas);
+    }
+
+    // A single key is cheaper to fill, but every point read or update pays
+    // for the whole set
+    let (blob, keys) = (&gas[0], &gas[1]);
+    assert!(blob[0] < keys[0]);
+    assert!(blob[1] > keys[1]);
+    assert!(blob[2] > keys[2]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_storage_microbenchmarks() -> testresult::TestResult<()> {
+    let fixture = Fixture::builder()
+        .contract("contract", contract_wasm()?)
+        .start()
+        .await?;
+    let contract = fixture.contract("contract");
+
+    // Same value under keys of different lengths
+    let key_lengths: Vec<near_sdk::serde_json::Value> = contract
+        .call_function("bench_key_lengths", json!({"value_len": 100}))
+        .transaction()
+        .gas(NearGas::from_tgas(300))
+        .with_signer(contract.account_id().clone(), fixture.signer.clone())
+        .send_to(&fixture.network)
+        .await?
+        .assert_success()
+        .json()?;
+    for cost in &key_lengths {
+        println!(
+            "{}-byte key: write {} gas, read {} gas, {} bytes",
+            cost["key_len"], cost["write_gas"], cost["read_gas"], cost["storage_bytes"]
+        );
+    }
+    assert_eq!(key_lengths.len(), 3);
+
+    // The highest bid stored borsh-encoded and as JSON
+    let mut encoded_bytes = Vec::new();
+    for method in ["store_bid_borsh", "store_bid_json"] {
+        let cost: near_sdk::serde_json::Value = contract
+            .call_function(method, ())
+            .transaction()
+            .gas(NearGas::from_tgas(300))
+            .with_signer(contract.account_id().clone(), fixture.signer.clone())
+            .send_to(&fixture.network)
+            .await?
+            .assert_success()
+            .json()?;
+        println!(
+            "{method}: {} bytes, write {} gas, read {} gas",
+            cost["bytes"], cost["write_gas"], cost["read_gas"]
+        );
+        encoded_bytes.push(cost["bytes"].as_u64().unwrap());
+    }
+    assert!(encoded_bytes[1] > encoded_bytes[0]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_value_size_sweep() -> testresult::TestResult<()> {
+    let mut fixture = Fixture::builder().start().await?;
+
+    for (name, wasm) in [
+        ("contract", contract_wasm()?),
+        ("default_contract", nested_wasm("default-contract")?),
+    ] {
+        fixture.deploy(name, wasm).await?;
+        let contract = fixture.contract(name);
+
+        let mut write_gas = Vec::new();
+        for size_kb in [1, 4, 16, 64] {
+            let write_result = contract
+                .call_function("write_blob", json!({"size_kb": size_kb}))
+                .transaction()
+                .gas(NearGas::from_tgas(300))
+                .with_signer(contract.account_id().clone(), fixture.signer.clone())
+                .send_to(&fixture.network)
+                .await?
+                .assert_success();
+            let read_result = contract
+                .call_function("read_blob", ())
+                .transaction()
+                .gas(NearGas::from_tgas(300))
+                .with_signer(
+                    fixture.auctioneer.account_id().clone(),
+                    fixture.signer.clone(),
+                )
+                .send_to(&fixture.network)
+                .await?
+                .assert_success();
+            let len: u32 = read_result.json()?;
+            assert_eq!(len, size_kb * 1024);
+            println!(
+                "{name} {size_kb} KiB: write {:?} Ggas, read {:?} Ggas",
+                write_result.total_gas_burnt.as_ggas(),
+                read_result.total_gas_burnt.as_ggas()
+            );
+            write_gas.push(write_result.total_gas_burnt);
+        }
+
+        // Storage costs are charged per byte, so bigger values cost more
+        assert!(write_gas.windows(2).all(|pair| pair[0] < pair[1]));
+    }
 
     Ok(())
 }
 
-async fn create_subaccount(
-    sandbox: &near_sandbox::Sandbox,
-    name: &str,
-) -> testresult::TestResult<near_api::Account> {
-    let account_id: AccountId = name.parse().unwrap();
-    sandbox
-        .create_account(account_id.clone())
-        .initial_balance(NearToken::from_near(10))
-        .send()
+#[tokio::test]
+async fn test_seeded_fills_match() -> testresult::TestResult<()> {
+    const SEED: u64 = 42;
+
+    let mut fixture = Fixture::builder().start().await?;
+    let bidder = fixture
+        .create_account(&testdata::account_id(SEED, 0))
         .await?;
-    Ok(near_api::Account(account_id))
+
+    let expected: Vec<u8> = (0..1000).map(|i| testdata::value(SEED, i)).collect();
+    for (name, wasm) in [
+        ("contract", contract_wasm()?),
+        ("default_contract", nested_wasm("default-contract")?),
+    ] {
+        fixture.deploy(name, wasm).await?;
+        let contract = fixture.contract(name);
+
+        contract
+            .call_function("bid", ())
+            .transaction()
+            .deposit(NearToken::from_near(1))
+            .with_signer(bidder.account_id().clone(), fixture.signer.clone())
+            .send_to(&fixture.network)
+            .await?
+            .assert_success();
+
+        // Split differently per contract: the data only depends on the index
+        let batches: &[(u32, u32)] = match name {
+            "contract" => &[(0, 1000)],
+            _ => &[(0, 400), (400, 600)],
+        };
+        for method in ["fill_vector", "fill_sdk_vector"] {
+            for (start, count) in batches {
+                contract
+                    .call_function(
+                        method,
+                        json!({"count": count, "start": start, "seed": SEED}),
+                    )
+                    .transaction()
+                    .gas(NearGas::from_tgas(300))
+                    .with_signer(contract.account_id().clone(), fixture.signer.clone())
+                    .send_to(&fixture.network)
+                    .await?
+                    .assert_success();
+            }
+        }
+
+        for view in ["get_vector", "get_sdk_vector"] {
+            let data: Vec<u8> = contract
+                .call_function(view, ())
+                .read_only()
+                .fetch_from(&fixture.network)
+                .await?
+                .data;
+            assert_eq!(data, expected, "{name} {view}");
+        }
+    }
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_serialization_format_overhead() -> testresult::TestResult<()> {
+    let mut fixture = Fixture::builder().account("alice").start().await?;
+    let alice = fixture.account("alice").clone();
+
+    let mut storage_locked = Vec::new();
+    // The three contracts: per-key borsh, single borsh blob and per-key JSON
+    for (name, wasm) in [
+        ("contract", contract_wasm()?),
+        ("default_contract", nested_wasm("default-contract")?),
+        ("json_contract", nested_wasm("json-state-contract")?),
+    ] {
+        let deploy_result = fixture.deploy(name, wasm).await?;
+        let account = fixture.account(name);
+        let contract = account.as_contract();
+
+        let bid_result = contract
+            .call_function("bid", ())
+            .transaction()
+            .deposit(NearToken::from_near(1))
+            .with_signer(alice.account_id().clone(), fixture.signer.clone())
+            .send_to(&fixture.network)
+            .await?
+            .assert_success();
+
+        let fill_vector_result = contract
+            .call_function("fill_vector", json!({"count": 1000, "start": 0}))
+            .transaction()
+            .gas(NearGas::from_tgas(30))
+            .with_signer(contract.account_id().clone(), fixture.signer.clone())
+            .send_to(&fixture.network)
+            .await?
+            .assert_success();
+
+        let locked = account
+            .tokens()
+            .near_balance()
+            .fetch_from(&fixture.network)
+            .await?
+            .storage_locked;
+        println!(
+            "{name}: deploy {:?} Ggas, bid {:?} Ggas, fill_vector {:?} Ggas, storage_locked {:?}",
+            deploy_result.total_gas_burnt.as_ggas(),
+            bid_result.total_gas_burnt.as_ggas(),
+            fill_vector_result.total_gas_burnt.as_ggas(),
+            locked.exact_amount_display()
+        );
+        storage_locked.push(locked);
+    }
+
+    // JSON spells every vector element out as text, so it takes more room
+    // than the same state in borsh
+    assert!(storage_locked[2] > storage_locked[1]);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_compression_tradeoff() -> testresult::TestResult<()> {
+    let mut fixture = Fixture::builder().start().await?;
+
+    let mut results = Vec::new();
+    // The contract with and without the compression codec
+    for (name, wasm) in [
+        ("plain", contract_wasm()?),
+        ("compressed", contract_wasm_with_features("compression")?),
+    ] {
+        fixture.deploy(name, wasm).await?;
+        let account = fixture.account(name);
+        let contract = account.as_contract();
+
+        let storage_before = account
+            .tokens()
+            .near_balance()
+            .fetch_from(&fixture.network)
+            .await?
+            .storage_locked;
+
+        // Fill the vector twice so the second call also pays for decompression
+        let mut fill_gas = NearGas::from_gas(0);
+        for round in 0..2 {
+            let fill_result = contract
+                .call_function("fill_vector", json!({"count": 1000, "start": round * 1000}))
+                .transaction()
+                .gas(NearGas::from_tgas(300))
+                .with_signer(contract.account_id().clone(), fixture.signer.clone())
+                .send_to(&fixture.network)
+                .await?
+                .assert_success();
+            fill_gas = fill_gas.saturating_add(fill_result.total_gas_burnt);
+        }
+
+        let storage_after = account
+            .tokens()
+            .near_balance()
+            .fetch_from(&fixture.network)
+            .await?
+            .storage_locked;
+        let staked = storage_after.saturating_sub(storage_before);
+
+        let vector: Vec<u8> = contract
+            .call_function("get_vector", ())
+            .read_only()
+            .fetch_from(&fixture.network)
+            .await?
+            .data;
+        assert_eq!(vector.len(), 2000);
+
+        println!(
+            "{name}: fill_vector x2 {:?} Ggas, vector staking {:?}",
+            fill_gas.as_ggas(),
+            staked.exact_amount_display()
+        );
+        results.push((fill_gas, staked));
+    }
+
+    // Compression costs gas on every write but locks less NEAR for storage
+    let (plain_gas, plain_staked) = results[0];
+    let (compressed_gas, compressed_staked) = results[1];
+    assert!(compressed_gas > plain_gas);
+    assert!(compressed_staked < plain_staked);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_storage_strategies() -> testresult::TestResult<()> {
+    let mut fixture = Fixture::builder().start().await?;
+
+    let mut results = Vec::new();
+    // The three strategies: raw keys, `store::*` and legacy `collections`
+    for (name, wasm) in [
+        ("contract", contract_wasm()?),
+        ("default_contract", nested_wasm("default-contract")?),
+        (
+            "legacy_contract",
+            nested_wasm("legacy-collections-contract")?,
+        ),
+    ] {
+        fixture.deploy(name, wasm).await?;
+        let account = fixture.account(name);
+        let contract = account.as_contract();
+
+        let mut gas = Vec::new();
+        for method in ["fill_sdk_vector", "fill_sdk_iterable_map"] {
+            let result = contract
+                .call_function(method, json!({"count": 200, "start": 0}))
+                .transaction()
+                .gas(NearGas::from_tgas(300))
+                .with_signer(contract.account_id().clone(), fixture.signer.clone())
+                .send_to(&fixture.network)
+                .await?
+                .assert_success();
+            gas.push(result.total_gas_burnt);
+        }
+
+        let locked = account
+            .tokens()
+            .near_balance()
+            .fetch_from(&fixture.network)
+            .await?
+            .storage_locked;
+        println!(
+            "{name}: fill_sdk_vector {:?} Ggas, fill_sdk_iterable_map {:?} Ggas, storage_locked {:?}",
+            gas[0].as_ggas(),
+            gas[1].as_ggas(),
+            locked.exact_amount_display()
+        );
+        results.push(gas);
+    }
+
+    // Legacy collections write through on every insert, while `store::*`
+    // batches the writes until the end of the call
+    assert!(results[2][0] > results[1][0]);
+    assert!(results[2][1] > results[1][1]);
+
+    Ok(())
+}
+
+/// Every measurement taken by `test_difference_between_contracts`, written to
+/// `target/gas-report.json` and `target/gas-report.csv` so runs can be
+/// diffed across SDK versions.
+#[derive(near_sdk::serde::Serialize, Default)]
+#[serde(crate = "near_sdk::serde")]
+struct GasReport {
+    gas: Vec<GasMeasurement>,
+    storage_locked: Vec<StorageMeasurement>,
+}
+
+#[derive(near_sdk::serde::Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct GasMeasurement {
+    contract: String,
+    method: String,
+    gas_burnt: u64,
+}
+
+#[derive(near_sdk::serde::Serialize)]
+#[serde(crate = "near_sdk::serde")]
+struct StorageMeasurement {
+    contract: String,
+    stage: String,
+    /// Yoctonear, as a string since it doesn't fit in a JSON number
+    amount: String,
+}
+
+impl GasReport {
+    fn record_gas(&mut self, contract: &str, method: &str, gas_burnt: NearGas) {
+        self.gas.push(GasMeasurement {
+            contract: contract.to_string(),
+            method: method.to_string(),
+            gas_burnt: gas_burnt.as_gas(),
+        });
+    }
+
+    fn record_storage(&mut self, contract: &str, stage: &str, amount: NearToken) {
+        self.storage_locked.push(StorageMeasurement {
+            contract: contract.to_string(),
+            stage: stage.to_string(),
+            amount: amount.as_yoctonear().to_string(),
+        });
+    }
+
+    /// Asserts that every method of `contract` with a bound in
+    /// `benches/thresholds.toml` burnt no more gas than that bound.
+    fn assert_within_thresholds(&self, contract: &str) {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("benches/thresholds.toml");
+        let thresholds: toml_edit::DocumentMut = std::fs::read_to_string(path)
+            .expect("benches/thresholds.toml is readable")
+            .parse()
+            .expect("benches/thresholds.toml is valid TOML");
+        let Some(bounds) = thresholds.get(contract) else {
+            return;
+        };
+        for measurement in self.gas.iter().filter(|m| m.contract == contract) {
+            let Some(max_ggas) = bounds.get(&measurement.method).and_then(|b| b.as_integer())
+            else {
+                continue;
+            };
+            let burnt = NearGas::from_gas(measurement.gas_burnt);
+            assert!(
+                burnt <= NearGas::from_ggas(max_ggas as u64),
+                "{contract} {} burnt {} Ggas, over its {max_ggas} Ggas threshold",
+                measurement.method,
+                burnt.as_ggas()
+            );
+        }
+    }
+
+    /// Compares every gas measurement against `benches/gas-snapshot.toml`
+    /// and fails on any that moved by more than the tolerance percentage or
+    /// has no entry. With `UPDATE_GAS_SNAPSHOT` set all of them are written
+    /// to the snapshot instead of checked.
+    fn assert_matches_snapshot(&self) {
+        let mut snapshot = read_gas_snapshot();
+        let tolerance = snapshot_tolerance(&snapshot);
+        let update = std::env::var_os("UPDATE_GAS_SNAPSHOT").is_some();
+
+        if update {
+            for measurement in &self.gas {
+                snapshot
+                    .entry(&measurement.contract)
+                    .or_insert(toml_edit::table())
+                    .as_table_mut()
+                    .expect("contracts are tables in benches/gas-snapshot.toml")
+                    [&measurement.method] = toml_edit::value(measurement.gas_burnt as i64);
+            }
+            std::fs::write(gas_snapshot_path(), snapshot.to_string())
+                .expect("benches/gas-snapshot.toml is writable");
+            return;
+        }
+
+        let mut changed = Vec::new();
+        for measurement in &self.gas {
+            let recorded = snapshot
+                .get(&measurement.contract)
+                .and_then(|methods| methods.get(&measurement.method))
+                .and_then(|gas| gas.as_integer());
+            let Some(recorded) = recorded else {
+                changed.push(format!(
+                    "{} {}: not in the snapshot, measured {} gas",
+                    measurement.contract, measurement.method, measurement.gas_burnt
+                ));
+                continue;
+            };
+            let change = (measurement.gas_burnt as f64 - recorded as f64) * 100.0 / recorded as f64;
+            if change.abs() > tolerance {
+                changed.push(format!(
+                    "{} {}: {recorded} -> {} gas ({change:+.1}%)",
+                    measurement.contract, measurement.method, measurement.gas_burnt
+                ));
+            }
+        }
+        assert!(
+            changed.is_empty(),
+            "gas is missing from the snapshot or moved by more than {tolerance}%, rerun \
+             with UPDATE_GAS_SNAPSHOT=1 and commit benches/gas-snapshot.toml if that is \
+             expected:\n{}",
+            changed.join("\n")
+        );
+    }
+
+    fn write(&self) -> std::io::Result<()> {
+        let target = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("target");
+        std::fs::create_dir_all(&target)?;
+        std::fs::write(
+            target.join("gas-report.json"),
+            near_sdk::serde_json::to_string_pretty(self)?,
+        )?;
+
+        let mut csv = String::from("contract,method,gas_burnt\n");
+        for measurement in &self.gas {
+            csv += &format!(
+                "{},{},{}\n",
+                measurement.contract, measurement.method, measurement.gas_burnt
+            );
+        }
+        std::fs::write(target.join("gas-report.csv"), csv)
+    }
+}
+
+fn gas_snapshot_path() -> std::path::PathBuf {
+    std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("benches/gas-snapshot.toml")
+}
+
+fn read_gas_snapshot() -> toml_edit::DocumentMut {
+    std::fs::read_to_string(gas_snapshot_path())
+        .expect("benches/gas-snapshot.toml is readable")
+        .parse()
+        .expect("benches/gas-snapshot.toml is valid TOML")
+}
+
+/// How far, in percent, a measurement may drift from the snapshot:
+/// `GAS_SNAPSHOT_TOLERANCE` if set, otherwise the file's `tolerance_percent`.
+fn snapshot_tolerance(snapshot: &toml_edit::DocumentMut) -> f64 {
+    if let Ok(tolerance) = std::env::var("GAS_SNAPSHOT_TOLERANCE") {
+        return tolerance
+            .parse()
+            .expect("GAS_SNAPSHOT_TOLERANCE is a percentage");
+    }
+    let tolerance = &snapshot["tolerance_percent"];
+    tolerance
+        .as_float()
+        .or_else(|| tolerance.as_integer().map(|percent| percent as f64))
+        .expect("benches/gas-snapshot.toml sets tolerance_percent")
+}
+
+/// Prints the per-phase breakdown logged by contracts built with `gas-profile`.
+/// Runs `scenario` on `contract` through `run_benchmark`, signed by the
+/// contract account since both contracts keep the fills to themselves.
+async fn run_benchmark(
+    fixture: &Fixture,
+    contract: &near_api::Contract,
+    scenario: &str,
+) -> testresult::TestResult<(BenchResult, NearGas)> {
+    let result = contract
+        .call_function("run_benchmark", json!({"name": scenario}))
+        .transaction()
+        .gas(NearGas::from_tgas(300))
+        .with_signer(contract.account_id().clone(), fixture.signer.clone())
+        .send_to(&fixture.network)
+        .await?
+        .assert_success();
+    Ok((result.json()?, result.total_gas_burnt))
+}
+
+fn print_bench_result(contract: &str, bench: &BenchResult, gas_burnt: NearGas) {
+    println!(
+        "{}_{contract}_result_gas: {:?} Ggas, storage {} -> {} bytes",
+        bench.name,
+        gas_burnt.as_ggas(),
+        bench.storage_before.0,
+        bench.storage_after.0
+    );
+    if !bench.gas_profile.is_null() {
+        println!("  {}", bench.gas_profile);
+    }
+}
+
+fn print_gas_profile(result: &near_api::types::transaction::result::ExecutionSuccess) {
+    for log in result.logs() {
+        if let Some(profile) = log.strip_prefix("gas_profile ") {
+            println!("  {profile}");
+        }
+    }
 }
@@ -0,0 +1,120 @@
+use near_api::types::transaction::result::ExecutionFinalResult;
+use near_api::{AccountId, NearToken};
+use near_sdk::json_types::U64;
+use near_sdk::serde_json::json;
+use tokio::task::JoinSet;
+
+mod common;
+
+use common::{contract_wasm, Bid, Fixture, STORAGE_DEPOSIT};
+
+const BIDDERS: usize = 24;
+
+/// Gas an account may have spent on its one bid.
+const GAS_ALLOWANCE: NearToken = NearToken::from_millinear(50);
+
+#[tokio::test]
+async fn test_interleaved_bids_stay_consistent() -> testresult::TestResult<()> {
+    let names: Vec<String> = (0..BIDDERS).map(|n| format!("bidder{n}")).collect();
+    let fixture = names
+        .iter()
+        .fold(Fixture::builder(), |builder, name| builder.account(name))
+        .contract("contract", contract_wasm()?)
+        .start()
+        .await?;
+    let contract = fixture.contract("contract");
+    for name in &names {
+        fixture
+            .register_storage(&contract, fixture.account(name).account_id())
+            .await?;
+    }
+
+    // Everyone bids at once, with the amounts shuffled so that lower bids
+    // regularly land after higher ones
+    let mut bids = JoinSet::new();
+    for (n, name) in names.iter().enumerate() {
+        let amount = NearToken::from_millinear(1000 + 100 * ((n * 7) % BIDDERS) as u128);
+        let bidder = fixture.account(name).account_id().clone();
+        let call = contract
+            .call_function("bid", ())
+            .transaction()
+            .deposit(amount)
+            .with_signer(bidder.clone(), fixture.signer.clone());
+        let network = fixture.network.clone();
+        bids.spawn(async move {
+            let result: ExecutionFinalResult = call.send_to(&network).await?;
+            testresult::TestResult::Ok((bidder, amount, result.is_success()))
+        });
+    }
+    let mut accepted: Vec<(AccountId, NearToken)> = Vec::new();
+    for result in bids.join_all().await {
+        let (bidder, amount, success) = result?;
+        if success {
+            accepted.push((bidder, amount));
+        }
+    }
+    accepted.sort_by_key(|(_, amount)| *amount);
+    let (winner, winning_bid) = accepted.last().cloned().expect("the first bid always wins");
+
+    // A single highest bid: the largest accepted one
+    let highest_bid: Bid = contract
+        .call_function("get_highest_bid", ())
+        .read_only()
+        .fetch_from(&fixture.network)
+        .await?
+        .data;
+    assert_eq!(highest_bid.bidder, winner);
+    assert_eq!(highest_bid.bid, winning_bid);
+
+    let bid_count: U64 = contract
+        .call_function("get_bid_count", ())
+        .read_only()
+        .fetch_from(&fixture.network)
+        .await?
+        .data;
+    assert_eq!(bid_count.0, accepted.len() as u64);
+    let total_volume: NearToken = contract
+        .call_function("get_total_volume", ())
+        .read_only()
+        .fetch_from(&fixture.network)
+        .await?
+        .data;
+    let accepted_volume = accepted
+        .iter()
+        .fold(NearToken::from_near(0), |sum, (_, amount)| {
+            sum.saturating_add(*amount)
+        });
+    assert_eq!(total_volume, accepted_volume);
+
+    // Every loser got their deposit back exactly once: missing a refund
+    // costs at least a NEAR and a second one adds at least a NEAR, while gas
+    // stays well under the allowance
+    let refunds: Vec<(AccountId, NearToken)> = contract
+        .call_function("get_all_refunds", json!({"from": 0, "limit": 100}))
+        .read_only()
+        .fetch_from(&fixture.network)
+        .await?
+        .data;
+    assert!(refunds.is_empty(), "refunds bounced: {refunds:?}");
+    for name in &names {
+        let account = fixture.account(name);
+        let balance = account
+            .tokens()
+            .near_balance()
+            .fetch_from(&fixture.network)
+            .await?
+            .total;
+        let registered = NearToken::from_near(10).saturating_sub(STORAGE_DEPOSIT);
+        let expected = if account.account_id() == &winner {
+            registered.saturating_sub(winning_bid)
+        } else {
+            registered
+        };
+        assert!(
+            balance <= expected && balance > expected.saturating_sub(GAS_ALLOWANCE),
+            "{name} has {balance}, expected about {expected}"
+        );
+    }
+
+    Ok(())
+}
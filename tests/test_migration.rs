@@ -0,0 +1,118 @@
+use near_api::NearToken;
+use near_sdk::json_types::U64;
+use near_sdk::serde_json::{json, Value};
+
+mod common;
+
+use common::{contract_wasm, nested_wasm, Bid, Fixture};
+
+#[tokio::test]
+async fn test_migrate_from_v1() -> testresult::TestResult<()> {
+    let fixture = Fixture::builder()
+        .account("alice")
+        .contract("contract", nested_wasm("v1-contract")?)
+        .start()
+        .await?;
+    let alice = fixture.account("alice");
+    let contract = fixture.contract("contract");
+
+    // Fill the v1 layout: a bid without the block metadata and both vectors
+    contract
+        .call_function("bid", ())
+        .transaction()
+        .deposit(NearToken::from_near(1))
+        .with_signer(alice.account_id().clone(), fixture.signer.clone())
+        .send_to(&fixture.network)
+        .await?
+        .assert_success();
+    for (method, count) in [("fill_vector", 300), ("fill_sdk_vector", 10)] {
+        contract
+            .call_function(method, json!({"count": count}))
+            .transaction()
+            .with_signer(
+                fixture.auctioneer.account_id().clone(),
+                fixture.signer.clone(),
+            )
+            .send_to(&fixture.network)
+            .await?
+            .assert_success();
+    }
+
+    // Upgrade the code and migrate in the same transaction, as the contract
+    near_api::Contract::deploy(contract.account_id().clone())
+        .use_code(contract_wasm()?)
+        .with_init_call("migrate", ())?
+        .with_signer(fixture.signer.clone())
+        .send_to(&fixture.network)
+        .await?
+        .assert_success();
+
+    // The v1 bid decodes with the new fields zeroed
+    let highest_bid: Value = contract
+        .call_function("get_highest_bid", ())
+        .read_only()
+        .fetch_from(&fixture.network)
+        .await?
+        .data;
+    let bid: Bid = near_sdk::serde_json::from_value(highest_bid.clone())?;
+    assert_eq!(&bid.bidder, alice.account_id());
+    assert_eq!(bid.bid, NearToken::from_near(1));
+    assert_eq!(highest_bid["bid_time"], "0");
+    assert_eq!(highest_bid["premium"], false);
+
+    let end_time: U64 = contract
+        .call_function("get_auction_end_time", ())
+        .read_only()
+        .fetch_from(&fixture.network)
+        .await?
+        .data;
+    assert_eq!(end_time.0, fixture.end_time);
+    let auctioneer: near_api::AccountId = contract
+        .call_function("get_auctioneer", ())
+        .read_only()
+        .fetch_from(&fixture.network)
+        .await?
+        .data;
+    assert_eq!(&auctioneer, fixture.auctioneer.account_id());
+    let claimed: bool = contract
+        .call_function("get_claimed", ())
+        .read_only()
+        .fetch_from(&fixture.network)
+        .await?
+        .data;
+    assert!(!claimed);
+
+    let vector: Vec<u8> = contract
+        .call_function("get_vector", ())
+        .read_only()
+        .fetch_from(&fixture.network)
+        .await?
+        .data;
+    assert_eq!(vector, (0..300).map(|i| i as u8).collect::<Vec<_>>());
+    let sdk_vector: Vec<u8> = contract
+        .call_function("get_sdk_vector", ())
+        .read_only()
+        .fetch_from(&fixture.network)
+        .await?
+        .data;
+    assert_eq!(sdk_vector, (0..10).collect::<Vec<u8>>());
+
+    // The migrated contract keeps working, and migrating again is a no-op
+    contract
+        .call_function("bid", ())
+        .transaction()
+        .deposit(NearToken::from_near(2))
+        .with_signer(alice.account_id().clone(), fixture.signer.clone())
+        .send_to(&fixture.network)
+        .await?
+        .assert_success();
+    contract
+        .call_function("migrate", ())
+        .transaction()
+        .with_signer(contract.account_id().clone(), fixture.signer.clone())
+        .send_to(&fixture.network)
+        .await?
+        .assert_success();
+
+    Ok(())
+}
@@ -0,0 +1,359 @@
+use std::collections::BTreeMap;
+
+use near_api::NearToken;
+use near_sdk::borsh::{self, BorshDeserialize};
+use near_sdk::json_types::Base64VecU8;
+use near_sdk::serde_json::{self, json};
+use near_sdk::AccountId;
+
+mod common;
+
+use common::{contract_wasm, nested_wasm, Fixture};
+
+#[derive(BorshDeserialize, Debug, PartialEq)]
+#[borsh(crate = "near_sdk::borsh")]
+struct Bid {
+    bidder: AccountId,
+    bid: near_sdk::NearToken,
+    bid_time: u64,
+    bid_block_height: u64,
+    bid_block_timestamp: u64,
+    bid_epoch_height: u64,
+    premium: bool,
+}
+
+/// The bidder and amount the root contract keeps apart from the rest of the
+/// highest bid.
+#[derive(BorshDeserialize)]
+#[borsh(crate = "near_sdk::borsh")]
+struct HotBid {
+    bidder: AccountId,
+    bid: near_sdk::NearToken,
+}
+
+/// The end time, auctioneer and claim status, packed under one key. An open
+/// or claimed status encodes like `false` or `true`.
+#[derive(BorshDeserialize)]
+#[borsh(crate = "near_sdk::borsh")]
+struct AuctionConfig {
+    auction_end_time: u64,
+    auctioneer: AccountId,
+    claimed: bool,
+}
+
+/// `store::Vector` as it is serialized: its length and element prefix.
+#[derive(BorshDeserialize)]
+#[borsh(crate = "near_sdk::borsh")]
+struct VectorHeader {
+    len: u32,
+    prefix: Vec<u8>,
+}
+
+/// `store::IterableMap`: a vector of keys and a map of values.
+#[derive(BorshDeserialize)]
+#[borsh(crate = "near_sdk::borsh")]
+struct IterableMapHeader {
+    keys: VectorHeader,
+    values_prefix: Vec<u8>,
+}
+
+/// The leading fields of the default contract's `STATE` record.
+#[derive(BorshDeserialize)]
+#[borsh(crate = "near_sdk::borsh")]
+struct DefaultState {
+    highest_bid: Bid,
+    auction_end_time: u64,
+    auctioneer: AccountId,
+    claimed: bool,
+    vector: Vec<u8>,
+    sdk_vector: VectorHeader,
+    sdk_iterable_map: IterableMapHeader,
+}
+
+/// What both contracts store, independent of where they store it. SDK
+/// collection elements are keyed relative to their collection's prefix.
+#[derive(Debug, PartialEq)]
+struct LogicalState {
+    bidder: AccountId,
+    bid: near_sdk::NearToken,
+    auction_end_time: u64,
+    auctioneer: AccountId,
+    claimed: bool,
+    vector: Vec<u8>,
+    sdk_vector_len: u32,
+    sdk_vector: BTreeMap<Vec<u8>, Vec<u8>>,
+    sdk_iterable_map_len: u32,
+    sdk_iterable_map_keys: BTreeMap<Vec<u8>, Vec<u8>>,
+    sdk_iterable_map_values: BTreeMap<Vec<u8>, Vec<u8>>,
+}
+
+type Trie = BTreeMap<Vec<u8>, Vec<u8>>;
+
+/// Mirrors the contract's `key!`: every segment prefixed with its length.
+fn key(segments: &[&[u8]]) -> Vec<u8> {
+    segments
+        .iter()
+        .flat_map(|segment| [&[segment.len() as u8][..], segment].concat())
+        .collect()
+}
+
+/// The payload of a value sealed by the root contract's integrity envelope:
+/// a version byte and a CRC-32 come first.
+fn unseal(sealed: &[u8]) -> &[u8] {
+    assert_eq!(sealed[0], 1, "unknown envelope version");
+    &sealed[5..]
+}
+
+fn decode<T: BorshDeserialize>(bytes: &[u8]) -> T {
+    borsh::from_slice(bytes).expect("value decodes")
+}
+
+/// Entries under `prefix`, with the prefix stripped from their keys.
+fn under(trie: &Trie, prefix: &[u8]) -> BTreeMap<Vec<u8>, Vec<u8>> {
+    trie.range(prefix.to_vec()..)
+        .take_while(|(key, _)| key.starts_with(prefix))
+        .map(|(key, value)| (key[prefix.len()..].to_vec(), value.clone()))
+        .collect()
+}
+
+fn from_base64(s: String) -> Vec<u8> {
+    serde_json::from_value::<Base64VecU8>(json!(s))
+        .expect("valid base64")
+        .0
+}
+
+fn to_base64(bytes: &[u8]) -> String {
+    serde_json::to_value(Base64VecU8::from(bytes.to_vec()))
+        .unwrap()
+        .as_str()
+        .unwrap()
+        .to_string()
+}
+
+async fn fetch_trie(
+    fixture: &Fixture,
+    contract: &near_api::Contract,
+) -> testresult::TestResult<Trie> {
+    Ok(contract
+        .view_storage()
+        .fetch_from(&fixture.network)
+        .await?
+        .data
+        .values
+        .into_iter()
+        .map(|item| (from_base64(item.key.0), from_base64(item.value.0)))
+        .collect())
+}
+
+fn logical_from_default(trie: &Trie) -> LogicalState {
+    let state: DefaultState =
+        BorshDeserialize::deserialize(&mut &trie[b"STATE".as_slice()][..]).expect("STATE decodes");
+    LogicalState {
+        bidder: state.highest_bid.bidder,
+        bid: state.highest_bid.bid,
+        auction_end_time: state.auction_end_time,
+        auctioneer: state.auctioneer,
+        claimed: state.claimed,
+        vector: state.vector,
+        sdk_vector_len: state.sdk_vector.len,
+        sdk_vector: under(trie, &state.sdk_vector.prefix),
+        sdk_iterable_map_len: state.sdk_iterable_map.keys.len,
+        sdk_iterable_map_keys: under(trie, &state.sdk_iterable_map.keys.prefix),
+        sdk_iterable_map_values: under(trie, &state.sdk_iterable_map.values_prefix),
+    }
+}
+
+fn logical_from_raw_keys(trie: &Trie) -> LogicalState {
+    let value = |segments: &[&[u8]]| unseal(&trie[&key(segments)]).to_vec();
+    let highest_bid: HotBid = decode(&value(&[b"auction", b"highest_bid"]));
+    let config: AuctionConfig = decode(&value(&[b"auction", b"config"]));
+    let sdk_vector: VectorHeader = decode(&value(&[b"coll", b"header", b"sdk_vector"]));
+    let sdk_iterable_map: IterableMapHeader =
+        decode(&value(&[b"coll", b"header", b"sdk_iterable_map"]));
+
+    // The benchmark vector is split into chunks next to its length
+    let len: u32 = decode(&value(&[b"bench", b"vector", b"len"]));
+    let mut vector = Vec::new();
+    for n in 0u32.. {
+        if vector.len() >= len as usize {
+            break;
+        }
+        let chunk: Vec<u8> = decode(&value(&[b"bench", b"vector", b"chunk", &n.to_le_bytes()]));
+        vector.extend(chunk);
+    }
+
+    LogicalState {
+        bidder: highest_bid.bidder,
+        bid: highest_bid.bid,
+        auction_end_time: config.auction_end_time,
+        auctioneer: config.auctioneer,
+        claimed: config.claimed,
+        vector,
+        sdk_vector_len: sdk_vector.len,
+        sdk_vector: under(trie, &sdk_vector.prefix),
+        sdk_iterable_map_len: sdk_iterable_map.keys.len,
+        sdk_iterable_map_keys: under(trie, &sdk_iterable_map.keys.prefix),
+        sdk_iterable_map_values: under(trie, &sdk_iterable_map.values_prefix),
+    }
+}
+
+#[tokio::test]
+async fn test_raw_keys_match_the_default_layout() -> testresult::TestResult<()> {
+    let fixture = Fixture::builder()
+        .account("alice")
+        .account("bob")
+        .contract("contract", contract_wasm()?)
+        .contract("default_contract", nested_wasm("default-contract")?)
+        .start()
+        .await?;
+
+    let mut tries = Vec::new();
+    // With storage balances paying for the receipts, both contracts record
+    // the full deposits
+    let contract = fixture.contract("contract");
+    for bidder in ["alice", "bob"] {
+        fixture
+            .register_storage(&contract, fixture.account(bidder).account_id())
+            .await?;
+    }
+    for name in ["contract", "default_contract"] {
+        let contract = fixture.contract(name);
+        for (bidder, near) in [("alice", 1), ("bob", 2)] {
+            contract
+                .call_function("bid", ())
+                .transaction()
+                .deposit(NearToken::from_near(near))
+                .with_signer(
+                    fixture.account(bidder).account_id().clone(),
+                    fixture.signer.clone(),
+                )
+                .send_to(&fixture.network)
+                .await?
+                .assert_success();
+        }
+        for method in ["fill_vector", "fill_sdk_vector", "fill_sdk_iterable_map"] {
+            contract
+                .call_function(method, json!({"count": 600, "start": 0}))
+                .transaction()
+                .gas(near_api::NearGas::from_tgas(300))
+                .with_signer(contract.account_id().clone(), fixture.signer.clone())
+                .send_to(&fixture.network)
+                .await?
+                .assert_success();
+        }
+        tries.push(fetch_trie(&fixture, &contract).await?);
+    }
+
+    let raw_keys = logical_from_raw_keys(&tries[0]);
+    let default = logical_from_default(&tries[1]);
+    assert_eq!(raw_keys.bidder.as_str(), "bob.sandbox");
+    assert_eq!(raw_keys.vector.len(), 600);
+    assert_eq!(raw_keys.sdk_vector.len(), 600);
+    // Keys wrap at 255, so the map ends up with one entry per `u8`
+    assert_eq!(raw_keys.sdk_iterable_map_keys.len(), 256);
+    assert_eq!(raw_keys, default);
+
+    // Besides `STATE` and the lazily loaded big value, the default contract
+    // only has collection elements, all of which were compared above
+    let elements = default.sdk_vector.len()
+        + default.sdk_iterable_map_keys.len()
+        + default.sdk_iterable_map_values.len();
+    assert!(tries[1].contains_key(b"b".as_slice()));
+    assert_eq!(tries[1].len(), 2 + elements);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_corrupted_values_are_reported() -> testresult::TestResult<()> {
+    let fixture = Fixture::builder()
+        .account("alice")
+        .contract("contract", contract_wasm()?)
+        .start()
+        .await?;
+    let contract = fixture.contract("contract");
+    contract
+        .call_function("bid", ())
+        .transaction()
+        .deposit(NearToken::from_near(1))
+        .with_signer(
+            fixture.account("alice").account_id().clone(),
+            fixture.signer.clone(),
+        )
+        .send_to(&fixture.network)
+        .await?
+        .assert_success();
+
+    // Cut the last byte off the highest bid, as a half-applied manual patch
+    // would, and leave the config shorter than its envelope header
+    let highest_bid_key = key(&[b"auction", b"highest_bid"]);
+    let config_key = key(&[b"auction", b"config"]);
+    let trie = fetch_trie(&fixture, &contract).await?;
+    let highest_bid = &trie[&highest_bid_key];
+    fixture
+        .sandbox
+        .patch_state(contract.account_id().clone())
+        .storage(
+            to_base64(&highest_bid_key),
+            to_base64(&highest_bid[..highest_bid.len() - 1]),
+        )
+        .storage(to_base64(&config_key), to_base64(&[1, 2]))
+        .send()
+        .await?;
+
+    // `verify_state` names both keys and what is wrong with them
+    let failures: Vec<near_sdk::serde_json::Value> = contract
+        .call_function("verify_state", ())
+        .read_only()
+        .fetch_from(&fixture.network)
+        .await?
+        .data;
+    let mut failures: Vec<(Vec<u8>, String)> = failures
+        .into_iter()
+        .map(|failure| {
+            (
+                from_base64(failure["key"].as_str().unwrap().to_string()),
+                failure["reason"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect();
+    failures.sort();
+    let mut expected = vec![
+        (highest_bid_key, "checksum mismatch".to_string()),
+        (config_key, "value is shorter than its envelope".to_string()),
+    ];
+    expected.sort();
+    assert_eq!(failures, expected);
+
+    // Reads fail with the integrity error instead of a borsh one, including
+    // the `try_get_*` views that only tolerate missing keys
+    for view in [
+        "get_highest_bid",
+        "try_get_highest_bid",
+        "try_get_auctioneer",
+    ] {
+        let err = contract
+            .call_function(view, ())
+            .read_only::<near_sdk::serde_json::Value>()
+            .fetch_from(&fixture.network)
+            .await
+            .expect_err("the value is corrupted");
+        assert!(
+            format!("{err:?}").contains("Integrity check failed"),
+            "{view}: {err:?}"
+        );
+    }
+    contract
+        .call_function("bid", ())
+        .transaction()
+        .deposit(NearToken::from_near(2))
+        .with_signer(
+            fixture.account("alice").account_id().clone(),
+            fixture.signer.clone(),
+        )
+        .send_to(&fixture.network)
+        .await?
+        .assert_failure();
+
+    Ok(())
+}
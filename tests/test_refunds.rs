@@ -0,0 +1,126 @@
+use near_api::types::transaction::result::ExecutionFinalResult;
+use near_api::{NearGas, NearToken};
+use near_sdk::serde_json::json;
+
+mod common;
+
+use common::{contract_wasm, nested_wasm, Fixture};
+
+async fn refund_owed(
+    fixture: &Fixture,
+    account_id: &near_api::AccountId,
+) -> testresult::TestResult<NearToken> {
+    Ok(fixture
+        .contract("contract")
+        .call_function("get_refund_owed", json!({"account_id": account_id}))
+        .read_only()
+        .fetch_from(&fixture.network)
+        .await?
+        .data)
+}
+
+/// Retries the refund to `account_id`, signed by alice.
+async fn retry_refund(
+    fixture: &Fixture,
+    account_id: &near_api::AccountId,
+) -> testresult::TestResult<ExecutionFinalResult> {
+    Ok(fixture
+        .contract("contract")
+        .call_function("retry_refund", json!({"account_id": account_id}))
+        .transaction()
+        .with_signer(
+            fixture.account("alice").account_id().clone(),
+            fixture.signer.clone(),
+        )
+        .send_to(&fixture.network)
+        .await?)
+}
+
+#[tokio::test]
+async fn test_failed_refunds_can_be_retried() -> testresult::TestResult<()> {
+    let mut fixture = Fixture::builder()
+        .account("alice")
+        .contract("contract", contract_wasm()?)
+        .start()
+        .await?;
+    let contract = fixture.contract("contract");
+    let alice = fixture.account("alice").clone();
+
+    let refuser = fixture.create_account("refuser").await?;
+    near_api::Contract::deploy(refuser.account_id().clone())
+        .use_code(nested_wasm("refusing-bidder")?)
+        .without_init_call()
+        .with_signer(fixture.signer.clone())
+        .send_to(&fixture.network)
+        .await?
+        .assert_success();
+    let refuser_contract = refuser.as_contract();
+    fixture
+        .register_storage(&contract, refuser.account_id())
+        .await?;
+
+    // Bid through the refusing bidder, then take its account away
+    refuser_contract
+        .call_function("bid", json!({"auction": contract.account_id()}))
+        .transaction()
+        .deposit(NearToken::from_near(1))
+        .gas(NearGas::from_tgas(100))
+        .with_signer(alice.account_id().clone(), fixture.signer.clone())
+        .send_to(&fixture.network)
+        .await?
+        .assert_success();
+    refuser_contract
+        .call_function("leave", json!({"beneficiary": alice.account_id()}))
+        .transaction()
+        .with_signer(refuser.account_id().clone(), fixture.signer.clone())
+        .send_to(&fixture.network)
+        .await?
+        .assert_success();
+
+    // Outbidding it still works, and the refund that bounced is owed
+    contract
+        .call_function("bid", ())
+        .transaction()
+        .deposit(NearToken::from_near(2))
+        .with_signer(alice.account_id().clone(), fixture.signer.clone())
+        .send_to(&fixture.network)
+        .await?
+        .assert_success();
+    assert_eq!(
+        refund_owed(&fixture, refuser.account_id()).await?,
+        NearToken::from_near(1)
+    );
+
+    // Retrying while the account is gone bounces again and keeps the debt
+    retry_refund(&fixture, refuser.account_id())
+        .await?
+        .assert_success();
+    assert_eq!(
+        refund_owed(&fixture, refuser.account_id()).await?,
+        NearToken::from_near(1)
+    );
+
+    // Once the account exists again the retry pays it out
+    fixture.create_account("refuser").await?;
+    retry_refund(&fixture, refuser.account_id())
+        .await?
+        .assert_success();
+    assert_eq!(
+        refund_owed(&fixture, refuser.account_id()).await?,
+        NearToken::from_near(0)
+    );
+    let balance = refuser
+        .tokens()
+        .near_balance()
+        .fetch_from(&fixture.network)
+        .await?
+        .total;
+    assert_eq!(balance, NearToken::from_near(11));
+
+    // With nothing owed there is nothing to retry
+    retry_refund(&fixture, refuser.account_id())
+        .await?
+        .assert_failure();
+
+    Ok(())
+}
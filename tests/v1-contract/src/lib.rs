@@ -0,0 +1,91 @@
+// Find all our documentation at https://docs.near.org
+use near_sdk::borsh;
+use near_sdk::env::{storage_read, storage_write};
+use near_sdk::json_types::U64;
+use near_sdk::{env, near, require, store, AccountId, NearToken, Promise};
+
+/// `Bid` before the block metadata fields were added.
+#[near(serializers = [json, borsh])]
+#[derive(Clone)]
+pub struct Bid {
+    pub bidder: AccountId,
+    pub bid: NearToken,
+}
+
+// Everything lives under flat, hand-picked keys; the root contract's
+// `migrate` knows how to move them
+#[near(contract_state)]
+#[derive(Default)]
+pub struct Contract {}
+
+fn read<T: borsh::BorshDeserialize>(key: &[u8]) -> T {
+    borsh::from_slice(&storage_read(key).unwrap()).unwrap()
+}
+
+fn write<T: borsh::BorshSerialize>(key: &[u8], value: &T) {
+    storage_write(key, &borsh::to_vec(value).unwrap());
+}
+
+#[near]
+impl Contract {
+    #[init]
+    #[private] // only callable by the contract's account
+    pub fn init(end_time: U64, auctioneer: AccountId) -> Self {
+        let highest_bid = Bid {
+            bidder: env::current_account_id(),
+            bid: NearToken::from_yoctonear(1),
+        };
+        write(b"highest_bid", &highest_bid);
+        write(b"auction_end_time", &end_time);
+        write(b"auctioneer", &auctioneer);
+        write(b"claimed", &false);
+        write(b"vector", &Vec::<u8>::new());
+        write(b"s", &store::Vector::<u8>::new(b"s"));
+        write(b"i", &store::IterableMap::<u8, u8>::new(b"m"));
+
+        Self {}
+    }
+
+    #[payable]
+    pub fn bid(&mut self) -> Promise {
+        // Assert the auction is still ongoing
+        let auction_end_time: U64 = read(b"auction_end_time");
+        require!(
+            env::block_timestamp() < auction_end_time.0,
+            "Auction has ended"
+        );
+
+        // Check if the deposit is higher than the current bid
+        let bid = env::attached_deposit();
+        let last: Bid = read(b"highest_bid");
+        require!(bid > last.bid, "You must place a higher bid");
+
+        write(
+            b"highest_bid",
+            &Bid {
+                bidder: env::predecessor_account_id(),
+                bid,
+            },
+        );
+
+        // Transfer tokens back to the last bidder
+        Promise::new(last.bidder).transfer(last.bid)
+    }
+
+    pub fn fill_vector(&mut self, count: u32) {
+        let mut vector: Vec<u8> = read(b"vector");
+        vector.extend((0..count).map(|i| i as u8));
+        write(b"vector", &vector);
+    }
+
+    pub fn fill_sdk_vector(&mut self, count: u32) {
+        let mut sdk_vector: store::Vector<u8> = read(b"s");
+        sdk_vector.extend((0..count).map(|i| i as u8));
+        sdk_vector.flush();
+        write(b"s", &sdk_vector);
+    }
+
+    pub fn get_highest_bid(&self) -> Bid {
+        read(b"highest_bid")
+    }
+}
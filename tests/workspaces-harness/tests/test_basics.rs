@@ -0,0 +1,218 @@
+use std::collections::HashMap;
+
+use near_sdk::serde_json::json;
+use near_workspaces::network::Sandbox;
+use near_workspaces::result::ExecutionFinalResult;
+use near_workspaces::types::{Gas, NearToken};
+use near_workspaces::{Account, AccountId, Contract, Worker};
+
+#[derive(near_sdk::serde::Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+struct Bid {
+    bidder: AccountId,
+    bid: NearToken,
+}
+
+/// The auction contract at the root of the repository.
+fn contract_wasm() -> testresult::TestResult<Vec<u8>> {
+    let manifest = concat!(env!("CARGO_MANIFEST_DIR"), "/../../Cargo.toml");
+    let wasm_path = cargo_near_build::build_with_cli(
+        cargo_near_build::BuildOpts::builder()
+            .manifest_path(manifest)
+            .build(),
+    )?;
+    Ok(std::fs::read(wasm_path)?)
+}
+
+/// A sandbox with the auction deployed and initialized to end a minute from
+/// now, an auctioneer and `names` as accounts with 10 NEAR each.
+struct Fixture {
+    worker: Worker<Sandbox>,
+    contract: Contract,
+    auctioneer: Account,
+    accounts: HashMap<String, Account>,
+}
+
+impl Fixture {
+    async fn start(names: &[&str]) -> testresult::TestResult<Self> {
+        let worker = near_workspaces::sandbox().await?;
+        let root = worker.root_account()?;
+        let auctioneer = create_subaccount(&root, "auctioneer").await?;
+        let mut accounts = HashMap::new();
+        for name in names {
+            accounts.insert(name.to_string(), create_subaccount(&root, name).await?);
+        }
+
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::SystemTime::UNIX_EPOCH)?
+            .as_secs();
+        let a_minute_from_now = (now + 60) * 1000000000;
+        let contract = create_subaccount(&root, "contract")
+            .await?
+            .deploy(&contract_wasm()?)
+            .await?
+            .into_result()?;
+        contract
+            .call("init")
+            .args_json(
+                json!({"end_time": a_minute_from_now.to_string(), "auctioneer": auctioneer.id()}),
+            )
+            .transact()
+            .await?
+            .into_result()?;
+
+        Ok(Self {
+            worker,
+            contract,
+            auctioneer,
+            accounts,
+        })
+    }
+
+    fn account(&self, name: &str) -> &Account {
+        self.accounts
+            .get(name)
+            .unwrap_or_else(|| panic!("no account named {name} in the fixture"))
+    }
+
+    async fn highest_bid(&self) -> testresult::TestResult<Bid> {
+        Ok(self.contract.view("get_highest_bid").await?.json()?)
+    }
+
+    async fn claim(&self) -> testresult::TestResult<ExecutionFinalResult> {
+        Ok(self
+            .auctioneer
+            .call(self.contract.id(), "claim")
+            .deposit(NearToken::from_yoctonear(1))
+            .gas(Gas::from_tgas(30))
+            .transact()
+            .await?)
+    }
+}
+
+async fn create_subaccount(root: &Account, name: &str) -> testresult::TestResult<Account> {
+    Ok(root
+        .create_subaccount(name)
+        .initial_balance(NearToken::from_near(10))
+        .transact()
+        .await?
+        .into_result()?)
+}
+
+/// `key!` from the auction contract: every segment prefixed with its length.
+fn key(segments: &[&[u8]]) -> Vec<u8> {
+    segments
+        .iter()
+        .flat_map(|segment| [&[segment.len() as u8][..], segment].concat())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_contract_is_operational() -> testresult::TestResult<()> {
+    let fixture = Fixture::start(&["alice", "bob"]).await?;
+    let alice = fixture.account("alice");
+    let bob = fixture.account("bob");
+    let contract_id = fixture.contract.id();
+
+    // Alice makes first bid
+    alice
+        .call(contract_id, "bid")
+        .deposit(NearToken::from_near(1))
+        .transact()
+        .await?
+        .into_result()?;
+    let highest_bid = fixture.highest_bid().await?;
+    assert_eq!(highest_bid.bid, NearToken::from_near(1));
+    assert_eq!(&highest_bid.bidder, alice.id());
+
+    let alice_balance = alice.view_account().await?.balance;
+
+    // Bob makes a higher bid, and Alice gets hers back
+    bob.call(contract_id, "bid")
+        .deposit(NearToken::from_near(2))
+        .transact()
+        .await?
+        .into_result()?;
+    let highest_bid = fixture.highest_bid().await?;
+    assert_eq!(highest_bid.bid, NearToken::from_near(2));
+    assert_eq!(&highest_bid.bidder, bob.id());
+    assert_eq!(
+        alice.view_account().await?.balance,
+        alice_balance.saturating_add(NearToken::from_near(1))
+    );
+
+    // Alice tries to make a bid with less NEAR than the previous
+    let outcome = alice
+        .call(contract_id, "bid")
+        .deposit(NearToken::from_near(1))
+        .transact()
+        .await?;
+    assert!(outcome.is_failure());
+
+    // Auctioneer claims auction but did not finish
+    assert!(fixture.claim().await?.is_failure());
+
+    fixture.worker.fast_forward(200).await?;
+
+    // Auctioneer claims the auction and gets the winning bid
+    fixture.claim().await?.into_result()?;
+    let auctioneer_balance = fixture.auctioneer.view_account().await?.balance;
+    assert!(auctioneer_balance <= NearToken::from_near(12));
+    assert!(auctioneer_balance > NearToken::from_millinear(11990));
+
+    // Neither a second claim nor a late bid goes through
+    assert!(fixture.claim().await?.is_failure());
+    let outcome = alice
+        .call(contract_id, "bid")
+        .deposit(NearToken::from_near(1))
+        .transact()
+        .await?;
+    assert!(outcome.is_failure());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_corrupted_values_are_reported() -> testresult::TestResult<()> {
+    let fixture = Fixture::start(&["alice"]).await?;
+    fixture
+        .account("alice")
+        .call(fixture.contract.id(), "bid")
+        .deposit(NearToken::from_near(1))
+        .transact()
+        .await?
+        .into_result()?;
+
+    // Cut the last byte off the sealed highest bid
+    let highest_bid_key = key(&[b"auction", b"highest_bid"]);
+    let state = fixture.contract.view_state().await?;
+    let highest_bid = &state[&highest_bid_key];
+    fixture
+        .worker
+        .patch_state(
+            fixture.contract.id(),
+            &highest_bid_key,
+            &highest_bid[..highest_bid.len() - 1],
+        )
+        .await?;
+
+    let failures: Vec<near_sdk::serde_json::Value> =
+        fixture.contract.view("verify_state").await?.json()?;
+    assert_eq!(failures.len(), 1);
+    assert_eq!(failures[0]["reason"], "checksum mismatch");
+
+    for view in ["get_highest_bid", "try_get_highest_bid"] {
+        let err = fixture
+            .contract
+            .view(view)
+            .await
+            .err()
+            .expect("the value is corrupted");
+        assert!(
+            format!("{err:?}").contains("Integrity check failed"),
+            "{view}: {err:?}"
+        );
+    }
+
+    Ok(())
+}